@@ -0,0 +1,100 @@
+//! What the viewer's `spawn_blocking` frame reads buy: concurrent handlers
+//! each read a whole recording while a 1 ms heartbeat task measures how late
+//! the runtime wakes it, first with reads inline on the workers (as the
+//! handlers used to do) and then moved to the blocking pool.
+
+use ghostline_core::{Frame, GhostlineReader, GhostlineWriter, Header};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const FRAMES: usize = 2_000;
+const BODY_BYTES: usize = 8 * 1024;
+const CONCURRENT_READS: usize = 16;
+const WORKERS: usize = 2;
+
+fn read_all(path: &Path) -> usize {
+    let mut reader = GhostlineReader::open(path).unwrap();
+    let mut total = 0;
+    reader.scan(|_, frame| total += frame.response_bytes.len()).unwrap();
+    total
+}
+
+/// Heartbeat lateness (p50, p99, max) and wall time while the reads run.
+fn measure(path: Arc<PathBuf>, blocking: bool) -> (Duration, Duration, Duration, Duration) {
+    let rt = tokio::runtime::Builder::new_multi_thread().worker_threads(WORKERS).enable_all().build().unwrap();
+    rt.block_on(async move {
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let heartbeat = tokio::spawn({
+            let done = done.clone();
+            async move {
+                let mut late = Vec::new();
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    let start = Instant::now();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    late.push(start.elapsed().saturating_sub(Duration::from_millis(1)));
+                }
+                late
+            }
+        });
+        let start = Instant::now();
+        let reads: Vec<_> = (0..CONCURRENT_READS)
+            .map(|_| {
+                let path = path.clone();
+                tokio::spawn(async move {
+                    if blocking {
+                        tokio::task::spawn_blocking(move || read_all(&path)).await.unwrap()
+                    } else {
+                        read_all(&path)
+                    }
+                })
+            })
+            .collect();
+        for read in reads {
+            read.await.unwrap();
+        }
+        let wall = start.elapsed();
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        let mut late = heartbeat.await.unwrap();
+        late.sort();
+        let at = |q: f64| late[((late.len() - 1) as f64 * q) as usize];
+        (at(0.5), at(0.99), *late.last().unwrap(), wall)
+    })
+}
+
+fn main() {
+    let path = std::env::temp_dir().join(format!("ghostline-bench-viewer-{}.ghostline", std::process::id()));
+    let mut writer = GhostlineWriter::new(std::io::BufWriter::new(std::fs::File::create(&path).unwrap()), &Header::default()).unwrap();
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for i in 0..FRAMES {
+        let body: Vec<u8> = (0..BODY_BYTES)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        writer.append(&Frame::new(format!("request {}", i).into_bytes(), body, 10, i as u64)).unwrap();
+    }
+    writer.finish().unwrap();
+    println!(
+        "{} concurrent reads of {} frames of {} B on {} workers; heartbeat lateness:",
+        CONCURRENT_READS, FRAMES, BODY_BYTES, WORKERS
+    );
+
+    let path = Arc::new(path);
+    for (label, blocking) in [("inline", false), ("spawn_blocking", true)] {
+        let (p50, p99, max, wall) = measure(path.clone(), blocking);
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        println!(
+            "  {:<15} p50 {:>7.2} ms  p99 {:>7.2} ms  max {:>7.2} ms  (reads took {:.0} ms)",
+            label,
+            ms(p50),
+            ms(p99),
+            ms(max),
+            ms(wall)
+        );
+    }
+    let _ = std::fs::remove_file(path.as_path());
+}
@@ -1,3 +1,71 @@
+use crate::timefmt::Timezone;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// How chatty the CLI is, set once from the global `-q`/`-v` flags. Errors
+/// and command output print regardless.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Banners and per-frame logs are suppressed.
+    Quiet,
+    #[default]
+    Normal,
+    /// Adds debug detail such as per-request hashes.
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(v: Verbosity) {
+    VERBOSITY.store(v as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+static STDOUT_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Mark stdout as carrying data (`proxy --out -`), so the lines below that
+/// normally print there go to stderr instead.
+pub fn take_stdout() {
+    STDOUT_TAKEN.store(true, Ordering::Relaxed);
+}
+
+/// `println!`, or `eprintln!` once [`take_stdout`] has been called.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if STDOUT_TAKEN.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// `eprintln!` unless `--quiet`; for banners and progress lines.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::banner::verbosity() > $crate::banner::Verbosity::Quiet {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// `eprintln!` only with `--verbose`.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::banner::verbosity() == $crate::banner::Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {debug, info};
+
 pub const LOGO: &str = r#"
    ██████╗ ██╗  ██╗ ██████╗ ███████╗████████╗██╗     ██╗███╗   ██╗███████╗
   ██╔════╝ ██║  ██║██╔═══██╗██╔════╝╚══██╔══╝██║     ██║████╗  ██║██╔════╝
@@ -8,6 +76,9 @@ pub const LOGO: &str = r#"
 "#;
 
 pub fn print_startup(proxy_port: u16, viewer_port: u16) {
+    if verbosity() == Verbosity::Quiet {
+        return;
+    }
     println!("{}", LOGO);
     println!("  v2.0.0 — Deterministic replay for AI agents.\n");
     println!("  ✓ Proxy listening on  http://localhost:{}", proxy_port);
@@ -26,9 +97,12 @@ pub fn print_startup(proxy_port: u16, viewer_port: u16) {
     println!("└────────────────────────────────────────────────────────┘\n");
 }
 
-pub fn print_frame(index: usize, latency_ms: u64, size_bytes: usize) {
-    let now = chrono::Local::now().format("%H:%M:%S");
-    println!(
+pub fn print_frame(timezone: Timezone, index: usize, latency_ms: u64, size_bytes: usize) {
+    if verbosity() == Verbosity::Quiet {
+        return;
+    }
+    let now = crate::timefmt::clock_now(timezone);
+    outln!(
         "[{}] ● FRAME {} | {}ms | {:.1}KB",
         now,
         index,
@@ -36,3 +110,47 @@ pub fn print_frame(index: usize, latency_ms: u64, size_bytes: usize) {
         size_bytes as f64 / 1024.0
     );
 }
+
+/// Aggregate line printed every so often while recording.
+pub fn print_throughput(timezone: Timezone, frames_per_sec: f64, bytes: u64, mean_latency_ms: u64) {
+    if verbosity() == Verbosity::Quiet {
+        return;
+    }
+    let now = crate::timefmt::clock_now(timezone);
+    outln!(
+        "[{}] ◆ {:.1} frames/s | {:.1}KB | {}ms mean",
+        now,
+        frames_per_sec,
+        bytes as f64 / 1024.0,
+        mean_latency_ms
+    );
+}
+
+/// Print the port a server actually bound as `<var>=<port>` on stdout (stderr
+/// once [`take_stdout`] was called), for harnesses that start it with `--port 0`, and optionally write it to
+/// `port_file`. The file is written via rename so pollers never see it partial.
+pub fn announce_port(var: &str, port: u16, port_file: Option<&std::path::Path>) -> std::io::Result<()> {
+    use std::io::Write;
+    outln!("{}={}", var, port);
+    std::io::stdout().flush()?;
+    if let Some(path) = port_file {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, format!("{}\n", port))?;
+        std::fs::rename(&tmp, path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_file_holds_just_the_port() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("replay.port");
+        announce_port("GHOSTLINE_REPLAY_PORT", 43123, Some(&path)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().trim(), "43123");
+        assert!(!path.with_extension("tmp").exists());
+    }
+}
@@ -0,0 +1,174 @@
+use ghostline_core::{Frame, GhostlineReader, GhostlineWriter, FLAG_DELTA_REQUESTS};
+use std::collections::HashMap;
+use std::io::{self, Read, Seek, Write};
+
+/// Which frame survives when a request hash appears more than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeepMode {
+    First,
+    /// What replay serves for a repeated hash.
+    #[default]
+    Last,
+}
+
+/// Order of the rewritten frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Capture timestamp; ties keep their index order.
+    #[default]
+    Timestamp,
+    /// Current index order.
+    Index,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CompactReport {
+    pub frames_before: usize,
+    pub frames_after: usize,
+}
+
+/// Indices of the frames that survive deduplication by request hash, in
+/// `sort` order.
+pub fn compact_order(frames: &[Frame], keep: KeepMode, sort: SortKey) -> Vec<usize> {
+    let mut survivor: HashMap<[u8; 32], usize> = HashMap::new();
+    for (i, frame) in frames.iter().enumerate() {
+        match keep {
+            KeepMode::First => {
+                survivor.entry(frame.request_hash).or_insert(i);
+            }
+            KeepMode::Last => {
+                survivor.insert(frame.request_hash, i);
+            }
+        }
+    }
+    let mut order: Vec<usize> = (0..frames.len())
+        .filter(|&i| survivor[&frames[i].request_hash] == i)
+        .collect();
+    if sort == SortKey::Timestamp {
+        order.sort_by_key(|&i| frames[i].timestamp);
+    }
+    order
+}
+
+/// Read every frame of `reader` in index order and write the compacted run to
+/// `out` with a fresh index, keeping the source header.
+///
+/// Surviving frames are copied as stored blocks, without recompressing (see
+/// [`rewrite`]).
+pub fn compact<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    out: W,
+    keep: KeepMode,
+    sort: SortKey,
+) -> io::Result<CompactReport> {
+    let frames = read_frames(reader)?;
+    let order = compact_order(&frames, keep, sort);
+    rewrite(reader, &frames, &order, out)?;
+    Ok(CompactReport {
+        frames_before: frames.len(),
+        frames_after: order.len(),
+    })
+}
+
+/// Write only the frames `keep` accepts, in index order, with a fresh index
+/// and the source header; as [`compact`], blocks are copied when they can be.
+pub fn filter<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    out: W,
+    keep: impl Fn(&Frame) -> bool,
+) -> io::Result<CompactReport> {
+    let frames = read_frames(reader)?;
+    let order: Vec<usize> = (0..frames.len()).filter(|&i| keep(&frames[i])).collect();
+    rewrite(reader, &frames, &order, out)?;
+    Ok(CompactReport {
+        frames_before: frames.len(),
+        frames_after: order.len(),
+    })
+}
+
+/// Every frame of `reader`, in index order.
+fn read_frames<R: Read + Seek>(reader: &mut GhostlineReader<R>) -> io::Result<Vec<Frame>> {
+    let mut indexed = Vec::with_capacity(reader.frame_count());
+    reader.scan(|i, frame| indexed.push((i, frame.clone())))?;
+    indexed.sort_by_key(|(i, _)| *i);
+    Ok(indexed.into_iter().map(|(_, f)| f).collect())
+}
+
+/// Write `frames[order]` to `out`. Stored blocks are copied as they are
+/// unless the file delta-encodes requests and the chain has to be rebuilt.
+fn rewrite<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    frames: &[Frame],
+    order: &[usize],
+    out: W,
+) -> io::Result<()> {
+    let header = reader.header();
+    let mut writer = GhostlineWriter::new(out, &header)?;
+    for &i in order {
+        if header.flags & FLAG_DELTA_REQUESTS != 0 {
+            writer.append(&frames[i])?;
+        } else {
+            let (block, codec) = reader.get_frame_raw(i)?;
+            writer.append_raw(&block, frames[i].request_hash, codec)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::Header;
+    use std::io::Cursor;
+
+    fn frame(req: &[u8], resp: &[u8], ts: u64) -> Frame {
+        Frame::new(req.to_vec(), resp.to_vec(), 1, ts)
+    }
+
+    #[test]
+    fn dedupes_and_sorts() {
+        let frames = vec![frame(b"a", b"1", 30), frame(b"b", b"2", 10), frame(b"a", b"3", 20)];
+
+        assert_eq!(compact_order(&frames, KeepMode::Last, SortKey::Timestamp), vec![1, 2]);
+        assert_eq!(compact_order(&frames, KeepMode::First, SortKey::Index), vec![0, 1]);
+    }
+
+    #[test]
+    fn compact_copies_stored_blocks() {
+        let mut src = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut src, &Header::default()).unwrap();
+        for f in [frame(b"a", b"1", 20), frame(b"b", b"2", 10)] {
+            writer.append(&f).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(src)).unwrap();
+        let mut out = Vec::new();
+        compact(&mut reader, &mut out, KeepMode::Last, SortKey::Timestamp).unwrap();
+
+        let mut compacted = GhostlineReader::from_reader(Cursor::new(out)).unwrap();
+        assert_eq!(compacted.get_frame_raw(0).unwrap(), reader.get_frame_raw(1).unwrap());
+        assert_eq!(compacted.get_frame(1).unwrap().response_bytes, b"1");
+    }
+
+    #[test]
+    fn filter_keeps_matching_frames_in_order() {
+        let mut src = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut src, &Header::default()).unwrap();
+        for f in [frame(b"a", br#"{"error":{"type":"overloaded_error"}}"#, 1), frame(b"b", b"{}", 2), frame(b"c", b"{}", 3)] {
+            writer.append(&f).unwrap();
+        }
+        writer.append(&Frame::builder().request(b"d".to_vec()).response(b"{}".to_vec()).status(Some(500)).build()).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(src)).unwrap();
+        let mut out = Vec::new();
+        let report = filter(&mut reader, &mut out, Frame::is_error).unwrap();
+        assert_eq!(report, CompactReport { frames_before: 4, frames_after: 2 });
+
+        let mut filtered = GhostlineReader::from_reader(Cursor::new(out)).unwrap();
+        assert!(filtered.get_frame(0).unwrap().content_eq(&frame(b"a", br#"{"error":{"type":"overloaded_error"}}"#, 0)));
+        assert_eq!(filtered.get_frame(1).unwrap().status, Some(500));
+    }
+}
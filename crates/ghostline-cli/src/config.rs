@@ -1,4 +1,8 @@
+use crate::timefmt::Timezone;
+use ghostline_core::HashAlgo;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +12,10 @@ pub struct Config {
     pub viewer: ViewerConfig,
     pub recording: RecordingConfig,
     pub display: DisplayConfig,
+    #[serde(default)]
+    pub server: ServerConfig,
+    #[serde(default)]
+    pub replay: ReplayConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,16 +23,53 @@ pub struct AuthConfig {
     pub claude_token: Option<String>,
 }
 
+impl AuthConfig {
+    /// The token as entered, decoded from its base64 storage.
+    pub fn token(&self) -> Option<String> {
+        use base64::Engine;
+        let decoded = base64::engine::general_purpose::STANDARD.decode(self.claude_token.as_deref()?).ok()?;
+        String::from_utf8(decoded).ok()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProxyConfig {
     pub port: u16,
     pub target: String,
+    /// Request bodies larger than this are streamed upstream instead of buffered.
+    #[serde(default = "default_stream_threshold")]
+    pub stream_threshold_bytes: usize,
+    /// Request header whose value selects an entry of `routes`.
+    #[serde(default = "default_route_header")]
+    pub route_header: String,
+    /// Upstream base URL per `route_header` value; requests without a
+    /// matching route go to `target`.
+    #[serde(default)]
+    pub routes: HashMap<String, String>,
+}
+
+fn default_stream_threshold() -> usize {
+    8 * 1024 * 1024
+}
+
+fn default_route_header() -> String {
+    "x-provider".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ViewerConfig {
     pub port: u16,
     pub auto_open_browser: bool,
+    /// Live messages a `/ws/live` socket can fall behind by before it skips
+    /// ahead (and is told how many it missed). Each slot holds one message
+    /// for as long as the slowest socket hasn't read it, so raising this
+    /// trades memory for fewer drops with a slow viewer and a fast proxy.
+    #[serde(default = "default_broadcast_capacity")]
+    pub broadcast_capacity: usize,
+}
+
+fn default_broadcast_capacity() -> usize {
+    256
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,11 +77,193 @@ pub struct RecordingConfig {
     pub output_dir: String,
     pub scrub: bool,
     pub default_model: String,
+    /// How frame timestamps are assigned.
+    #[serde(default)]
+    pub clock: ClockMode,
+    /// Store each request as a delta against the previous one. Much smaller
+    /// files for long agent sessions; only readable by ghostline-core.
+    #[serde(default)]
+    pub delta_requests: bool,
+    /// Truncate request and response bodies longer than this when recording.
+    /// Truncated frames are marked and keep the full request's hash.
+    #[serde(default)]
+    pub max_body_bytes: Option<usize>,
+    /// JSON paths (e.g. `messages[*].content`) redacted from request and
+    /// response bodies when `scrub` is on.
+    #[serde(default)]
+    pub scrub_json_paths: Vec<String>,
+    /// Leave `event: ping` keepalives out of recorded SSE response bodies.
+    #[serde(default)]
+    pub drop_sse_pings: bool,
+    /// File of rules for which requests get recorded; see `filter.rs` for
+    /// the syntax. Everything is recorded without one.
+    #[serde(default)]
+    pub filter_file: Option<String>,
+    /// Hold at most this many index entries in memory while recording,
+    /// parking the rest in a temp file until the recording is finalized.
+    /// Unset keeps the whole index in memory.
+    #[serde(default)]
+    pub index_spill_entries: Option<usize>,
+    /// Request hash algorithm for new recordings, `sha256` or `blake3`.
+    /// Stored in each file's header, so readers use the file's own.
+    #[serde(default)]
+    pub hash_algo: HashAlgo,
+    /// Write a `<file>.meta.json` summary next to each finished recording.
+    #[serde(default)]
+    pub emit_meta: bool,
+    /// Store request bodies. Off keeps only their hash and length, for
+    /// metadata-only recordings.
+    #[serde(default = "default_true")]
+    pub capture_request_body: bool,
+    /// Store response bodies. Off keeps only their length; replay then
+    /// answers those requests as misses.
+    #[serde(default = "default_true")]
+    pub capture_response_body: bool,
+    /// Which request's headers frames record: the client's, or the one
+    /// forwarded upstream.
+    #[serde(default)]
+    pub capture: CaptureMode,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Source of frame timestamps.
+///
+/// `MonotonicFromZero` replaces wall-clock capture times with milliseconds
+/// since the session's first frame (which is 0), and writes a header
+/// `started_at` of 0, so repeated recordings of the same traffic produce
+/// comparable files. `timestamp` then no longer identifies when a frame was
+/// captured; `latency_ms` is still measured, so anything that paces replay
+/// from recorded latencies behaves the same in either mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClockMode {
+    #[default]
+    Wall,
+    MonotonicFromZero,
+}
+
+impl std::str::FromStr for ClockMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "wall" => Ok(Self::Wall),
+            "monotonic-from-zero" => Ok(Self::MonotonicFromZero),
+            _ => anyhow::bail!("unknown clock mode '{}' (use wall or monotonic-from-zero)", s),
+        }
+    }
+}
+
+/// Side of the proxy whose request headers a frame records.
+///
+/// `Client` keeps the headers as the client sent them; `Upstream` keeps the
+/// ones actually forwarded, after the proxy drops `host`, hop-by-hop headers
+/// and the route header. Bodies are forwarded unchanged, so the request body
+/// and its hash are the same either way and both replay alike.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptureMode {
+    #[default]
+    Client,
+    Upstream,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DisplayConfig {
     pub colors: bool,
+    /// Zone every command prints timestamps in.
+    #[serde(default)]
+    pub timezone: Timezone,
+    /// Bytes of each body `ghostline show` prints before cutting it off.
+    #[serde(default = "default_preview_bytes")]
+    pub preview_bytes: usize,
+}
+
+fn default_preview_bytes() -> usize {
+    2000
+}
+
+/// Settings for `ghostline replay`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReplayConfig {
+    /// Headers added to every replayed response, for clients that expect one
+    /// (`server`, `anthropic-ratelimit-*`) the recording doesn't have.
+    /// `--response-header` flags add to these and win on the same name.
+    #[serde(default)]
+    pub response_headers: HashMap<String, String>,
+}
+
+/// Settings shared by the proxy, replay and viewer servers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub bind: BindAddr,
+}
+
+/// Loopback address the servers listen on. Always loopback: recordings hold
+/// API traffic and must not be reachable from the network.
+///
+/// `Localhost` binds both IPv4 and IPv6 on the same port, for systems where
+/// `localhost` resolves to `::1`; where IPv6 is unavailable it warns and
+/// serves on IPv4 alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum BindAddr {
+    #[default]
+    #[serde(rename = "127.0.0.1")]
+    #[value(name = "127.0.0.1")]
+    Ipv4,
+    #[serde(rename = "::1")]
+    #[value(name = "::1")]
+    Ipv6,
+    #[serde(rename = "localhost")]
+    #[value(name = "localhost")]
+    Localhost,
+}
+
+impl BindAddr {
+    /// Where a client on this machine reaches a server bound this way.
+    pub fn connect_addr(self, port: u16) -> SocketAddr {
+        match self {
+            Self::Ipv6 => SocketAddr::from((Ipv6Addr::LOCALHOST, port)),
+            Self::Ipv4 | Self::Localhost => SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        }
+    }
+
+    /// Host part of a URL for [`connect_addr`](Self::connect_addr).
+    pub fn url_host(self) -> &'static str {
+        match self {
+            Self::Ipv6 => "[::1]",
+            Self::Ipv4 | Self::Localhost => "localhost",
+        }
+    }
+
+    /// Bind non-blocking listeners on `port`. For `Localhost` the IPv6 one
+    /// takes whatever port IPv4 got, so port 0 still yields a single port,
+    /// and is skipped with a warning if it can't be bound.
+    pub fn listen(self, port: u16) -> std::io::Result<Vec<TcpListener>> {
+        let listeners = match self {
+            Self::Ipv4 => vec![TcpListener::bind((Ipv4Addr::LOCALHOST, port))?],
+            Self::Ipv6 => vec![TcpListener::bind((Ipv6Addr::LOCALHOST, port))?],
+            Self::Localhost => {
+                let v4 = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+                let v4_port = v4.local_addr()?.port();
+                match TcpListener::bind((Ipv6Addr::LOCALHOST, v4_port)) {
+                    Ok(v6) => vec![v4, v6],
+                    Err(e) => {
+                        eprintln!("ghostline: warning: cannot bind [::1]:{} ({}); listening on 127.0.0.1 only", v4_port, e);
+                        vec![v4]
+                    }
+                }
+            }
+        };
+        for listener in &listeners {
+            listener.set_nonblocking(true)?;
+        }
+        Ok(listeners)
+    }
 }
 
 impl Default for Config {
@@ -46,22 +273,168 @@ impl Default for Config {
             proxy: ProxyConfig {
                 port: 9000,
                 target: "https://api.anthropic.com".to_string(),
+                stream_threshold_bytes: default_stream_threshold(),
+                route_header: default_route_header(),
+                routes: HashMap::new(),
             },
             viewer: ViewerConfig {
                 port: 5173,
                 auto_open_browser: true,
+                broadcast_capacity: default_broadcast_capacity(),
             },
             recording: RecordingConfig {
                 output_dir: "~/.ghostline/runs".to_string(),
                 scrub: true,
                 default_model: "claude-3-haiku-20240307".to_string(),
+                clock: ClockMode::Wall,
+                delta_requests: false,
+                max_body_bytes: None,
+                scrub_json_paths: Vec::new(),
+                drop_sse_pings: false,
+                filter_file: None,
+                index_spill_entries: None,
+                hash_algo: HashAlgo::Sha256,
+                emit_meta: false,
+                capture_request_body: true,
+                capture_response_body: true,
+                capture: CaptureMode::Client,
             },
-            display: DisplayConfig { colors: true },
+            display: DisplayConfig { colors: true, timezone: Timezone::Utc, preview_bytes: default_preview_bytes() },
+            server: ServerConfig::default(),
+            replay: ReplayConfig::default(),
         }
     }
 }
 
+/// One layer of configuration in which every field is optional: the config
+/// file, and the env and flag layers above it. [`Config::merge`] applies the
+/// fields a layer sets and leaves the rest.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialConfig {
+    pub auth: PartialAuthConfig,
+    pub proxy: PartialProxyConfig,
+    pub viewer: PartialViewerConfig,
+    pub recording: PartialRecordingConfig,
+    pub display: PartialDisplayConfig,
+    pub server: PartialServerConfig,
+    pub replay: PartialReplayConfig,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialAuthConfig {
+    pub claude_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialProxyConfig {
+    pub port: Option<u16>,
+    pub target: Option<String>,
+    pub stream_threshold_bytes: Option<usize>,
+    pub route_header: Option<String>,
+    pub routes: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialViewerConfig {
+    pub port: Option<u16>,
+    pub auto_open_browser: Option<bool>,
+    pub broadcast_capacity: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialRecordingConfig {
+    pub output_dir: Option<String>,
+    pub scrub: Option<bool>,
+    pub default_model: Option<String>,
+    pub clock: Option<ClockMode>,
+    pub delta_requests: Option<bool>,
+    pub max_body_bytes: Option<usize>,
+    pub scrub_json_paths: Option<Vec<String>>,
+    pub drop_sse_pings: Option<bool>,
+    pub filter_file: Option<String>,
+    pub index_spill_entries: Option<usize>,
+    pub hash_algo: Option<HashAlgo>,
+    pub emit_meta: Option<bool>,
+    pub capture_request_body: Option<bool>,
+    pub capture_response_body: Option<bool>,
+    pub capture: Option<CaptureMode>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialDisplayConfig {
+    pub colors: Option<bool>,
+    pub timezone: Option<Timezone>,
+    pub preview_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialServerConfig {
+    pub bind: Option<BindAddr>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PartialReplayConfig {
+    pub response_headers: Option<HashMap<String, String>>,
+}
+
+/// Copy each field `layer` sets onto `base`.
+macro_rules! overlay {
+    ($base:expr, $layer:expr, $($field:ident),+ $(,)?) => {
+        $(if let Some(value) = $layer.$field {
+            $base.$field = value;
+        })+
+    };
+}
+
 impl Config {
+    /// Overlay the fields `layer` sets. Layers merge in order — defaults, the
+    /// config file, then the global CLI flags — so each wins over the ones
+    /// before it.
+    pub fn merge(&mut self, layer: PartialConfig) {
+        let PartialConfig { auth, proxy, viewer, recording, display, server, replay } = layer;
+        if auth.claude_token.is_some() {
+            self.auth.claude_token = auth.claude_token;
+        }
+        overlay!(self.proxy, proxy, port, target, stream_threshold_bytes, route_header, routes);
+        overlay!(self.viewer, viewer, port, auto_open_browser, broadcast_capacity);
+        if recording.max_body_bytes.is_some() {
+            self.recording.max_body_bytes = recording.max_body_bytes;
+        }
+        if recording.filter_file.is_some() {
+            self.recording.filter_file = recording.filter_file;
+        }
+        if recording.index_spill_entries.is_some() {
+            self.recording.index_spill_entries = recording.index_spill_entries;
+        }
+        overlay!(
+            self.recording,
+            recording,
+            output_dir,
+            scrub,
+            default_model,
+            clock,
+            delta_requests,
+            scrub_json_paths,
+            drop_sse_pings,
+            hash_algo,
+            emit_meta,
+            capture_request_body,
+            capture_response_body,
+            capture,
+        );
+        overlay!(self.display, display, colors, timezone, preview_bytes);
+        overlay!(self.server, server, bind);
+        overlay!(self.replay, replay, response_headers);
+    }
+
     pub fn config_dir() -> PathBuf {
         dirs::home_dir()
             .expect("no home dir")
@@ -79,9 +452,13 @@ impl Config {
             .join("runs")
     }
 
+    /// The defaults with the config file at `path` merged over them; keys
+    /// the file leaves out keep their defaults.
     pub fn load(path: &Path) -> anyhow::Result<Self> {
         let content = std::fs::read_to_string(path)?;
-        Ok(toml::from_str(&content)?)
+        let mut cfg = Self::default();
+        cfg.merge(toml::from_str(&content)?);
+        Ok(cfg)
     }
 
     pub fn load_or_default() -> Self {
@@ -125,6 +502,79 @@ mod tests {
         assert!(cfg.recording.scrub);
     }
 
+    #[test]
+    fn test_config_missing_new_fields() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        let mut cfg = Config::default();
+        cfg.auth.claude_token = Some("test-token".to_string());
+        let content = toml::to_string_pretty(&cfg)
+            .unwrap()
+            .lines()
+            .filter(|l| !l.starts_with("stream_threshold_bytes"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        std::fs::write(&path, content).unwrap();
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded.proxy.stream_threshold_bytes, default_stream_threshold());
+        assert_eq!(loaded.proxy.route_header, "x-provider");
+        assert!(loaded.proxy.routes.is_empty());
+        assert_eq!(loaded.auth.claude_token, Some("test-token".to_string()));
+    }
+
+    #[test]
+    fn test_capture_mode_default_and_names() {
+        let mut cfg = Config::default();
+        assert_eq!(cfg.recording.capture, CaptureMode::Client);
+        cfg.recording.capture = CaptureMode::Upstream;
+        let toml_str = toml::to_string_pretty(&cfg).unwrap();
+        assert!(toml_str.contains("capture = \"upstream\""));
+        let parsed: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(parsed.recording.capture, CaptureMode::Upstream);
+    }
+
+    #[test]
+    fn test_clock_mode_names() {
+        let mut cfg = Config::default();
+        cfg.recording.clock = "monotonic-from-zero".parse().unwrap();
+        let toml_str = toml::to_string_pretty(&cfg).unwrap();
+        assert!(toml_str.contains("clock = \"monotonic-from-zero\""));
+        assert!("sundial".parse::<ClockMode>().is_err());
+    }
+
+    #[test]
+    fn test_bind_addr_names_and_listeners() {
+        assert_eq!(Config::default().server.bind, BindAddr::Ipv4);
+        assert_eq!(<BindAddr as clap::ValueEnum>::from_str("::1", false), Ok(BindAddr::Ipv6));
+        assert!(<BindAddr as clap::ValueEnum>::from_str("0.0.0.0", false).is_err());
+        let mut cfg = Config::default();
+        cfg.server.bind = BindAddr::Localhost;
+        assert!(toml::to_string_pretty(&cfg).unwrap().contains("bind = \"localhost\""));
+
+        let listeners = BindAddr::Ipv4.listen(0).unwrap();
+        let addr = listeners[0].local_addr().unwrap();
+        assert!(addr.ip().is_loopback() && addr.port() != 0);
+    }
+
+    #[test]
+    fn test_config_layers_merge_in_order() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let path = tmp.path().join("config.toml");
+        std::fs::write(&path, "[proxy]\nport = 9100\n\n[recording]\nscrub = false\nmax_body_bytes = 1024\n").unwrap();
+        let mut cfg = Config::load(&path).unwrap();
+        assert_eq!((cfg.proxy.port, cfg.recording.scrub, cfg.recording.max_body_bytes), (9100, false, Some(1024)));
+        assert_eq!(cfg.proxy.target, Config::default().proxy.target);
+        assert_eq!(cfg.viewer.port, 5173);
+
+        // A later layer wins where it sets a field and leaves the rest alone.
+        let flags: PartialConfig = toml::from_str("[proxy]\nport = 9200\n[server]\nbind = \"::1\"").unwrap();
+        cfg.merge(flags);
+        assert_eq!((cfg.proxy.port, cfg.server.bind), (9200, BindAddr::Ipv6));
+        assert!(!cfg.recording.scrub);
+        cfg.merge(PartialConfig::default());
+        assert_eq!(cfg.proxy.port, 9200);
+    }
+
     #[test]
     fn test_config_roundtrip() {
         let tmp = tempfile::TempDir::new().unwrap();
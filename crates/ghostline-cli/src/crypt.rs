@@ -0,0 +1,211 @@
+use anyhow::Context;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use ghostline_core::{GhostlineReader, ENCRYPTED_MAGIC};
+use std::io::{Cursor, IsTerminal, Read, Seek};
+use std::path::Path;
+
+use crate::stdio::Input;
+
+/// Passphrase source for servers and non-interactive use.
+pub const PASSPHRASE_ENV: &str = "GHOSTLINE_PASSPHRASE";
+
+/// Encrypted container layout:
+///
+/// ```text
+/// [magic "GHSTLENC": 8] [version: u32] [argon2 m_cost, t_cost, p_cost: u32 each]
+/// [salt: 16] [nonce: 24] [XChaCha20-Poly1305(plain .ghostline file)]
+/// ```
+///
+/// The header up to the nonce is authenticated as associated data. The
+/// plaintext is the whole unmodified .ghostline file.
+const CONTAINER_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = 8 + 4 + 12 + SALT_LEN + NONCE_LEN;
+/// Refuse KDF costs above these, so a crafted file can't exhaust memory
+/// (`m_cost`, KiB: 2 GiB) or stall the reader in key derivation (`t_cost`
+/// passes, `p_cost` lanes). The defaults are 19 MiB, 2 and 1.
+const MAX_M_COST: u32 = 1 << 21;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 16;
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(ENCRYPTED_MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: Params) -> anyhow::Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+fn encrypt_with(plain: &[u8], passphrase: &str, params: Params) -> anyhow::Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + plain.len() + 16);
+    out.extend_from_slice(ENCRYPTED_MAGIC);
+    out.extend_from_slice(&CONTAINER_VERSION.to_le_bytes());
+    for cost in [params.m_cost(), params.t_cost(), params.p_cost()] {
+        out.extend_from_slice(&cost.to_le_bytes());
+    }
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce);
+
+    let key = derive_key(passphrase, &salt, params)?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    let sealed = cipher
+        .encrypt(&nonce, Payload { msg: plain, aad: &out })
+        .map_err(|_| anyhow::anyhow!("encryption failed"))?;
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+/// Encrypt a whole .ghostline file with a key derived from `passphrase`.
+pub fn encrypt(plain: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    encrypt_with(plain, passphrase, Params::default())
+}
+
+/// [`encrypt`] with key derivation cheap enough for tests elsewhere.
+#[cfg(test)]
+pub(crate) fn encrypt_for_test(plain: &[u8], passphrase: &str) -> Vec<u8> {
+    encrypt_with(plain, passphrase, Params::new(64, 1, 1, Some(32)).unwrap()).unwrap()
+}
+
+/// Decrypt a container written by [`encrypt`], returning the original file.
+pub fn decrypt(data: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    if !is_encrypted(data) {
+        anyhow::bail!("not an encrypted .ghostline file");
+    }
+    if data.len() < HEADER_LEN {
+        anyhow::bail!("encrypted file is truncated");
+    }
+    let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let version = u32_at(8);
+    if version != CONTAINER_VERSION {
+        anyhow::bail!("unsupported encrypted container version {}", version);
+    }
+    let (m_cost, t_cost, p_cost) = (u32_at(12), u32_at(16), u32_at(20));
+    if m_cost > MAX_M_COST || t_cost > MAX_T_COST || p_cost > MAX_P_COST {
+        anyhow::bail!("encrypted file asks for an unreasonable key-derivation cost");
+    }
+    let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+        .map_err(|e| anyhow::anyhow!("invalid key-derivation parameters: {}", e))?;
+    let salt = &data[24..24 + SALT_LEN];
+    let nonce = XNonce::from_slice(&data[24 + SALT_LEN..HEADER_LEN]);
+
+    let key = derive_key(passphrase, salt, params)?;
+    XChaCha20Poly1305::new(&key.into())
+        .decrypt(
+            nonce,
+            Payload {
+                msg: &data[HEADER_LEN..],
+                aad: &data[..HEADER_LEN],
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("wrong passphrase, or the file has been modified"))
+}
+
+/// Passphrase from `GHOSTLINE_PASSPHRASE`; for servers, which can't prompt.
+pub fn env_passphrase() -> anyhow::Result<String> {
+    std::env::var(PASSPHRASE_ENV)
+        .with_context(|| format!("file is encrypted — set {} to read it", PASSPHRASE_ENV))
+}
+
+fn prompt(confirm: bool) -> anyhow::Result<String> {
+    if let Ok(p) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(p);
+    }
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("no terminal to ask for a passphrase — set {}", PASSPHRASE_ENV);
+    }
+    let mut input = dialoguer::Password::new().with_prompt("Passphrase");
+    if confirm {
+        input = input.with_confirmation("Confirm passphrase", "passphrases don't match");
+    }
+    Ok(input.interact()?)
+}
+
+/// Passphrase for reading: `GHOSTLINE_PASSPHRASE`, else ask on the terminal.
+pub fn read_passphrase() -> anyhow::Result<String> {
+    prompt(false)
+}
+
+/// Passphrase for a new encrypted file; asked twice when prompting.
+pub fn new_passphrase() -> anyhow::Result<String> {
+    prompt(true)
+}
+
+/// Pass `input` through, or decrypt it into memory if it is an encrypted
+/// container, calling `passphrase` only then.
+pub fn decrypt_input(
+    mut input: Input,
+    passphrase: impl FnOnce() -> anyhow::Result<String>,
+) -> anyhow::Result<Input> {
+    let mut magic = [0u8; 8];
+    let head = input.read_exact(&mut magic);
+    input.rewind()?;
+    if head.is_err() || &magic != ENCRYPTED_MAGIC {
+        return Ok(input);
+    }
+    let mut data = Vec::new();
+    input.read_to_end(&mut data)?;
+    Ok(Input::Memory(Cursor::new(decrypt(&data, &passphrase()?)?)))
+}
+
+/// Open a recording on disk for a server, decrypting with `GHOSTLINE_PASSPHRASE`.
+pub fn open_for_server(path: &Path) -> anyhow::Result<GhostlineReader<Input>> {
+    let file = Input::File(std::io::BufReader::new(std::fs::File::open(path)?));
+    Ok(GhostlineReader::from_reader(decrypt_input(file, env_passphrase)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast() -> Params {
+        Params::new(64, 1, 1, Some(32)).unwrap()
+    }
+
+    #[test]
+    fn roundtrip_and_wrong_passphrase() {
+        let plain = b"GHSTLINE pretend file contents".to_vec();
+        let sealed = encrypt_with(&plain, "hunter2", fast()).unwrap();
+        assert!(is_encrypted(&sealed));
+        assert_eq!(decrypt(&sealed, "hunter2").unwrap(), plain);
+        assert!(decrypt(&sealed, "hunter3").is_err());
+
+        let mut tampered = sealed.clone();
+        tampered[16] ^= 1; // t_cost is authenticated
+        assert!(decrypt(&tampered, "hunter2").is_err());
+
+        // Costs past the limits are refused before any key is derived.
+        for at in [12, 16, 20] {
+            let mut costly = sealed.clone();
+            costly[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            let err = decrypt(&costly, "hunter2").unwrap_err().to_string();
+            assert!(err.contains("unreasonable key-derivation cost"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn decrypt_input_passes_plain_files_through() {
+        let plain = Input::Memory(Cursor::new(b"GHSTLINE...".to_vec()));
+        let mut out = decrypt_input(plain, || panic!("no passphrase needed")).unwrap();
+        let mut back = Vec::new();
+        out.read_to_end(&mut back).unwrap();
+        assert_eq!(back, b"GHSTLINE...");
+
+        let sealed = encrypt_with(b"inner", "pw", fast()).unwrap();
+        let mut out = decrypt_input(Input::Memory(Cursor::new(sealed)), || Ok("pw".into())).unwrap();
+        let mut back = Vec::new();
+        out.read_to_end(&mut back).unwrap();
+        assert_eq!(back, b"inner");
+    }
+}
@@ -0,0 +1,276 @@
+use ghostline_core::{Frame, GhostlineReader, HashAlgo};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Seek};
+
+/// What `diff` keeps of each frame: enough to match and compare, not the bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameKey {
+    pub request_hash: [u8; 32],
+    pub response_hash: [u8; 32],
+    pub latency_ms: u64,
+}
+
+pub fn frame_keys<R: Read + Seek>(reader: &mut GhostlineReader<R>) -> io::Result<Vec<FrameKey>> {
+    let mut keys = Vec::with_capacity(reader.frame_count());
+    reader.scan(|_, frame| {
+        keys.push(FrameKey {
+            request_hash: frame.request_hash,
+            response_hash: Frame::hash_request(&frame.response_bytes),
+            latency_ms: frame.latency_ms,
+        })
+    })?;
+    Ok(keys)
+}
+
+/// Frames only match by request hash, so two runs hashed with different
+/// algorithms (`recording.hash_algo`) would share none.
+pub fn check_hash_algos(a: HashAlgo, b: HashAlgo) -> anyhow::Result<()> {
+    anyhow::ensure!(a == b, "the runs hash requests differently ({} vs {}), so no frames can match", a, b);
+    Ok(())
+}
+
+/// A frame of A and the frame of B recorded for the same request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchedFrame {
+    pub a: usize,
+    pub b: usize,
+    pub same_response: bool,
+    pub latency_a_ms: u64,
+    pub latency_b_ms: u64,
+    /// Latency change from A to B, in percent of A's (`null` in JSON when A's was 0).
+    pub latency_change_pct: f64,
+}
+
+fn pct_change(a: f64, b: f64) -> f64 {
+    if a == 0.0 {
+        if b == 0.0 { 0.0 } else { f64::INFINITY }
+    } else {
+        (b - a) / a * 100.0
+    }
+}
+
+/// Two recordings of the same scenario, frames matched by request hash. A
+/// hash recorded several times pairs its occurrences in order.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RunDiff {
+    pub frames_a: usize,
+    pub frames_b: usize,
+    pub matched: Vec<MatchedFrame>,
+    pub only_in_a: Vec<usize>,
+    pub only_in_b: Vec<usize>,
+}
+
+pub fn diff(a: &[FrameKey], b: &[FrameKey]) -> RunDiff {
+    let mut unmatched_b: HashMap<[u8; 32], VecDeque<usize>> = HashMap::new();
+    for (i, key) in b.iter().enumerate() {
+        unmatched_b.entry(key.request_hash).or_default().push_back(i);
+    }
+    let mut result = RunDiff {
+        frames_a: a.len(),
+        frames_b: b.len(),
+        ..Default::default()
+    };
+    for (i, key) in a.iter().enumerate() {
+        match unmatched_b.get_mut(&key.request_hash).and_then(VecDeque::pop_front) {
+            Some(j) => result.matched.push(MatchedFrame {
+                a: i,
+                b: j,
+                same_response: key.response_hash == b[j].response_hash,
+                latency_a_ms: key.latency_ms,
+                latency_b_ms: b[j].latency_ms,
+                latency_change_pct: pct_change(key.latency_ms as f64, b[j].latency_ms as f64),
+            }),
+            None => result.only_in_a.push(i),
+        }
+    }
+    result.only_in_b = unmatched_b.into_values().flatten().collect();
+    result.only_in_b.sort_unstable();
+    result
+}
+
+/// Latency of the matched frames in A against B.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LatencyComparison {
+    pub total_a_ms: u64,
+    pub total_b_ms: u64,
+    pub mean_a_ms: f64,
+    pub mean_b_ms: f64,
+    /// `total_b_ms` against `total_a_ms`, in percent.
+    pub change_pct: f64,
+    /// Flag frames more than this many percent slower in B.
+    pub threshold_pct: f64,
+    /// Matched frames over the threshold, slowest change first.
+    pub slower: Vec<MatchedFrame>,
+}
+
+impl RunDiff {
+    pub fn changed_responses(&self) -> usize {
+        self.matched.iter().filter(|m| !m.same_response).count()
+    }
+
+    pub fn compare_latency(&self, threshold_pct: f64) -> LatencyComparison {
+        let total_a_ms = self.matched.iter().map(|m| m.latency_a_ms).sum();
+        let total_b_ms = self.matched.iter().map(|m| m.latency_b_ms).sum();
+        let mean = |total: u64| if self.matched.is_empty() { 0.0 } else { total as f64 / self.matched.len() as f64 };
+        let mut slower: Vec<_> = self
+            .matched
+            .iter()
+            .filter(|m| m.latency_change_pct > threshold_pct)
+            .cloned()
+            .collect();
+        slower.sort_by(|x, y| y.latency_change_pct.total_cmp(&x.latency_change_pct));
+        LatencyComparison {
+            total_a_ms,
+            total_b_ms,
+            mean_a_ms: mean(total_a_ms),
+            mean_b_ms: mean(total_b_ms),
+            change_pct: pct_change(total_a_ms as f64, total_b_ms as f64),
+            threshold_pct,
+            slower,
+        }
+    }
+}
+
+/// How a matched frame's response changed from A to B, located by the first
+/// byte where the bodies part.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ResponseChange {
+    pub a: usize,
+    pub b: usize,
+    /// Hex-encoded request hash the two frames share.
+    pub request_hash: String,
+    pub size_a: usize,
+    pub size_b: usize,
+    /// Offset of the first differing byte (the shorter length when one body
+    /// is a prefix of the other).
+    pub first_difference: usize,
+    /// Up to [`EXCERPT_BYTES`] of each body from `first_difference`, as text
+    /// (invalid UTF-8 replaced).
+    pub excerpt_a: String,
+    pub excerpt_b: String,
+}
+
+pub const EXCERPT_BYTES: usize = 64;
+
+fn response_change(a: usize, b: usize, frame_a: &Frame, frame_b: &Frame) -> ResponseChange {
+    let (body_a, body_b) = (&frame_a.response_bytes, &frame_b.response_bytes);
+    let first_difference = body_a.iter().zip(body_b.iter()).take_while(|(x, y)| x == y).count();
+    let excerpt = |body: &[u8]| {
+        let end = body.len().min(first_difference + EXCERPT_BYTES);
+        String::from_utf8_lossy(&body[first_difference.min(end)..end]).into_owned()
+    };
+    ResponseChange {
+        a,
+        b,
+        request_hash: hex::encode(frame_a.request_hash),
+        size_a: body_a.len(),
+        size_b: body_b.len(),
+        first_difference,
+        excerpt_a: excerpt(body_a),
+        excerpt_b: excerpt(body_b),
+    }
+}
+
+impl RunDiff {
+    /// The matched frames whose responses differ, read back from both files.
+    pub fn response_changes<R: Read + Seek, S: Read + Seek>(
+        &self,
+        a: &mut GhostlineReader<R>,
+        b: &mut GhostlineReader<S>,
+    ) -> io::Result<Vec<ResponseChange>> {
+        self.matched
+            .iter()
+            .filter(|m| !m.same_response)
+            .map(|m| Ok(response_change(m.a, m.b, &a.get_frame(m.a)?, &b.get_frame(m.b)?)))
+            .collect()
+    }
+}
+
+fn fmt_indices(indices: &[usize]) -> String {
+    indices.iter().map(|i| format!("[{}]", i)).collect::<Vec<_>>().join(" ")
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Frames:      A {} · B {}", self.frames_a, self.frames_b)?;
+        write!(
+            f,
+            "Matched:     {} ({} same response, {} changed)",
+            self.matched.len(),
+            self.matched.len() - self.changed_responses(),
+            self.changed_responses()
+        )?;
+        if !self.only_in_a.is_empty() {
+            write!(f, "\nOnly in A:   {}", fmt_indices(&self.only_in_a))?;
+        }
+        if !self.only_in_b.is_empty() {
+            write!(f, "\nOnly in B:   {}", fmt_indices(&self.only_in_b))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LatencyComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Latency:     total {}ms → {}ms ({:+.1}%) · mean {:.0}ms → {:.0}ms",
+            self.total_a_ms, self.total_b_ms, self.change_pct, self.mean_a_ms, self.mean_b_ms
+        )?;
+        write!(f, "Slower than +{}%: {}", self.threshold_pct, self.slower.len())?;
+        for m in &self.slower {
+            write!(
+                f,
+                "\n  A[{}] → B[{}]  {}ms → {}ms ({:+.1}%)",
+                m.a,
+                m.b,
+                m.latency_a_ms,
+                m.latency_b_ms,
+                m.latency_change_pct
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(request: &str, response: &str, latency_ms: u64) -> FrameKey {
+        FrameKey {
+            request_hash: Frame::hash_request(request.as_bytes()),
+            response_hash: Frame::hash_request(response.as_bytes()),
+            latency_ms,
+        }
+    }
+
+    #[test]
+    fn matches_by_hash_in_order() {
+        let a = [key("x", "1", 100), key("y", "2", 100), key("x", "3", 100), key("gone", "4", 10)];
+        let b = [key("x", "1", 150), key("new", "5", 10), key("y", "changed", 90), key("x", "3", 100)];
+        let d = diff(&a, &b);
+        let pairs: Vec<_> = d.matched.iter().map(|m| (m.a, m.b, m.same_response)).collect();
+        assert_eq!(pairs, vec![(0, 0, true), (1, 2, false), (2, 3, true)]);
+        assert_eq!((d.only_in_a.as_slice(), d.only_in_b.as_slice()), (&[3][..], &[1][..]));
+
+        let latency = d.compare_latency(20.0);
+        assert_eq!((latency.total_a_ms, latency.total_b_ms), (300, 340));
+        assert!((latency.change_pct - 13.33).abs() < 0.01);
+        assert_eq!(latency.slower.iter().map(|m| m.a).collect::<Vec<_>>(), vec![0]);
+    }
+
+    #[test]
+    fn response_change_locates_first_difference() {
+        let frame = |response: &str| Frame::new(b"req".to_vec(), response.as_bytes().to_vec(), 1, 0);
+        let change = response_change(2, 5, &frame(r#"{"text":"hello"}"#), &frame(r#"{"text":"help me"}"#));
+        assert_eq!((change.a, change.b, change.first_difference), (2, 5, 12));
+        assert_eq!((change.excerpt_a.as_str(), change.excerpt_b.as_str()), (r#"lo"}"#, r#"p me"}"#));
+        assert_eq!((change.size_a, change.size_b), (16, 18));
+
+        let prefix = response_change(0, 0, &frame("abc"), &frame("abcdef"));
+        assert_eq!((prefix.first_difference, prefix.excerpt_a.as_str(), prefix.excerpt_b.as_str()), (3, "", "def"));
+    }
+}
@@ -0,0 +1,288 @@
+use base64::Engine;
+use crate::stdio;
+use crate::timefmt::{format_minute, Timezone};
+use ghostline_core::{Frame, GhostlineReader};
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+
+/// Render a standalone HTML viewer with the recording embedded as base64.
+///
+/// Viewer JS/CSS are read from `GHOSTLINE_VIEWER_DIST` (default `viewer/dist`).
+/// `file` may be `-` to read the recording from stdin. Encrypted recordings
+/// are decrypted first, since the viewer can't.
+pub fn render_html(file: &str) -> anyhow::Result<String> {
+    let raw = read_decrypted(stdio::open_input(file)?, crate::crypt::read_passphrase)?;
+    let filename = if stdio::is_stdio(file) {
+        "stdin.ghostline".into()
+    } else {
+        std::path::Path::new(file)
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+    };
+    html_document(&raw, &filename)
+}
+
+/// The whole of `input`, decrypted if it is an encrypted container.
+fn read_decrypted(
+    input: stdio::Input,
+    passphrase: impl FnOnce() -> anyhow::Result<String>,
+) -> anyhow::Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    crate::crypt::decrypt_input(input, passphrase)?.read_to_end(&mut raw)?;
+    Ok(raw)
+}
+
+/// The HTML viewer page for recording `raw`, titled with `filename`.
+fn html_document(raw: &[u8], filename: &str) -> anyhow::Result<String> {
+    let data_b64 = base64::engine::general_purpose::STANDARD.encode(raw);
+    let viewer_dir =
+        std::env::var("GHOSTLINE_VIEWER_DIST").unwrap_or_else(|_| "viewer/dist".to_string());
+    let assets_dir = format!("{}/assets", viewer_dir);
+    let mut js_content = String::new();
+    let mut css_content = String::new();
+    for entry in std::fs::read_dir(&assets_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("js") => js_content = std::fs::read_to_string(&path)?,
+            Some("css") => css_content = std::fs::read_to_string(&path)?,
+            _ => {}
+        }
+    }
+    if js_content.is_empty() || css_content.is_empty() {
+        anyhow::bail!(
+            "viewer assets not found in {}. Run 'cd viewer && npm run build' or set GHOSTLINE_VIEWER_DIST",
+            assets_dir
+        );
+    }
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8" />
+  <meta name="viewport" content="width=device-width, initial-scale=1.0" />
+  <title>Ghostline — {filename}</title>
+  <style>{css_content}</style>
+</head>
+<body>
+  <div id="root"></div>
+  <script id="ghostline-data" type="application/octet-stream" data-filename="{filename}">{data_b64}</script>
+  <script type="module">{js_content}</script>
+</body>
+</html>"#
+    ))
+}
+
+/// Which frames a JSON export holds, by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    All,
+    Frame(usize),
+    /// The first `n` frames.
+    Head(usize),
+    /// The last `n` frames.
+    Tail(usize),
+}
+
+/// Render frames as pretty-printed JSON, the ones `selection` picks.
+/// `file` may be `-` to read the recording from stdin.
+pub fn render_json(file: &str, selection: Selection, decode: bool) -> anyhow::Result<String> {
+    let mut reader = stdio::open_reader(file)?;
+    Ok(serde_json::to_string_pretty(&frames_json(&mut reader, selection, decode)?)?)
+}
+
+/// A body for `--decode`: JSON (or MessagePack) as JSON, other UTF-8 as a
+/// string, `None` for binary, which keeps its base64.
+fn decoded_body(json: Option<serde_json::Value>, bytes: &[u8]) -> Option<serde_json::Value> {
+    json.or_else(|| std::str::from_utf8(bytes).ok().map(|text| text.into()))
+}
+
+/// The JSON export shape: each frame's summary plus base64 bodies, in index
+/// order. With `decode`, bodies that decode are given as `request` and
+/// `response` instead (a streamed response as its reconstructed message).
+pub fn frames_json<R: Read + Seek>(
+    reader: &mut GhostlineReader<R>,
+    selection: Selection,
+    decode: bool,
+) -> anyhow::Result<Vec<serde_json::Value>> {
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let to_json = |i: usize, frame: &Frame| {
+        let mut json = serde_json::to_value(frame.summary(i)).unwrap_or_default();
+        let request = decode.then(|| decoded_body(frame.request_json(), &frame.request_bytes)).flatten();
+        let response = decode
+            .then(|| decoded_body(frame.reconstruct_message().or_else(|| frame.response_json()), &frame.response_bytes))
+            .flatten();
+        match request {
+            Some(request) => json["request"] = request,
+            None => json["request_b64"] = b64.encode(&frame.request_bytes).into(),
+        }
+        match response {
+            Some(response) => json["response"] = response,
+            None => json["response_b64"] = b64.encode(&frame.response_bytes).into(),
+        }
+        json["response_content_type"] = ghostline_core::sniff_content_type(&frame.response_bytes).into();
+        json
+    };
+    let count = reader.frame_count();
+    let range = match selection {
+        Selection::All => None,
+        Selection::Frame(idx) => Some(idx..idx + 1),
+        Selection::Head(n) => Some(0..n.min(count)),
+        Selection::Tail(n) => Some(count.saturating_sub(n)..count),
+    };
+    let frames = match range {
+        Some(range) => range.map(|i| Ok(to_json(i, &reader.get_frame(i)?))).collect::<anyhow::Result<_>>()?,
+        None => {
+            let mut frames = Vec::with_capacity(reader.frame_count());
+            reader.scan(|i, frame| frames.push(to_json(i, frame)))?;
+            // scan visits in file order; keep the output in index order.
+            frames.sort_by_key(|f| f["index"].as_u64());
+            frames
+        }
+    };
+    Ok(frames)
+}
+
+/// One recording in an `export-all` archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    pub source: String,
+    /// File name of the export, relative to the archive directory.
+    pub output: String,
+    pub frames: usize,
+    /// Unix ms, as in [`crate::runs::RunFile::started_at`].
+    pub started_at: u64,
+}
+
+/// Export every `.ghostline` in `dir` to `out` in `format` (`html` or
+/// `json`) and write an `index.html` linking them, newest first. Files that
+/// can't be read or exported are reported and left out.
+pub fn export_all(dir: &Path, out: &Path, format: &str, timezone: Timezone) -> anyhow::Result<Vec<ArchiveEntry>> {
+    let ext = if format == "html" { "html" } else { "json" };
+    std::fs::create_dir_all(out)?;
+    let mut entries = Vec::new();
+    for run in crate::runs::list_runs(dir)? {
+        let source = run.path.to_string_lossy().into_owned();
+        let name = run.name;
+        // Read (and decrypt) each run once, for both the count and the export.
+        let export = || -> anyhow::Result<(usize, String)> {
+            let raw = read_decrypted(stdio::open_input(&source)?, crate::crypt::read_passphrase)?;
+            let mut reader = GhostlineReader::from_reader(Cursor::new(&raw[..]))?;
+            let rendered = match ext {
+                "html" => html_document(&raw, name.rsplit('/').next().unwrap_or(&name))?,
+                _ => serde_json::to_string_pretty(&frames_json(&mut reader, Selection::All, false)?)?,
+            };
+            Ok((reader.frame_count(), rendered))
+        };
+        let exported = export();
+        let (frames, rendered) = match exported {
+            Ok(exported) => exported,
+            Err(e) => {
+                eprintln!("ghostline: warning: skipping {}: {:#}", name, e);
+                continue;
+            }
+        };
+        let output = format!("{}.{}", name.trim_end_matches(".ghostline"), ext);
+        // Runs in subdirectories keep them, so the index links `project/run.html`.
+        if let Some(parent) = out.join(&output).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(out.join(&output), rendered)?;
+        entries.push(ArchiveEntry { source: name, output, frames, started_at: run.started_at });
+    }
+    std::fs::write(out.join("index.html"), render_index(&entries, timezone))?;
+    Ok(entries)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// The archive's table of contents.
+pub fn render_index(entries: &[ArchiveEntry], timezone: Timezone) -> String {
+    let rows: String = entries
+        .iter()
+        .map(|e| {
+            let date = format_minute(e.started_at, timezone);
+            format!(
+                "    <tr><td><a href=\"{href}\">{name}</a></td><td>{frames}</td><td>{date}</td></tr>\n",
+                href = escape_html(&e.output),
+                name = escape_html(&e.source),
+                frames = e.frames,
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="UTF-8" />
+  <title>Ghostline recordings</title>
+  <style>body {{ font-family: system-ui, sans-serif; margin: 2rem; }} td, th {{ padding: 0.25rem 1rem; text-align: left; }}</style>
+</head>
+<body>
+  <h1>Ghostline recordings</h1>
+  <table>
+    <tr><th>Recording</th><th>Frames</th><th>Started</th></tr>
+{rows}  </table>
+</body>
+</html>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_links_each_export() {
+        let html = render_index(&[ArchiveEntry {
+            source: "a<b>.ghostline".into(),
+            output: "a<b>.html".into(),
+            frames: 3,
+            started_at: 1_700_000_000_000,
+        }], Timezone::Utc);
+        assert!(html.contains(r#"<a href="a&lt;b&gt;.html">a&lt;b&gt;.ghostline</a></td><td>3</td><td>2023-11-14 22:13 UTC</td>"#), "{}", html);
+    }
+
+    #[test]
+    fn decrypts_before_embedding() {
+        let mut plain = Vec::new();
+        let mut writer = ghostline_core::GhostlineWriter::new(&mut plain, &ghostline_core::Header::default()).unwrap();
+        writer.append(&Frame::new(b"q".to_vec(), b"a".to_vec(), 1, 0)).unwrap();
+        writer.finish().unwrap();
+        let sealed = crate::crypt::encrypt_for_test(&plain, "hunter2");
+
+        let memory = |data: &[u8]| stdio::Input::Memory(Cursor::new(data.to_vec()));
+        assert_eq!(read_decrypted(memory(&sealed), || Ok("hunter2".into())).unwrap(), plain);
+        assert!(read_decrypted(memory(&sealed), || Ok("wrong".into())).is_err());
+        let unasked = || -> anyhow::Result<String> { panic!("plain files need no passphrase") };
+        assert_eq!(read_decrypted(memory(&plain), unasked).unwrap(), plain);
+    }
+
+    #[test]
+    fn head_and_tail_slice_and_decode() {
+        use ghostline_core::{GhostlineWriter, Header};
+        let mut src = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut src, &Header::default()).unwrap();
+        for (request, response) in [(&b"{\"q\":0}"[..], &b"{\"a\":0}"[..]), (b"{\"q\":1}", b"plain"), (b"{\"q\":2}", b"\xc1\xff")] {
+            writer.append(&Frame::new(request.to_vec(), response.to_vec(), 1, 0)).unwrap();
+        }
+        writer.finish().unwrap();
+        let mut reader = GhostlineReader::from_reader(std::io::Cursor::new(src)).unwrap();
+
+        let indices = |frames: Vec<serde_json::Value>| frames.iter().map(|f| f["index"].as_u64().unwrap()).collect::<Vec<_>>();
+        assert_eq!(indices(frames_json(&mut reader, Selection::Head(2), false).unwrap()), vec![0, 1]);
+        assert_eq!(indices(frames_json(&mut reader, Selection::Tail(2), false).unwrap()), vec![1, 2]);
+        assert_eq!(indices(frames_json(&mut reader, Selection::Tail(10), false).unwrap()), vec![0, 1, 2]);
+
+        let decoded = frames_json(&mut reader, Selection::All, true).unwrap();
+        assert_eq!(decoded[0]["request"], serde_json::json!({ "q": 0 }));
+        assert_eq!(decoded[0]["response"], serde_json::json!({ "a": 0 }));
+        assert_eq!(decoded[1]["response"], "plain");
+        assert!(decoded[2].get("response").is_none());
+        assert_eq!(decoded[2]["response_b64"], "wf8=");
+    }
+}
@@ -0,0 +1,198 @@
+//! `recording.filter_file`: which requests the proxy records.
+//!
+//! One rule per line; blank lines and lines starting with `#` are skipped. A
+//! rule excludes the requests it matches and `!` in front re-includes them.
+//! As in `.gitignore` the last matching rule decides, and a request no rule
+//! matches is recorded.
+//!
+//! ```text
+//! # token counting is noise
+//! /v1/messages/count_tokens
+//! # nothing from the haiku evals...
+//! json:model=claude-3-haiku*
+//! # ...except the ones under review
+//! !json:metadata.user_id=review-*
+//! ```
+//!
+//! A rule starting with `/` is a glob over the request path: `*` and `?`
+//! stay within a segment, `**` crosses them. `json:<path>` matches request
+//! bodies in which the JSON path (as in `recording.scrub_json_paths`) exists,
+//! and `json:<path>=<glob>` those where a value it selects, as text, matches
+//! the glob.
+
+use crate::scrub::JsonPath;
+use anyhow::Context;
+use serde_json::Value;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Path(String),
+    Json { path: JsonPath, value: Option<String> },
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    negated: bool,
+    pattern: Pattern,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecordFilter {
+    rules: Vec<Rule>,
+}
+
+impl std::str::FromStr for RecordFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, rule) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest.trim_start()),
+                None => (false, line),
+            };
+            let pattern = if rule.starts_with('/') {
+                Pattern::Path(rule.to_string())
+            } else if let Some(predicate) = rule.strip_prefix("json:") {
+                let (path, value) = match predicate.split_once('=') {
+                    Some((path, value)) => (path, Some(value.to_string())),
+                    None => (predicate, None),
+                };
+                let path = path.trim().parse().map_err(anyhow::Error::msg).with_context(|| format!("line {}", n + 1))?;
+                Pattern::Json { path, value }
+            } else {
+                anyhow::bail!("line {}: expected a /path glob or json:<path>[=<glob>], got {:?}", n + 1, line);
+            };
+            rules.push(Rule { negated, pattern });
+        }
+        Ok(Self { rules })
+    }
+}
+
+impl RecordFilter {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("cannot read filter file {}", path.display()))?;
+        text.parse().with_context(|| format!("bad filter file {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Whether any rule looks at the request body, not just its path.
+    pub fn reads_bodies(&self) -> bool {
+        self.rules.iter().any(|rule| matches!(rule.pattern, Pattern::Json { .. }))
+    }
+
+    /// Whether a request to `path` with body `body` should be recorded.
+    pub fn records(&self, path: &str, body: &[u8]) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+        // Parsed at most once, and only if a JSON rule needs it.
+        let mut json: Option<Option<Value>> = None;
+        let mut recorded = true;
+        for rule in &self.rules {
+            let matched = match &rule.pattern {
+                Pattern::Path(glob) => glob_match(glob.as_bytes(), path.as_bytes(), true),
+                Pattern::Json { path, value } => {
+                    let Some(body) = json.get_or_insert_with(|| serde_json::from_slice(body).ok()) else {
+                        continue;
+                    };
+                    let selected = path.select(body);
+                    match value {
+                        None => !selected.is_empty(),
+                        Some(glob) => selected.iter().any(|v| {
+                            let text = match v {
+                                Value::String(s) => s.clone(),
+                                other => other.to_string(),
+                            };
+                            glob_match(glob.as_bytes(), text.as_bytes(), false)
+                        }),
+                    }
+                }
+            };
+            if matched {
+                recorded = rule.negated;
+            }
+        }
+        recorded
+    }
+}
+
+/// Match `text` against a glob. With `segmented`, `*` and `?` don't match
+/// `/` and `**` matches anything; otherwise `*` matches anything.
+fn glob_match(pattern: &[u8], text: &[u8], segmented: bool) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => {
+            let (rest, crosses) = match rest.strip_prefix(b"*") {
+                Some(after) if segmented => (after, true),
+                _ => (rest, !segmented),
+            };
+            for i in 0..=text.len() {
+                if glob_match(rest, &text[i..], segmented) {
+                    return true;
+                }
+                if !crosses && text.get(i) == Some(&b'/') {
+                    return false;
+                }
+            }
+            false
+        }
+        Some((b'?', rest)) => match text.split_first() {
+            Some((&c, after)) if !(segmented && c == b'/') => glob_match(rest, after, segmented),
+            _ => false,
+        },
+        Some((c, rest)) => text.first() == Some(c) && glob_match(rest, &text[1..], segmented),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match(b"/v1/*", b"/v1/messages", true));
+        assert!(!glob_match(b"/v1/*", b"/v1/messages/count_tokens", true));
+        assert!(glob_match(b"/v1/**", b"/v1/messages/count_tokens", true));
+        assert!(glob_match(b"/v?/messages", b"/v1/messages", true));
+        assert!(glob_match(b"claude-*", b"claude-3/haiku", false));
+        assert!(!glob_match(b"claude-*", b"gpt-4", false));
+    }
+
+    #[test]
+    fn last_matching_rule_decides() {
+        let filter: RecordFilter = "
+            # comments and blank lines are skipped
+
+            /v1/messages/count_tokens
+            json:model=claude-3-haiku*
+            !json:metadata.user_id=review-*
+            json:stream
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(filter.len(), 4);
+        assert!(!filter.records("/v1/messages/count_tokens", b"{}"));
+        assert!(filter.records("/v1/messages", br#"{"model":"claude-sonnet-4"}"#));
+        assert!(!filter.records("/v1/messages", br#"{"model":"claude-3-haiku-20240307"}"#));
+        assert!(filter.records("/v1/messages", br#"{"model":"claude-3-haiku","metadata":{"user_id":"review-7"}}"#));
+        assert!(!filter.records("/v1/messages", br#"{"model":"x","stream":true}"#));
+        assert!(filter.records("/v1/messages", b"not json"));
+        assert!(RecordFilter::default().records("/anything", b""));
+    }
+
+    #[test]
+    fn rejects_unknown_rules() {
+        let err = "/ok\nmodel=x".parse::<RecordFilter>().unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+        assert!("json:a[".parse::<RecordFilter>().is_err());
+    }
+}
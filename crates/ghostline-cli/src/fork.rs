@@ -0,0 +1,166 @@
+//! `ghostline fork`: a new run made of part of another, with a header that
+//! points back at it — a prefix to branch from (`--at`), or any slice
+//! (`--range`) to cut a mid-session bug down to a minimal reproduction.
+
+use ghostline_core::{GhostlineReader, GhostlineWriter, Header};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{Read, Seek, Write};
+use std::str::FromStr;
+
+/// Frames `start..end` of a run, written `a..b` or `a..=b` on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRange {
+    pub start: usize,
+    /// Exclusive.
+    pub end: usize,
+}
+
+impl FromStr for FrameRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.trim().split_once("..").ok_or_else(|| format!("{:?} is not a range; use a..b or a..=b", s))?;
+        let index = |n: &str| n.parse::<usize>().map_err(|_| format!("{:?} is not a frame index", n));
+        let (start, inclusive_end) = match end.strip_prefix('=') {
+            Some(last) => (index(start)?, index(last)?.checked_add(1).ok_or("range end is too large")?),
+            None => (index(start)?, index(end)?),
+        };
+        if inclusive_end < start {
+            return Err(format!("range {} is inverted; it ends before it starts", s));
+        }
+        if inclusive_end == start {
+            return Err(format!("range {} is empty", s));
+        }
+        Ok(FrameRange { start, end: inclusive_end })
+    }
+}
+
+impl fmt::Display for FrameRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FrameRange {
+    /// Check that every frame of the range is in a file of `frame_count`.
+    pub fn check(&self, frame_count: usize) -> anyhow::Result<()> {
+        if self.end > frame_count {
+            anyhow::bail!(
+                "range {} is out of bounds — file has {} frames{}",
+                self,
+                frame_count,
+                if frame_count > 0 { format!(" (0..{})", frame_count) } else { String::new() }
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A run's id as fork lineage records it: SHA-256 of its `started_at` and its
+/// first frame's request hash.
+pub fn run_id(started_at: u64, first_request_hash: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(started_at.to_le_bytes());
+    hasher.update(first_request_hash);
+    hasher.finalize().into()
+}
+
+/// What [`fork`] wrote: the output and the lineage ids on either side.
+pub struct Forked<W> {
+    pub out: W,
+    /// The new run's own id, from the `started_at` the fork was given.
+    pub run_id: [u8; 32],
+    pub parent_run_id: [u8; 32],
+}
+
+/// Write frames `range` of `reader` to `out` as a new run whose header names
+/// `reader`'s run as the parent, with the range's first step when it isn't 0
+/// (`fork_start_step`) and its last (`fork_at_step`). The fork starts now (and
+/// after the parent), so it never shares the parent's run id. The range must
+/// have been [checked](FrameRange::check).
+pub fn fork<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    out: W,
+    range: FrameRange,
+) -> anyhow::Result<Forked<W>> {
+    let parent_run_id = run_id(reader.started_at, &reader.index_entries()[0].request_hash);
+    let header = Header {
+        parent_run_id: Some(parent_run_id),
+        fork_at_step: Some(u32::try_from(range.end - 1)?),
+        fork_start_step: (range.start > 0).then(|| u32::try_from(range.start)).transpose()?,
+        started_at: started_after(reader.started_at),
+        ..reader.header()
+    };
+    let mut writer = GhostlineWriter::new(out, &header)?;
+    for i in range.start..range.end {
+        writer.append(&reader.get_frame(i)?)?;
+    }
+    let run_id = run_id(header.started_at, &reader.index_entries()[range.start].request_hash);
+    Ok(Forked { out: writer.finish()?, run_id, parent_run_id })
+}
+
+/// The current time in Unix ms, or `parent + 1` if the clock says otherwise.
+fn started_after(parent: u64) -> u64 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+    now.max(parent.saturating_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::Frame;
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_and_checks_ranges() {
+        assert_eq!("10..50".parse(), Ok(FrameRange { start: 10, end: 50 }));
+        assert_eq!("10..=50".parse(), Ok(FrameRange { start: 10, end: 51 }));
+        for bad in ["10", "a..5", "5..", "50..10", "3..3", "4..=2"] {
+            assert!(bad.parse::<FrameRange>().is_err(), "{}", bad);
+        }
+        let range = FrameRange { start: 2, end: 6 };
+        assert!(range.check(6).is_ok());
+        assert!(range.check(5).unwrap_err().to_string().contains("out of bounds"));
+    }
+
+    #[test]
+    fn slices_record_their_parent_and_offset() {
+        let header = Header { started_at: 1_700_000_000_000, ..Default::default() };
+        let mut writer = GhostlineWriter::new(Cursor::new(Vec::new()), &header).unwrap();
+        for i in 0..6u8 {
+            writer.append(&Frame::new(vec![b'q', i], vec![b'r', i], 1, i as u64)).unwrap();
+        }
+        let mut reader = GhostlineReader::from_reader(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+        let parent = run_id(reader.started_at, &reader.index_entries()[0].request_hash);
+
+        let forked = fork(&mut reader, Cursor::new(Vec::new()), "2..5".parse().unwrap()).unwrap();
+        let parent_run_id = forked.parent_run_id;
+        let mut slice = GhostlineReader::from_reader(Cursor::new(forked.out.into_inner())).unwrap();
+        assert_eq!((slice.parent_run_id, parent_run_id), (Some(parent), parent));
+        assert_eq!((slice.fork_start_step, slice.fork_at_step), (Some(2), Some(4)));
+        assert_eq!(slice.frame_count(), 3);
+        assert_eq!(slice.get_frame(0).unwrap().request_bytes, vec![b'q', 2]);
+        assert_ne!(run_id(slice.started_at, &slice.index_entries()[0].request_hash), parent);
+
+        let forked = fork(&mut reader, Cursor::new(Vec::new()), FrameRange { start: 0, end: 2 }).unwrap();
+        let prefix_id = forked.run_id;
+        let prefix = GhostlineReader::from_reader(Cursor::new(forked.out.into_inner())).unwrap();
+        assert_eq!((prefix.fork_start_step, prefix.fork_at_step), (None, Some(1)));
+        // Same first frame as the parent, but not the same run.
+        assert_eq!(prefix.index_entries()[0].request_hash, reader.index_entries()[0].request_hash);
+        assert!(prefix.started_at > reader.started_at);
+        assert_eq!(run_id(prefix.started_at, &prefix.index_entries()[0].request_hash), prefix_id);
+        assert_ne!(prefix_id, parent);
+
+        // A parent stamped in the future still gets a later fork.
+        let header = Header { started_at: u64::MAX - 1, ..Default::default() };
+        let mut writer = GhostlineWriter::new(Cursor::new(Vec::new()), &header).unwrap();
+        writer.append(&Frame::new(b"q".to_vec(), b"r".to_vec(), 1, 0)).unwrap();
+        let mut future = GhostlineReader::from_reader(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+        let out = fork(&mut future, Cursor::new(Vec::new()), FrameRange { start: 0, end: 1 }).unwrap().out;
+        assert_eq!(GhostlineReader::from_reader(Cursor::new(out.into_inner())).unwrap().started_at, u64::MAX);
+    }
+}
@@ -0,0 +1,156 @@
+use base64::Engine;
+use ghostline_core::{Frame, GhostlineWriter, Header};
+use serde_json::Value;
+use std::io::BufWriter;
+
+/// Convert the `log.entries` of a HAR document into frames.
+///
+/// Request bodies come from `request.postData.text`, response bodies from
+/// `response.content.text` (base64-decoded when `encoding` says so). Only POST
+/// entries are kept unless `all` is set. `response.status` is kept too.
+pub fn frames_from_har(har: &Value, all: bool) -> anyhow::Result<Vec<Frame>> {
+    let entries = har
+        .pointer("/log/entries")
+        .and_then(|e| e.as_array())
+        .ok_or_else(|| anyhow::anyhow!("not a HAR file: missing log.entries"))?;
+
+    let mut frames = Vec::new();
+    for entry in entries {
+        let method = entry
+            .pointer("/request/method")
+            .and_then(|m| m.as_str())
+            .unwrap_or("");
+        if !all && !method.eq_ignore_ascii_case("POST") {
+            continue;
+        }
+
+        let request_bytes = entry
+            .pointer("/request/postData/text")
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .as_bytes()
+            .to_vec();
+
+        let content = entry.pointer("/response/content");
+        let text = content
+            .and_then(|c| c.get("text"))
+            .and_then(|t| t.as_str())
+            .unwrap_or("");
+        let response_bytes = match content.and_then(|c| c.get("encoding")).and_then(|e| e.as_str()) {
+            Some("base64") => base64::engine::general_purpose::STANDARD.decode(text)?,
+            _ => text.as_bytes().to_vec(),
+        };
+
+        let latency_ms = entry
+            .get("time")
+            .and_then(|t| t.as_f64())
+            .map(|t| t.max(0.0).round() as u64)
+            .unwrap_or(0);
+        let timestamp = entry
+            .get("startedDateTime")
+            .and_then(|t| t.as_str())
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|dt| dt.timestamp_millis().max(0) as u64)
+            .unwrap_or(0);
+
+        // HAR uses 0 for an entry that never got a response.
+        let status = entry
+            .pointer("/response/status")
+            .and_then(|s| s.as_u64())
+            .and_then(|s| u16::try_from(s).ok())
+            .filter(|&s| s != 0);
+
+        frames.push(
+            Frame::builder()
+                .request(request_bytes)
+                .response(response_bytes)
+                .latency_ms(latency_ms)
+                .timestamp(timestamp)
+                .status(status)
+                .build(),
+        );
+    }
+    Ok(frames)
+}
+
+/// Import a HAR file into a new .ghostline file. Returns the number of frames written.
+pub fn import_har(har_path: &str, output: &str, all: bool) -> anyhow::Result<usize> {
+    let har: Value = serde_json::from_slice(&std::fs::read(har_path)?)?;
+    let frames = frames_from_har(&har, all)?;
+
+    let started_at = frames
+        .iter()
+        .map(|f| f.timestamp)
+        .min()
+        .unwrap_or_else(|| chrono::Utc::now().timestamp_millis() as u64);
+    let header = Header {
+        started_at,
+        ..Default::default()
+    };
+
+    let mut buf_writer = BufWriter::new(std::fs::File::create(output)?);
+    let mut writer = GhostlineWriter::new(&mut buf_writer, &header)?;
+    for frame in &frames {
+        writer.append(frame)?;
+    }
+    writer.finish()?;
+    Ok(frames.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_har() -> Value {
+        json!({
+            "log": {
+                "entries": [
+                    {
+                        "startedDateTime": "2026-01-01T00:00:00.000Z",
+                        "time": 123.6,
+                        "request": {
+                            "method": "POST",
+                            "url": "https://api.anthropic.com/v1/messages",
+                            "postData": { "mimeType": "application/json", "text": "{\"model\":\"m\"}" }
+                        },
+                        "response": {
+                            "status": 200,
+                            "content": { "mimeType": "application/json", "text": "eyJvayI6dHJ1ZX0=", "encoding": "base64" }
+                        }
+                    },
+                    {
+                        "startedDateTime": "2026-01-01T00:00:01.000Z",
+                        "time": 5,
+                        "request": { "method": "GET", "url": "https://api.anthropic.com/v1/models" },
+                        "response": { "status": 200, "content": { "text": "[]" } }
+                    }
+                ]
+            }
+        })
+    }
+
+    #[test]
+    fn har_post_entries_only_by_default() {
+        let frames = frames_from_har(&sample_har(), false).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].request_bytes, b"{\"model\":\"m\"}");
+        assert_eq!(frames[0].response_bytes, b"{\"ok\":true}");
+        assert_eq!(frames[0].latency_ms, 124);
+        assert_eq!(frames[0].timestamp, 1767225600000);
+        assert_eq!(frames[0].status, Some(200));
+    }
+
+    #[test]
+    fn har_all_entries() {
+        let frames = frames_from_har(&sample_har(), true).unwrap();
+        assert_eq!(frames.len(), 2);
+        assert!(frames[1].request_bytes.is_empty());
+        assert_eq!(frames[1].response_bytes, b"[]");
+    }
+
+    #[test]
+    fn har_missing_entries_is_error() {
+        assert!(frames_from_har(&json!({"log": {}}), false).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use ghostline_core::IndexEntry;
+
+/// `--hash` for commands that pick one frame: a request hash, or like git a
+/// unique prefix of one.
+#[derive(Debug, Clone, Default, clap::Args)]
+pub struct HashArgs {
+    /// Pick the frame by request hash (a unique hex prefix is enough)
+    #[arg(long, value_name = "HEX")]
+    pub hash: Option<String>,
+    /// Require the full 64-character hash instead of accepting a prefix
+    #[arg(long, requires = "hash")]
+    pub exact: bool,
+}
+
+impl HashArgs {
+    /// The frame to act on: `index` if given, else the one `--hash` names.
+    pub fn frame_index(&self, entries: &[IndexEntry], index: Option<usize>) -> anyhow::Result<usize> {
+        match (index, &self.hash) {
+            (Some(index), _) => Ok(index),
+            (None, Some(hash)) => resolve(entries, hash, self.exact),
+            (None, None) => anyhow::bail!("pass a frame index or --hash"),
+        }
+    }
+}
+
+/// The first frame recorded for the request hash `hex` names, matching the
+/// whole hash when `exact` and any unique prefix otherwise. Ambiguous
+/// prefixes list the hashes they could mean.
+pub fn resolve(entries: &[IndexEntry], hex: &str, exact: bool) -> anyhow::Result<usize> {
+    let prefix = hex.trim().to_ascii_lowercase();
+    if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+        anyhow::bail!("{:?} is not a hex hash", hex);
+    }
+    if prefix.len() > 64 || (exact && prefix.len() != 64) {
+        anyhow::bail!("{:?} is not a full 64-character hash", hex);
+    }
+    // Hashes recorded more than once are one candidate, at their first frame.
+    let mut candidates: Vec<(String, usize)> = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let full = hex::encode(entry.request_hash);
+        if full.starts_with(&prefix) && !candidates.iter().any(|(h, _)| *h == full) {
+            candidates.push((full, i));
+        }
+    }
+    match candidates.as_slice() {
+        [] => anyhow::bail!("no frame with request hash {}", prefix),
+        [(_, index)] => Ok(*index),
+        _ => {
+            let list: Vec<_> = candidates.iter().map(|(h, i)| format!("  {}  [{}]", h, i)).collect();
+            anyhow::bail!("hash prefix {} is ambiguous; it matches:\n{}", prefix, list.join("\n"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(first: u8, second: u8) -> IndexEntry {
+        let mut request_hash = [0u8; 32];
+        request_hash[0] = first;
+        request_hash[1] = second;
+        IndexEntry { request_hash, offset: 0, decompressed_size: 0 }
+    }
+
+    #[test]
+    fn resolves_unique_prefixes() {
+        let entries = [entry(0xab, 0x01), entry(0xab, 0x02), entry(0xcd, 0), entry(0xab, 0x01)];
+        assert_eq!(resolve(&entries, "ab01", false).unwrap(), 0);
+        assert_eq!(resolve(&entries, "AB02", false).unwrap(), 1);
+        assert_eq!(resolve(&entries, "c", false).unwrap(), 2);
+
+        let ambiguous = resolve(&entries, "ab", false).unwrap_err().to_string();
+        assert!(ambiguous.contains("ab01") && ambiguous.contains("ab02"), "{}", ambiguous);
+        assert_eq!(ambiguous.lines().count(), 3, "{}", ambiguous);
+        assert!(resolve(&entries, "ef", false).unwrap_err().to_string().contains("no frame"));
+        assert!(resolve(&entries, "xyz", false).is_err());
+
+        assert!(resolve(&entries, "cd", true).is_err());
+        let full = hex::encode(entries[2].request_hash);
+        assert_eq!(resolve(&entries, &full, true).unwrap(), 2);
+    }
+}
@@ -0,0 +1,145 @@
+use ghostline_core::Body;
+use std::io::{self, Write};
+
+/// Which bodies `ghostline show --raw-hex` dumps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Part {
+    Request,
+    Response,
+    #[default]
+    Both,
+}
+
+impl Part {
+    pub fn bodies(self) -> &'static [Body] {
+        match self {
+            Self::Request => &[Body::Request],
+            Self::Response => &[Body::Response],
+            Self::Both => &[Body::Request, Body::Response],
+        }
+    }
+}
+
+const WIDTH: usize = 16;
+
+/// A writer that formats what it is given like `hexdump -C`: offset, sixteen
+/// hex bytes in two groups of eight, then the printable ASCII. Repeated lines
+/// collapse to `*`. Bytes past `limit` are accepted and dropped, so a body can
+/// be streamed through it whatever its size.
+pub struct HexDump<W: Write> {
+    out: W,
+    offset: u64,
+    limit: Option<u64>,
+    line: Vec<u8>,
+    prev: Option<Vec<u8>>,
+    squeezed: bool,
+}
+
+impl<W: Write> HexDump<W> {
+    pub fn new(out: W, limit: Option<u64>) -> Self {
+        Self {
+            out,
+            offset: 0,
+            limit,
+            line: Vec::with_capacity(WIDTH),
+            prev: None,
+            squeezed: false,
+        }
+    }
+
+    fn emit_line(&mut self) -> io::Result<()> {
+        let start = self.offset - self.line.len() as u64;
+        if self.line.len() == WIDTH && self.prev.as_deref() == Some(&self.line[..]) {
+            if !self.squeezed {
+                writeln!(self.out, "*")?;
+                self.squeezed = true;
+            }
+            self.line.clear();
+            return Ok(());
+        }
+        self.squeezed = false;
+
+        let mut hex = String::with_capacity(3 * WIDTH + 1);
+        for i in 0..WIDTH {
+            if i == WIDTH / 2 {
+                hex.push(' ');
+            }
+            match self.line.get(i) {
+                Some(b) => hex.push_str(&format!("{:02x} ", b)),
+                None => hex.push_str("   "),
+            }
+        }
+        let ascii: String = self
+            .line
+            .iter()
+            .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+            .collect();
+        writeln!(self.out, "{:08x}  {} |{}|", start, hex, ascii)?;
+        self.prev = Some(std::mem::take(&mut self.line));
+        Ok(())
+    }
+
+    /// Write any partial last line and the end offset, returning the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.line.is_empty() {
+            self.emit_line()?;
+        }
+        if self.offset > 0 {
+            writeln!(self.out, "{:08x}", self.offset)?;
+        }
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for HexDump<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let room = self.limit.map_or(buf.len() as u64, |l| l.saturating_sub(self.offset));
+        for &b in &buf[..buf.len().min(room as usize)] {
+            self.line.push(b);
+            self.offset += 1;
+            if self.line.len() == WIDTH {
+                self.emit_line()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dump(data: &[u8], limit: Option<u64>) -> String {
+        let mut d = HexDump::new(Vec::new(), limit);
+        // Odd-sized writes, as a streamed body would arrive.
+        for chunk in data.chunks(5) {
+            d.write_all(chunk).unwrap();
+        }
+        String::from_utf8(d.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn matches_hexdump_c() {
+        assert_eq!(
+            dump(b"{\"model\":\"claude\"}\n", None),
+            "00000000  7b 22 6d 6f 64 65 6c 22  3a 22 63 6c 61 75 64 65  |{\"model\":\"claude|\n\
+             00000010  22 7d 0a                                          |\"}.|\n\
+             00000013\n"
+        );
+        assert_eq!(
+            dump(&[0u8; 64], None),
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n*\n00000040\n"
+        );
+        assert_eq!(dump(b"", None), "");
+    }
+
+    #[test]
+    fn stops_at_limit() {
+        let out = dump(b"abcdefghijklmnopqrstuvwxyz", Some(4));
+        assert_eq!(out, format!("00000000  61 62 63 64{}  |abcd|\n00000004\n", " ".repeat(37)));
+    }
+}
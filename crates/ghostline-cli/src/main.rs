@@ -1,15 +1,35 @@
 mod banner;
+mod compact;
 mod config;
+mod crypt;
+mod diff;
+mod export;
+mod filter;
+mod fork;
+mod har;
+mod hashref;
+mod hexdump;
+mod meta;
+mod preview;
 mod proxy;
 mod replay;
+mod runs;
+mod scrub;
+mod send;
+mod sign;
+mod stats;
+mod stdio;
+mod timefmt;
+mod verify;
 mod viewer_assets;
 mod viewer_server;
+mod watch;
 mod wizard;
 
-use base64::Engine;
+use anyhow::Context;
 use clap::{Parser, Subcommand};
-use config::Config;
-use ghostline_core::{GhostlineReader, MAGIC};
+use config::{BindAddr, Config};
+use ghostline_core::{Body, Frame, GhostlineReader, MAGIC};
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -18,6 +38,20 @@ use std::sync::Arc;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// Loopback address servers listen on: 127.0.0.1, ::1, or localhost for both
+    /// (overrides server.bind)
+    #[arg(long, global = true, value_enum)]
+    bind: Option<BindAddr>,
+    /// Suppress banners and per-frame logs; errors still print
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Add debug detail such as per-request hashes
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Never prompt or open a browser, for scripts and CI: fail instead of
+    /// running the setup wizard. On by default when CI=true
+    #[arg(long, global = true)]
+    non_interactive: bool,
 }
 
 #[derive(Subcommand)]
@@ -28,14 +62,104 @@ enum Commands {
     Record {
         /// Session name
         name: Option<String>,
+        /// Write to `<name>.ghostline`, overwriting the previous recording of this session
+        #[arg(long, requires = "name")]
+        replace: bool,
+        /// Overwrite without asking
+        #[arg(long, requires = "replace")]
+        force: bool,
+        /// Stop recording and finalize the file after this many seconds
+        #[arg(long, value_name = "SECS")]
+        duration: Option<u64>,
     },
     /// Launch proxy in replay mode
     Replay {
         /// Path to the .ghostline file
         file: String,
-        /// Port for the replay proxy
+        /// Port for the replay proxy (0 picks a free port)
         #[arg(short, long, default_value = "8384")]
         port: u16,
+        /// Write the bound port to this file once listening
+        #[arg(long)]
+        port_file: Option<PathBuf>,
+        /// On a hash miss, match requests by canonical JSON (sorted keys, no whitespace)
+        #[arg(long)]
+        canonical_json: bool,
+        /// Serve a file's contents instead of the recorded response for a request
+        /// hash (or unique prefix), without changing the recording; repeatable
+        #[arg(long = "override", value_name = "HASH=FILE")]
+        overrides: Vec<replay::OverrideArg>,
+        /// HTTP status for overridden responses
+        #[arg(long, default_value = "500", value_parser = clap::value_parser!(u16).range(100..600))]
+        override_status: u16,
+        /// Serve only frames recorded with these statuses (e.g. `200` or
+        /// `200,201`); hits on other frames are misses. Unrecorded statuses count as 200
+        #[arg(long, value_name = "CODES", value_delimiter = ',', value_parser = clap::value_parser!(u16).range(100..600))]
+        only_status: Vec<u16>,
+        /// Answer a request recorded more than once (e.g. a 529, then the retry's
+        /// 200) with each recorded response in turn instead of always the last
+        #[arg(long)]
+        advance_duplicates: bool,
+        /// Add this header to every replayed response (repeatable); wins over
+        /// replay.response_headers and replay's own headers of the same name
+        #[arg(long = "response-header", value_name = "NAME=VALUE")]
+        response_headers: Vec<replay::ResponseHeader>,
+        /// Handle requests under path FROM as if sent under TO (e.g.
+        /// `/api/v1=/v1`), for clients whose base path differs from the
+        /// recording's; repeatable, first match wins
+        #[arg(long = "map-path", value_name = "FROM=TO")]
+        path_maps: Vec<replay::PathMap>,
+        /// Also require the request headers recorded with a frame (its
+        /// anthropic-beta features) to match; a hit that differs is a miss
+        #[arg(long)]
+        strict: bool,
+        /// Forward requests the recording misses to the upstream and record them
+        /// to this new file (finalized on exit); the replayed file is not changed
+        #[arg(long, value_name = "FILE")]
+        record_to: Option<PathBuf>,
+        /// With --record-to: upstream for missed requests (defaults to proxy.target)
+        #[arg(long, value_name = "URL", requires = "record_to")]
+        target: Option<String>,
+        /// Read frames from the file as requests need them instead of loading the
+        /// whole recording, for files too large for memory
+        #[arg(long, conflicts_with = "canonical_json")]
+        lazy: bool,
+        /// With --lazy: keep at most this many MiB of recently served frames cached
+        #[arg(long, value_name = "MIB", default_value = "256", requires = "lazy")]
+        memory_budget: usize,
+        /// Answer each hit after its recorded latency times this factor
+        /// (`0.5` is twice as fast)
+        #[arg(long, value_name = "FACTOR")]
+        latency_scale: Option<f64>,
+        /// Simulate recorded latency (at --latency-scale, default 1) but wait at
+        /// most this many ms per hit, so outlier frames don't dominate the run
+        #[arg(long, value_name = "MS")]
+        latency_cap: Option<u64>,
+        /// With a command: exit non-zero if it sent any request not in the recording
+        #[arg(long, requires = "cmd")]
+        assert_complete: bool,
+        /// On shutdown (Ctrl+C, or when the command exits), write per-frame hit
+        /// counts and the unused frames to this JSON file
+        #[arg(long, value_name = "PATH")]
+        coverage: Option<PathBuf>,
+        /// Command to run against the replay server (after `--`), with
+        /// ANTHROPIC_BASE_URL set; replay stops when it exits
+        #[arg(last = true)]
+        cmd: Vec<String>,
+    },
+    /// Replay a file and open it in the viewer, with replay hits and misses on the live feed
+    Serve {
+        /// Path to the .ghostline file
+        file: String,
+        /// Port for the replay proxy (0 picks a free port)
+        #[arg(long, default_value = "8384")]
+        replay_port: u16,
+        /// Port for the viewer (defaults to the configured viewer port)
+        #[arg(long)]
+        viewer_port: Option<u16>,
+        /// On a hash miss, match requests by canonical JSON (sorted keys, no whitespace)
+        #[arg(long)]
+        canonical_json: bool,
     },
     /// List recorded sessions
     Runs {
@@ -46,36 +170,263 @@ enum Commands {
     Viewer,
     /// Inspect a .ghostline file
     Inspect {
-        /// Path to the .ghostline file
+        /// Path to the .ghostline file (`-` for stdin)
+        file: String,
+        /// Print the header summary only, without the per-frame listing
+        #[arg(long, conflicts_with = "frames")]
+        summary: bool,
+        /// Print only the frame count (for scripting)
+        #[arg(long)]
+        count: bool,
+        /// Always list every frame, even for large files
+        #[arg(long)]
+        frames: bool,
+        /// Print the physical layout: header size, index offset, and each frame's
+        /// byte offset and compressed/decompressed sizes
+        #[arg(long, conflicts_with_all = ["summary", "count"])]
+        layout: bool,
+        /// Print only the format version and exit 0 if this build can read the
+        /// file, 3 if it needs a newer ghostline, 4 if it isn't a ghostline file
+        #[arg(long, conflicts_with_all = ["summary", "count", "frames", "layout"])]
+        check_version: bool,
+    },
+    /// Print size and latency statistics for a .ghostline file
+    Stats {
+        /// Path to the .ghostline file (`-` for stdin)
         file: String,
+        /// Print the numbers and histograms as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Summarize a recording as JSON: run id, start, frames, duration, models, token totals
+    Meta {
+        /// Path to the .ghostline file (`-` for stdin)
+        file: String,
+        /// Write the summary here instead of printing it (e.g. `<file>.meta.json`)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare two recordings, matching frames by request hash
+    Diff {
+        /// The baseline recording (`-` for stdin)
+        a: String,
+        /// The recording to compare against it
+        b: String,
+        /// Also compare the latency of matched frames and flag the ones that got slower
+        #[arg(long)]
+        compare_latency: bool,
+        /// With --compare-latency: flag frames more than this many percent slower in B
+        #[arg(long, value_name = "PCT", default_value = "20", requires = "compare_latency")]
+        slower_than: f64,
+        /// Print the comparison as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check a .ghostline file's hashes and flag ambiguous replay keys
+    Verify {
+        /// Path to the .ghostline file (`-` for stdin)
+        file: String,
+    },
+    /// Create an ed25519 key pair for `sign`: <path> (secret) and <path>.pub
+    Keygen { path: PathBuf },
+    /// Sign a recording, writing the signature to <file>.sig
+    Sign {
+        file: PathBuf,
+        /// Secret key from `ghostline keygen`
+        #[arg(long)]
+        key: PathBuf,
+        /// Signature path (default: <file>.sig)
+        #[arg(long)]
+        sig: Option<PathBuf>,
+    },
+    /// Check a recording's signature against a public key
+    VerifySignature {
+        file: PathBuf,
+        /// Public key (<path>.pub from `ghostline keygen`)
+        #[arg(long)]
+        key: PathBuf,
+        /// Signature path (default: <file>.sig)
+        #[arg(long)]
+        sig: Option<PathBuf>,
     },
     /// Export a .ghostline file
     Export {
-        /// Path to the .ghostline file
+        /// Path to the .ghostline file (`-` for stdin)
         file: String,
-        /// Output path
+        /// Output path (`-` for stdout)
         #[arg(short, long)]
         output: Option<String>,
         /// Frame index (JSON only)
         #[arg(long)]
         frame: Option<usize>,
+        /// Only the first N frames (JSON only), for a quick look
+        #[arg(long, value_name = "N", conflicts_with_all = ["frame", "tail"])]
+        head: Option<usize>,
+        /// Only the last N frames (JSON only)
+        #[arg(long, value_name = "N", conflicts_with = "frame")]
+        tail: Option<usize>,
+        /// Give bodies as JSON or text instead of base64 where they decode
+        /// (JSON only); streamed responses as their reconstructed message
+        #[arg(long)]
+        decode: bool,
         /// Output format: json or html
         #[arg(long, default_value = "json")]
         format: String,
     },
+    /// Export every recording in a directory, plus an index.html linking them
+    ExportAll {
+        /// Directory of .ghostline files (defaults to the runs directory)
+        dir: Option<PathBuf>,
+        /// Directory to write the exports and index.html into
+        #[arg(long)]
+        out: PathBuf,
+        /// Output format: html or json
+        #[arg(long, default_value = "html")]
+        format: String,
+    },
     /// Show a single frame in detail
     Show {
+        /// Path to the .ghostline file (`-` for stdin)
         file: String,
-        index: usize,
+        #[arg(required_unless_present = "hash", conflicts_with = "hash")]
+        index: Option<usize>,
+        #[command(flatten)]
+        hash: hashref::HashArgs,
+        /// Dump bodies as hex and ASCII (`hexdump -C` style), streamed, instead
+        /// of the metadata and preview
+        #[arg(long)]
+        raw_hex: bool,
+        /// Which body to dump
+        #[arg(long, value_enum, default_value = "both", requires = "raw_hex")]
+        part: hexdump::Part,
+        /// Dump at most this many bytes of each body
+        #[arg(long, requires = "raw_hex")]
+        limit: Option<u64>,
+        /// Print bodies in full instead of the first `display.preview_bytes`
+        #[arg(long, conflicts_with_all = ["preview_bytes", "raw_hex"])]
+        full: bool,
+        /// Preview at most this many bytes of each body (overrides
+        /// `display.preview_bytes`)
+        #[arg(long, value_name = "N", conflicts_with = "raw_hex")]
+        preview_bytes: Option<usize>,
     },
-    /// Fork a run at a specific step
-    Fork {
+    /// Write a frame's response (or request) body to stdout, streamed
+    Cat {
+        /// Path to the .ghostline file (`-` for stdin)
         file: String,
+        #[arg(required_unless_present = "hash", conflicts_with = "hash")]
+        index: Option<usize>,
+        #[command(flatten)]
+        hash: hashref::HashArgs,
+        /// Write the request body instead of the response
         #[arg(long)]
-        at: usize,
+        request: bool,
+    },
+    /// Deduplicate, re-sort and rewrite a file with a fresh index
+    Compact {
+        file: String,
+        /// Output path (defaults to rewriting the file in place)
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Which frame to keep for a repeated request hash
+        #[arg(long, value_enum, default_value = "last")]
+        keep: compact::KeepMode,
+        /// Order of the rewritten frames
+        #[arg(long, value_enum, default_value = "timestamp")]
+        sort: compact::SortKey,
+    },
+    /// Write the frames matching a filter to a new file
+    Filter {
+        file: String,
+        /// Keep only failed requests (status 400 or more, or an error body)
+        #[arg(long, required = true)]
+        errors: bool,
+        /// Output path (defaults to `<file>-errors.ghostline`)
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Encrypt a file with a passphrase (from GHOSTLINE_PASSPHRASE or a prompt)
+    Encrypt {
+        file: String,
+        #[arg(short, long)]
+        out: String,
+    },
+    /// Decrypt a file written by `ghostline encrypt`
+    Decrypt {
+        file: String,
+        #[arg(short, long)]
+        out: String,
+    },
+    /// Fork a run at a specific step, or cut a range of steps out of it
+    Fork {
+        file: String,
+        #[arg(long, required_unless_present_any = ["hash", "range"], conflicts_with_all = ["hash", "range"])]
+        at: Option<usize>,
+        #[command(flatten)]
+        hash: hashref::HashArgs,
+        /// Keep only frames `a..b` (end exclusive) or `a..=b` instead of a prefix
+        #[arg(long, value_name = "A..B", conflicts_with = "hash")]
+        range: Option<fork::FrameRange>,
+        /// Output path (`-` for stdout)
         #[arg(short, long)]
         output: Option<String>,
     },
+    /// Send one recorded request to the live API and print both responses
+    Send {
+        /// Path to the .ghostline file (`-` for stdin)
+        file: String,
+        /// Frame index
+        #[arg(long, required_unless_present = "hash", conflicts_with = "hash")]
+        frame: Option<usize>,
+        #[command(flatten)]
+        hash: hashref::HashArgs,
+        /// Upstream base URL (defaults to the frame's recorded upstream, then proxy.target)
+        #[arg(short, long)]
+        target: Option<String>,
+        /// Request path, which frames don't record
+        #[arg(long, default_value = send::DEFAULT_PATH)]
+        path: String,
+        /// Also print a line diff of the recorded and live responses
+        #[arg(long)]
+        diff: bool,
+    },
+    /// Add or remove labels on a frame (rewrites the file)
+    Tag {
+        file: String,
+        /// Frame index to tag
+        #[arg(long, required_unless_present = "hash", conflicts_with = "hash")]
+        frame: Option<usize>,
+        #[command(flatten)]
+        hash: hashref::HashArgs,
+        /// Label to add (repeatable)
+        #[arg(long)]
+        add: Vec<String>,
+        /// Label to remove (repeatable)
+        #[arg(long)]
+        remove: Vec<String>,
+    },
+    /// Watch a directory and export each new recording as it is finalized
+    Watch {
+        /// Directory to watch for .ghostline files
+        dir: String,
+        /// Export format: html or json
+        #[arg(long, default_value = "html")]
+        format: String,
+        /// Directory to write exports into
+        #[arg(short, long)]
+        out: String,
+    },
+    /// Import a HAR capture into a .ghostline file
+    Import {
+        /// Path to the .har file
+        har: String,
+        /// Output .ghostline path
+        #[arg(short, long)]
+        output: String,
+        /// Import every entry, not just POST requests
+        #[arg(long)]
+        all: bool,
+    },
     /// Search frames (requires Python SDK)
     Search {
         file: String,
@@ -85,12 +436,36 @@ enum Commands {
     },
     /// Run a transparent recording proxy only
     Proxy {
+        /// Port to listen on (0 picks a free port)
         #[arg(short, long, default_value = "9000")]
         port: u16,
+        /// Write the bound port to this file once listening
+        #[arg(long)]
+        port_file: Option<PathBuf>,
+        /// Directory for the recording, or `-` to stream it to stdout
         #[arg(short, long, default_value = "./ghostline-runs/")]
         out: PathBuf,
         #[arg(short, long, default_value = "https://api.anthropic.com")]
         target: String,
+        /// Stream request bodies larger than this many bytes instead of buffering them
+        #[arg(long, default_value = "8388608")]
+        stream_threshold: usize,
+        /// Frame timestamp source
+        #[arg(long, value_enum, default_value = "wall")]
+        clock: config::ClockMode,
+        /// Record request headers as the client sent them, or as forwarded
+        /// upstream (overrides recording.capture)
+        #[arg(long, value_enum)]
+        capture: Option<config::CaptureMode>,
+        /// Store each request as a delta against the previous one
+        #[arg(long)]
+        delta_requests: bool,
+        /// Truncate request and response bodies longer than this many bytes
+        #[arg(long)]
+        max_body_bytes: Option<usize>,
+        /// Stop recording and finalize the file after this many seconds
+        #[arg(long, value_name = "SECS")]
+        duration: Option<u64>,
     },
     /// Run a command with ANTHROPIC_BASE_URL set automatically
     Run {
@@ -111,6 +486,25 @@ enum Commands {
 enum RunsCommand {
     /// Delete a recorded session
     Delete { name: String },
+    /// Move a recorded session into a subdirectory of the runs directory
+    Move {
+        /// Run name as `ghostline runs` lists it
+        name: String,
+        /// Subdirectory to move it to (`project`, `a/b`, or `.` for the top level)
+        subdir: String,
+    },
+    /// Delete old recordings in bulk
+    Prune {
+        /// Remove runs started longer ago than this (e.g. 12h, 7d, 2w)
+        #[arg(long)]
+        older_than: Option<String>,
+        /// Keep only the newest N runs
+        #[arg(long)]
+        keep: Option<usize>,
+        /// Show what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -121,43 +515,119 @@ enum ConfigCommand {
     Set { key: String, value: String },
 }
 
+/// Above this many frames, `inspect` shows the summary unless `--frames` is given.
+const INSPECT_LIST_LIMIT: usize = 50;
+
+/// A frame or header timestamp in the configured `display.timezone`.
 fn fmt_ts(ms: u64) -> String {
-    chrono::DateTime::from_timestamp_millis(ms as i64)
-        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-        .unwrap_or_else(|| format!("{}", ms))
+    timefmt::format_timestamp(ms, Config::load_or_default().display.timezone)
 }
 
-fn print_data_preview(data: &[u8], label: &str) {
-    println!("\n--- {} ({} bytes) ---", label, data.len());
-    match std::str::from_utf8(data) {
-        Ok(s) if s.len() <= 2000 => println!("{}", s),
-        Ok(s) => println!("{}...", &s[..2000]),
-        Err(_) => {
-            if let Ok(val) = rmp_serde::from_slice::<serde_json::Value>(data) {
-                if let Ok(json) = serde_json::to_string_pretty(&val) {
-                    let truncated = if json.len() > 2000 { &json[..2000] } else { &json };
-                    println!("{}", truncated);
-                    return;
-                }
-            }
-            let hex_preview: String = data
-                .iter()
-                .take(64)
-                .map(|b| format!("{:02x}", b))
-                .collect::<Vec<_>>()
-                .join(" ");
-            println!(
-                "{}{}",
-                hex_preview,
-                if data.len() > 64 { "..." } else { "" }
-            );
-        }
+/// `inspect --check-version` exit code for a file from a newer major format.
+const EXIT_NEWER_FORMAT: i32 = 3;
+/// `inspect --check-version` exit code for a file that isn't a recording.
+const EXIT_NOT_GHOSTLINE: i32 = 4;
+
+/// `inspect --check-version`: print the file's format version and return the
+/// exit code. Only the header is read. An encrypted container counts as
+/// readable, since its version is only known after decrypting it.
+fn check_format_version<R: std::io::Read + std::io::Seek>(input: &mut R) -> anyhow::Result<i32> {
+    let mut head = [0u8; 12];
+    let complete = input.read_exact(&mut head).is_ok();
+    if head.starts_with(ghostline_core::ENCRYPTED_MAGIC) {
+        println!("encrypted");
+        return Ok(0);
+    }
+    if !complete || !head.starts_with(MAGIC) {
+        eprintln!("not a ghostline file");
+        return Ok(EXIT_NOT_GHOSTLINE);
+    }
+    let version = u32::from_le_bytes([head[8], head[9], head[10], head[11]]);
+    let major = (version >> 16) as u16;
+    println!("{}.{}", major, version as u16);
+    if major > ghostline_core::FORMAT_MAJOR {
+        eprintln!(
+            "format {}.x is newer than this build reads ({}.x); upgrade ghostline",
+            major,
+            ghostline_core::FORMAT_MAJOR
+        );
+        return Ok(EXIT_NEWER_FORMAT);
+    }
+    input.rewind()?;
+    if let Err(e) = ghostline_core::read_header(input) {
+        eprintln!("unreadable header: {}", e);
+        return Ok(EXIT_NOT_GHOSTLINE);
+    }
+    Ok(0)
+}
+
+/// `inspect --layout`: where the header, frames and index sit in the file.
+/// A frame's compressed size runs to the next frame (or the index) and
+/// excludes its 4-byte length prefix.
+fn print_layout<R: std::io::Read + std::io::Seek>(reader: &GhostlineReader<R>) {
+    let entries = reader.index_entries();
+    println!("Header:      {} bytes", reader.header_len());
+    println!(
+        "Index:       offset {} ({} entries)",
+        reader.index_offset(),
+        entries.len()
+    );
+    let mut starts: Vec<u64> = entries.iter().map(|e| e.offset).collect();
+    starts.sort_unstable();
+    for (i, entry) in entries.iter().enumerate() {
+        let next = starts.partition_point(|&o| o <= entry.offset);
+        let end = starts.get(next).copied().unwrap_or(reader.index_offset());
+        let decompressed = match entry.decompressed_size {
+            0 => "-".to_string(),
+            n => n.to_string(),
+        };
+        println!(
+            "  [{}] offset {:>10}  compressed {:>8}  decompressed {:>8}",
+            i,
+            entry.offset,
+            end.saturating_sub(entry.offset + 4),
+            decompressed
+        );
     }
 }
 
+/// Apply a `--bind` flag over the configured bind address.
+fn with_bind(mut cfg: Config, bind: Option<BindAddr>) -> Config {
+    cfg.merge(config::PartialConfig {
+        server: config::PartialServerConfig { bind },
+        ..Default::default()
+    });
+    cfg
+}
+
+/// `--non-interactive`, or `CI=true` (or `1`) as CI services set it.
+fn non_interactive(flag: bool) -> bool {
+    flag || std::env::var("CI").is_ok_and(|v| v.eq_ignore_ascii_case("true") || v == "1")
+}
+
+/// `cfg` with the browser kept closed when nobody is there to look at it.
+fn headless(mut cfg: Config, non_interactive: bool) -> Config {
+    if non_interactive {
+        cfg.viewer.auto_open_browser = false;
+    }
+    cfg
+}
+
+/// The setup wizard, or an error saying how to configure without it.
+fn wizard_unless(non_interactive: bool) -> anyhow::Result<Config> {
+    if non_interactive {
+        anyhow::bail!(
+            "the setup wizard can't prompt with --non-interactive (or CI=true); \
+             run `ghostline setup-token` in a terminal once, or write {}",
+            Config::config_path().display()
+        );
+    }
+    wizard::run_wizard()
+}
+
 /// Launch proxy + viewer + browser (the main "ghostline" experience)
-async fn launch_all(cfg: &Config) -> anyhow::Result<()> {
-    let (frame_tx, _) = tokio::sync::broadcast::channel::<String>(256);
+async fn launch_all(cfg: &Config, options: proxy::ProxyOptions) -> anyhow::Result<()> {
+    let frame_tx = viewer_server::FrameSender::from_config(cfg);
     let frame_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
 
     let cfg = Arc::new(cfg.clone());
@@ -187,7 +657,15 @@ async fn launch_all(cfg: &Config) -> anyhow::Result<()> {
     // Run proxy (blocking on main task)
     let out = Config::runs_dir();
     let target = cfg.proxy.target.clone();
-    proxy::run_proxy(cfg.proxy.port, out, target, Some(frame_tx), frame_count).await?;
+    proxy::run_proxy(
+        cfg.proxy.port,
+        out,
+        target,
+        Some(frame_tx),
+        frame_count,
+        options,
+    )
+    .await?;
 
     viewer_handle.abort();
     Ok(())
@@ -195,29 +673,60 @@ async fn launch_all(cfg: &Config) -> anyhow::Result<()> {
 
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    banner::set_verbosity(match (cli.quiet, cli.verbose) {
+        (true, _) => banner::Verbosity::Quiet,
+        (_, true) => banner::Verbosity::Verbose,
+        _ => banner::Verbosity::Normal,
+    });
+    let non_interactive = non_interactive(cli.non_interactive);
 
     match cli.command {
         None => {
             // Default: wizard if not configured, else launch all
             let cfg = if !Config::config_path().exists() {
-                wizard::run_wizard()?
+                wizard_unless(non_interactive).context("ghostline is not configured")?
             } else {
                 Config::load_or_default()
             };
+            let cfg = headless(with_bind(cfg, cli.bind), non_interactive);
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(launch_all(&cfg))?;
+            rt.block_on(launch_all(&cfg, proxy::ProxyOptions::from_config(&cfg)))?;
         }
         Some(Commands::SetupToken) => {
-            wizard::run_wizard()?;
+            wizard_unless(non_interactive)?;
         }
-        Some(Commands::Record { name: _ }) => {
-            let cfg = Config::load_or_default();
+        Some(Commands::Record { name, replace, force, duration }) => {
+            let cfg = headless(with_bind(Config::load_or_default(), cli.bind), non_interactive);
+            let mut options = proxy::ProxyOptions::from_config(&cfg);
+            if let Some(name) = &name {
+                proxy::check_session_name(name)?;
+            }
+            if replace {
+                let filename = proxy::run_filename(chrono::Utc::now(), name.as_deref(), true);
+                let path = Config::runs_dir().join(filename);
+                if path.exists() && !force {
+                    use std::io::IsTerminal;
+                    if non_interactive || !std::io::stdin().is_terminal() {
+                        anyhow::bail!("{} exists — pass --force to overwrite it", path.display());
+                    }
+                    let overwrite = dialoguer::Confirm::new()
+                        .with_prompt(format!("Overwrite {}?", path.display()))
+                        .default(false)
+                        .interact()?;
+                    if !overwrite {
+                        return Ok(());
+                    }
+                }
+            }
+            options.session = name;
+            options.duration = duration.map(std::time::Duration::from_secs);
+            options.replace = replace;
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(launch_all(&cfg))?;
+            rt.block_on(launch_all(&cfg, options))?;
         }
         Some(Commands::Viewer) => {
-            let cfg = Config::load_or_default();
-            let (frame_tx, _) = tokio::sync::broadcast::channel::<String>(256);
+            let cfg = with_bind(Config::load_or_default(), cli.bind);
+            let frame_tx = viewer_server::FrameSender::from_config(&cfg);
             let frame_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
             let rt = tokio::runtime::Runtime::new()?;
             rt.block_on(viewer_server::start(Arc::new(cfg), frame_tx, frame_count))?;
@@ -229,52 +738,54 @@ fn main() -> anyhow::Result<()> {
                     println!("No runs directory found at {}", runs_dir.display());
                     return Ok(());
                 }
-                let mut entries: Vec<_> = std::fs::read_dir(&runs_dir)?
-                    .flatten()
-                    .filter(|e| {
-                        e.path()
-                            .extension()
-                            .map(|ext| ext == "ghostline")
-                            .unwrap_or(false)
-                    })
-                    .collect();
-                entries.sort_by_key(|e| e.file_name());
-                if entries.is_empty() {
+                let mut runs = runs::list_runs(&runs_dir)?;
+                runs.sort_by(|a, b| a.name.cmp(&b.name));
+                if runs.is_empty() {
                     println!("No recorded sessions found.");
                 } else {
-                    for entry in entries {
-                        let path = entry.path();
-                        let name = path.file_name().unwrap().to_string_lossy();
-                        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
-                        println!("  {} ({:.1} KB)", name, size as f64 / 1024.0);
+                    for run in runs {
+                        println!("  {} ({:.1} KB)", run.name, run.size as f64 / 1024.0);
                     }
                 }
             }
             Some(RunsCommand::Delete { name }) => {
-                let path = Config::runs_dir().join(&name);
+                let path = runs::resolve_run(&Config::runs_dir(), &name)
+                    .ok_or_else(|| anyhow::anyhow!("{:?} is not a run name", name))?;
                 if path.exists() {
-                    std::fs::remove_file(&path)?;
+                    runs::delete_run(&path)?;
                     println!("Deleted: {}", name);
                 } else {
                     println!("Not found: {}", name);
                 }
             }
+            Some(RunsCommand::Move { name, subdir }) => {
+                let moved = runs::move_run(&Config::runs_dir(), &name, &subdir)?;
+                println!("Moved {} → {}", name, moved);
+            }
+            Some(RunsCommand::Prune {
+                older_than,
+                keep,
+                dry_run,
+            }) => {
+                runs::prune(&Config::runs_dir(), older_than.as_deref(), keep, dry_run)?;
+            }
         },
         Some(Commands::Run { cmd }) => {
             if cmd.is_empty() {
                 anyhow::bail!("Usage: ghostline run <command> [args...]");
             }
-            let cfg = Config::load_or_default();
-            let proxy_url = format!("http://localhost:{}", cfg.proxy.port);
+            let cfg = headless(with_bind(Config::load_or_default(), cli.bind), non_interactive);
+            let bind = cfg.server.bind;
+            let proxy_url = format!("http://{}:{}", bind.url_host(), cfg.proxy.port);
 
             // Check if proxy is already running by trying to connect
-            let proxy_running = std::net::TcpStream::connect(format!("127.0.0.1:{}", cfg.proxy.port)).is_ok();
+            let proxy_running = std::net::TcpStream::connect(bind.connect_addr(cfg.proxy.port)).is_ok();
 
             if !proxy_running {
                 // Start proxy + viewer in background, then run command
                 let rt = tokio::runtime::Runtime::new()?;
                 rt.block_on(async {
-                    let (frame_tx, _) = tokio::sync::broadcast::channel::<String>(256);
+                    let frame_tx = viewer_server::FrameSender::from_config(&cfg);
                     let frame_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
                     let cfg = Arc::new(cfg.clone());
                     let proxy_port = cfg.proxy.port;
@@ -297,14 +808,15 @@ fn main() -> anyhow::Result<()> {
                     let target = cfg.proxy.target.clone();
                     let ptx = frame_tx.clone();
                     let pfc = frame_count.clone();
+                    let options = proxy::ProxyOptions::from_config(&cfg);
                     tokio::spawn(async move {
-                        let _ = proxy::run_proxy(proxy_port, out, target, Some(ptx), pfc).await;
+                        let _ = proxy::run_proxy(proxy_port, out, target, Some(ptx), pfc, options).await;
                     });
 
                     // Wait for proxy to be ready
                     for _ in 0..50 {
                         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-                        if std::net::TcpStream::connect(format!("127.0.0.1:{}", proxy_port)).is_ok() {
+                        if std::net::TcpStream::connect(bind.connect_addr(proxy_port)).is_ok() {
                             break;
                         }
                     }
@@ -316,7 +828,7 @@ fn main() -> anyhow::Result<()> {
                     }
 
                     // Run the user's command
-                    eprintln!("\n Running: {} (with ANTHROPIC_BASE_URL={})\n", cmd.join(" "), proxy_url);
+                    banner::info!("\n Running: {} (with ANTHROPIC_BASE_URL={})\n", cmd.join(" "), proxy_url);
                     let status = tokio::process::Command::new(&cmd[0])
                         .args(&cmd[1..])
                         .env("ANTHROPIC_BASE_URL", &proxy_url)
@@ -330,7 +842,7 @@ fn main() -> anyhow::Result<()> {
                 })?;
             } else {
                 // Proxy already running, just exec the command
-                eprintln!("Running: {} (with ANTHROPIC_BASE_URL={})", cmd.join(" "), proxy_url);
+                banner::info!("Running: {} (with ANTHROPIC_BASE_URL={})", cmd.join(" "), proxy_url);
                 let status = std::process::Command::new(&cmd[0])
                     .args(&cmd[1..])
                     .env("ANTHROPIC_BASE_URL", &proxy_url)
@@ -386,10 +898,58 @@ fn main() -> anyhow::Result<()> {
                 let mut cfg = Config::load_or_default();
                 match key.as_str() {
                     "proxy.port" => cfg.proxy.port = value.parse()?,
+                    "proxy.stream_threshold_bytes" => cfg.proxy.stream_threshold_bytes = value.parse()?,
                     "viewer.port" => cfg.viewer.port = value.parse()?,
                     "viewer.auto_open_browser" => cfg.viewer.auto_open_browser = value.parse()?,
+                    "viewer.broadcast_capacity" => {
+                        cfg.viewer.broadcast_capacity = match value.parse()? {
+                            0 => anyhow::bail!("viewer.broadcast_capacity must be at least 1"),
+                            n => n,
+                        }
+                    }
                     "recording.scrub" => cfg.recording.scrub = value.parse()?,
+                    "recording.clock" => cfg.recording.clock = value.parse()?,
+                    "recording.delta_requests" => cfg.recording.delta_requests = value.parse()?,
+                    "recording.max_body_bytes" => {
+                        cfg.recording.max_body_bytes = match value.as_str() {
+                            "none" => None,
+                            v => Some(v.parse()?),
+                        }
+                    }
+                    "recording.index_spill_entries" => {
+                        cfg.recording.index_spill_entries = match value.as_str() {
+                            "none" => None,
+                            "0" => anyhow::bail!("recording.index_spill_entries must be at least 1 (or none)"),
+                            v => Some(v.parse()?),
+                        }
+                    }
+                    "recording.hash_algo" => cfg.recording.hash_algo = value.parse().map_err(anyhow::Error::msg)?,
+                    "recording.emit_meta" => cfg.recording.emit_meta = value.parse()?,
+                    "recording.capture_request_body" => cfg.recording.capture_request_body = value.parse()?,
+                    "recording.capture_response_body" => cfg.recording.capture_response_body = value.parse()?,
+                    "recording.capture" => {
+                        cfg.recording.capture = clap::ValueEnum::from_str(&value, false)
+                            .map_err(|_| anyhow::anyhow!("unknown capture mode '{}' (use client or upstream)", value))?
+                    }
+                    "recording.scrub_json_paths" => {
+                        cfg.recording.scrub_json_paths =
+                            value.split(',').map(str::trim).filter(|p| !p.is_empty()).map(String::from).collect()
+                    }
+                    "recording.drop_sse_pings" => cfg.recording.drop_sse_pings = value.parse()?,
+                    "recording.filter_file" => {
+                        cfg.recording.filter_file = match value.as_str() {
+                            "none" => None,
+                            v => Some(v.to_string()),
+                        }
+                    }
                     "display.colors" => cfg.display.colors = value.parse()?,
+                    "display.timezone" => cfg.display.timezone = value.parse()?,
+                    "display.preview_bytes" => cfg.display.preview_bytes = value.parse()?,
+                    "server.bind" => {
+                        cfg.server.bind = clap::ValueEnum::from_str(&value, false).map_err(|_| {
+                            anyhow::anyhow!("unknown bind address '{}' (use 127.0.0.1, ::1 or localhost)", value)
+                        })?
+                    }
                     _ => anyhow::bail!("Unknown config key: {}", key),
                 }
                 cfg.save(&Config::config_path())?;
@@ -397,158 +957,440 @@ fn main() -> anyhow::Result<()> {
             }
         },
         // Legacy commands preserved from v1
-        Some(Commands::Inspect { file }) => {
-            let reader = GhostlineReader::open(&file)?;
+        Some(Commands::Inspect {
+            file,
+            summary,
+            count,
+            frames,
+            layout,
+            check_version,
+        }) => {
+            if check_version {
+                let code = check_format_version(&mut stdio::open_input(&file)?)?;
+                if code != 0 {
+                    std::process::exit(code);
+                }
+                return Ok(());
+            }
+            let reader = stdio::open_reader(&file)?;
+            if count {
+                println!("{}", reader.frame_count());
+                return Ok(());
+            }
             let magic = std::str::from_utf8(MAGIC).unwrap_or("?");
             println!("Magic:       {}", magic);
-            println!("Version:     {}", reader.version);
+            let build_format = ghostline_core::format_version();
+            println!(
+                "Version:     {}.{} (ghostline {} writes {}.{})",
+                reader.version >> 16,
+                reader.version as u16,
+                env!("CARGO_PKG_VERSION"),
+                build_format >> 16,
+                build_format as u16
+            );
+            if !reader.writer_version.is_empty() {
+                println!("Written by:  ghostline {} ({})", reader.writer_version, reader.platform);
+            }
             println!("Frames:      {}", reader.frame_count());
             println!("Started at:  {}", fmt_ts(reader.started_at));
+            println!("Hashed with: {}", reader.hash_algo);
             if let Some(sha) = &reader.git_sha {
                 println!("Git SHA:     {}", hex::encode(sha));
             }
             if let Some(run_id) = &reader.parent_run_id {
                 println!("Parent run:  {}", hex::encode(run_id));
-                if let Some(step) = reader.fork_at_step {
-                    println!("Forked at:   step {}", step);
+                match (reader.fork_start_step, reader.fork_at_step) {
+                    (Some(start), Some(step)) => println!("Forked from: steps {}..={}", start, step),
+                    (None, Some(step)) => println!("Forked at:   step {}", step),
+                    _ => {}
                 }
             }
-            for i in 0..reader.frame_count() {
-                println!("  [{}]", i);
+            if let Some(signer) = &reader.signer_fingerprint {
+                println!("Signed by:   {}", hex::encode(signer));
+            }
+            let list = frames || (!summary && reader.frame_count() <= INSPECT_LIST_LIMIT);
+            if layout {
+                print_layout(&reader);
+            } else if list {
+                for i in 0..reader.frame_count() {
+                    println!("  [{}]", i);
+                }
+            } else if !summary {
+                println!("  ({} frames — pass --frames to list them)", reader.frame_count());
             }
         }
+        Some(Commands::Stats { file, json }) => {
+            let mut reader = stdio::open_reader(&file)?;
+            let stats = stats::compute(&mut reader)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                println!("{}", stats);
+            }
+        }
+        Some(Commands::Meta { file, output }) => {
+            let mut reader = stdio::open_reader(&file)?;
+            let summary = meta::compute(&mut reader)?;
+            let json = serde_json::to_string_pretty(&summary)?;
+            match output {
+                Some(path) => std::fs::write(&path, json + "\n")?,
+                None => println!("{}", json),
+            }
+        }
+        Some(Commands::Diff {
+            a,
+            b,
+            compare_latency,
+            slower_than,
+            json,
+        }) => {
+            let (mut reader_a, mut reader_b) = (stdio::open_reader(&a)?, stdio::open_reader(&b)?);
+            diff::check_hash_algos(reader_a.hash_algo, reader_b.hash_algo)?;
+            let run_diff = diff::diff(&diff::frame_keys(&mut reader_a)?, &diff::frame_keys(&mut reader_b)?);
+            let latency = compare_latency.then(|| run_diff.compare_latency(slower_than));
+            if json {
+                let mut out = serde_json::to_value(&run_diff)?;
+                if let Some(latency) = &latency {
+                    out["latency"] = serde_json::to_value(latency)?;
+                }
+                println!("{}", serde_json::to_string_pretty(&out)?);
+            } else {
+                println!("{}", run_diff);
+                if let Some(latency) = latency {
+                    println!("{}", latency);
+                }
+            }
+        }
+        Some(Commands::Verify { file }) => {
+            let report = verify::verify(stdio::open_decrypted(&file)?)?;
+            verify::print_report(&report);
+            if report.has_errors() {
+                anyhow::bail!("{} failed verification", file);
+            }
+        }
+        Some(Commands::Keygen { path }) => {
+            let public = sign::generate_key(&path)?;
+            println!("Secret key: {}", path.display());
+            println!("Public key: {}.pub", path.display());
+            println!("Fingerprint: {}", hex::encode(sign::fingerprint(&public)));
+        }
+        Some(Commands::Sign { file, key, sig }) => {
+            let sig = sig.unwrap_or_else(|| sign::sidecar_path(&file));
+            let signature = sign::sign(&file, &sign::load_signing_key(&key)?, &sig)?;
+            println!("Signed {} → {}", file.display(), sig.display());
+            println!("  Signer: {}", hex::encode(signature.signer));
+            println!("  SHA-256: {}", hex::encode(signature.sha256));
+        }
+        Some(Commands::VerifySignature { file, key, sig }) => {
+            let sig = sig.unwrap_or_else(|| sign::sidecar_path(&file));
+            sign::verify(&file, &sign::load_verifying_key(&key)?, &sig)
+                .map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+            println!("✓ {} is signed by the given key and unmodified", file.display());
+        }
         Some(Commands::Export {
             file,
             output,
             frame: frame_idx,
+            head,
+            tail,
+            decode,
             format,
         }) => {
             if format == "html" {
-                let raw = std::fs::read(&file)?;
-                let data_b64 = base64::engine::general_purpose::STANDARD.encode(&raw);
-                let filename = std::path::Path::new(&file)
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_string_lossy();
-                let viewer_dir = std::env::var("GHOSTLINE_VIEWER_DIST")
-                    .unwrap_or_else(|_| "viewer/dist".to_string());
-                let assets_dir = format!("{}/assets", viewer_dir);
-                let mut js_content = String::new();
-                let mut css_content = String::new();
-                for entry in std::fs::read_dir(&assets_dir)? {
-                    let entry = entry?;
-                    let path = entry.path();
-                    match path.extension().and_then(|e| e.to_str()) {
-                        Some("js") => js_content = std::fs::read_to_string(&path)?,
-                        Some("css") => css_content = std::fs::read_to_string(&path)?,
-                        _ => {}
-                    }
-                }
-                if js_content.is_empty() || css_content.is_empty() {
-                    anyhow::bail!(
-                        "viewer assets not found in {}. Run 'cd viewer && npm run build' or set GHOSTLINE_VIEWER_DIST",
-                        assets_dir
-                    );
-                }
-                let html = format!(
-                    r#"<!DOCTYPE html>
-<html lang="en">
-<head>
-  <meta charset="UTF-8" />
-  <meta name="viewport" content="width=device-width, initial-scale=1.0" />
-  <title>Ghostline — {filename}</title>
-  <style>{css_content}</style>
-</head>
-<body>
-  <div id="root"></div>
-  <script id="ghostline-data" type="application/octet-stream" data-filename="{filename}">{data_b64}</script>
-  <script type="module">{js_content}</script>
-</body>
-</html>"#
-                );
+                let html = export::render_html(&file)?;
                 let out_path = output.unwrap_or_else(|| {
-                    file.trim_end_matches(".ghostline").to_string() + ".html"
+                    if stdio::is_stdio(&file) {
+                        stdio::STDIO.to_string()
+                    } else {
+                        file.trim_end_matches(".ghostline").to_string() + ".html"
+                    }
                 });
-                std::fs::write(&out_path, &html)?;
-                println!("Exported → {} ({:.1} KB)", out_path, html.len() as f64 / 1024.0);
+                if stdio::is_stdio(&out_path) {
+                    print!("{}", html);
+                } else {
+                    std::fs::write(&out_path, &html)?;
+                    println!("Exported → {} ({:.1} KB)", out_path, html.len() as f64 / 1024.0);
+                }
             } else {
-                let mut reader = GhostlineReader::open(&file)?;
-                let b64 = base64::engine::general_purpose::STANDARD;
-                let range: Vec<usize> = match frame_idx {
-                    Some(idx) => vec![idx],
-                    None => (0..reader.frame_count()).collect(),
+                let selection = match (frame_idx, head, tail) {
+                    (Some(idx), _, _) => export::Selection::Frame(idx),
+                    (_, Some(n), _) => export::Selection::Head(n),
+                    (_, _, Some(n)) => export::Selection::Tail(n),
+                    _ => export::Selection::All,
                 };
-                let mut frames = Vec::new();
-                for i in range {
-                    let frame = reader.get_frame(i)?;
-                    let obj = serde_json::json!({
-                        "frame_index": i,
-                        "request_hash": hex::encode(frame.request_hash),
-                        "latency_ms": frame.latency_ms,
-                        "timestamp": frame.timestamp,
-                        "request_b64": b64.encode(&frame.request_bytes),
-                        "response_b64": b64.encode(&frame.response_bytes),
-                    });
-                    frames.push(obj);
-                }
-                let json = serde_json::to_string_pretty(&frames)?;
-                match output {
+                let json = export::render_json(&file, selection, decode)?;
+                match output.filter(|path| !stdio::is_stdio(path)) {
                     Some(path) => std::fs::write(&path, &json)?,
                     None => println!("{}", json),
                 }
             }
         }
-        Some(Commands::Show { file, index }) => {
-            let mut reader = GhostlineReader::open(&file)?;
+        Some(Commands::ExportAll { dir, out, format }) => {
+            let dir = dir.unwrap_or_else(Config::runs_dir);
+            let entries = export::export_all(&dir, &out, &format, Config::load_or_default().display.timezone)?;
+            println!("Exported {} recordings → {}", entries.len(), out.join("index.html").display());
+        }
+        Some(Commands::Show {
+            file,
+            index,
+            hash,
+            raw_hex: true,
+            part,
+            limit,
+            ..
+        }) => {
+            use std::io::Write;
+            let mut reader = stdio::open_reader(&file)?;
+            let index = hash.frame_index(reader.index_entries(), index)?;
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+            for &body in part.bodies() {
+                let label = if body == Body::Request { "Request" } else { "Response" };
+                writeln!(out, "--- {} ---", label)?;
+                let mut dump = hexdump::HexDump::new(&mut out, limit);
+                let len = reader.get_body_to(index, body, &mut dump)?;
+                dump.finish()?;
+                if let Some(limit) = limit.filter(|&l| l < len) {
+                    writeln!(out, "({} of {} bytes shown)", limit, len)?;
+                }
+            }
+            out.flush()?;
+        }
+        Some(Commands::Show { file, index, hash, full, preview_bytes, .. }) => {
+            let preview = (!full).then(|| preview_bytes.unwrap_or(Config::load_or_default().display.preview_bytes));
+            let mut reader = stdio::open_reader(&file)?;
+            let index = hash.frame_index(reader.index_entries(), index)?;
             let frame = reader.get_frame(index)?;
             println!("Frame [{}]", index);
             println!("  Hash:      {}", hex::encode(frame.request_hash));
             println!("  Timestamp: {}", fmt_ts(frame.timestamp));
             println!("  Latency:   {}ms", frame.latency_ms);
-            println!("  Request:   {} bytes", frame.request_bytes.len());
-            println!("  Response:  {} bytes", frame.response_bytes.len());
-            print_data_preview(&frame.request_bytes, "Request");
-            print_data_preview(&frame.response_bytes, "Response");
+            let size = |len: usize, original: Option<u64>| match original {
+                Some(orig) => format!("{} bytes (truncated from {})", len, orig),
+                None => format!("{} bytes", len),
+            };
+            println!("  Request:   {}", size(frame.request_bytes.len(), frame.original_request_len));
+            println!("  Response:  {}", size(frame.response_bytes.len(), frame.original_response_len));
+            if let Some(status) = frame.status {
+                println!("  Status:    {}", status);
+            }
+            if !frame.tags.is_empty() {
+                println!("  Tags:      {}", frame.tags.join(", "));
+            }
+            if !frame.anthropic_betas.is_empty() {
+                println!("  Betas:     {}", frame.anthropic_betas.join(", "));
+            }
+            if let Some(upstream) = &frame.upstream {
+                println!("  Upstream:  {}", upstream);
+            }
+            if frame.http_version.is_some() || frame.upstream_http_version.is_some() {
+                let show = |v: Option<ghostline_core::HttpVersion>| v.map_or("unknown", |v| v.as_str());
+                println!(
+                    "  HTTP:      client {}, upstream {}",
+                    show(frame.http_version),
+                    show(frame.upstream_http_version)
+                );
+            }
+            if !frame.request_headers.is_empty() {
+                println!("  Headers:");
+                for (name, value) in &frame.request_headers {
+                    println!("    {}: {}", name, value);
+                }
+            }
+            preview::print_body(&frame.request_bytes, frame.request_json(), "Request", preview);
+            preview::print_body(&frame.response_bytes, frame.response_json(), "Response", preview);
+            if let Some(message) = frame.reconstruct_message() {
+                println!("\n--- Reconstructed message ---");
+                preview::print_text(&serde_json::to_string_pretty(&message)?, preview);
+            }
+        }
+        Some(Commands::Cat { file, index, hash, request }) => {
+            use std::io::Write;
+            let mut reader = stdio::open_reader(&file)?;
+            let index = hash.frame_index(reader.index_entries(), index)?;
+            let body = if request { Body::Request } else { Body::Response };
+            let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+            reader.get_body_to(index, body, &mut out)?;
+            out.flush()?;
         }
-        Some(Commands::Fork { file, at, output }) => {
-            use ghostline_core::{GhostlineWriter, Header};
-            use sha2::{Digest, Sha256};
+        Some(Commands::Compact {
+            file,
+            output,
+            keep,
+            sort,
+        }) => {
+            let size_before = std::fs::metadata(&file)?.len();
+            let mut reader = GhostlineReader::open(&file)?;
+            let out_path = output.unwrap_or_else(|| file.clone());
+            // Write beside the target and rename, so compacting in place never
+            // leaves a half-written file.
+            let tmp_path = format!("{}.compact.tmp", out_path);
+            let report = compact::compact(
+                &mut reader,
+                std::io::BufWriter::new(std::fs::File::create(&tmp_path)?),
+                keep,
+                sort,
+            )?;
+            drop(reader);
+            std::fs::rename(&tmp_path, &out_path)?;
+            let size_after = std::fs::metadata(&out_path)?.len();
+            println!("Compacted {} → {}", file, out_path);
+            println!("  Frames: {} → {}", report.frames_before, report.frames_after);
+            println!("  Size:   {} → {} bytes", size_before, size_after);
+        }
+        Some(Commands::Filter { file, errors: _, output }) => {
+            let mut reader = GhostlineReader::open(&file)?;
+            let out_path = output.unwrap_or_else(|| format!("{}-errors.ghostline", file.trim_end_matches(".ghostline")));
+            let tmp_path = format!("{}.filter.tmp", out_path);
+            let report = compact::filter(
+                &mut reader,
+                std::io::BufWriter::new(std::fs::File::create(&tmp_path)?),
+                Frame::is_error,
+            )?;
+            drop(reader);
+            std::fs::rename(&tmp_path, &out_path)?;
+            println!("Kept {} of {} frames (errors) → {}", report.frames_after, report.frames_before, out_path);
+        }
+        Some(Commands::Encrypt { file, out }) => {
+            let plain = std::fs::read(&file)?;
+            if crypt::is_encrypted(&plain) {
+                anyhow::bail!("{} is already encrypted", file);
+            }
+            // Refuse to encrypt something that isn't a recording.
+            ghostline_core::read_header(&mut plain.as_slice())?;
+            let sealed = crypt::encrypt(&plain, &crypt::new_passphrase()?)?;
+            std::fs::write(&out, sealed)?;
+            eprintln!("Encrypted {} → {}", file, out);
+        }
+        Some(Commands::Decrypt { file, out }) => {
+            let sealed = std::fs::read(&file)?;
+            let plain = crypt::decrypt(&sealed, &crypt::read_passphrase()?)?;
+            std::fs::write(&out, plain)?;
+            eprintln!("Decrypted {} → {}", file, out);
+        }
+        Some(Commands::Fork { file, at, hash, range, output }) => {
+            use std::io::Write;
 
             let mut reader = GhostlineReader::open(&file)?;
             let frame_count = reader.frame_count();
-            if at >= frame_count {
-                anyhow::bail!(
-                    "step {} out of range — file has {} frames (0..{})",
-                    at,
-                    frame_count,
-                    frame_count - 1
-                );
-            }
-            let first_frame = reader.get_frame(0)?;
-            let mut hasher = Sha256::new();
-            hasher.update(reader.started_at.to_le_bytes());
-            hasher.update(first_frame.request_hash);
-            let parent_run_id: [u8; 32] = hasher.finalize().into();
+            let range = match range {
+                Some(range) => range,
+                None => {
+                    let at = hash.frame_index(reader.index_entries(), at)?;
+                    if at >= frame_count {
+                        anyhow::bail!(
+                            "step {} out of range — file has {} frames (0..{})",
+                            at,
+                            frame_count,
+                            frame_count.saturating_sub(1)
+                        );
+                    }
+                    fork::FrameRange { start: 0, end: at + 1 }
+                }
+            };
+            range.check(frame_count)?;
             let out_path = output.unwrap_or_else(|| {
                 let stem = file.trim_end_matches(".ghostline");
-                format!("{}-fork-{}.ghostline", stem, at)
+                match range.start {
+                    0 => format!("{}-fork-{}.ghostline", stem, range.end - 1),
+                    start => format!("{}-fork-{}-{}.ghostline", stem, start, range.end - 1),
+                }
             });
-            let out_file = std::fs::File::create(&out_path)?;
-            let mut buf_writer = std::io::BufWriter::new(out_file);
-            let header = Header {
-                started_at: reader.started_at,
-                git_sha: reader.git_sha,
-                parent_run_id: Some(parent_run_id),
-                fork_at_step: Some(at as u32),
-            };
-            let mut writer = GhostlineWriter::new(&mut buf_writer, &header)?;
-            for i in 0..=at {
-                let frame = reader.get_frame(i)?;
+            if stdio::is_stdio(&out_path) {
+                stdio::guard_binary_stdout()?;
+            }
+            let fork::Forked { mut out, run_id, parent_run_id } =
+                fork::fork(&mut reader, stdio::create_output(&out_path)?, range)?;
+            out.flush()?;
+            let to_stdout = stdio::is_stdio(&out_path);
+            let mut summary = format!(
+                "Forked {} frames ({}..={}) → {}",
+                range.end - range.start,
+                range.start,
+                range.end - 1,
+                if to_stdout { "stdout" } else { &out_path }
+            );
+            summary += &format!("\nRun id:     {}", hex::encode(run_id));
+            summary += &format!("\nParent run: {}", hex::encode(parent_run_id));
+            // Keep stdout clean for the binary when piping.
+            if to_stdout {
+                eprintln!("{}", summary);
+            } else {
+                println!("{}", summary);
+            }
+        }
+        Some(Commands::Send { file, frame, hash, target, path, diff }) => {
+            let cfg = Config::load_or_default();
+            let mut reader = stdio::open_reader(&file)?;
+            let index = hash.frame_index(reader.index_entries(), frame)?;
+            let recorded = reader.get_frame(index)?;
+            if recorded.original_request_len.is_some() {
+                anyhow::bail!("frame {}'s request was truncated when recorded, so it can't be sent again", index);
+            }
+            let target = target.or_else(|| recorded.upstream.clone()).unwrap_or_else(|| cfg.proxy.target.clone());
+            let url = proxy::upstream_url(&target, &path.parse()?)?;
+            let scrubber = scrub::Scrubber::new(&proxy::scrub_json_paths(&cfg))?;
+            let rt = tokio::runtime::Runtime::new()?;
+            let live = rt.block_on(send::send(
+                &proxy::upstream_client()?,
+                url.clone(),
+                &recorded,
+                cfg.auth.token().as_deref(),
+                &scrubber,
+            ))?;
+            send::print_comparison(index, &url, &recorded, &live, diff);
+        }
+        Some(Commands::Tag {
+            file,
+            frame: index,
+            hash,
+            add,
+            remove,
+        }) => {
+            use ghostline_core::GhostlineWriter;
+
+            if add.is_empty() && remove.is_empty() {
+                anyhow::bail!("nothing to do — pass --add <label> and/or --remove <label>");
+            }
+            let mut reader = GhostlineReader::open(&file)?;
+            let index = hash.frame_index(reader.index_entries(), index)?;
+            if index >= reader.frame_count() {
+                anyhow::bail!(
+                    "frame {} out of range — file has {} frames",
+                    index,
+                    reader.frame_count()
+                );
+            }
+
+            // Rewrite into a sibling temp file, then swap it into place.
+            let tmp_path = format!("{}.tmp", file);
+            let mut buf_writer = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+            let mut writer = GhostlineWriter::new(&mut buf_writer, &reader.header())?;
+            let mut tags = Vec::new();
+            for i in 0..reader.frame_count() {
+                let mut frame = reader.get_frame(i)?;
+                if i == index {
+                    frame.tags.retain(|t| !remove.contains(t));
+                    for label in &add {
+                        if !frame.tags.contains(label) {
+                            frame.tags.push(label.clone());
+                        }
+                    }
+                    tags = frame.tags.clone();
+                }
                 writer.append(&frame)?;
             }
             writer.finish()?;
-            println!("Forked {} frames (0..={}) → {}", at + 1, at, out_path);
-            println!("Parent run: {}", hex::encode(parent_run_id));
+            drop(buf_writer);
+            drop(reader);
+            std::fs::rename(&tmp_path, &file)?;
+            println!("Frame [{}] tags: {}", index, if tags.is_empty() { "(none)".to_string() } else { tags.join(", ") });
+        }
+        Some(Commands::Watch { dir, format, out }) => {
+            watch::watch(&dir, &format, &out)?;
+        }
+        Some(Commands::Import { har, output, all }) => {
+            let count = har::import_har(&har, &output, all)?;
+            println!("Imported {} frames from {} → {}", count, har, output);
         }
         Some(Commands::Search { file, query, top }) => {
             let script = format!(
@@ -577,14 +1419,173 @@ print(f"Backend: {{backend}}")
                 anyhow::bail!("search requires Python SDK: pip install ghostline");
             }
         }
-        Some(Commands::Replay { file, port }) => {
+        Some(Commands::Replay {
+            file,
+            port,
+            port_file,
+            canonical_json,
+            overrides,
+            override_status,
+            only_status,
+            advance_duplicates,
+            response_headers,
+            path_maps,
+            strict,
+            record_to,
+            target,
+            lazy,
+            memory_budget,
+            latency_scale,
+            latency_cap,
+            assert_complete,
+            coverage,
+            cmd,
+        }) => {
+            let overrides = overrides
+                .into_iter()
+                .map(|o| {
+                    let body = std::fs::read(&o.file)
+                        .map_err(|e| anyhow::anyhow!("cannot read override body {}: {}", o.file.display(), e))?;
+                    Ok(replay::OverrideSpec {
+                        hash: o.hash,
+                        status: override_status,
+                        body,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if latency_scale.is_some_and(|s| !(s.is_finite() && s >= 0.0)) {
+                anyhow::bail!("--latency-scale must be a non-negative number");
+            }
+            let cfg = with_bind(Config::load_or_default(), cli.bind);
+            // Configured headers first, sorted for a stable order, so flags win.
+            let mut configured: Vec<_> = cfg.replay.response_headers.iter().collect();
+            configured.sort();
+            let response_headers = configured
+                .into_iter()
+                .map(|(name, value)| replay::ResponseHeader::new(name, value).map_err(anyhow::Error::msg))
+                .chain(response_headers.into_iter().map(Ok))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let record_to = match record_to {
+                Some(path) => {
+                    if path.canonicalize().is_ok_and(|p| std::fs::canonicalize(&file).is_ok_and(|f| f == p)) {
+                        anyhow::bail!("--record-to must name a new file, not the recording being replayed");
+                    }
+                    let target = target.unwrap_or_else(|| cfg.proxy.target.clone());
+                    Some(proxy::LiveRecorder::create(path, &target, proxy::ProxyOptions::from_config(&cfg))?)
+                }
+                None => None,
+            };
+            let rt = tokio::runtime::Runtime::new()?;
+            let options = replay::ReplayOptions {
+                canonical_json,
+                port_file,
+                overrides,
+                only_status,
+                advance_duplicates,
+                response_headers,
+                strict,
+                record_to: record_to.map(Arc::new),
+                lazy: lazy.then_some(memory_budget << 20),
+                bind: cfg.server.bind,
+                coverage,
+                latency: (latency_scale.is_some() || latency_cap.is_some())
+                    .then(|| replay::LatencySim { scale: latency_scale.unwrap_or(1.0), cap_ms: latency_cap }),
+                path_maps,
+                ..Default::default()
+            };
+            if cmd.is_empty() {
+                rt.block_on(replay::run_replay_server(&file, port, options))?;
+            } else {
+                let code = rt.block_on(replay::run_with_command(file, port, options, cmd, assert_complete))?;
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+        }
+        Some(Commands::Serve {
+            file,
+            replay_port,
+            viewer_port,
+            canonical_json,
+        }) => {
+            let mut cfg = with_bind(Config::load_or_default(), cli.bind);
+            if let Some(port) = viewer_port {
+                cfg.viewer.port = port;
+            }
+            let bind = cfg.server.bind;
+            let frame_count = crypt::open_for_server(file.as_ref())?.frame_count();
+            let name = std::path::Path::new(&file)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| file.clone());
+            let loaded = replay::LoadedFrames::default();
+            let frame_tx = viewer_server::FrameSender::from_config(&cfg);
+            let state = viewer_server::ViewerState {
+                config: Arc::new(cfg),
+                frame_tx: frame_tx.clone(),
+                frame_count: Arc::new(std::sync::atomic::AtomicUsize::new(frame_count)),
+                scope: Some(viewer_server::ScopedRun {
+                    name,
+                    path: PathBuf::from(&file),
+                    frames: loaded.clone(),
+                }),
+            };
+            let options = replay::ReplayOptions {
+                canonical_json,
+                events: Some(frame_tx),
+                loaded: Some(loaded),
+                bind,
+                ..Default::default()
+            };
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(replay::run_replay_server(&file, port))?;
+            rt.block_on(async {
+                tokio::try_join!(
+                    replay::run_replay_server(&file, replay_port, options),
+                    viewer_server::serve(state),
+                )
+            })?;
         }
-        Some(Commands::Proxy { port, out, target }) => {
+        Some(Commands::Proxy {
+            port,
+            port_file,
+            out,
+            target,
+            stream_threshold,
+            clock,
+            capture,
+            delta_requests,
+            max_body_bytes,
+            duration,
+        }) => {
             let frame_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+            let cfg = with_bind(Config::load_or_default(), cli.bind);
+            let options = proxy::ProxyOptions {
+                stream_threshold,
+                clock,
+                delta_requests,
+                max_body_bytes,
+                port_file,
+                session: None,
+                replace: false,
+                throughput: proxy::show_throughput(&cfg),
+                bind: cfg.server.bind,
+                route_header: cfg.proxy.route_header.to_ascii_lowercase(),
+                routes: cfg.proxy.routes.clone(),
+                scrub_json_paths: proxy::scrub_json_paths(&cfg),
+                drop_sse_pings: cfg.recording.drop_sse_pings,
+                filter_file: cfg.recording.filter_file.as_ref().map(PathBuf::from),
+                timezone: cfg.display.timezone,
+                index_spill_entries: cfg.recording.index_spill_entries,
+                hash_algo: cfg.recording.hash_algo,
+                emit_meta: cfg.recording.emit_meta,
+                capture_request_body: cfg.recording.capture_request_body,
+                capture_response_body: cfg.recording.capture_response_body,
+                capture: capture.unwrap_or(cfg.recording.capture),
+                duration: duration.map(std::time::Duration::from_secs),
+                deterministic: proxy::deterministic_from_env(),
+            };
             let rt = tokio::runtime::Runtime::new()?;
-            rt.block_on(proxy::run_proxy(port, out, target, None, frame_count))?;
+            rt.block_on(proxy::run_proxy(port, out, target, None, frame_count, options))?;
         }
     }
 
@@ -0,0 +1,138 @@
+//! `ghostline meta`: a small JSON summary of a recording — who, when, how
+//! much, which models and how many tokens — for cataloguing many runs without
+//! opening each binary file. With `recording.emit_meta` the proxy writes it
+//! next to every recording it finishes, as `<file>.meta.json`.
+
+use ghostline_core::{GhostlineReader, HashAlgo};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// Token counts summed over every response's `usage`.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct TokenTotals {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct RunMeta {
+    /// Hex of the id fork lineage knows the run by ([`fork::run_id`](crate::fork::run_id)),
+    /// what a fork's `parent_run_id` names; `None` for a run with no frames.
+    pub run_id: Option<String>,
+    /// RFC 3339; `None` when the header says 0 (`monotonic-from-zero` clock).
+    pub started_at: Option<String>,
+    pub frames: usize,
+    /// Time between the first and last frame's timestamps, as in `stats`.
+    pub duration_ms: u64,
+    /// The upstream the requests went to, when that is known: from the
+    /// proxy that recorded it, or the one upstream every frame names.
+    pub target: Option<String>,
+    pub format_version: String,
+    pub hash_algo: HashAlgo,
+    /// Frames per request `model`; requests without one aren't counted.
+    pub models: BTreeMap<String, usize>,
+    pub tokens: TokenTotals,
+}
+
+/// Where the metadata of `file` is written.
+pub fn sidecar_path(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".meta.json");
+    PathBuf::from(name)
+}
+
+pub fn compute<R: Read + Seek>(reader: &mut GhostlineReader<R>) -> io::Result<RunMeta> {
+    let mut models = BTreeMap::new();
+    let mut tokens = TokenTotals::default();
+    let mut upstreams = std::collections::BTreeSet::new();
+    let (mut first_ts, mut last_ts) = (u64::MAX, 0);
+    reader.scan(|_, frame| {
+        first_ts = first_ts.min(frame.timestamp);
+        last_ts = last_ts.max(frame.timestamp);
+        upstreams.insert(frame.upstream.clone());
+        if let Some(model) = frame.request_json().as_ref().and_then(|r| r["model"].as_str()) {
+            *models.entry(model.to_string()).or_insert(0) += 1;
+        }
+        if let Some(usage) = frame.reconstruct_message().or_else(|| frame.response_json()).and_then(|m| m.get("usage").cloned()) {
+            let count = |field: &str| usage[field].as_u64().unwrap_or(0);
+            tokens.input_tokens += count("input_tokens");
+            tokens.output_tokens += count("output_tokens");
+            tokens.cache_creation_input_tokens += count("cache_creation_input_tokens");
+            tokens.cache_read_input_tokens += count("cache_read_input_tokens");
+        }
+    })?;
+    let started_at = (reader.started_at > 0)
+        .then(|| chrono::DateTime::from_timestamp_millis(reader.started_at as i64))
+        .flatten()
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true));
+    let run_id = reader
+        .index_entries()
+        .first()
+        .map(|first| hex::encode(crate::fork::run_id(reader.started_at, &first.request_hash)));
+    let target = match (upstreams.len(), upstreams.pop_first()) {
+        (1, Some(upstream)) => upstream,
+        _ => None,
+    };
+    Ok(RunMeta {
+        run_id,
+        started_at,
+        frames: reader.frame_count(),
+        duration_ms: last_ts.saturating_sub(first_ts),
+        target,
+        format_version: format!("{}.{}", reader.version >> 16, reader.version as u16),
+        hash_algo: reader.hash_algo,
+        models,
+        tokens,
+    })
+}
+
+/// Write `path`'s metadata to its [`sidecar_path`], with `target` when the
+/// caller knows it.
+pub fn write_sidecar(path: &Path, target: Option<&str>) -> anyhow::Result<PathBuf> {
+    let mut reader = GhostlineReader::open(path)?;
+    let mut meta = compute(&mut reader)?;
+    if let Some(target) = target {
+        meta.target = Some(target.to_string());
+    }
+    let sidecar = sidecar_path(path);
+    std::fs::write(&sidecar, serde_json::to_string_pretty(&meta)? + "\n")?;
+    Ok(sidecar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::{Frame, GhostlineWriter, Header};
+    use std::io::Cursor;
+
+    #[test]
+    fn counts_models_and_sums_usage() {
+        let frame = |model: &str, response: &str, ts: u64| {
+            let mut frame = Frame::new(format!(r#"{{"model":"{}"}}"#, model).into_bytes(), response.as_bytes().to_vec(), 5, ts);
+            frame.upstream = Some("https://api.anthropic.com".to_string());
+            frame
+        };
+        let header = Header { started_at: 1_700_000_000_000, ..Default::default() };
+        let mut writer = GhostlineWriter::new(Cursor::new(Vec::new()), &header).unwrap();
+        writer.append(&frame("haiku", r#"{"usage":{"input_tokens":10,"output_tokens":4}}"#, 1_000)).unwrap();
+        writer.append(&frame("haiku", r#"{"usage":{"input_tokens":5,"cache_read_input_tokens":100}}"#, 1_500)).unwrap();
+        writer.append(&frame("sonnet", "not json", 4_000)).unwrap();
+        let mut reader = GhostlineReader::from_reader(Cursor::new(writer.finish().unwrap().into_inner())).unwrap();
+
+        let meta = compute(&mut reader).unwrap();
+        let first = reader.index_entries()[0].request_hash;
+        assert_eq!(meta.run_id, Some(hex::encode(crate::fork::run_id(1_700_000_000_000, &first))));
+        assert_eq!(meta.started_at.as_deref(), Some("2023-11-14T22:13:20.000Z"));
+        assert_eq!((meta.frames, meta.duration_ms), (3, 3_000));
+        assert_eq!(meta.target.as_deref(), Some("https://api.anthropic.com"));
+        assert_eq!(meta.models, BTreeMap::from([("haiku".to_string(), 2), ("sonnet".to_string(), 1)]));
+        assert_eq!(
+            meta.tokens,
+            TokenTotals { input_tokens: 15, output_tokens: 4, cache_read_input_tokens: 100, ..Default::default() }
+        );
+    }
+}
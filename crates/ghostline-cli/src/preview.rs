@@ -0,0 +1,101 @@
+//! Body previews for `ghostline show`: the first `display.preview_bytes` of
+//! each body (`--preview-bytes` for one run, everything with `--full`), cut
+//! where a JSON value ends rather than mid-token when the body is JSON.
+
+/// Print `data` under a `--- label ---` heading: as text, as `decoded`
+/// pretty-printed when it isn't UTF-8 (e.g. MessagePack), or else as the
+/// first bytes in hex. `limit` of `None` prints the whole body.
+pub fn print_body(data: &[u8], decoded: Option<serde_json::Value>, label: &str, limit: Option<usize>) {
+    let content_type = ghostline_core::sniff_content_type(data);
+    println!("\n--- {} ({} bytes, {}) ---", label, data.len(), content_type);
+    if let Ok(text) = std::str::from_utf8(data) {
+        return print_text(text, limit);
+    }
+    if let Some(json) = decoded.and_then(|v| serde_json::to_string_pretty(&v).ok()) {
+        return print_text(&json, limit);
+    }
+    let hex_preview: String = data.iter().take(64).map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+    println!("{}{}", hex_preview, if data.len() > 64 { "..." } else { "" });
+}
+
+/// Print `text`, or its first `limit` bytes and how much was left out.
+pub fn print_text(text: &str, limit: Option<usize>) {
+    let end = limit.map_or(text.len(), |max| cut_point(text, max));
+    if end == text.len() {
+        println!("{}", text);
+    } else {
+        println!("{}...", &text[..end]);
+        println!("({} of {} bytes shown; --full prints everything)", end, text.len());
+    }
+}
+
+/// Where to cut `text` to keep at most `max` bytes: after the last JSON value
+/// that fits when `text` is an object or array, else at the last character
+/// boundary. All of `text` when it fits. A value boundary in the first half
+/// of the limit is passed over too, so one long string (a prompt, say) still
+/// shows `max` bytes instead of next to nothing.
+pub fn cut_point(text: &str, max: usize) -> usize {
+    if text.len() <= max {
+        return text.len();
+    }
+    let json = text.trim_start().starts_with(['{', '[']);
+    let value_end = json.then(|| json_value_end(text, max)).flatten().filter(|&end| end >= max / 2);
+    value_end.unwrap_or_else(|| {
+        (0..=max).rev().find(|&i| text.is_char_boundary(i)).unwrap_or(0)
+    })
+}
+
+/// The end of the last complete value in a JSON container that lies within
+/// the first `max` bytes: just before a separating comma, or just after a
+/// closing bracket.
+fn json_value_end(text: &str, max: usize) -> Option<usize> {
+    let (mut in_string, mut escaped) = (false, false);
+    let mut end = None;
+    for (i, b) in text.bytes().take(max).enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b',' => end = Some(i),
+            b'}' | b']' => end = Some(i + 1),
+            _ => {}
+        }
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_json_between_values() {
+        let json = r#"{"a":"x,y}","b":[1,2],"c":"long value"}"#;
+        assert_eq!(cut_point(json, 200), json.len());
+        // Inside "c"'s value: back to before the comma that ends "b".
+        assert_eq!(&json[..cut_point(json, 30)], r#"{"a":"x,y}","b":[1,2]"#);
+        // Commas and brackets inside strings don't count.
+        assert_eq!(cut_point(json, 10), 10);
+        // Plain text is cut at a character boundary.
+        assert_eq!(cut_point("héllo", 2), 1);
+    }
+
+    #[test]
+    fn long_string_values_still_fill_the_preview() {
+        let json = format!(r#"{{"model":"m","prompt":"{}","n":1}}"#, "é".repeat(500));
+        // The only boundary is after "model", far short of the limit: cut
+        // inside the string, on a character boundary, instead.
+        let end = cut_point(&json, 101);
+        assert!(json.is_char_boundary(end) && (100..=101).contains(&end), "{}", end);
+        // With no boundary at all, likewise.
+        let json = format!(r#"["{}"]"#, "x".repeat(500));
+        assert_eq!(cut_point(&json, 100), 100);
+    }
+}
@@ -1,54 +1,522 @@
-use ghostline_core::{Frame, GhostlineWriter, Header};
+use futures_util::{FutureExt, StreamExt};
+use ghostline_core::hash::Hasher;
+use ghostline_core::{Frame, GhostlineWriter, HashAlgo, Header, HttpVersion, FLAG_DELTA_REQUESTS};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server};
-use std::io::BufWriter;
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Read, Seek, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
 
+use crate::banner::{debug, info};
+use crate::config::{BindAddr, CaptureMode, ClockMode, Config};
+use crate::filter::RecordFilter;
+use crate::scrub::{self, Scrubber};
+use crate::timefmt::Timezone;
 use crate::viewer_server::FrameSender;
 
-type Writer = GhostlineWriter<BufWriter<std::fs::File>>;
+// Boxed so a recording can go to stdout as well as a file; `Send` because it
+// is written from the writer thread, which rules out a `StdoutLock`.
+type Writer = GhostlineWriter<BufWriter<Box<dyn Write + Send>>>;
 
+/// Settings every request reads, fixed once the recording is open, and the
+/// little state frames share. Requests run side by side, each forwarding,
+/// hashing and scrubbing its own frame; they only take `recording` to number
+/// their frame and queue it for the writer thread.
 struct ProxyState {
     target: String,
     client: reqwest::Client,
-    writer: Option<Writer>,
-    frame_count: usize,
     frame_tx: Option<FrameSender>,
     shared_frame_count: Arc<std::sync::atomic::AtomicUsize>,
     run_name: String,
+    options: ProxyOptions,
+    scrubber: Scrubber,
+    filter: RecordFilter,
+    recording: std::sync::Mutex<Recording>,
+}
+
+struct Recording {
+    /// `None` once finalized.
+    writer: Option<WriterThread>,
+    frame_count: usize,
+    /// Capture time of the first frame, used by `ClockMode::MonotonicFromZero`.
+    first_frame_at: Option<Instant>,
+    /// Present when `ProxyOptions::throughput` is on.
+    throughput: Option<Throughput>,
+}
+
+/// The thread that compresses and appends frames, in the order they were
+/// numbered, so a large frame's write never holds up another request. Frames
+/// queue in memory while it catches up; a spooled request body stays in its
+/// file until written.
+struct WriterThread {
+    frames: std::sync::mpsc::Sender<(Frame, Option<Spooled>)>,
+    thread: std::thread::JoinHandle<Writer>,
+}
+
+impl WriterThread {
+    fn spawn(mut writer: Writer) -> io::Result<Self> {
+        let (frames, queue) = std::sync::mpsc::channel::<(Frame, Option<Spooled>)>();
+        let thread = std::thread::Builder::new().name("ghostline-writer".into()).spawn(move || {
+            // Stages spooled frames' compressed blocks; made on first use.
+            let mut scratch = None;
+            for (frame, spooled) in queue {
+                let written = match spooled {
+                    None => writer.append(&frame),
+                    Some(spooled) => match &mut scratch {
+                        Some(file) => writer.append_streamed(&frame, spooled.file, spooled.len, file),
+                        None => tempfile::tempfile().and_then(|file| {
+                            writer.append_streamed(&frame, spooled.file, spooled.len, scratch.insert(file))
+                        }),
+                    },
+                };
+                if let Err(e) = written {
+                    eprintln!("[proxy] write error: {}", e);
+                }
+            }
+            writer
+        })?;
+        Ok(Self { frames, thread })
+    }
+
+    /// Wait for the queued frames to be written and hand the writer back.
+    fn join(self) -> anyhow::Result<Writer> {
+        drop(self.frames);
+        self.thread.join().map_err(|_| anyhow::anyhow!("the recording's writer thread panicked"))
+    }
+}
+
+/// Report throughput after this many frames, or once this much time has
+/// passed when the next frame arrives, whichever comes first.
+const THROUGHPUT_EVERY_FRAMES: usize = 50;
+const THROUGHPUT_EVERY: Duration = Duration::from_secs(30);
+
+/// Frames recorded since the last throughput report.
+struct Throughput {
+    since: Instant,
+    frames: usize,
+    bytes: u64,
+    latency_ms: u64,
+}
+
+#[derive(Debug, PartialEq)]
+struct ThroughputReport {
+    frames_per_sec: f64,
+    bytes: u64,
+    mean_latency_ms: u64,
+}
+
+impl Throughput {
+    fn new(now: Instant) -> Self {
+        Self { since: now, frames: 0, bytes: 0, latency_ms: 0 }
+    }
+
+    /// Count a frame, returning a report and starting a new window when one is due.
+    fn record(&mut self, now: Instant, bytes: u64, latency_ms: u64) -> Option<ThroughputReport> {
+        self.frames += 1;
+        self.bytes += bytes;
+        self.latency_ms += latency_ms;
+        let elapsed = now.saturating_duration_since(self.since);
+        if self.frames < THROUGHPUT_EVERY_FRAMES && elapsed < THROUGHPUT_EVERY {
+            return None;
+        }
+        let report = ThroughputReport {
+            frames_per_sec: self.frames as f64 / elapsed.as_secs_f64().max(0.001),
+            bytes: self.bytes,
+            mean_latency_ms: self.latency_ms / self.frames as u64,
+        };
+        *self = Self::new(now);
+        Some(report)
+    }
+}
+
+/// Recording and startup settings for [`run_proxy`], taken from config or CLI flags.
+#[derive(Debug, Clone)]
+pub struct ProxyOptions {
+    /// Request bodies larger than this are streamed upstream instead of buffered.
+    pub stream_threshold: usize,
+    pub clock: ClockMode,
+    /// Delta-encode requests against the previous frame (header flag).
+    pub delta_requests: bool,
+    /// Truncate bodies longer than this before writing the frame.
+    pub max_body_bytes: Option<usize>,
+    /// Where to write the bound port, for callers that pass port 0.
+    pub port_file: Option<PathBuf>,
+    /// Session name used in the recording's file name.
+    pub session: Option<String>,
+    /// Record a named session to `<name>.ghostline`, truncating any previous run.
+    pub replace: bool,
+    /// Print a periodic throughput line alongside the per-frame lines.
+    pub throughput: bool,
+    pub bind: BindAddr,
+    /// Header whose value picks an upstream from `routes`.
+    pub route_header: String,
+    /// Upstream base URL per `route_header` value, tried before the target.
+    pub routes: HashMap<String, String>,
+    /// JSON paths redacted from both bodies before the frame is written.
+    pub scrub_json_paths: Vec<String>,
+    /// Leave keepalive pings out of recorded SSE response bodies.
+    pub drop_sse_pings: bool,
+    /// Rules for which requests are recorded (`recording.filter_file`).
+    pub filter_file: Option<PathBuf>,
+    /// Zone of the per-frame log's clock (`display.timezone`).
+    pub timezone: Timezone,
+    /// Spill the frame index to a temp file past this many entries.
+    pub index_spill_entries: Option<usize>,
+    /// Algorithm for request hashes, recorded in the header (`recording.hash_algo`).
+    pub hash_algo: HashAlgo,
+    /// Write a `.meta.json` sidecar once finalized (`recording.emit_meta`).
+    pub emit_meta: bool,
+    /// Keep request bodies (`recording.capture_request_body`).
+    pub capture_request_body: bool,
+    /// Keep response bodies (`recording.capture_response_body`).
+    pub capture_response_body: bool,
+    /// Whose request headers frames record (`recording.capture`).
+    pub capture: CaptureMode,
+    /// Stop and finalize the recording after this long, if Ctrl+C hasn't come first.
+    pub duration: Option<Duration>,
+    /// Name recordings by counter instead of time and UUID ([`DETERMINISTIC_ENV`]).
+    pub deterministic: bool,
+}
+
+/// Set to `1` to make the CLI's own output reproducible, for end-to-end tests
+/// of ghostline itself: recordings are named `run-0001.ghostline` (or
+/// `<session>-0001.ghostline`), counting up past the files already in the
+/// output directory, instead of by timestamp and random UUID. Not meant for
+/// real recording, where names must not collide across machines. Encryption
+/// salts, nonces and signing keys stay random.
+pub const DETERMINISTIC_ENV: &str = "GHOSTLINE_DETERMINISTIC";
+
+pub fn deterministic_from_env() -> bool {
+    std::env::var_os(DETERMINISTIC_ENV).is_some_and(|v| v == "1")
+}
+
+impl ProxyOptions {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            stream_threshold: cfg.proxy.stream_threshold_bytes,
+            clock: cfg.recording.clock,
+            delta_requests: cfg.recording.delta_requests,
+            max_body_bytes: cfg.recording.max_body_bytes,
+            port_file: None,
+            session: None,
+            replace: false,
+            throughput: show_throughput(cfg),
+            bind: cfg.server.bind,
+            route_header: cfg.proxy.route_header.to_ascii_lowercase(),
+            routes: cfg.proxy.routes.clone(),
+            scrub_json_paths: scrub_json_paths(cfg),
+            drop_sse_pings: cfg.recording.drop_sse_pings,
+            filter_file: cfg.recording.filter_file.as_ref().map(PathBuf::from),
+            timezone: cfg.display.timezone,
+            index_spill_entries: cfg.recording.index_spill_entries,
+            hash_algo: cfg.recording.hash_algo,
+            emit_meta: cfg.recording.emit_meta,
+            capture_request_body: cfg.recording.capture_request_body,
+            capture_response_body: cfg.recording.capture_response_body,
+            capture: cfg.recording.capture,
+            duration: None,
+            deterministic: deterministic_from_env(),
+        }
+    }
+}
+
+/// Swap in a rewritten response body. A declared length that matched the
+/// recorded body still should; one that didn't stays flagged.
+fn replace_response(frame: &mut Frame, body: Vec<u8>) {
+    if frame.response_length_mismatch().is_none() && frame.original_content_length.is_some() {
+        frame.original_content_length = Some(body.len() as u64);
+    }
+    frame.response_bytes = body;
+}
+
+/// An SSE body without its `ping` events (`event: ping`, or a `data:` whose
+/// JSON `type` is `"ping"`), or `None` if it isn't a stream or has none.
+fn drop_sse_pings(body: &[u8]) -> Option<Vec<u8>> {
+    if ghostline_core::sniff_content_type(body) != "text/event-stream" {
+        return None;
+    }
+    let text = std::str::from_utf8(body).ok()?;
+    let is_ping = |event: &str| {
+        let mut named = None;
+        let mut data_type = None;
+        for line in event.lines() {
+            if let Some(name) = line.strip_prefix("event:") {
+                named = Some(name.trim() == "ping");
+            } else if let Some(data) = line.strip_prefix("data:") {
+                data_type = serde_json::from_str::<serde_json::Value>(data.trim())
+                    .ok()
+                    .and_then(|v| v["type"].as_str().map(|t| t == "ping"));
+            }
+        }
+        named.or(data_type).unwrap_or(false)
+    };
+    let mut out = String::with_capacity(text.len());
+    let mut event_start = 0;
+    let mut dropped = false;
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        pos += line.len();
+        // A blank line ends the event; a last event may be unterminated.
+        if line.trim_end_matches(['\r', '\n']).is_empty() || pos == text.len() {
+            let event = &text[event_start..pos];
+            if is_ping(event) {
+                dropped = true;
+            } else {
+                out.push_str(event);
+            }
+            event_start = pos;
+        }
+    }
+    dropped.then(|| out.into_bytes())
+}
+
+/// The configured `recording.scrub_json_paths`, or none when `scrub` is off.
+pub fn scrub_json_paths(cfg: &Config) -> Vec<String> {
+    if cfg.recording.scrub {
+        cfg.recording.scrub_json_paths.clone()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Throughput lines are terminal decoration: on with `display.colors` when
+/// stdout is a TTY.
+pub fn show_throughput(cfg: &Config) -> bool {
+    use std::io::IsTerminal;
+    cfg.display.colors && io::stdout().is_terminal()
+}
+
+/// Reject session names that couldn't be used as a plain file name.
+pub fn check_session_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) || name.contains("..") {
+        anyhow::bail!("invalid session name '{}' — use a plain name like my-repro", name);
+    }
+    Ok(())
+}
+
+/// File name for a new recording. A replaced named session gets the stable
+/// `<name>.ghostline`; otherwise the name is timestamped so runs never collide.
+pub fn run_filename(now: chrono::DateTime<chrono::Utc>, session: Option<&str>, replace: bool) -> String {
+    let stamp = now.format("%Y%m%d-%H%M%S");
+    match session {
+        Some(name) if replace => format!("{}.ghostline", name),
+        Some(name) => format!("{}-{}.ghostline", name, stamp),
+        None => format!("{}-{}.ghostline", stamp, uuid::Uuid::new_v4()),
+    }
+}
+
+/// File name for a new recording under [`DETERMINISTIC_ENV`]: the first `<prefix>-NNNN`
+/// not yet taken in `dir`, finished or not.
+pub fn counter_filename(dir: &Path, session: Option<&str>, replace: bool) -> String {
+    let prefix = match session {
+        Some(name) if replace => return format!("{}.ghostline", name),
+        Some(name) => name,
+        None => "run",
+    };
+    (1..)
+        .map(|n| format!("{}-{:04}.ghostline", prefix, n))
+        .find(|name| !dir.join(name).exists() && !partial_path(&dir.join(name)).exists())
+        .expect("some counter is free")
+}
+
+/// Tees a streamed request body into a running hash and a temp-file spool,
+/// so large uploads reach the upstream without being held in memory first.
+struct BodyTee {
+    /// `None` once `finish` has taken the hash.
+    hasher: Option<Hasher>,
+    algo: HashAlgo,
+    spool: std::fs::File,
+    len: u64,
+}
+
+/// A request body in its spool file, read from the start.
+struct Spooled {
+    file: std::fs::File,
+    /// How much of the file is recorded; less than was spooled once truncated.
+    len: u64,
+}
+
+impl Spooled {
+    fn read_all(mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(self.len as usize);
+        (&mut self.file).take(self.len).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+impl BodyTee {
+    fn new(algo: HashAlgo) -> io::Result<Self> {
+        Ok(Self {
+            hasher: Some(algo.hasher()),
+            algo,
+            spool: tempfile::tempfile()?,
+            len: 0,
+        })
+    }
+
+    fn write(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(chunk);
+        }
+        self.spool.write_all(chunk)?;
+        self.len += chunk.len() as u64;
+        Ok(())
+    }
+
+    /// Return the request hash and the spooled body.
+    fn finish(&mut self) -> io::Result<([u8; 32], Spooled)> {
+        let hash = self.hasher.take().unwrap_or_else(|| self.algo.hasher()).finalize();
+        self.spool.rewind()?;
+        Ok((hash, Spooled { file: self.spool.try_clone()?, len: self.len }))
+    }
+}
+
+/// Whether a request body should be streamed rather than buffered: it is
+/// larger than `threshold`, or chunked with no declared length.
+fn should_stream(headers: &hyper::HeaderMap, threshold: usize) -> bool {
+    match headers
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+    {
+        Some(len) => len > threshold,
+        None => headers
+            .get(hyper::header::TRANSFER_ENCODING)
+            .map(|v| v.as_bytes().eq_ignore_ascii_case(b"chunked"))
+            .unwrap_or(false),
+    }
+}
+
+/// Features listed in the request's `anthropic-beta` headers, comma-separated
+/// and possibly repeated, in the order sent.
+pub(crate) fn anthropic_betas(headers: &hyper::HeaderMap) -> Vec<String> {
+    headers
+        .get_all("anthropic-beta")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|b| !b.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Upstream base URL for a request: the route its `route_header` names, or
+/// `target` when there is none.
+fn route<'a>(target: &'a str, options: &'a ProxyOptions, headers: &hyper::HeaderMap) -> &'a str {
+    headers
+        .get(options.route_header.as_str())
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| options.routes.get(v.trim()))
+        .map_or(target, |t| t.trim_end_matches('/'))
+}
+
+/// HTTP client for upstream requests. It ignores `HTTP(S)_PROXY`, which may
+/// point back at a ghostline proxy.
+pub(crate) fn upstream_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().no_proxy().build()
+}
+
+/// The upstream URL for a request: `base`'s path followed by the request's
+/// path and query. Joined by hand rather than with `Url::join`, which would
+/// drop the base path and treat a `//host` request path as a new host. Runs
+/// of leading slashes in the request collapse; trailing ones are kept.
+pub(crate) fn upstream_url(base: &str, uri: &hyper::Uri) -> Result<url::Url, url::ParseError> {
+    let mut url = url::Url::parse(base)?;
+    let path = format!(
+        "{}/{}",
+        url.path().trim_end_matches('/'),
+        uri.path().trim_start_matches('/')
+    );
+    url.set_path(&path);
+    url.set_query(uri.query());
+    Ok(url)
+}
+
+/// Map hyper's protocol version to the recorded one.
+pub(crate) fn http_version(version: hyper::Version) -> Option<HttpVersion> {
+    match version {
+        hyper::Version::HTTP_09 => Some(HttpVersion::Http09),
+        hyper::Version::HTTP_10 => Some(HttpVersion::Http10),
+        hyper::Version::HTTP_11 => Some(HttpVersion::Http11),
+        hyper::Version::HTTP_2 => Some(HttpVersion::Http2),
+        hyper::Version::HTTP_3 => Some(HttpVersion::Http3),
+        _ => None,
+    }
 }
 
 async fn handle(
     req: Request<Body>,
-    state: Arc<Mutex<ProxyState>>,
+    s: Arc<ProxyState>,
 ) -> Result<Response<Body>, hyper::Error> {
+    let client_version = http_version(req.version());
     let method = req.method().clone();
     let uri = req.uri().clone();
     let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
     let headers = req.headers().clone();
-    let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
 
-    let s = state.lock().await;
-    let url = format!("{}{}", s.target, path);
+    let stream = should_stream(&headers, s.options.stream_threshold);
+    let hash_algo = s.options.hash_algo;
+
+    // Small bodies are buffered; large or chunked ones stream through a tee.
+    let (upstream_body, buffered, tee) = if stream {
+        let tee = match BodyTee::new(hash_algo) {
+            Ok(t) => Arc::new(std::sync::Mutex::new(t)),
+            Err(e) => {
+                eprintln!("[proxy] ERROR: cannot create spool file: {}", e);
+                return Ok(Response::builder().status(502).body(Body::from(format!("{}", e))).unwrap());
+            }
+        };
+        let spool = tee.clone();
+        let chunks = req.into_body().map(move |chunk| {
+            let chunk = chunk.map_err(io::Error::other)?;
+            spool.lock().unwrap().write(&chunk)?;
+            Ok::<_, io::Error>(chunk)
+        });
+        (reqwest::Body::wrap_stream(chunks), None, Some(tee))
+    } else {
+        // `Bytes` clones share the buffer: the upstream request and the frame
+        // see one copy of it.
+        let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
+        (reqwest::Body::from(body_bytes.clone()), Some(body_bytes), None)
+    };
+
+    let upstream_base = route(&s.target, &s.options, &headers).to_string();
+    let url = match upstream_url(&upstream_base, &uri) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("[proxy] ERROR: bad upstream URL {}: {}", upstream_base, e);
+            return Ok(Response::builder().status(502).body(Body::from(format!("{}", e))).unwrap());
+        }
+    };
     let mut builder = s.client.request(
         reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap(),
-        &url,
+        url,
     );
+    // The route header is for this proxy; upstreams don't need to see it.
+    let routing = !s.options.routes.is_empty();
     for (name, value) in headers.iter() {
         match name.as_str() {
             "host" | "connection" | "transfer-encoding" => continue,
+            n if routing && n == s.options.route_header => continue,
             n => builder = builder.header(n, value.as_bytes()),
         }
     }
-    builder = builder.body(body_bytes.to_vec());
-    drop(s);
-
+    let upstream = match builder.body(upstream_body).build() {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("[proxy] ERROR: {}", e);
+            return Ok(Response::builder().status(502).body(Body::from(format!("{}", e))).unwrap());
+        }
+    };
+    let request_headers = match s.options.capture {
+        CaptureMode::Client => scrub::recorded_headers(headers.iter().map(|(n, v)| (n.as_str(), v.as_bytes()))),
+        CaptureMode::Upstream => scrub::recorded_headers(upstream.headers().iter().map(|(n, v)| (n.as_str(), v.as_bytes()))),
+    };
     let start = Instant::now();
-    let resp = match builder.send().await {
+    let resp = match s.client.execute(upstream).await {
         Ok(r) => r,
         Err(e) => {
             eprintln!("[proxy] ERROR: {}", e);
@@ -58,49 +526,167 @@ async fn handle(
     let latency_ms = start.elapsed().as_millis() as u64;
 
     let status = resp.status();
+    let upstream_version = http_version(resp.version());
     let resp_headers = resp.headers().clone();
-    let resp_bytes = resp.bytes().await.unwrap_or_default();
+    // HEAD, 204 and 304 responses declare a length without sending a body.
+    let declared_length = (method != hyper::Method::HEAD && !matches!(status.as_u16(), 204 | 304))
+        .then(|| resp_headers.get(reqwest::header::CONTENT_LENGTH)?.to_str().ok()?.parse::<u64>().ok())
+        .flatten();
+    let resp_bytes = resp.bytes().await.unwrap_or_else(|e| {
+        eprintln!("[proxy] response body error: {}", e);
+        Default::default()
+    });
 
+    let captured_at = Instant::now();
     let now_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH).unwrap()
         .as_millis() as u64;
-    let frame = Frame::new(body_bytes.to_vec(), resp_bytes.to_vec(), latency_ms, now_ms);
+    // Multipart uploads are keyed by their parts, not the random boundary;
+    // the raw body is still what gets recorded.
+    let content_type = headers.get(hyper::header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    let spool_error = |e: io::Error| {
+        eprintln!("[proxy] spool error: {}", e);
+        Response::builder().status(502).body(Body::from(format!("{}", e))).unwrap()
+    };
+    // A spooled body is written into the frame straight from its file, unless
+    // something here needs all of it: multipart hashing, a JSON filter rule
+    // or scrubbing.
+    let (request_hash, request_bytes, mut spooled) = match (buffered, tee) {
+        (Some(body), _) => (hash_algo.hash_normalized(content_type, &body), Vec::from(body), None),
+        (None, Some(tee)) => {
+            let finished = tee.lock().unwrap().finish();
+            let (hash, spooled) = match finished {
+                Ok(finished) => finished,
+                Err(e) => return Ok(spool_error(e)),
+            };
+            let multipart = content_type.is_some_and(|ct| ct.starts_with("multipart/"));
+            if multipart || s.filter.reads_bodies() || !s.scrubber.is_empty() {
+                let bytes = match spooled.read_all() {
+                    Ok(bytes) => bytes,
+                    Err(e) => return Ok(spool_error(e)),
+                };
+                let hash = if multipart { hash_algo.hash_normalized(content_type, &bytes) } else { hash };
+                (hash, bytes, None)
+            } else {
+                (hash, Vec::new(), Some(spooled))
+            }
+        }
+        (None, None) => {
+            return Ok(spool_error(io::Error::other("the request body was neither buffered nor spooled")));
+        }
+    };
 
-    let mut s = state.lock().await;
-    if let Some(ref mut w) = s.writer {
-        if let Err(e) = w.append(&frame) {
-            eprintln!("[proxy] write error: {}", e);
+    // Filtered requests are forwarded as usual, just not recorded. Rules see
+    // the request as the client sent it, before any scrubbing.
+    if !s.filter.records(uri.path(), &request_bytes) {
+        debug!("[proxy] {} {} → {} not recorded (filter)", method, path, status.as_u16());
+        return Ok(client_response(status, &resp_headers, resp_bytes));
+    }
+    let mut frame = Frame::builder()
+        .request_hash(request_hash)
+        .request(request_bytes)
+        // The one copy of the response: the client is sent `resp_bytes` itself.
+        .response(resp_bytes.to_vec())
+        .latency_ms(latency_ms)
+        .timestamp(now_ms)
+        .http_version(client_version)
+        .upstream_http_version(upstream_version)
+        .anthropic_betas(anthropic_betas(&headers))
+        .request_headers(request_headers)
+        .upstream((!s.options.routes.is_empty()).then_some(upstream_base))
+        .status(Some(status.as_u16()))
+        .original_content_length(declared_length)
+        .build();
+    // Scrub whole bodies, before truncation. The hash is of the original
+    // request, so scrubbed recordings still replay.
+    if let Some(scrubbed) = s.scrubber.scrub(&frame.request_bytes) {
+        frame.request_bytes = scrubbed;
+    }
+    if let Some(scrubbed) = s.scrubber.scrub(&frame.response_bytes) {
+        replace_response(&mut frame, scrubbed);
+    }
+    if s.options.drop_sse_pings {
+        if let Some(stream) = drop_sse_pings(&frame.response_bytes) {
+            replace_response(&mut frame, stream);
+        }
+    }
+    if let Some(max) = s.options.max_body_bytes {
+        frame.truncate_bodies(max);
+        if let Some(spooled) = spooled.as_mut().filter(|spooled| spooled.len > max as u64) {
+            frame.original_request_len = Some(spooled.len);
+            frame.truncated = true;
+            spooled.len = max as u64;
         }
     }
-    s.frame_count += 1;
-    let fc = s.frame_count;
+    if !s.options.capture_request_body {
+        frame.drop_request_body();
+        if let Some(spooled) = spooled.take().filter(|spooled| spooled.len > 0) {
+            frame.original_request_len.get_or_insert(spooled.len);
+            frame.truncated = true;
+        }
+    }
+    let spooled_len = spooled.as_ref().map_or(0, |spooled| spooled.len as usize);
+    if !s.options.capture_response_body {
+        frame.drop_response_body();
+    }
+    let frame_bytes = (frame.request_bytes.len() + spooled_len + frame.response_bytes.len()) as u64;
+
+    // Number the frame and queue it in one step, so the file's order is the
+    // numbering's. Everything slow happens before or after.
+    let (fc, report, summary) = {
+        let mut r = s.recording.lock().unwrap();
+        if s.options.clock == ClockMode::MonotonicFromZero {
+            let first = *r.first_frame_at.get_or_insert(captured_at);
+            frame.timestamp = captured_at.saturating_duration_since(first).as_millis() as u64;
+        }
+        r.frame_count += 1;
+        let fc = r.frame_count;
+        let report = r.throughput.as_mut().and_then(|t| t.record(captured_at, frame_bytes, latency_ms));
+        let summary = s.frame_tx.is_some().then(|| {
+            let mut summary = frame.summary(fc - 1);
+            summary.request_size += spooled_len;
+            summary
+        });
+        match &r.writer {
+            Some(w) => {
+                if w.frames.send((frame, spooled)).is_err() {
+                    eprintln!("[proxy] write error: the writer thread has stopped");
+                }
+            }
+            None => debug!("[proxy] frame {} arrived after the recording was finalized; not written", fc),
+        }
+        (fc, report, summary)
+    };
     s.shared_frame_count.store(fc, std::sync::atomic::Ordering::Relaxed);
 
     // Broadcast frame to WebSocket viewers
-    if let Some(ref tx) = s.frame_tx {
-        let frame_json = serde_json::json!({
-            "index": fc,
-            "run_name": s.run_name,
-            "timestamp": chrono::Utc::now().to_rfc3339(),
-            "request_size": body_bytes.len(),
-            "response_size": resp_bytes.len(),
-            "latency_ms": latency_ms,
-        });
-        let _ = tx.send(frame_json.to_string());
+    if let (Some(tx), Some(summary)) = (&s.frame_tx, summary) {
+        let mut frame_json = serde_json::to_value(summary).unwrap_or_default();
+        frame_json["run_name"] = serde_json::json!(s.run_name);
+        tx.send(frame_json.to_string());
     }
-    drop(s);
+    let timezone = s.options.timezone;
 
-    crate::banner::print_frame(fc, latency_ms, resp_bytes.len());
+    crate::banner::print_frame(timezone, fc, latency_ms, resp_bytes.len());
+    debug!("[proxy] {} {} → {} hash {}", method, path, status.as_u16(), hex::encode(&request_hash[..8]));
+    if let Some(r) = report {
+        crate::banner::print_throughput(timezone, r.frames_per_sec, r.bytes, r.mean_latency_ms);
+    }
 
+    Ok(client_response(status, &resp_headers, resp_bytes))
+}
+
+/// The upstream response as relayed to the client.
+fn client_response(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap, body: hyper::body::Bytes) -> Response<Body> {
     let mut rb = Response::builder().status(status.as_u16());
-    for (name, value) in resp_headers.iter() {
+    for (name, value) in headers.iter() {
         match name.as_str() {
             "transfer-encoding" | "connection" => {}
             n => rb = rb.header(n, value.as_bytes()),
         }
     }
     rb = rb.header("x-ghostline-proxy", "true");
-    Ok(rb.body(Body::from(resp_bytes.to_vec())).unwrap())
+    rb.body(Body::from(body)).unwrap()
 }
 
 pub async fn run_proxy(
@@ -109,58 +695,500 @@ pub async fn run_proxy(
     target: String,
     frame_tx: Option<FrameSender>,
     shared_frame_count: Arc<std::sync::atomic::AtomicUsize>,
+    options: ProxyOptions,
 ) -> anyhow::Result<()> {
-    std::fs::create_dir_all(&out)?;
-
     let now = chrono::Utc::now();
-    let filename = format!("{}-{}.ghostline", now.format("%Y%m%d-%H%M%S"), uuid::Uuid::new_v4());
-    let filepath = out.join(&filename);
-
-    let file = BufWriter::new(std::fs::File::create(&filepath)?);
-    let header = Header { started_at: now.timestamp_millis() as u64, git_sha: None, parent_run_id: None, fork_at_step: None };
-    let writer = GhostlineWriter::new(file, &header)?;
+    let (output, sink, filename): (_, Box<dyn Write + Send>, _) = if out == Path::new(crate::stdio::STDIO) {
+        crate::stdio::guard_binary_stdout()?;
+        crate::banner::take_stdout();
+        (Output::Stdout, Box::new(io::stdout()), String::new())
+    } else {
+        std::fs::create_dir_all(&out)?;
+        let filename = if options.deterministic {
+            counter_filename(&out, options.session.as_deref(), options.replace)
+        } else {
+            run_filename(now, options.session.as_deref(), options.replace)
+        };
+        let path = out.join(&filename);
+        let partial = partial_path(&path);
+        let file = std::fs::File::create(&partial)?;
+        (Output::File { path, partial }, Box::new(file), filename)
+    };
 
-    let client = reqwest::Client::builder().no_proxy().build()?;
     let target_clean = target.trim_end_matches('/').to_string();
+    let bind = options.bind;
+    let state = open_recording(sink, now, filename, &target_clean, frame_tx, shared_frame_count, options)?;
+    let filter_banner = state
+        .options
+        .filter_file
+        .as_ref()
+        .map(|path| format!("{} ({} rules)", path.display(), state.filter.len()));
+    let state = Arc::new(state);
 
-    let state = Arc::new(Mutex::new(ProxyState {
-        target: target_clean.clone(),
-        client,
-        writer: Some(writer),
-        frame_count: 0,
-        frame_tx,
-        shared_frame_count,
-        run_name: filename.clone(),
-    }));
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], port));
-    eprintln!("ghostline transparent proxy");
-    eprintln!("  target: {}", target_clean);
-    eprintln!("  output: {}", filepath.display());
-    eprintln!("  listen: http://{}", addr);
-    eprintln!();
-    eprintln!("Usage: ANTHROPIC_BASE_URL=http://localhost:{} your-command", port);
-    eprintln!("Ctrl+C to stop and finalize the .ghostline file.");
-
+    let port_file = state.options.port_file.clone();
     let state2 = state.clone();
     let make_svc = make_service_fn(move |_| {
         let state = state2.clone();
         async move { Ok::<_, hyper::Error>(service_fn(move |req| handle(req, state.clone()))) }
     });
 
-    let server = Server::bind(&addr).serve(make_svc);
-    let graceful = server.with_graceful_shutdown(async {
-        tokio::signal::ctrl_c().await.ok();
-        eprintln!("\n[proxy] shutting down...");
-    });
-    graceful.await?;
+    // Port 0 lets the OS pick; report whatever was bound.
+    let listeners = bind.listen(port)?;
+    let addrs = listeners.iter().map(|l| l.local_addr()).collect::<io::Result<Vec<_>>>()?;
+    let bound_port = addrs[0].port();
+    info!("ghostline transparent proxy");
+    info!("  target: {}", target_clean);
+    let mut routes: Vec<_> = state.options.routes.clone().into_iter().collect();
+    routes.sort();
+    for (value, route_target) in routes {
+        info!("  route:  {} → {}", value, route_target);
+    }
+    match &output {
+        Output::File { path, .. } => info!("  output: {} (written on exit)", path.display()),
+        Output::Stdout => info!("  output: stdout (index written on exit)"),
+    }
+    if let Some(filter) = filter_banner {
+        info!("  filter: {}", filter);
+    }
+    for addr in &addrs {
+        info!("  listen: http://{}", addr);
+    }
+    info!();
+    info!("Usage: ANTHROPIC_BASE_URL=http://{}:{} your-command", bind.url_host(), bound_port);
+    let duration = state.options.duration;
+    match duration {
+        Some(d) => info!("Stops after {}s, or on Ctrl+C, and finalizes the .ghostline file.", d.as_secs()),
+        None => info!("Ctrl+C to stop and finalize the .ghostline file."),
+    }
+    if port == 0 || port_file.is_some() {
+        crate::banner::announce_port("GHOSTLINE_PROXY_PORT", bound_port, port_file.as_deref())?;
+    }
+    let shutdown = async move {
+        let elapsed = async {
+            match duration {
+                Some(d) => tokio::time::sleep(d).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("\n[proxy] interrupted, shutting down..."),
+            _ = elapsed => info!("[proxy] --duration {}s elapsed, shutting down...", duration.unwrap_or_default().as_secs()),
+        }
+    }
+    .shared();
+    let servers = listeners
+        .into_iter()
+        .map(|l| Ok(Server::from_tcp(l)?.serve(make_svc.clone()).with_graceful_shutdown(shutdown.clone())))
+        .collect::<hyper::Result<Vec<_>>>()?;
+    futures_util::future::try_join_all(servers).await?;
 
-    // Finalize
-    let mut s = state.lock().await;
-    let fc = s.frame_count;
-    if let Some(w) = s.writer.take() {
-        w.finish()?;
+    let fc = finalize(&state, &output)?;
+    match &output {
+        Output::File { path, .. } => info!("[proxy] recorded {} frames to {}", fc, path.display()),
+        Output::Stdout => info!("[proxy] recorded {} frames to stdout", fc),
     }
-    eprintln!("[proxy] recorded {} frames to {}", fc, filepath.display());
     Ok(())
 }
+
+/// Where a recording goes.
+enum Output {
+    /// `path`, written as `partial` until finished.
+    File { path: PathBuf, partial: PathBuf },
+    /// Streamed out as it is recorded, the index last; readers need it
+    /// captured somewhere seekable first.
+    Stdout,
+}
+
+/// Where a recording is written until it is finalized: a name `runs` and the
+/// viewer skip, renamed into place once finished so nothing ever opens a file
+/// without its index.
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+/// Start the recording in `sink` and the state [`handle`] records into,
+/// checking the upstream URLs and loading scrub and filter rules.
+fn open_recording(
+    sink: Box<dyn Write + Send>,
+    now: chrono::DateTime<chrono::Utc>,
+    run_name: String,
+    target: &str,
+    frame_tx: Option<FrameSender>,
+    shared_frame_count: Arc<std::sync::atomic::AtomicUsize>,
+    options: ProxyOptions,
+) -> anyhow::Result<ProxyState> {
+    let file = BufWriter::new(sink);
+    let started_at = match options.clock {
+        ClockMode::Wall => now.timestamp_millis() as u64,
+        ClockMode::MonotonicFromZero => 0,
+    };
+    let flags = if options.delta_requests { FLAG_DELTA_REQUESTS } else { 0 };
+    let header = Header { started_at, flags, hash_algo: options.hash_algo, ..Default::default() };
+    let mut writer = GhostlineWriter::new(file, &header)?;
+    if let Some(entries) = options.index_spill_entries {
+        writer = writer.with_index_spill(tempfile::tempfile()?, entries);
+    }
+
+    let client = upstream_client()?;
+    for base in std::iter::once(target).chain(options.routes.values().map(String::as_str)) {
+        url::Url::parse(base).map_err(|e| anyhow::anyhow!("invalid upstream URL {}: {}", base, e))?;
+    }
+
+    let scrubber = Scrubber::new(&options.scrub_json_paths)?;
+    let filter = match &options.filter_file {
+        Some(path) => RecordFilter::load(path)?,
+        None => RecordFilter::default(),
+    };
+    let throughput = options.throughput.then(|| Throughput::new(Instant::now()));
+    let recording = Recording {
+        writer: Some(WriterThread::spawn(writer)?),
+        frame_count: 0,
+        first_frame_at: None,
+        throughput,
+    };
+    Ok(ProxyState {
+        target: target.to_string(),
+        client,
+        frame_tx,
+        shared_frame_count,
+        run_name,
+        options,
+        scrubber,
+        filter,
+        recording: std::sync::Mutex::new(recording),
+    })
+}
+
+/// Write the index and move the recording into place; the number of frames.
+fn finalize(s: &ProxyState, output: &Output) -> anyhow::Result<usize> {
+    let (writer, frame_count) = {
+        let mut r = s.recording.lock().unwrap();
+        (r.writer.take(), r.frame_count)
+    };
+    if let Some(w) = writer {
+        let w = w.join()?;
+        let duplicates = w.duplicate_hash_count();
+        if duplicates > 0 {
+            eprintln!(
+                "[proxy] {} frames repeat an earlier request hash; replay will serve the latest response for each",
+                duplicates
+            );
+        }
+        w.finish()?.flush()?;
+        match output {
+            Output::File { path, partial } => {
+                std::fs::rename(partial, path)?;
+                if s.options.emit_meta {
+                    let sidecar = crate::meta::write_sidecar(path, Some(&s.target))?;
+                    debug!("[proxy] metadata written to {}", sidecar.display());
+                }
+            }
+            Output::Stdout if s.options.emit_meta => {
+                eprintln!("[proxy] recording.emit_meta needs a file to sit next to; none written for stdout");
+            }
+            Output::Stdout => {}
+        }
+    }
+    Ok(frame_count)
+}
+
+/// A recording fed one request at a time through the proxy's forwarding and
+/// capture, for `ghostline replay --record-to`: the requests replay misses.
+pub struct LiveRecorder {
+    state: Arc<ProxyState>,
+    output: Output,
+}
+
+impl LiveRecorder {
+    /// Start a recording at `path` (written as `<path>.partial` until
+    /// [`finish`](Self::finish)) of requests forwarded to `target`.
+    pub fn create(path: PathBuf, target: &str, options: ProxyOptions) -> anyhow::Result<Self> {
+        let partial = partial_path(&path);
+        let run_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let target = target.trim_end_matches('/');
+        let sink = Box::new(std::fs::File::create(&partial)?);
+        let state = open_recording(sink, chrono::Utc::now(), run_name, target, None, Default::default(), options)?;
+        Ok(Self { state: Arc::new(state), output: Output::File { path, partial } })
+    }
+
+    pub fn path(&self) -> &Path {
+        match &self.output {
+            Output::File { path, .. } => path,
+            Output::Stdout => Path::new(crate::stdio::STDIO),
+        }
+    }
+
+    /// Frames recorded so far.
+    pub fn recorded(&self) -> usize {
+        self.state.recording.lock().unwrap().frame_count
+    }
+
+    /// Send `req` upstream and record it, as the proxy would.
+    pub async fn forward(&self, req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+        handle(req, self.state.clone()).await
+    }
+
+    /// Finalize the recording; the number of frames in it.
+    pub async fn finish(&self) -> anyhow::Result<usize> {
+        finalize(&self.state, &self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::GhostlineReader;
+    use std::collections::HashSet;
+
+    #[test]
+    fn records_concurrent_requests_each_with_its_own_response() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // An upstream that echoes the body, slower for some requests so
+            // that they finish out of order.
+            let upstream = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+                Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let n: u64 = std::str::from_utf8(&body).unwrap().trim_start_matches("req-").parse().unwrap();
+                    tokio::time::sleep(Duration::from_millis((n * 7) % 20)).await;
+                    Ok::<_, hyper::Error>(Response::new(Body::from(format!("resp-{}", n))))
+                }))
+            }));
+            let target = format!("http://{}", upstream.local_addr());
+            tokio::spawn(upstream);
+
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("run.ghostline");
+            let options = ProxyOptions::from_config(&Config::default());
+            let recorder = Arc::new(LiveRecorder::create(path.clone(), &target, options).unwrap());
+            let requests = (0..100).map(|n| {
+                let recorder = recorder.clone();
+                tokio::spawn(async move {
+                    let req = Request::post("/v1/messages").body(Body::from(format!("req-{}", n))).unwrap();
+                    let resp = recorder.forward(req).await.unwrap();
+                    hyper::body::to_bytes(resp.into_body()).await.unwrap()
+                })
+            });
+            let responses = futures_util::future::join_all(requests).await;
+            for (n, resp) in responses.into_iter().enumerate() {
+                assert_eq!(resp.unwrap(), format!("resp-{}", n).as_bytes());
+            }
+            assert_eq!(recorder.finish().await.unwrap(), 100);
+
+            let mut reader = GhostlineReader::open(&path).unwrap();
+            let mut seen = HashSet::new();
+            reader
+                .scan(|_, frame| {
+                    let request = String::from_utf8(frame.request_bytes.clone()).unwrap();
+                    let expected = Frame::new(request.clone().into_bytes(), request.replace("req-", "resp-").into_bytes(), 0, 0);
+                    assert!(frame.content_eq(&expected), "{}", request);
+                    seen.insert(request);
+                })
+                .unwrap();
+            assert_eq!(seen.len(), 100);
+        });
+    }
+
+    #[test]
+    fn records_spooled_request_bodies() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let upstream = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+                Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    Ok::<_, hyper::Error>(Response::new(Body::from(body.len().to_string())))
+                }))
+            }));
+            let target = format!("http://{}", upstream.local_addr());
+            tokio::spawn(upstream);
+
+            let body: Vec<u8> = (0..=255u8).cycle().take(50_000).collect();
+            let dir = tempfile::tempdir().unwrap();
+            for max_body_bytes in [None, Some(1000)] {
+                let path = dir.path().join("run.ghostline");
+                let options = ProxyOptions { stream_threshold: 1024, max_body_bytes, ..ProxyOptions::from_config(&Config::default()) };
+                let recorder = LiveRecorder::create(path.clone(), &target, options).unwrap();
+                let req = Request::post("/v1/files").header("content-length", body.len()).body(Body::from(body.clone())).unwrap();
+                let resp = recorder.forward(req).await.unwrap();
+                assert_eq!(hyper::body::to_bytes(resp.into_body()).await.unwrap(), "50000");
+                recorder.finish().await.unwrap();
+
+                let frame = GhostlineReader::open(&path).unwrap().get_frame(0).unwrap();
+                let kept = max_body_bytes.unwrap_or(body.len());
+                let expected = Frame::builder()
+                    .request_hash(HashAlgo::Sha256.hash(&body))
+                    .request(body[..kept].to_vec())
+                    .response(b"50000".to_vec())
+                    .build();
+                assert!(frame.content_eq(&expected));
+                assert_eq!(frame.original_request_len, max_body_bytes.map(|_| body.len() as u64));
+            }
+        });
+    }
+
+    #[test]
+    fn records_headers_from_the_capture_side() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let upstream = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+                Ok::<_, hyper::Error>(service_fn(|_req: Request<Body>| async move {
+                    Ok::<_, hyper::Error>(Response::new(Body::from("{}")))
+                }))
+            }));
+            let target = format!("http://{}", upstream.local_addr());
+            tokio::spawn(upstream);
+
+            let dir = tempfile::tempdir().unwrap();
+            for capture in [CaptureMode::Client, CaptureMode::Upstream] {
+                let path = dir.path().join("run.ghostline");
+                let options = ProxyOptions { capture, ..ProxyOptions::from_config(&Config::default()) };
+                let recorder = LiveRecorder::create(path.clone(), &target, options).unwrap();
+                let req = Request::post("/v1/messages")
+                    .header("host", "localhost:9000")
+                    .header("connection", "keep-alive")
+                    .header("x-api-key", "sk-ant-secret")
+                    .header("anthropic-version", "2023-06-01")
+                    .body(Body::from("{}"))
+                    .unwrap();
+                recorder.forward(req).await.unwrap();
+                recorder.finish().await.unwrap();
+
+                let frame = GhostlineReader::open(&path).unwrap().get_frame(0).unwrap();
+                let header = |name: &str| frame.request_headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+                assert_eq!(header("x-api-key"), Some(scrub::REDACTED));
+                assert_eq!(header("anthropic-version"), Some("2023-06-01"));
+                let hop_by_hop = (header("host"), header("connection"));
+                match capture {
+                    CaptureMode::Client => assert_eq!(hop_by_hop, (Some("localhost:9000"), Some("keep-alive"))),
+                    CaptureMode::Upstream => assert_eq!(hop_by_hop, (None, None)),
+                }
+                assert!(frame.content_eq(&Frame::new(b"{}".to_vec(), b"{}".to_vec(), 0, 0)));
+            }
+        });
+    }
+
+    #[test]
+    fn drops_sse_pings() {
+        let body = "event: message_start\ndata: {\"type\":\"message_start\"}\n\n\
+                    event: ping\ndata: {\"type\": \"ping\"}\n\n\
+                    data: {\"type\":\"ping\"}\r\n\r\n\
+                    event: content_block_delta\ndata: {\"type\":\"content_block_delta\"}\n\n\
+                    event: ping\ndata: {\"type\": \"ping\"}";
+        let kept = String::from_utf8(drop_sse_pings(body.as_bytes()).unwrap()).unwrap();
+        assert_eq!(
+            kept,
+            "event: message_start\ndata: {\"type\":\"message_start\"}\n\n\
+             event: content_block_delta\ndata: {\"type\":\"content_block_delta\"}\n\n"
+        );
+        assert_eq!(drop_sse_pings(kept.as_bytes()), None);
+        assert_eq!(drop_sse_pings(br#"{"type":"ping"}"#), None);
+    }
+
+    #[test]
+    fn session_file_names() {
+        let now = chrono::DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().to_utc();
+        assert_eq!(run_filename(now, Some("my-repro"), true), "my-repro.ghostline");
+        assert_eq!(run_filename(now, Some("my-repro"), false), "my-repro-20260102-030405.ghostline");
+        assert!(run_filename(now, None, false).starts_with("20260102-030405-"));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(counter_filename(dir.path(), None, false), "run-0001.ghostline");
+        std::fs::write(dir.path().join("run-0001.ghostline"), b"").unwrap();
+        std::fs::write(dir.path().join("run-0002.ghostline.partial"), b"").unwrap();
+        assert_eq!(counter_filename(dir.path(), None, false), "run-0003.ghostline");
+        assert_eq!(counter_filename(dir.path(), Some("my-repro"), false), "my-repro-0001.ghostline");
+        assert_eq!(counter_filename(dir.path(), Some("my-repro"), true), "my-repro.ghostline");
+
+        assert!(check_session_name("my-repro").is_ok());
+        for bad in ["", ".hidden", "a/b", "..", "a\\b"] {
+            assert!(check_session_name(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn throughput_reports_every_n_frames_or_interval() {
+        let start = Instant::now();
+        let mut t = Throughput::new(start);
+        for i in 1..THROUGHPUT_EVERY_FRAMES {
+            assert!(t.record(start + Duration::from_millis(i as u64), 100, 10).is_none());
+        }
+        let report = t.record(start + Duration::from_secs(5), 100, 60).unwrap();
+        assert_eq!(report.frames_per_sec, 10.0);
+        assert_eq!(report.bytes, 100 * THROUGHPUT_EVERY_FRAMES as u64);
+        assert_eq!(report.mean_latency_ms, 11);
+
+        let later = start + Duration::from_secs(5) + THROUGHPUT_EVERY;
+        let report = t.record(later, 7, 40).unwrap();
+        assert_eq!((report.bytes, report.mean_latency_ms), (7, 40));
+    }
+
+    #[test]
+    fn parses_anthropic_beta_headers() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(anthropic_betas(&headers).is_empty());
+        headers.append("anthropic-beta", "files-api-2025-04-14, ,token-efficient-tools-2025-02-19".parse().unwrap());
+        headers.append("anthropic-beta", "output-128k-2025-02-19".parse().unwrap());
+        assert_eq!(
+            anthropic_betas(&headers),
+            vec!["files-api-2025-04-14", "token-efficient-tools-2025-02-19", "output-128k-2025-02-19"]
+        );
+    }
+
+    #[test]
+    fn routes_by_header_value() {
+        let mut cfg = Config::default();
+        cfg.proxy.route_header = "X-Provider".to_string();
+        cfg.proxy.routes.insert("openai".to_string(), "https://api.openai.com/".to_string());
+        let options = ProxyOptions::from_config(&cfg);
+
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(route("https://api.anthropic.com", &options, &headers), "https://api.anthropic.com");
+        headers.insert("x-provider", "openai".parse().unwrap());
+        assert_eq!(route("https://api.anthropic.com", &options, &headers), "https://api.openai.com");
+        headers.insert("x-provider", "mistral".parse().unwrap());
+        assert_eq!(route("https://api.anthropic.com", &options, &headers), "https://api.anthropic.com");
+    }
+
+    #[test]
+    fn joins_upstream_urls() {
+        let join = |base: &str, uri: &str| upstream_url(base, &uri.parse().unwrap()).unwrap().to_string();
+        assert_eq!(join("https://api.anthropic.com", "/v1/messages"), "https://api.anthropic.com/v1/messages");
+        assert_eq!(join("https://api.anthropic.com", "/"), "https://api.anthropic.com/");
+        assert_eq!(join("https://gw.example/anthropic/", "/"), "https://gw.example/anthropic/");
+        assert_eq!(join("https://gw.example/anthropic/", "/v1/models/"), "https://gw.example/anthropic/v1/models/");
+        assert_eq!(join("https://gw.example/anthropic", "/?beta=true"), "https://gw.example/anthropic/?beta=true");
+        assert_eq!(join("https://gw.example/anthropic", "//evil.test/x"), "https://gw.example/anthropic/evil.test/x");
+        assert_eq!(join("http://127.0.0.1:8080", "/v1/messages?a=1&b"), "http://127.0.0.1:8080/v1/messages?a=1&b");
+        assert!(upstream_url("not a url", &"/".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn stream_decision() {
+        let mut headers = hyper::HeaderMap::new();
+        assert!(!should_stream(&headers, 1024));
+        headers.insert(hyper::header::CONTENT_LENGTH, "512".parse().unwrap());
+        assert!(!should_stream(&headers, 1024));
+        headers.insert(hyper::header::CONTENT_LENGTH, "4096".parse().unwrap());
+        assert!(should_stream(&headers, 1024));
+
+        let mut chunked = hyper::HeaderMap::new();
+        chunked.insert(hyper::header::TRANSFER_ENCODING, "chunked".parse().unwrap());
+        assert!(should_stream(&chunked, 1024));
+    }
+
+    #[test]
+    fn tee_hash_matches_buffered_hash() {
+        let mut tee = BodyTee::new(HashAlgo::Sha256).unwrap();
+        tee.write(b"hello ").unwrap();
+        tee.write(b"world").unwrap();
+        let (hash, spooled) = tee.finish().unwrap();
+        assert_eq!((spooled.len, spooled.read_all().unwrap()), (11, b"hello world".to_vec()));
+        assert_eq!(hash, Frame::hash_request(b"hello world"));
+
+        let mut tee = BodyTee::new(HashAlgo::Blake3).unwrap();
+        tee.write(b"hello world").unwrap();
+        assert_eq!(tee.finish().unwrap().0, HashAlgo::Blake3.hash(b"hello world"));
+    }
+}
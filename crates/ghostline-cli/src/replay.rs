@@ -1,145 +1,1208 @@
-use ghostline_core::{Frame, GhostlineReader};
+use ghostline_core::{Frame, HttpVersion, ReplayOutcome, Replayer};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server, StatusCode};
-use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::io;
-use std::net::SocketAddr;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use tokio::sync::Mutex;
 
-/// Pre-loaded frame cache keyed by request hash.
+use crate::banner::{debug, info};
+use crate::config::BindAddr;
+use crate::proxy::LiveRecorder;
+use crate::viewer_server::FrameSender;
+
+/// A replay file's frames in file order, published once the cache has loaded
+/// so other servers (the `serve` viewer) can share them.
+pub type LoadedFrames = Arc<OnceLock<Arc<Vec<Frame>>>>;
+
+/// Pre-loaded frames with hit/miss counters.
 struct ReplayCache {
-    frames: HashMap<[u8; 32], Frame>,
+    replayer: Replayer,
+    /// Canonical-JSON hash → raw request hash, when `--canonical-json` is on.
+    canonical: Option<HashMap<[u8; 32], [u8; 32]>>,
     hits: u64,
+    canonical_hits: u64,
+    overridden: u64,
     misses: u64,
+    /// Hits turned into misses by `--strict`; also counted in `misses`.
+    strict_misses: u64,
+    /// Added to every hit and override (`--response-header`).
+    response_headers: Vec<ResponseHeader>,
+    /// `--strict`: a hit must also send the request headers recorded with it.
+    strict: bool,
+    /// `--record-to`: misses are forwarded upstream and recorded here.
+    record_to: Option<Arc<LiveRecorder>>,
+    /// `--latency-scale` / `--latency-cap`: hits wait out their recorded latency.
+    latency: Option<LatencySim>,
+    /// Hits whose simulated latency `--latency-cap` shortened.
+    latency_capped: u64,
 }
 
 impl ReplayCache {
-    fn lookup(&mut self, hash: &[u8; 32]) -> Option<&Frame> {
-        if let Some(frame) = self.frames.get(hash) {
-            self.hits += 1;
-            Some(frame)
+    fn new(replayer: Replayer) -> Self {
+        Self {
+            replayer,
+            canonical: None,
+            hits: 0,
+            canonical_hits: 0,
+            overridden: 0,
+            misses: 0,
+            strict_misses: 0,
+            response_headers: Vec::new(),
+            strict: false,
+            record_to: None,
+            latency: None,
+            latency_capped: 0,
+        }
+    }
+
+    /// Look up by raw hash, falling back to the canonical-JSON hash of `body`.
+    /// A frame `accept` turns down is a miss.
+    fn lookup(
+        &mut self,
+        hash: &[u8; 32],
+        body: &[u8],
+        accept: impl FnOnce(&Frame) -> bool,
+    ) -> std::io::Result<ReplayOutcome<'_>> {
+        let key = if self.replayer.contains(hash) {
+            Some(*hash)
         } else {
-            self.misses += 1;
-            None
+            self.canonical
+                .as_ref()
+                .and_then(|c| canonical_hash(body).and_then(|h| c.get(&h)))
+                .copied()
+                .filter(|raw| self.replayer.contains(raw))
+        };
+        // Unrecorded hashes still go to the replayer, which may hold an override.
+        let outcome = self.replayer.try_lookup_if(&key.unwrap_or(*hash), accept)?;
+        match outcome {
+            ReplayOutcome::Hit { .. } => {
+                self.hits += 1;
+                if key != Some(*hash) {
+                    self.canonical_hits += 1;
+                }
+            }
+            ReplayOutcome::Overridden { .. } => self.overridden += 1,
+            ReplayOutcome::Miss { .. } | ReplayOutcome::Declined { .. } => self.misses += 1,
+        }
+        Ok(outcome)
+    }
+}
+
+/// Simulated latency: each hit is answered after its recorded latency times
+/// `scale`, clamped to `cap_ms`, so a few pathological frames can't dominate
+/// a latency-aware test run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencySim {
+    pub scale: f64,
+    pub cap_ms: Option<u64>,
+}
+
+impl LatencySim {
+    /// How long to wait for a frame recorded at `latency_ms`, and whether the
+    /// cap shortened it.
+    fn delay(&self, latency_ms: u64) -> (Duration, bool) {
+        let scaled = (latency_ms as f64 * self.scale) as u64;
+        match self.cap_ms {
+            Some(cap) if scaled > cap => (Duration::from_millis(cap), true),
+            _ => (Duration::from_millis(scaled), false),
+        }
+    }
+}
+
+/// `--override <hash>=<file>`: serve `file` instead of the recorded response
+/// for the request whose hash is, or starts with, `hash` (hex).
+#[derive(Debug, Clone)]
+pub struct OverrideArg {
+    pub hash: String,
+    pub file: PathBuf,
+}
+
+impl std::str::FromStr for OverrideArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (hash, file) = s.split_once('=').ok_or("expected <hash>=<file>")?;
+        if hash.is_empty() || hash.len() > 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("{:?} is not a hex request hash or hash prefix", hash));
+        }
+        Ok(Self {
+            hash: hash.to_ascii_lowercase(),
+            file: PathBuf::from(file),
+        })
+    }
+}
+
+/// `--response-header <name>=<value>`, or an entry of
+/// `replay.response_headers`: a header added to every replayed response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResponseHeader {
+    pub name: hyper::header::HeaderName,
+    pub value: hyper::header::HeaderValue,
+}
+
+impl ResponseHeader {
+    pub fn new(name: &str, value: &str) -> Result<Self, String> {
+        let name = hyper::header::HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("{:?} is not a valid header name", name))?;
+        // Replay always sends the body it has, with its own length.
+        if name == hyper::header::CONTENT_LENGTH || name == hyper::header::TRANSFER_ENCODING {
+            return Err(format!("{} is set by replay and can't be injected", name));
+        }
+        let value = hyper::header::HeaderValue::from_str(value.trim())
+            .map_err(|_| format!("{:?} is not a valid value for header {}", value, name))?;
+        Ok(Self { name, value })
+    }
+}
+
+impl std::str::FromStr for ResponseHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (name, value) = s.split_once('=').ok_or("expected <name>=<value>")?;
+        Self::new(name, value)
+    }
+}
+
+/// `--map-path <from>=<to>`: requests whose path is `from`, or lies under it,
+/// are handled as if sent to `to` instead (`/api/v1=/v1` turns
+/// `/api/v1/messages` into `/v1/messages`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMap {
+    pub from: String,
+    pub to: String,
+}
+
+impl std::str::FromStr for PathMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (from, to) = s.split_once('=').ok_or("expected <from>=<to>")?;
+        for path in [from, to] {
+            if !path.starts_with('/') || path.parse::<hyper::Uri>().is_err() || path.contains(['?', '#']) {
+                return Err(format!("{:?} is not a request path (e.g. /v1)", path));
+            }
+        }
+        Ok(Self { from: from.to_string(), to: to.to_string() })
+    }
+}
+
+/// `path` rewritten by the first of `maps` it falls under, or `None` if none
+/// changes it. `from` matches whole segments: `/v1` covers `/v1/messages`
+/// but not `/v10`.
+fn map_path(maps: &[PathMap], path: &str) -> Option<String> {
+    let (map, rest) = maps.iter().find_map(|map| {
+        let rest = path.strip_prefix(map.from.trim_end_matches('/'))?;
+        (rest.is_empty() || rest.starts_with('/')).then_some((map, rest))
+    })?;
+    let mapped = match (map.to.trim_end_matches('/'), rest) {
+        ("", "") => "/".to_string(),
+        (to, rest) => format!("{}{}", to, rest),
+    };
+    (mapped != path).then_some(mapped)
+}
+
+/// Set `headers` on a replayed response, replacing any of replay's own
+/// headers of the same name.
+fn inject_headers(response: &mut Response<Body>, headers: &[ResponseHeader]) {
+    for header in headers {
+        response.headers_mut().insert(header.name.clone(), header.value.clone());
+    }
+}
+
+/// A response to serve in place of the recorded one for `hash` (hex, or a
+/// prefix of a recorded hash).
+#[derive(Debug, Clone)]
+pub struct OverrideSpec {
+    pub hash: String,
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// The recorded request hash `prefix` names; it must match exactly one.
+fn resolve_hash(replayer: &Replayer, prefix: &str) -> anyhow::Result<[u8; 32]> {
+    let mut matches = replayer.request_hashes().filter(|h| hex::encode(h).starts_with(prefix));
+    let first = matches
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no recorded request hash starts with {}", prefix))?;
+    if matches.any(|h| h != first) {
+        anyhow::bail!("hash prefix {} matches more than one request", prefix);
+    }
+    Ok(first)
+}
+
+/// Rebuild a JSON value with object keys in sorted order.
+fn canonicalize(value: serde_json::Value) -> serde_json::Value {
+    use serde_json::Value;
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().map(|(k, v)| (k, canonicalize(v))).collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+        other => other,
+    }
+}
+
+/// SHA-256 of the compact, sorted-key serialization of a JSON body.
+/// Returns None if the body isn't JSON.
+fn canonical_hash(body: &[u8]) -> Option<[u8; 32]> {
+    let value: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let bytes = serde_json::to_vec(&canonicalize(value)).ok()?;
+    Some(Frame::hash_request(&bytes))
+}
+
+/// Index a .ghostline file by request hash, loading all its frames unless
+/// `lazy` gives a memory budget to read them on demand within.
+fn load_cache(
+    path: &str,
+    canonical_json: bool,
+    overrides: Vec<OverrideSpec>,
+    only_status: Vec<u16>,
+    advance_duplicates: bool,
+    lazy: Option<usize>,
+) -> anyhow::Result<ReplayCache> {
+    let mut replayer = match lazy {
+        Some(budget) => {
+            if canonical_json {
+                anyhow::bail!("--canonical-json hashes every recorded request up front; it can't be combined with --lazy");
+            }
+            let mut magic = [0u8; 8];
+            let head = std::io::Read::read_exact(&mut std::fs::File::open(path)?, &mut magic);
+            if head.is_ok() && crate::crypt::is_encrypted(&magic) {
+                anyhow::bail!("--lazy reads frames straight from the file; decrypt {} first", path);
+            }
+            Replayer::open_lazy(path, budget)?
         }
+        None => Replayer::from_reader(&mut crate::crypt::open_for_server(path.as_ref())?)?,
+    };
+    if !only_status.is_empty() {
+        replayer.set_allowed_statuses(only_status);
     }
+    if advance_duplicates {
+        replayer.advance_duplicates();
+    }
+    for spec in overrides {
+        let hash = resolve_hash(&replayer, &spec.hash)?;
+        replayer.set_response_override(hash, spec.status, spec.body);
+    }
+    let canonical = canonical_json.then(|| {
+        let mut c = HashMap::new();
+        for frame in replayer.frames().into_iter().flat_map(|f| f.iter()) {
+            if let Some(h) = canonical_hash(&frame.request_bytes) {
+                c.entry(h).or_insert(frame.request_hash);
+            }
+        }
+        c
+    });
+
+    Ok(ReplayCache { canonical, ..ReplayCache::new(replayer) })
+}
+
+/// A request header recorded with a frame that the replayed request sends
+/// differently.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct HeaderMismatch {
+    header: &'static str,
+    recorded: Vec<String>,
+    received: Vec<String>,
+}
+
+/// `--strict`: how the request's headers differ from `frame`'s. Frames store
+/// only the `anthropic-beta` features, compared as a set.
+fn header_mismatches(frame: &Frame, betas: &[String]) -> Vec<HeaderMismatch> {
+    let sorted = |list: &[String]| {
+        let mut list = list.to_vec();
+        list.sort();
+        list.dedup();
+        list
+    };
+    let (recorded, received) = (sorted(&frame.anthropic_betas), sorted(betas));
+    if recorded == received {
+        return Vec::new();
+    }
+    vec![HeaderMismatch { header: "anthropic-beta", recorded, received }]
+}
+
+/// Why a request gets no recorded response.
+enum MissReason {
+    /// Nothing answers its hash.
+    Unrecorded,
+    /// `--strict`: frame `index` answers its hash but was recorded with other
+    /// request headers.
+    Headers { index: usize, mismatches: Vec<HeaderMismatch> },
+    /// Frame `index` answers its hash but was recorded without its response
+    /// body (`recording.capture_response_body = false`).
+    NotCaptured { index: usize },
 }
 
-/// Load all frames from a .ghostline file into a hash map.
-fn load_cache(path: &str) -> io::Result<ReplayCache> {
-    let mut reader = GhostlineReader::open(path)?;
-    let count = reader.frame_count();
-    let mut frames = HashMap::with_capacity(count);
+/// Answer a request the recording has no response for: forwarded upstream
+/// and recorded with `--record-to`, else a 404 saying why.
+async fn serve_miss(
+    parts: hyper::http::request::Parts,
+    body: hyper::body::Bytes,
+    hash: [u8; 32],
+    reason: MissReason,
+    record_to: Option<Arc<LiveRecorder>>,
+    events: &Option<FrameSender>,
+    missed: &Option<MissLog>,
+) -> Result<Response<Body>, hyper::Error> {
+    let (method, uri) = (parts.method.clone(), parts.uri.clone());
+    if let Some(log) = missed {
+        log.lock().unwrap().push((hash, body.to_vec()));
+    }
+    let live = if record_to.is_some() { ", forwarding live" } else { "" };
+    match &reason {
+        MissReason::Unrecorded => info!("[replay] MISS {} {} — hash {}{}", method, uri, hex::encode(&hash[..8]), live),
+        MissReason::Headers { index, mismatches } => {
+            info!("[replay] STRICT MISS {} {} — frame [{}] was recorded with other headers{}", method, uri, index, live);
+            for m in mismatches {
+                info!("[replay]   {}: recorded [{}], got [{}]", m.header, m.recorded.join(", "), m.received.join(", "));
+            }
+        }
+        MissReason::NotCaptured { index } => {
+            info!("[replay] MISS {} {} — frame [{}] was recorded without its response body{}", method, uri, index, live);
+        }
+    }
+    if let Some(recorder) = record_to {
+        let mut response = recorder.forward(Request::from_parts(parts, Body::from(body))).await?;
+        send_event(events, serde_json::json!({
+            "type": "replay_live",
+            "status": response.status().as_u16(),
+            "request_hash": hex::encode(hash),
+            "path": uri.path(),
+        }));
+        response.headers_mut().insert("x-ghostline-replay", hyper::header::HeaderValue::from_static("live"));
+        return Ok(response);
+    }
+    let mut event = serde_json::json!({
+        "type": "replay_miss",
+        "status": 404,
+        "request_hash": hex::encode(hash),
+        "path": uri.path(),
+    });
+    let mut body = serde_json::json!({
+        "error": "no cached response for this request",
+        "request_hash": hex::encode(hash),
+    });
+    match &reason {
+        MissReason::Unrecorded => {}
+        MissReason::Headers { index, mismatches } => {
+            event["index"] = serde_json::json!(index);
+            event["header_mismatches"] = serde_json::json!(mismatches);
+            body["error"] = serde_json::json!("request headers differ from the recording (--strict)");
+            body["frame_index"] = serde_json::json!(index);
+            body["header_mismatches"] = serde_json::json!(mismatches);
+        }
+        MissReason::NotCaptured { index } => {
+            event["index"] = serde_json::json!(index);
+            event["reason"] = serde_json::json!("response_not_captured");
+            body["error"] = serde_json::json!(
+                "the recording kept no response body for this request (recording.capture_response_body = false)"
+            );
+            body["frame_index"] = serde_json::json!(index);
+        }
+    }
+    send_event(events, event);
+    Ok(Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .header("content-type", "application/json")
+        .header("x-ghostline-replay", "miss")
+        .body(Body::from(body.to_string()))
+        .unwrap())
+}
 
-    for i in 0..count {
-        let frame = reader.get_frame(i)?;
-        frames.insert(frame.request_hash, frame);
+fn hyper_version(version: HttpVersion) -> hyper::Version {
+    match version {
+        HttpVersion::Http09 => hyper::Version::HTTP_09,
+        HttpVersion::Http10 => hyper::Version::HTTP_10,
+        HttpVersion::Http11 => hyper::Version::HTTP_11,
+        HttpVersion::Http2 => hyper::Version::HTTP_2,
+        HttpVersion::Http3 => hyper::Version::HTTP_3,
     }
+}
+
+/// Hashes and bodies of requests that missed the cache, in arrival order.
+pub type MissLog = Arc<std::sync::Mutex<Vec<([u8; 32], Vec<u8>)>>>;
+
+/// Shared replay cache; `None` until `load_cache` has finished.
+type SharedCache = Arc<Mutex<Option<ReplayCache>>>;
 
-    Ok(ReplayCache {
-        frames,
-        hits: 0,
-        misses: 0,
+/// Optional knobs for [`run_replay_server`].
+#[derive(Default)]
+pub struct ReplayOptions {
+    /// On a hash miss, match requests by canonical JSON.
+    pub canonical_json: bool,
+    /// Write the bound port here once listening.
+    pub port_file: Option<PathBuf>,
+    /// Broadcast a JSON event for every hit and miss.
+    pub events: Option<FrameSender>,
+    /// Publish the loaded frames here.
+    pub loaded: Option<LoadedFrames>,
+    /// Responses to serve instead of the recorded ones.
+    pub overrides: Vec<OverrideSpec>,
+    /// Serve only frames recorded with one of these statuses; empty serves all.
+    pub only_status: Vec<u16>,
+    /// Serve a request recorded more than once with its frames in turn.
+    pub advance_duplicates: bool,
+    /// Headers added to every hit and override, in order; later ones win.
+    pub response_headers: Vec<ResponseHeader>,
+    /// Miss hits whose request headers differ from the recorded ones.
+    pub strict: bool,
+    /// Forward misses upstream and record them here, leaving the replayed
+    /// file untouched.
+    pub record_to: Option<Arc<LiveRecorder>>,
+    /// Read frames from the file as they are requested, caching up to this
+    /// many bytes of them, instead of loading the whole recording.
+    pub lazy: Option<usize>,
+    pub bind: BindAddr,
+    /// Keep the body of every missed request here.
+    pub missed: Option<MissLog>,
+    /// Sent the bound port once the cache has loaded and requests are served.
+    pub ready: Option<tokio::sync::oneshot::Sender<u16>>,
+    /// Write a per-frame coverage report here when the server stops.
+    pub coverage: Option<PathBuf>,
+    /// Stop serving when this fires.
+    pub shutdown: Option<tokio::sync::oneshot::Receiver<()>>,
+    /// Wait out each hit's recorded latency before answering it.
+    pub latency: Option<LatencySim>,
+    /// Rewrite incoming request paths, first match wins.
+    pub path_maps: Vec<PathMap>,
+}
+
+/// Which frames a replay session served: per-frame hit counts and the
+/// frames never used, for checking a test exercised the whole recording.
+fn coverage_report(file: &str, replayer: &Replayer) -> serde_json::Value {
+    let hits = replayer.hit_counts();
+    let frames: Vec<_> = replayer
+        .request_hashes()
+        .zip(hits)
+        .enumerate()
+        .map(|(i, (hash, &hits))| {
+            serde_json::json!({
+                "index": i,
+                "request_hash": hex::encode(hash),
+                "hits": hits,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "file": file,
+        "total_frames": hits.len(),
+        "frames_hit": hits.iter().filter(|&&h| h > 0).count(),
+        "unused": replayer.unused_frames(),
+        "frames": frames,
     })
 }
 
+fn send_event(events: &Option<FrameSender>, event: serde_json::Value) {
+    if let Some(tx) = events {
+        tx.send(event.to_string());
+    }
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
 async fn handle_request(
-    req: Request<Body>,
-    cache: Arc<Mutex<ReplayCache>>,
+    mut req: Request<Body>,
+    cache: SharedCache,
+    events: Option<FrameSender>,
+    missed: Option<MissLog>,
+    path_maps: Arc<[PathMap]>,
 ) -> Result<Response<Body>, hyper::Error> {
+    // Before anything routes on or logs the path, and before the body is
+    // hashed, so forwarded misses go to the mapped path too.
+    if let Some(path) = map_path(&path_maps, req.uri().path()) {
+        let mapped = match req.uri().query() {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
+        };
+        if let Ok(uri) = mapped.parse() {
+            info!("[replay] {} {} → {}", req.method(), req.uri(), mapped);
+            *req.uri_mut() = uri;
+        }
+    }
     let method = req.method().clone();
     let uri = req.uri().clone();
 
-    // GET /status — health check
-    if method == hyper::Method::GET && uri.path() == "/status" {
-        let c = cache.lock().await;
-        let body = serde_json::json!({
-            "ok": true,
-            "cached_frames": c.frames.len(),
-            "hits": c.hits,
-            "misses": c.misses,
-        });
-        return Ok(Response::builder()
-            .status(200)
-            .header("content-type", "application/json")
-            .body(Body::from(body.to_string()))
-            .unwrap());
+    if method == hyper::Method::GET {
+        match uri.path() {
+            // GET /healthz — process is up
+            "/healthz" => {
+                return Ok(json_response(StatusCode::OK, serde_json::json!({ "status": "ok" })));
+            }
+            // GET /readyz — cache is loaded and requests can be served
+            "/readyz" => {
+                return Ok(if cache.lock().await.is_some() {
+                    json_response(StatusCode::OK, serde_json::json!({ "status": "ready" }))
+                } else {
+                    json_response(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        serde_json::json!({ "status": "loading" }),
+                    )
+                });
+            }
+            // GET /status — cache stats
+            "/status" => {
+                let guard = cache.lock().await;
+                let mut body = match guard.as_ref() {
+                    Some(c) => serde_json::json!({
+                        "ok": true,
+                        "ready": true,
+                        "cached_frames": c.replayer.unique_hashes(),
+                        "unused_frames": c.replayer.unused_frames().len(),
+                        "hits": c.hits,
+                        "canonical_hits": c.canonical_hits,
+                        "overrides": c.replayer.override_count(),
+                        "overridden": c.overridden,
+                        "misses": c.misses,
+                        "strict_misses": c.strict_misses,
+                        "filtered": c.replayer.filtered_count(),
+                        "latency_capped": c.latency_capped,
+                    }),
+                    None => serde_json::json!({ "ok": true, "ready": false }),
+                };
+                if let Some((cached, budget)) = guard.as_ref().and_then(|c| c.replayer.lazy_cache_usage()) {
+                    body["lazy_cache"] = serde_json::json!({ "cached_bytes": cached, "budget_bytes": budget });
+                }
+                if let Some(recorder) = guard.as_ref().and_then(|c| c.record_to.as_ref()) {
+                    body["recorded_live"] = serde_json::json!(recorder.recorded());
+                }
+                return Ok(json_response(StatusCode::OK, body));
+            }
+            // GET /hashes — every frame's request hash, in file order, for
+            // clients to check up front what they will hit
+            "/hashes" => {
+                let guard = cache.lock().await;
+                let Some(c) = guard.as_ref() else {
+                    return Ok(json_response(
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        serde_json::json!({ "error": "replay cache is still loading" }),
+                    ));
+                };
+                let frames: Vec<_> = c
+                    .replayer
+                    .request_hashes()
+                    .enumerate()
+                    .map(|(index, hash)| serde_json::json!({ "index": index, "request_hash": hex::encode(hash) }))
+                    .collect();
+                let body = serde_json::json!({ "hash_algo": c.replayer.hash_algo(), "frames": frames });
+                return Ok(json_response(StatusCode::OK, body));
+            }
+            _ => {}
+        }
     }
 
-    // For all other requests: hash the body, look up cached response
-    let body_bytes = hyper::body::to_bytes(req.into_body()).await?;
+    // For all other requests: hash the body the way the proxy did, look up cached response
+    let (parts, body) = req.into_parts();
+    let content_type = parts
+        .headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let betas = crate::proxy::anthropic_betas(&parts.headers);
+    let body_bytes = hyper::body::to_bytes(body).await?;
+
+    // A lazy cache reads the answering frame from the file, so look it up
+    // on the blocking pool rather than stall this worker holding the lock.
+    let looked_up = {
+        let (cache, events, method, uri) = (cache.clone(), events.clone(), method.clone(), uri.clone());
+        let body = body_bytes.clone();
+        tokio::task::spawn_blocking(move || {
+            answer(&mut cache.blocking_lock(), content_type.as_deref(), &body, &betas, &method, &uri, &events)
+        })
+        .await
+        .expect("replay lookup panicked")
+    };
+    match looked_up {
+        Answer::Serve { response, delay } => {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            Ok(response)
+        }
+        Answer::Miss { hash, reason, record_to } => {
+            serve_miss(parts, body_bytes, hash, reason, record_to, &events, &missed).await
+        }
+    }
+}
 
-    let mut hasher = Sha256::new();
-    hasher.update(&body_bytes);
-    let hash: [u8; 32] = hasher.finalize().into();
+/// What the cache answers a request with.
+enum Answer {
+    /// A recorded frame, an override or an error, to send after `delay`.
+    Serve { response: Response<Body>, delay: Option<Duration> },
+    Miss {
+        hash: [u8; 32],
+        reason: MissReason,
+        record_to: Option<Arc<LiveRecorder>>,
+    },
+}
 
-    let mut c = cache.lock().await;
-    match c.lookup(&hash) {
-        Some(frame) => {
-            eprintln!(
+/// Look a request up in the cache and build what it is answered with.
+/// Blocking: a lazy cache may read the frame from the file.
+fn answer(
+    cache: &mut Option<ReplayCache>,
+    content_type: Option<&str>,
+    body_bytes: &[u8],
+    betas: &[String],
+    method: &hyper::Method,
+    uri: &hyper::Uri,
+    events: &Option<FrameSender>,
+) -> Answer {
+    let serve = |response| Answer::Serve { response, delay: None };
+    let Some(c) = cache.as_mut() else {
+        return serve(json_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({ "error": "replay cache is still loading" }),
+        ));
+    };
+    // Hash with the recording's own algorithm, from its header.
+    let hash = c.replayer.hash_algo().hash_normalized(content_type, body_bytes);
+    // `lookup` borrows the cache for as long as the outcome lives.
+    let response_headers = c.response_headers.clone();
+    let strict = c.strict;
+    let record_to = c.record_to.clone();
+    let latency = c.latency;
+    let outcome = match c.lookup(&hash, body_bytes, |frame| {
+        !frame.response_dropped() && (!strict || header_mismatches(frame, betas).is_empty())
+    }) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("[replay] error: cannot read the frame recorded for hash {}: {}", hex::encode(&hash[..8]), e);
+            return serve(json_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({ "error": format!("cannot read the recorded frame: {}", e) }),
+            ));
+        }
+    };
+    match outcome {
+        ReplayOutcome::Hit { index, frame } => {
+            let status = frame.status.unwrap_or(200);
+            send_event(events, serde_json::json!({
+                "type": "replay_hit",
+                "status": status,
+                "index": index,
+                "request_hash": hex::encode(hash),
+                "latency_ms": frame.latency_ms,
+                "response_size": frame.response_bytes.len(),
+            }));
+            info!(
                 "[replay] HIT {} {} → {}ms latency, {} bytes",
                 method,
                 uri,
                 frame.latency_ms,
                 frame.response_bytes.len()
             );
+            debug!("[replay]   hash {} → frame [{}]", hex::encode(&hash[..8]), index);
             // Serve the cached response bytes directly
-            // The response_bytes contain the raw response body as captured
-            Ok(Response::builder()
-                .status(200)
-                .header("content-type", "application/json")
+            // The response_bytes contain the raw response body as captured;
+            // headers weren't recorded, so the content type is sniffed.
+            // Advertise the upstream's recorded protocol; hyper falls back to
+            // the connection's own version when it can't speak that one.
+            let mut response = Response::builder().status(status);
+            if frame.truncated {
+                eprintln!(
+                    "[replay] warning: frame [{}] was truncated when recorded; the response body is incomplete",
+                    index
+                );
+                response = response.header("x-ghostline-truncated", "true");
+            }
+            if let Some((declared, recorded)) = frame.response_length_mismatch() {
+                eprintln!(
+                    "[replay] warning: frame [{}] recorded {} response bytes but upstream declared content-length {}; serving the recorded body",
+                    index, recorded, declared
+                );
+                response = response.header("x-ghostline-content-length-mismatch", declared.to_string());
+            }
+            if let Some(version) = frame.upstream_http_version {
+                response = response
+                    .version(hyper_version(version))
+                    .header("x-ghostline-http-version", version.as_str());
+            }
+            let mut response = response
+                .header("content-type", ghostline_core::sniff_content_type(&frame.response_bytes))
                 .header("x-ghostline-replay", "true")
                 .header("x-ghostline-latency-ms", frame.latency_ms.to_string())
+                .header("x-ghostline-frame-index", index.to_string())
+                .header("x-ghostline-request-hash", hex::encode(frame.request_hash))
+                .header("content-length", frame.response_bytes.len().to_string())
                 .body(Body::from(frame.response_bytes.clone()))
-                .unwrap())
+                .unwrap();
+            inject_headers(&mut response, &response_headers);
+            // Slept out after the lock is released, so other requests are
+            // served meanwhile.
+            let Some(latency) = latency else { return serve(response) };
+            let (delay, capped) = latency.delay(frame.latency_ms);
+            if capped {
+                debug!("[replay]   latency capped to {}ms", delay.as_millis());
+                c.latency_capped += 1;
+            }
+            Answer::Serve { response, delay: Some(delay) }
         }
-        None => {
-            eprintln!(
-                "[replay] MISS {} {} — hash {}",
-                method,
-                uri,
-                hex::encode(&hash[..8])
-            );
-            let body = serde_json::json!({
-                "error": "no cached response for this request",
-                "request_hash": hex::encode(&hash),
-            });
-            Ok(Response::builder()
-                .status(StatusCode::NOT_FOUND)
-                .header("content-type", "application/json")
-                .header("x-ghostline-replay", "miss")
-                .body(Body::from(body.to_string()))
-                .unwrap())
+        ReplayOutcome::Overridden { index, response } => {
+            send_event(events, serde_json::json!({
+                "type": "replay_override",
+                "status": response.status,
+                "index": index,
+                "request_hash": hex::encode(hash),
+                "response_size": response.body.len(),
+            }));
+            info!("[replay] OVERRIDE {} {} → {}", method, uri, response.status);
+            let mut response = Response::builder()
+                .status(response.status)
+                .header("content-type", ghostline_core::sniff_content_type(&response.body))
+                .header("x-ghostline-replay", "override")
+                .body(Body::from(response.body.clone()))
+                .unwrap();
+            inject_headers(&mut response, &response_headers);
+            serve(response)
+        }
+        ReplayOutcome::Declined { index, frame } => {
+            let reason = if frame.response_dropped() {
+                MissReason::NotCaptured { index }
+            } else {
+                MissReason::Headers { index, mismatches: header_mismatches(frame, betas) }
+            };
+            if let MissReason::Headers { .. } = reason {
+                c.strict_misses += 1;
+            }
+            Answer::Miss { hash, reason, record_to }
         }
+        ReplayOutcome::Miss { hash } => Answer::Miss { hash, reason: MissReason::Unrecorded, record_to },
     }
 }
 
-pub async fn run_replay_server(file: &str, port: u16) -> anyhow::Result<()> {
-    let cache = Arc::new(Mutex::new(load_cache(file)?));
-    let frame_count = cache.lock().await.frames.len();
-
-    let addr = SocketAddr::from(([127, 0, 0, 1], port));
-
-    eprintln!("ghostline replay proxy");
-    eprintln!("  file:   {}", file);
-    eprintln!("  frames: {}", frame_count);
-    eprintln!("  listen: http://{}", addr);
-    eprintln!();
-    eprintln!("Point your AI client at http://{}/ to replay cached responses.", addr);
-    eprintln!("GET /status for cache stats. Ctrl+C to stop.");
+/// Serve replays of `file` on `port` (0 picks a free port, announced on stdout
+/// as `GHOSTLINE_REPLAY_PORT=<n>` and written to `options.port_file` if given).
+pub async fn run_replay_server(file: &str, port: u16, options: ReplayOptions) -> anyhow::Result<()> {
+    let ReplayOptions {
+        canonical_json,
+        port_file,
+        events,
+        loaded,
+        overrides,
+        only_status,
+        advance_duplicates,
+        response_headers,
+        strict,
+        record_to,
+        lazy,
+        bind,
+        missed,
+        ready,
+        coverage,
+        shutdown,
+        latency,
+        path_maps,
+    } = options;
+    let path_maps: Arc<[PathMap]> = path_maps.into();
+    let cache: SharedCache = Arc::new(Mutex::new(None));
 
+    let svc_cache = cache.clone();
     let make_svc = make_service_fn(move |_conn| {
-        let cache = cache.clone();
+        let cache = svc_cache.clone();
+        let events = events.clone();
+        let missed = missed.clone();
+        let path_maps = path_maps.clone();
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req| {
-                handle_request(req, cache.clone())
+                handle_request(req, cache.clone(), events.clone(), missed.clone(), path_maps.clone())
             }))
         }
     });
+    let listeners = bind.listen(port)?;
+    let addrs = listeners.iter().map(|l| l.local_addr()).collect::<std::io::Result<Vec<_>>>()?;
+    let servers = listeners
+        .into_iter()
+        .map(|l| Ok(Server::from_tcp(l)?.serve(make_svc.clone())))
+        .collect::<hyper::Result<Vec<_>>>()?;
+    let addr = format!("{}:{}", bind.url_host(), addrs[0].port());
+
+    info!("ghostline replay proxy");
+    info!("  file:   {}", file);
+    if canonical_json {
+        info!("  match:  raw hash, then canonical JSON");
+    }
+    if !overrides.is_empty() {
+        info!("  overrides: {}", overrides.len());
+    }
+    if !only_status.is_empty() {
+        let codes: Vec<String> = only_status.iter().map(u16::to_string).collect();
+        info!("  status: only {}; other frames miss", codes.join(", "));
+    }
+    if advance_duplicates {
+        info!("  repeats: a request recorded more than once gets its recorded responses in order");
+    }
+    if strict {
+        info!("  match:  strict; hits must send the recorded anthropic-beta features");
+    }
+    if let Some(recorder) = &record_to {
+        info!("  misses: forwarded live and recorded to {}", recorder.path().display());
+    }
+    for header in &response_headers {
+        info!("  header: {}: {}", header.name, header.value.to_str().unwrap_or("<binary>"));
+    }
+    if let Some(budget) = lazy {
+        info!("  frames: read on demand, caching up to {} MiB", budget >> 20);
+    }
+    if let Some(LatencySim { scale, cap_ms }) = latency {
+        match cap_ms {
+            Some(cap) => info!("  latency: recorded × {}, at most {}ms", scale, cap),
+            None => info!("  latency: recorded × {}", scale),
+        }
+    }
+    for addr in &addrs {
+        info!("  listen: http://{}", addr);
+    }
+    info!();
+    info!("Point your AI client at http://{}/ to replay cached responses.", addr);
+    info!("GET /status for cache stats, /hashes for the recorded request hashes, /healthz and /readyz for probes. Ctrl+C to stop.");
+    if port == 0 || port_file.is_some() {
+        crate::banner::announce_port("GHOSTLINE_REPLAY_PORT", addrs[0].port(), port_file.as_deref())?;
+    }
+
+    // Serve probes while the cache loads; /readyz flips to 200 once it's in.
+    let server = tokio::spawn(futures_util::future::try_join_all(servers));
 
-    Server::bind(&addr).serve(make_svc).await?;
+    let path = file.to_string();
+    let mut cache_data = tokio::task::spawn_blocking(move || load_cache(&path, canonical_json, overrides, only_status, advance_duplicates, lazy)).await??;
+    cache_data.response_headers = response_headers;
+    cache_data.strict = strict;
+    cache_data.record_to = record_to.clone();
+    cache_data.latency = latency;
+    info!("[replay] indexed {} frames", cache_data.replayer.frame_count());
+    let frames = cache_data.replayer.frames().cloned();
+    *cache.lock().await = Some(cache_data);
+    if let (Some(slot), Some(frames)) = (loaded, frames) {
+        let _ = slot.set(frames);
+    }
+    if let Some(tx) = ready {
+        let _ = tx.send(addrs[0].port());
+    }
+
+    // Stop on `shutdown`, or on Ctrl+C when there's a coverage report, live
+    // recording or capped-latency count to report; otherwise serve until the
+    // process is killed.
+    let stop = async {
+        match shutdown {
+            Some(rx) => {
+                let _ = rx.await;
+            }
+            None if coverage.is_some() || record_to.is_some() || latency.and_then(|l| l.cap_ms).is_some() => {
+                tokio::signal::ctrl_c().await.ok();
+            }
+            None => futures_util::future::pending().await,
+        }
+    };
+    tokio::select! {
+        result = server => {
+            result??;
+        }
+        _ = stop => {}
+    }
 
+    if let Some(cap) = latency.and_then(|l| l.cap_ms) {
+        let capped = cache.lock().await.as_ref().map_or(0, |c| c.latency_capped);
+        info!("[replay] {} hits had their simulated latency capped at {}ms", capped, cap);
+    }
+    if let Some(recorder) = record_to {
+        let recorded = recorder.finish().await?;
+        let hits = cache.lock().await.as_ref().map_or(0, |c| c.hits);
+        info!(
+            "[replay] {} served from the recording, {} recorded live → {}",
+            hits,
+            recorded,
+            recorder.path().display()
+        );
+    }
+    if let Some(path) = coverage {
+        let guard = cache.lock().await;
+        let replayer = &guard.as_ref().expect("cache is loaded").replayer;
+        let report = coverage_report(file, replayer);
+        std::fs::write(&path, serde_json::to_string_pretty(&report)?)?;
+        info!(
+            "[replay] coverage: {}/{} frames hit → {}",
+            report["frames_hit"],
+            report["total_frames"],
+            path.display()
+        );
+    }
     Ok(())
 }
+
+/// Replay `file` for the duration of `cmd`, run with `ANTHROPIC_BASE_URL`
+/// pointing at the replay server. Returns the exit code to leave with: the
+/// command's, or 1 under `assert_complete` when any request missed (their
+/// bodies are printed, as the recording is stale for them).
+pub async fn run_with_command(
+    file: String,
+    port: u16,
+    mut options: ReplayOptions,
+    cmd: Vec<String>,
+    assert_complete: bool,
+) -> anyhow::Result<i32> {
+    let missed = MissLog::default();
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel();
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+    options.missed = Some(missed.clone());
+    options.ready = Some(ready_tx);
+    options.shutdown = Some(stop_rx);
+    let host = options.bind.url_host();
+    let server = tokio::spawn(async move { run_replay_server(&file, port, options).await });
+
+    let port = match ready_rx.await {
+        Ok(port) => port,
+        // The server stopped before it was ready; surface its error.
+        Err(_) => {
+            server.await??;
+            anyhow::bail!("replay server exited before it was ready");
+        }
+    };
+    let url = format!("http://{}:{}", host, port);
+    info!("\n Running: {} (with ANTHROPIC_BASE_URL={})\n", cmd.join(" "), url);
+    let status = tokio::process::Command::new(&cmd[0])
+        .args(&cmd[1..])
+        .env("ANTHROPIC_BASE_URL", &url)
+        .status()
+        .await?;
+    // Let the server stop on its own so it can write the coverage report.
+    let _ = stop_tx.send(());
+    server.await??;
+
+    let missed = std::mem::take(&mut *missed.lock().unwrap());
+    let code = status.code().unwrap_or(1);
+    if missed.is_empty() {
+        info!("[replay] every request was in the recording");
+        return Ok(code);
+    }
+    info!("[replay] {} request(s) were not in the recording", missed.len());
+    if !assert_complete {
+        return Ok(code);
+    }
+    for (i, (hash, body)) in missed.iter().enumerate() {
+        eprintln!(
+            "\n--- missed request {} (hash {}) ---\n{}",
+            i + 1,
+            hex::encode(&hash[..8]),
+            String::from_utf8_lossy(body)
+        );
+    }
+    eprintln!("\n[replay] --assert-complete: the recording is stale");
+    Ok(if code == 0 { 1 } else { code })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A loaded cache over `frames`, shared the way the server holds it.
+    fn shared_cache(frames: Vec<Frame>, configure: impl FnOnce(&mut ReplayCache)) -> SharedCache {
+        let mut cache = ReplayCache::new(Replayer::new(frames));
+        configure(&mut cache);
+        Arc::new(Mutex::new(Some(cache)))
+    }
+
+    #[test]
+    fn override_args_resolve_to_one_recorded_hash() {
+        assert!("abc=body.json".parse::<OverrideArg>().is_ok());
+        assert!("abc".parse::<OverrideArg>().is_err());
+        assert!("xyz=body.json".parse::<OverrideArg>().is_err());
+
+        let replayer = Replayer::new(vec![
+            Frame::new(b"a".to_vec(), b"1".to_vec(), 1, 0),
+            Frame::new(b"b".to_vec(), b"2".to_vec(), 1, 0),
+        ]);
+        let full = hex::encode(Frame::hash_request(b"a"));
+        assert_eq!(resolve_hash(&replayer, &full[..12]).unwrap(), Frame::hash_request(b"a"));
+        assert!(resolve_hash(&replayer, "").is_err());
+        assert!(resolve_hash(&replayer, &"0".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn coverage_lists_hits_and_unused_frames() {
+        let mut replayer = Replayer::new(vec![
+            Frame::new(b"a".to_vec(), b"1".to_vec(), 1, 0),
+            Frame::new(b"b".to_vec(), b"2".to_vec(), 1, 0),
+        ]);
+        replayer.lookup(&Frame::hash_request(b"b"));
+        replayer.lookup(&Frame::hash_request(b"b"));
+
+        let report = coverage_report("run.ghostline", &replayer);
+        assert_eq!(report["total_frames"], 2);
+        assert_eq!(report["frames_hit"], 1);
+        assert_eq!(report["unused"], serde_json::json!([0]));
+        assert_eq!(report["frames"][1]["hits"], 2);
+    }
+
+    #[test]
+    fn latency_scales_then_caps() {
+        let sim = LatencySim { scale: 0.5, cap_ms: Some(1000) };
+        assert_eq!(sim.delay(600), (Duration::from_millis(300), false));
+        assert_eq!(sim.delay(2000), (Duration::from_millis(1000), false));
+        assert_eq!(sim.delay(30_000), (Duration::from_millis(1000), true));
+        let uncapped = LatencySim { scale: 1.0, cap_ms: None };
+        assert_eq!(uncapped.delay(30_000), (Duration::from_millis(30_000), false));
+    }
+
+    #[test]
+    fn canonical_hash_ignores_key_order_and_whitespace() {
+        let a = canonical_hash(br#"{"model":"m","messages":[{"role":"user","content":"hi"}]}"#);
+        let b = canonical_hash(b"{ \"messages\": [ {\"content\": \"hi\", \"role\": \"user\"} ],\n  \"model\": \"m\" }");
+        assert!(a.is_some());
+        assert_eq!(a, b);
+        assert_ne!(a, canonical_hash(br#"{"model":"other"}"#));
+        assert!(canonical_hash(b"not json").is_none());
+    }
+
+    #[test]
+    fn lookup_falls_back_to_canonical() {
+        let frame = Frame::new(br#"{"a":1,"b":2}"#.to_vec(), b"ok".to_vec(), 5, 0);
+        let mut canonical = HashMap::new();
+        canonical.insert(canonical_hash(&frame.request_bytes).unwrap(), frame.request_hash);
+        let mut cache = ReplayCache { canonical: Some(canonical), ..ReplayCache::new(Replayer::new(vec![frame])) };
+
+        let body = br#"{ "b": 2, "a": 1 }"#;
+        assert!(matches!(cache.lookup(&Frame::hash_request(body), body, |_| true).unwrap(), ReplayOutcome::Hit { index: 0, .. }));
+        assert_eq!((cache.hits, cache.canonical_hits, cache.misses), (1, 1, 0));
+
+        cache.canonical = None;
+        assert!(matches!(cache.lookup(&Frame::hash_request(body), body, |_| true).unwrap(), ReplayOutcome::Miss { .. }));
+        assert_eq!(cache.misses, 1);
+    }
+
+    #[test]
+    fn hits_name_the_answering_frame() {
+        let frames = vec![
+            Frame::new(b"a".to_vec(), b"1".to_vec(), 1, 0),
+            Frame::new(b"b".to_vec(), b"2".to_vec(), 1, 0),
+        ];
+        let cache = shared_cache(frames, |c| {
+            c.response_headers = ["server=cloudflare", "content-type=text/plain", "Server=envoy"]
+                .iter()
+                .map(|h| h.parse().unwrap())
+                .collect();
+        });
+        let request = Request::post("/v1/messages").body(Body::from("b")).unwrap();
+        let response = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(handle_request(request, cache, None, None, Default::default()))
+            .unwrap();
+        let header = |name: &str| response.headers()[name].to_str().unwrap().to_string();
+        assert_eq!(header("x-ghostline-frame-index"), "1");
+        assert_eq!(header("x-ghostline-request-hash"), hex::encode(Frame::hash_request(b"b")));
+        // Injected headers replace replay's own, and the last of a name wins.
+        assert_eq!((header("server"), header("content-type")), ("envoy".into(), "text/plain".into()));
+        assert!("content-length=3".parse::<ResponseHeader>().is_err());
+    }
+
+    #[test]
+    fn maps_request_paths() {
+        let maps: Vec<PathMap> = ["/api/v1=/v1", "/api=/", "/v1/messages=/v1/messages"]
+            .iter()
+            .map(|m| m.parse().unwrap())
+            .collect();
+        assert_eq!(map_path(&maps, "/api/v1/messages").as_deref(), Some("/v1/messages"));
+        assert_eq!(map_path(&maps, "/api/healthz").as_deref(), Some("/healthz"));
+        assert_eq!(map_path(&maps, "/api").as_deref(), Some("/"));
+        assert_eq!(map_path(&maps, "/apix/healthz"), None);
+        assert_eq!(map_path(&maps, "/v1/messages"), None);
+        for bad in ["/v1", "v1=/v2", "/v1=", "/a=/b?x=1"] {
+            assert!(bad.parse::<PathMap>().is_err(), "{}", bad);
+        }
+
+        let cache = shared_cache(vec![Frame::new(b"q".to_vec(), b"ok".to_vec(), 1, 0)], |_| {});
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let maps: Arc<[PathMap]> = maps.into();
+        let send = |request| rt.block_on(handle_request(request, cache.clone(), None, None, maps.clone())).unwrap();
+        let probe = send(Request::get("/api/healthz?verbose=1").body(Body::empty()).unwrap());
+        assert_eq!(probe.status(), StatusCode::OK);
+        let hit = send(Request::post("/api/v1/messages").body(Body::from("q")).unwrap());
+        assert_eq!(hit.headers()["x-ghostline-replay"], "true");
+    }
+
+    #[test]
+    fn strict_misses_when_betas_differ() {
+        let frame = Frame::builder()
+            .request(b"q".to_vec())
+            .response(b"ok".to_vec())
+            .anthropic_betas(vec!["files-api-2025-04-14".into(), "output-128k-2025-02-19".into()])
+            .build();
+        let cache = shared_cache(vec![frame], |c| c.strict = true);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let send = |betas: &str| {
+            let request = Request::post("/v1/messages").header("anthropic-beta", betas).body(Body::from("q")).unwrap();
+            rt.block_on(handle_request(request, cache.clone(), None, None, Default::default())).unwrap()
+        };
+
+        let miss = send("files-api-2025-04-14");
+        assert_eq!(miss.status(), StatusCode::NOT_FOUND);
+        let body: serde_json::Value = serde_json::from_slice(&rt.block_on(hyper::body::to_bytes(miss.into_body())).unwrap()).unwrap();
+        assert_eq!(body["header_mismatches"][0]["received"], serde_json::json!(["files-api-2025-04-14"]));
+        // The frame that didn't answer isn't counted as served.
+        assert_eq!(rt.block_on(cache.lock()).as_ref().unwrap().replayer.unused_frames(), vec![0]);
+        let hit = send("output-128k-2025-02-19, files-api-2025-04-14");
+        assert_eq!(hit.headers()["x-ghostline-replay"], "true");
+
+        let guard = rt.block_on(cache.lock());
+        let c = guard.as_ref().unwrap();
+        assert_eq!((c.hits, c.misses, c.strict_misses), (1, 1, 1));
+    }
+
+    #[test]
+    fn readyz_waits_for_the_cache() {
+        let cache: SharedCache = Arc::new(Mutex::new(None));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let readyz = || {
+            let request = Request::get("/readyz").body(Body::empty()).unwrap();
+            rt.block_on(handle_request(request, cache.clone(), None, None, Default::default())).unwrap().status()
+        };
+        assert_eq!(readyz(), StatusCode::SERVICE_UNAVAILABLE);
+        *rt.block_on(cache.lock()) = Some(ReplayCache::new(Replayer::new(Vec::new())));
+        assert_eq!(readyz(), StatusCode::OK);
+    }
+
+    #[test]
+    fn hashes_lists_frames_in_file_order() {
+        let frames = vec![Frame::new(b"a".to_vec(), Vec::new(), 0, 0), Frame::new(b"b".to_vec(), Vec::new(), 0, 0)];
+        let cache: SharedCache = Arc::new(Mutex::new(None));
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let get = || {
+            let request = Request::get("/hashes").body(Body::empty()).unwrap();
+            rt.block_on(handle_request(request, cache.clone(), None, None, Default::default())).unwrap()
+        };
+        assert_eq!(get().status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        *rt.block_on(cache.lock()) = Some(ReplayCache::new(Replayer::new(frames)));
+        let body: serde_json::Value = serde_json::from_slice(&rt.block_on(hyper::body::to_bytes(get().into_body())).unwrap()).unwrap();
+        assert_eq!(body["hash_algo"], "sha256");
+        assert_eq!(body["frames"][1]["index"], 1);
+        assert_eq!(body["frames"][1]["request_hash"], hex::encode(Frame::hash_request(b"b")));
+    }
+
+    #[test]
+    fn frames_without_a_response_body_miss() {
+        let mut frame = Frame::new(b"q".to_vec(), b"ok".to_vec(), 0, 0);
+        frame.drop_response_body();
+        let cache = shared_cache(vec![frame], |_| {});
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let request = Request::post("/v1/messages").body(Body::from("q")).unwrap();
+        let miss = rt.block_on(handle_request(request, cache.clone(), None, None, Default::default())).unwrap();
+        assert_eq!(miss.status(), StatusCode::NOT_FOUND);
+        assert_eq!(miss.headers()["x-ghostline-replay"], "miss");
+        let body: serde_json::Value = serde_json::from_slice(&rt.block_on(hyper::body::to_bytes(miss.into_body())).unwrap()).unwrap();
+        assert_eq!(body["frame_index"], 0);
+
+        let guard = rt.block_on(cache.lock());
+        let c = guard.as_ref().unwrap();
+        assert_eq!((c.hits, c.misses), (0, 1));
+        assert_eq!(c.replayer.unused_frames(), vec![0]);
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::meta::sidecar_path;
+
+/// Parse a human duration such as `30m`, `12h`, `7d` or `2w`.
+pub fn parse_duration(s: &str) -> anyhow::Result<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow::anyhow!("missing unit in duration '{}' (use s, m, h, d or w)", s))?;
+    let (num, unit) = s.split_at(split);
+    let n: u64 = num
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid duration '{}'", s))?;
+    let unit_secs: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => anyhow::bail!("unknown duration unit '{}' (use s, m, h, d or w)", unit),
+    };
+    let secs = n
+        .checked_mul(unit_secs)
+        .ok_or_else(|| anyhow::anyhow!("duration '{}' is too long", s))?;
+    Ok(Duration::from_secs(secs))
+}
+
+/// A recording on disk with the time it was started.
+pub struct RunFile {
+    pub path: PathBuf,
+    /// Path relative to the listed directory, `/`-separated
+    /// (`project/run.ghostline`); what [`resolve_run`] takes back.
+    pub name: String,
+    pub size: u64,
+    /// Unix ms — header `started_at`, or file mtime if the header can't be read
+    /// or gives 0 (as `monotonic-from-zero` recordings do).
+    pub started_at: u64,
+}
+
+/// List `.ghostline` files in `dir` and its subdirectories, newest first.
+/// Hidden directories and symlinks are skipped.
+pub fn list_runs(dir: &Path) -> anyhow::Result<Vec<RunFile>> {
+    let mut runs = Vec::new();
+    collect_runs(dir, "", &mut runs)?;
+    runs.sort_by_key(|r| std::cmp::Reverse(r.started_at));
+    Ok(runs)
+}
+
+fn collect_runs(dir: &Path, prefix: &str, runs: &mut Vec<RunFile>) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            if plain_component(&file_name) {
+                collect_runs(&path, &format!("{}{}/", prefix, file_name), runs)?;
+            }
+            continue;
+        }
+        if path.extension().map(|e| e != "ghostline").unwrap_or(true) {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        let started_at = std::fs::File::open(&path)
+            .and_then(|f| ghostline_core::read_header(&mut std::io::BufReader::new(f)))
+            .ok()
+            .map(|(_, header)| header.started_at)
+            .filter(|&started_at| started_at != 0)
+            .unwrap_or_else(|| {
+                meta.modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0)
+            });
+        runs.push(RunFile {
+            path,
+            name: format!("{}{}", prefix, file_name),
+            size: meta.len(),
+            started_at,
+        });
+    }
+    Ok(())
+}
+
+/// A path component a run name may use: not empty, not hidden, and so not
+/// `.` or `..`, and free of separators and drive prefixes.
+fn plain_component(c: &str) -> bool {
+    !c.is_empty() && !c.starts_with('.') && !c.contains(['/', '\\', ':', '\0'])
+}
+
+/// The file for run `name` in `dir`: a `.ghostline` file name, optionally
+/// under subdirectories (`project/run.ghostline`). `None` unless every
+/// component is a [plain name](plain_component) and none that exists is a
+/// symlink, so the result can't leave `dir`. Components that don't exist yet
+/// are allowed, for [`move_run`]'s destination.
+pub fn resolve_run(dir: &Path, name: &str) -> Option<PathBuf> {
+    if !name.ends_with(".ghostline") {
+        return None;
+    }
+    let mut path = dir.to_path_buf();
+    for component in name.split('/') {
+        if !plain_component(component) {
+            return None;
+        }
+        path.push(component);
+        if path.symlink_metadata().is_ok_and(|m| m.file_type().is_symlink()) {
+            return None;
+        }
+    }
+    Some(path)
+}
+
+/// Move run `name` in `dir` into `subdir` (`project`, `a/b`, or `.` for `dir`
+/// itself), creating it, and its `.meta.json` sidecar with it. Returns the
+/// run's new name. Refuses to overwrite another run.
+pub fn move_run(dir: &Path, name: &str, subdir: &str) -> anyhow::Result<String> {
+    let from = resolve_run(dir, name).ok_or_else(|| anyhow::anyhow!("{:?} is not a run name", name))?;
+    if !from.is_file() {
+        anyhow::bail!("Not found: {}", name);
+    }
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    let subdir = subdir.trim_matches('/');
+    let new_name = match subdir {
+        "" | "." => file_name.to_string(),
+        _ => format!("{}/{}", subdir, file_name),
+    };
+    let to = resolve_run(dir, &new_name).ok_or_else(|| anyhow::anyhow!("{:?} is not a subdirectory name", subdir))?;
+    if to == from {
+        return Ok(new_name);
+    }
+    if to.exists() {
+        anyhow::bail!("{} already exists", new_name);
+    }
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::rename(&from, &to)?;
+    if sidecar_path(&from).exists() {
+        std::fs::rename(sidecar_path(&from), sidecar_path(&to))?;
+    }
+    Ok(new_name)
+}
+
+/// Delete the recording at `path` and its `.meta.json` sidecar, if any.
+pub fn delete_run(path: &Path) -> std::io::Result<()> {
+    std::fs::remove_file(path)?;
+    match std::fs::remove_file(sidecar_path(path)) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Pick the runs to remove: anything past the newest `keep`, plus anything
+/// started before `cutoff_ms`. `runs` must be sorted newest first.
+pub fn select_prune(runs: &[RunFile], cutoff_ms: Option<u64>, keep: Option<usize>) -> Vec<&RunFile> {
+    runs.iter()
+        .enumerate()
+        .filter(|(i, run)| {
+            keep.map(|k| *i >= k).unwrap_or(false)
+                || cutoff_ms.map(|c| run.started_at < c).unwrap_or(false)
+        })
+        .map(|(_, run)| run)
+        .collect()
+}
+
+/// Delete old recordings from `dir`, printing each removal and the space reclaimed.
+pub fn prune(
+    dir: &Path,
+    older_than: Option<&str>,
+    keep: Option<usize>,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if older_than.is_none() && keep.is_none() {
+        anyhow::bail!("specify --older-than <duration> and/or --keep <n>");
+    }
+    let cutoff_ms = match older_than {
+        Some(d) => {
+            // Reaching back past 1970 cuts off nothing.
+            let cutoff = SystemTime::now().checked_sub(parse_duration(d)?).unwrap_or(UNIX_EPOCH);
+            Some(cutoff.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64)
+        }
+        None => None,
+    };
+
+    if !dir.exists() {
+        println!("No runs directory found at {}", dir.display());
+        return Ok(());
+    }
+    let runs = list_runs(dir)?;
+    let doomed = select_prune(&runs, cutoff_ms, keep);
+    if doomed.is_empty() {
+        println!("Nothing to prune.");
+        return Ok(());
+    }
+
+    let mut reclaimed = 0u64;
+    for run in &doomed {
+        let name = &run.name;
+        if dry_run {
+            println!("  would remove {} ({:.1} KB)", name, run.size as f64 / 1024.0);
+        } else {
+            delete_run(&run.path)?;
+            println!("  removed {} ({:.1} KB)", name, run.size as f64 / 1024.0);
+        }
+        reclaimed += run.size;
+    }
+    println!(
+        "{} {} runs, {:.1} KB{}",
+        if dry_run { "Would prune" } else { "Pruned" },
+        doomed.len(),
+        reclaimed as f64 / 1024.0,
+        if dry_run { " (dry run)" } else { " reclaimed" }
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(name: &str, started_at: u64) -> RunFile {
+        RunFile {
+            path: PathBuf::from(name),
+            name: name.to_string(),
+            size: 1024,
+            started_at,
+        }
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1800));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 86400));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration(&format!("{}w", u64::MAX / 60)).is_err());
+        assert!(parse_duration("99999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn prune_by_keep_and_age() {
+        let runs = vec![run("c", 300), run("b", 200), run("a", 100)];
+        let names = |v: Vec<&RunFile>| v.iter().map(|r| r.path.to_string_lossy().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(select_prune(&runs, None, Some(1))), ["b", "a"]);
+        assert_eq!(names(select_prune(&runs, Some(150), None)), ["a"]);
+        assert_eq!(names(select_prune(&runs, Some(150), Some(2))), ["a"]);
+        assert!(select_prune(&runs, None, Some(5)).is_empty());
+    }
+
+    #[test]
+    fn prune_dates_zero_started_at_by_mtime() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        for (name, started_at) in [("zero.ghostline", 0), ("old.ghostline", 1_000_000_000_000)] {
+            let header = ghostline_core::Header { started_at, ..Default::default() };
+            let file = std::fs::File::create(tmp.path().join(name)).unwrap();
+            ghostline_core::GhostlineWriter::new(file, &header).unwrap().finish().unwrap();
+        }
+        let zero = list_runs(tmp.path()).unwrap().into_iter().find(|r| r.name == "zero.ghostline").unwrap();
+        assert!(zero.started_at > 1_000_000_000_000);
+
+        std::fs::write(tmp.path().join("old.ghostline.meta.json"), b"{}").unwrap();
+        prune(tmp.path(), Some("1d"), None, false).unwrap();
+        assert!(tmp.path().join("zero.ghostline").exists());
+        assert!(!tmp.path().join("old.ghostline").exists());
+        assert!(!tmp.path().join("old.ghostline.meta.json").exists());
+    }
+
+    #[test]
+    fn runs_in_subdirectories() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path();
+        for name in ["a.ghostline", "b.ghostline", "b.ghostline.meta.json"] {
+            std::fs::write(dir.join(name), b"x").unwrap();
+        }
+        std::fs::create_dir(dir.join(".cache")).unwrap();
+        std::fs::write(dir.join(".cache/c.ghostline"), b"x").unwrap();
+
+        assert_eq!(move_run(dir, "b.ghostline", "proj/api").unwrap(), "proj/api/b.ghostline");
+        assert!(dir.join("proj/api/b.ghostline.meta.json").exists());
+        let mut names: Vec<_> = list_runs(dir).unwrap().into_iter().map(|r| r.name).collect();
+        names.sort();
+        assert_eq!(names, ["a.ghostline", "proj/api/b.ghostline"]);
+
+        std::fs::write(dir.join("b.ghostline"), b"y").unwrap();
+        assert!(move_run(dir, "b.ghostline", "proj/api").is_err());
+        assert!(move_run(dir, "proj/api/b.ghostline", ".").is_err());
+        assert!(move_run(dir, "a.ghostline", "../out").is_err());
+
+        assert_eq!(resolve_run(dir, "proj/api/b.ghostline"), Some(dir.join("proj/api/b.ghostline")));
+        for bad in ["../a.ghostline", "proj/../a.ghostline", "/a.ghostline", ".cache/c.ghostline", "proj\\a.ghostline", "a.txt"] {
+            assert_eq!(resolve_run(dir, bad), None, "{}", bad);
+        }
+
+        #[cfg(unix)]
+        {
+            let outside = tempfile::TempDir::new().unwrap();
+            std::fs::write(outside.path().join("x.ghostline"), b"z").unwrap();
+            std::os::unix::fs::symlink(outside.path(), dir.join("linked")).unwrap();
+            std::os::unix::fs::symlink(outside.path().join("x.ghostline"), dir.join("x.ghostline")).unwrap();
+            assert_eq!(resolve_run(dir, "linked/x.ghostline"), None);
+            assert_eq!(resolve_run(dir, "x.ghostline"), None);
+            assert!(move_run(dir, "a.ghostline", "linked").is_err());
+            assert!(!list_runs(dir).unwrap().iter().any(|r| r.name == "x.ghostline"));
+        }
+    }
+}
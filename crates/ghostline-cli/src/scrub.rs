@@ -0,0 +1,228 @@
+use serde_json::Value;
+
+/// What a matched value is replaced with.
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+    AnyIndex,
+}
+
+/// A dotted path into a JSON body, e.g. `metadata.user_id` or
+/// `messages[*].content[0].text`. `[*]` matches every element of an array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Step>);
+
+impl std::str::FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut steps = Vec::new();
+        for segment in s.split('.') {
+            let (name, mut rest) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+            if name.contains(']') {
+                return Err(format!("stray bracket in JSON path {:?}", s));
+            } else if !name.is_empty() {
+                steps.push(Step::Key(name.to_string()));
+            } else if rest.is_empty() {
+                return Err(format!("empty segment in JSON path {:?}", s));
+            }
+            while !rest.is_empty() {
+                let (index, after) = rest
+                    .strip_prefix('[')
+                    .and_then(|r| r.split_once(']'))
+                    .ok_or_else(|| format!("unclosed or stray bracket in JSON path {:?}", s))?;
+                steps.push(match index {
+                    "*" => Step::AnyIndex,
+                    n => Step::Index(n.parse().map_err(|_| format!("bad array index {:?} in JSON path {:?}", n, s))?),
+                });
+                rest = after;
+            }
+        }
+        Ok(Self(steps))
+    }
+}
+
+impl JsonPath {
+    /// Every value the path reaches in `value`.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![value];
+        for step in &self.0 {
+            current = current
+                .into_iter()
+                .flat_map(|v| match (step, v) {
+                    (Step::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+                    (Step::Index(i), Value::Array(items)) => items.get(*i).into_iter().collect(),
+                    (Step::AnyIndex, Value::Array(items)) => items.iter().collect(),
+                    _ => Vec::new(),
+                })
+                .collect();
+        }
+        current
+    }
+}
+
+/// Replace whatever `steps` reaches in `value`; whether anything was.
+fn redact(value: &mut Value, steps: &[Step]) -> bool {
+    let Some((step, rest)) = steps.split_first() else {
+        *value = Value::String(REDACTED.to_string());
+        return true;
+    };
+    match (step, value) {
+        (Step::Key(key), Value::Object(map)) => map.get_mut(key).is_some_and(|v| redact(v, rest)),
+        (Step::Index(i), Value::Array(items)) => items.get_mut(*i).is_some_and(|v| redact(v, rest)),
+        (Step::AnyIndex, Value::Array(items)) => items.iter_mut().fold(false, |hit, v| redact(v, rest) | hit),
+        _ => false,
+    }
+}
+
+/// Request headers whose values are never recorded, whatever the config.
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "proxy-authorization", "x-api-key", "cookie"];
+
+/// A frame's `request_headers`: each `(name, value)` in order, with the
+/// values of credential headers replaced by [`REDACTED`]. Names are matched
+/// case-insensitively; values that aren't UTF-8 are kept lossily.
+pub fn recorded_headers<'a>(headers: impl IntoIterator<Item = (&'a str, &'a [u8])>) -> Vec<(String, String)> {
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            let name = name.to_ascii_lowercase();
+            let value = match CREDENTIAL_HEADERS.contains(&name.as_str()) {
+                true => REDACTED.to_string(),
+                false => String::from_utf8_lossy(value).into_owned(),
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+/// Redacts `recording.scrub_json_paths` from JSON bodies, and from each
+/// `data:` event of a streamed (SSE) response.
+#[derive(Debug, Clone, Default)]
+pub struct Scrubber {
+    paths: Vec<JsonPath>,
+}
+
+impl Scrubber {
+    pub fn new(paths: &[String]) -> anyhow::Result<Self> {
+        let paths = paths
+            .iter()
+            .map(|p| p.parse().map_err(anyhow::Error::msg))
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { paths })
+    }
+
+    /// Whether no paths are scrubbed, so bodies pass through untouched.
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    fn redact_all(&self, value: &mut Value) -> bool {
+        self.paths.iter().fold(false, |hit, path| redact(value, &path.0) | hit)
+    }
+
+    /// The scrubbed body, or `None` when nothing in it matched.
+    pub fn scrub(&self, body: &[u8]) -> Option<Vec<u8>> {
+        if self.paths.is_empty() {
+            return None;
+        }
+        if let Ok(mut value) = serde_json::from_slice::<Value>(body) {
+            return self.redact_all(&mut value).then(|| serde_json::to_vec(&value).unwrap_or_default());
+        }
+        if ghostline_core::sniff_content_type(body) != "text/event-stream" {
+            return None;
+        }
+        let text = std::str::from_utf8(body).ok()?;
+        let mut changed = false;
+        let mut out = String::with_capacity(text.len());
+        for line in text.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\r', '\n']);
+            let event = content
+                .strip_prefix("data:")
+                .and_then(|data| serde_json::from_str::<Value>(data.trim_start()).ok())
+                .and_then(|mut value| self.redact_all(&mut value).then_some(value));
+            match event {
+                Some(value) => {
+                    changed = true;
+                    out.push_str("data: ");
+                    out.push_str(&value.to_string());
+                    out.push_str(&line[content.len()..]);
+                }
+                _ => out.push_str(line),
+            }
+        }
+        changed.then(|| out.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrubber(paths: &[&str]) -> Scrubber {
+        Scrubber::new(&paths.iter().map(|p| p.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn redacts_through_nested_arrays() {
+        let body = br#"{"model":"m","metadata":{"user_id":"cust-123"},"messages":[
+            {"role":"user","content":[{"type":"text","text":"I am cust-123"},{"type":"image"}]},
+            {"role":"assistant","content":[{"type":"text","text":"hello"}]}]}"#;
+        let out = scrubber(&["messages[*].content[*].text", "metadata.user_id", "missing.path"])
+            .scrub(body)
+            .unwrap();
+        let value: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value["metadata"]["user_id"], REDACTED);
+        assert_eq!(value["messages"][0]["content"][0]["text"], REDACTED);
+        assert_eq!(value["messages"][1]["content"][0]["text"], REDACTED);
+        assert_eq!(value["messages"][0]["content"][1], serde_json::json!({"type": "image"}));
+        assert_eq!(value["model"], "m");
+
+        let first_only = scrubber(&["messages[0].role"]).scrub(body).unwrap();
+        let value: Value = serde_json::from_slice(&first_only).unwrap();
+        assert_eq!((value["messages"][0]["role"].as_str(), value["messages"][1]["role"].as_str()), (Some(REDACTED), Some("assistant")));
+
+        assert_eq!(scrubber(&["nope"]).scrub(body), None);
+        assert_eq!(scrubber(&["messages"]).scrub(b"not json"), None);
+    }
+
+    #[test]
+    fn redacts_stream_events() {
+        let body = b"event: delta\r\ndata: {\"delta\":{\"text\":\"secret\"}}\r\n\r\ndata: [DONE]\r\n";
+        let out = scrubber(&["delta.text"]).scrub(body).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "event: delta\r\ndata: {\"delta\":{\"text\":\"[REDACTED]\"}}\r\n\r\ndata: [DONE]\r\n"
+        );
+    }
+
+    #[test]
+    fn recorded_headers_redact_credentials() {
+        let headers = [
+            ("Content-Type", &b"application/json"[..]),
+            ("x-api-key", b"sk-ant-secret"),
+            ("Authorization", b"Bearer secret"),
+            ("anthropic-version", b"2023-06-01"),
+        ];
+        assert_eq!(
+            recorded_headers(headers),
+            [
+                ("content-type", "application/json"),
+                ("x-api-key", REDACTED),
+                ("authorization", REDACTED),
+                ("anthropic-version", "2023-06-01"),
+            ]
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        for bad in ["", "a..b", "a[", "a[x]", "a]b"] {
+            assert!(bad.parse::<JsonPath>().is_err(), "{:?}", bad);
+        }
+        assert!("[*][*]".parse::<JsonPath>().is_ok());
+    }
+}
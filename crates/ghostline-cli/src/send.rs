@@ -0,0 +1,195 @@
+//! `ghostline send`: one recorded request sent to the live API again, its
+//! fresh response printed next to the recorded one, to see whether the API's
+//! behavior has drifted since the recording.
+
+use crate::scrub::Scrubber;
+use ghostline_core::Frame;
+use std::time::Instant;
+
+/// Frames don't record the request path; Messages API calls are the norm.
+pub const DEFAULT_PATH: &str = "/v1/messages";
+
+/// `anthropic-version` sent with the request, which frames don't record either.
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Lines of unchanged context kept around each change in [`line_diff`].
+const DIFF_CONTEXT: usize = 3;
+
+/// The upstream's answer, as the frame that recording it would have written.
+pub struct LiveResponse {
+    pub frame: Frame,
+    pub version: reqwest::Version,
+    pub headers: reqwest::header::HeaderMap,
+}
+
+/// POST `recorded`'s request body to `url` with its `anthropic-beta`
+/// features and, when given, `token` as the API key. The response is
+/// scrubbed like a recorded one, so it compares like for like.
+pub async fn send(
+    client: &reqwest::Client,
+    url: url::Url,
+    recorded: &Frame,
+    token: Option<&str>,
+    scrubber: &Scrubber,
+) -> anyhow::Result<LiveResponse> {
+    let mut request = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, ghostline_core::sniff_content_type(&recorded.request_bytes))
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .body(recorded.request_bytes.clone());
+    if !recorded.anthropic_betas.is_empty() {
+        request = request.header("anthropic-beta", recorded.anthropic_betas.join(","));
+    }
+    if let Some(token) = token {
+        request = request.header("x-api-key", token);
+    }
+    let start = Instant::now();
+    let response = request.send().await?;
+    let (status, version, headers) = (response.status().as_u16(), response.version(), response.headers().clone());
+    let body = response.bytes().await?.to_vec();
+    let latency_ms = start.elapsed().as_millis() as u64;
+    let body = scrubber.scrub(&body).unwrap_or(body);
+    let frame = Frame::builder()
+        .request(recorded.request_bytes.clone())
+        .request_hash(recorded.request_hash)
+        .response(body)
+        .latency_ms(latency_ms)
+        .status(Some(status))
+        .build();
+    Ok(LiveResponse { frame, version, headers })
+}
+
+/// A response body for reading: a stream as the message it adds up to, JSON
+/// pretty-printed, other text as is.
+pub fn response_text(frame: &Frame) -> String {
+    match frame.reconstruct_message().or_else(|| frame.response_json()) {
+        Some(value) => serde_json::to_string_pretty(&value).unwrap_or_default(),
+        None => match std::str::from_utf8(&frame.response_bytes) {
+            Ok(text) => text.to_string(),
+            Err(_) => format!("<{} bytes of binary>", frame.response_bytes.len()),
+        },
+    }
+}
+
+fn status_line(status: Option<u16>) -> String {
+    match status {
+        Some(status) => status.to_string(),
+        None => "status not recorded".to_string(),
+    }
+}
+
+/// Print both responses, curl `-i` style for the live one, and with `diff`
+/// a line diff from the recorded response to the live one.
+pub fn print_comparison(index: usize, url: &url::Url, recorded: &Frame, live: &LiveResponse, diff: bool) {
+    let (recorded_text, live_text) = (response_text(recorded), response_text(&live.frame));
+    println!("Frame [{}] → POST {}", index, url);
+    println!(
+        "\n--- Recorded ({}, {}ms, {} bytes) ---",
+        status_line(recorded.status),
+        recorded.latency_ms,
+        recorded.response_bytes.len()
+    );
+    println!("{}", recorded_text);
+    println!(
+        "\n--- Live ({}, {}ms, {} bytes) ---",
+        status_line(live.frame.status),
+        live.frame.latency_ms,
+        live.frame.response_bytes.len()
+    );
+    let reason = live.frame.status.and_then(|s| reqwest::StatusCode::from_u16(s).ok()?.canonical_reason());
+    println!("{:?} {} {}", live.version, status_line(live.frame.status), reason.unwrap_or_default());
+    for (name, value) in &live.headers {
+        println!("{}: {}", name, String::from_utf8_lossy(value.as_bytes()));
+    }
+    println!("\n{}", live_text);
+    if diff {
+        println!("\n--- Diff (- recorded, + live) ---");
+        match line_diff(&recorded_text, &live_text) {
+            lines if lines.is_empty() => println!("(responses are identical)"),
+            lines => lines.iter().for_each(|line| println!("{}", line)),
+        }
+    }
+}
+
+/// Lines of `a` and `b` prefixed `-` (only in `a`), `+` (only in `b`) or ` `,
+/// keeping [`DIFF_CONTEXT`] unchanged lines around each change and `...`
+/// between hunks. Empty when the two are the same.
+pub fn line_diff(a: &str, b: &str) -> Vec<String> {
+    let (a, b): (Vec<&str>, Vec<&str>) = (a.lines().collect(), b.lines().collect());
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    if mid_a.is_empty() && mid_b.is_empty() {
+        return Vec::new();
+    }
+
+    // Longest common subsequence of the differing middle; past a size where
+    // the table gets large, everything in it is shown as replaced.
+    let mut ops: Vec<(char, &str)> = a[..prefix].iter().map(|l| (' ', *l)).collect();
+    if mid_a.len().saturating_mul(mid_b.len()) <= 4_000_000 {
+        let mut lcs = vec![vec![0u32; mid_b.len() + 1]; mid_a.len() + 1];
+        for i in (0..mid_a.len()).rev() {
+            for j in (0..mid_b.len()).rev() {
+                lcs[i][j] = if mid_a[i] == mid_b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < mid_a.len() || j < mid_b.len() {
+            if i < mid_a.len() && j < mid_b.len() && mid_a[i] == mid_b[j] {
+                ops.push((' ', mid_a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if j == mid_b.len() || (i < mid_a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(('-', mid_a[i]));
+                i += 1;
+            } else {
+                ops.push(('+', mid_b[j]));
+                j += 1;
+            }
+        }
+    } else {
+        ops.extend(mid_a.iter().map(|l| ('-', *l)));
+        ops.extend(mid_b.iter().map(|l| ('+', *l)));
+    }
+    ops.extend(a[a.len() - suffix..].iter().map(|l| (' ', *l)));
+
+    let near_change = |i: usize| {
+        let (from, to) = (i.saturating_sub(DIFF_CONTEXT), (i + DIFF_CONTEXT + 1).min(ops.len()));
+        ops[from..to].iter().any(|(op, _)| *op != ' ')
+    };
+    let mut lines = Vec::new();
+    let mut skipped = false;
+    for (i, (op, line)) in ops.iter().enumerate() {
+        if near_change(i) {
+            if skipped && !lines.is_empty() {
+                lines.push("...".to_string());
+            }
+            skipped = false;
+            lines.push(format!("{}{}", op, line));
+        } else {
+            skipped = true;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_diff_keeps_context_around_changes() {
+        let numbered = |n: usize, changed: Option<usize>| {
+            (0..n).map(|i| if Some(i) == changed { "changed".to_string() } else { i.to_string() }).collect::<Vec<_>>().join("\n")
+        };
+        assert!(line_diff(&numbered(5, None), &numbered(5, None)).is_empty());
+        assert_eq!(
+            line_diff(&numbered(20, None), &numbered(20, Some(10))),
+            vec![" 7", " 8", " 9", "-10", "+changed", " 11", " 12", " 13"]
+        );
+        assert_eq!(line_diff("a\nb", "a\nb\nc"), vec![" a", " b", "+c"]);
+
+        let two_hunks = line_diff(&numbered(30, None), &numbered(30, Some(2)).replace("\n25\n", "\n"));
+        assert_eq!(two_hunks.iter().filter(|l| *l == "...").count(), 1);
+        assert!(two_hunks.contains(&"-25".to_string()));
+    }
+}
@@ -0,0 +1,241 @@
+use anyhow::Context;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::OsRng;
+use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
+use ghostline_core::{GhostlineReader, GhostlineWriter, Header, FLAG_DELTA_REQUESTS};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+/// Sidecar layout (`<file>.sig`, plain text):
+///
+/// ```text
+/// ghostline-signature v1
+/// signer: <hex SHA-256 of the public key>
+/// sha256: <hex SHA-256 of the whole .ghostline file>
+/// signature: <hex ed25519 signature of CONTEXT ‖ sha256>
+/// ```
+///
+/// The signer fingerprint is also written into the recording's header before
+/// hashing, so the file itself says who is expected to have signed it.
+const SIDECAR_MAGIC: &str = "ghostline-signature v1";
+/// Prefix of the signed message, so the key can't be tricked into signing a
+/// ghostline hash for some other protocol.
+const CONTEXT: &[u8] = b"ghostline-signature-v1\0";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureFile {
+    pub signer: [u8; 32],
+    pub sha256: [u8; 32],
+    pub signature: [u8; 64],
+}
+
+impl SignatureFile {
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\nsigner: {}\nsha256: {}\nsignature: {}\n",
+            SIDECAR_MAGIC,
+            hex::encode(self.signer),
+            hex::encode(self.sha256),
+            hex::encode(self.signature)
+        )
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text.lines();
+        if lines.next() != Some(SIDECAR_MAGIC) {
+            anyhow::bail!("not a ghostline signature file");
+        }
+        let mut field = |name: &str| -> anyhow::Result<Vec<u8>> {
+            let line = lines.next().unwrap_or_default();
+            let value = line
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(": "))
+                .with_context(|| format!("signature file is missing `{}`", name))?;
+            hex::decode(value.trim()).with_context(|| format!("`{}` is not hex", name))
+        };
+        let fixed = |bytes: Vec<u8>, name: &str| anyhow::anyhow!("`{}` has the wrong length ({} bytes)", name, bytes.len());
+        Ok(Self {
+            signer: field("signer")?.try_into().map_err(|b| fixed(b, "signer"))?,
+            sha256: field("sha256")?.try_into().map_err(|b| fixed(b, "sha256"))?,
+            signature: field("signature")?.try_into().map_err(|b| fixed(b, "signature"))?,
+        })
+    }
+}
+
+/// Where the signature of `file` is kept by default.
+pub fn sidecar_path(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+pub fn fingerprint(key: &VerifyingKey) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+fn read_hex_key(path: &Path) -> anyhow::Result<[u8; 32]> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("cannot read key {}", path.display()))?;
+    hex::decode(text.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .with_context(|| format!("{} is not a hex-encoded 32-byte ed25519 key", path.display()))
+}
+
+pub fn load_signing_key(path: &Path) -> anyhow::Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&read_hex_key(path)?))
+}
+
+pub fn load_verifying_key(path: &Path) -> anyhow::Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&read_hex_key(path)?)
+        .map_err(|_| anyhow::anyhow!("{} is not a valid ed25519 public key", path.display()))
+}
+
+/// Write a new key pair: the secret seed to `path` (owner-only on Unix) and
+/// the public key to `<path>.pub`, both hex. Never overwrites.
+pub fn generate_key(path: &Path) -> anyhow::Result<VerifyingKey> {
+    let mut seed = [0u8; 32];
+    OsRng.fill_bytes(&mut seed);
+    let key = SigningKey::from_bytes(&seed);
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut secret = options
+        .open(path)
+        .with_context(|| format!("cannot create {}", path.display()))?;
+    writeln!(secret, "{}", hex::encode(seed))?;
+
+    let mut public_path = path.as_os_str().to_owned();
+    public_path.push(".pub");
+    let mut public = std::fs::OpenOptions::new().write(true).create_new(true).open(&public_path)?;
+    writeln!(public, "{}", hex::encode(key.verifying_key().as_bytes()))?;
+    Ok(key.verifying_key())
+}
+
+/// Copy `reader` to `out` with `signer` in the header. Stored blocks are
+/// copied as-is unless the file delta-encodes requests.
+fn rewrite_with_signer<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    out: W,
+    signer: [u8; 32],
+) -> std::io::Result<()> {
+    let header = Header {
+        signer_fingerprint: Some(signer),
+        ..reader.header()
+    };
+    let mut writer = GhostlineWriter::new(out, &header)?;
+    for i in 0..reader.frame_count() {
+        if header.flags & FLAG_DELTA_REQUESTS != 0 {
+            writer.append(&reader.get_frame(i)?)?;
+        } else {
+            let (block, codec) = reader.get_frame_raw(i)?;
+            writer.append_raw(&block, reader.index_entries()[i].request_hash, codec)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Sign `bytes`, a whole recording whose header already names `key`.
+pub fn sign_bytes(bytes: &[u8], key: &SigningKey) -> SignatureFile {
+    let sha256: [u8; 32] = Sha256::digest(bytes).into();
+    let message = [CONTEXT, &sha256].concat();
+    SignatureFile {
+        signer: fingerprint(&key.verifying_key()),
+        sha256,
+        signature: key.sign(&message).to_bytes(),
+    }
+}
+
+/// Record `key`'s fingerprint in `file`'s header (rewriting it in place if
+/// needed) and write the signature to `sig`.
+pub fn sign(file: &Path, key: &SigningKey, sig: &Path) -> anyhow::Result<SignatureFile> {
+    let signer = fingerprint(&key.verifying_key());
+    let mut reader = GhostlineReader::open(file).with_context(|| format!("cannot read {}", file.display()))?;
+    if reader.signer_fingerprint != Some(signer) {
+        // Write beside the target and rename, as compact does.
+        let mut tmp = file.as_os_str().to_owned();
+        tmp.push(".sign.tmp");
+        let tmp = PathBuf::from(tmp);
+        rewrite_with_signer(&mut reader, std::io::BufWriter::new(std::fs::File::create(&tmp)?), signer)?;
+        drop(reader);
+        std::fs::rename(&tmp, file)?;
+    }
+    let signature = sign_bytes(&std::fs::read(file)?, key);
+    std::fs::write(sig, signature.to_text())?;
+    Ok(signature)
+}
+
+/// Check `bytes` against `signature` and the expected public `key`.
+pub fn verify_bytes(bytes: &[u8], signature: &SignatureFile, key: &VerifyingKey) -> anyhow::Result<()> {
+    let expected = fingerprint(key);
+    if signature.signer != expected {
+        anyhow::bail!("signed by key {}, not the given key {}", hex::encode(&signature.signer[..8]), hex::encode(&expected[..8]));
+    }
+    let (_, header) = ghostline_core::read_header(&mut &bytes[..])?;
+    if header.signer_fingerprint != Some(expected) {
+        anyhow::bail!("the recording's header doesn't name this signer");
+    }
+    let sha256: [u8; 32] = Sha256::digest(bytes).into();
+    if sha256 != signature.sha256 {
+        anyhow::bail!("the recording has been modified since it was signed");
+    }
+    let message = [CONTEXT, &sha256].concat();
+    key.verify(&message, &ed25519_dalek::Signature::from_bytes(&signature.signature))
+        .map_err(|_| anyhow::anyhow!("the signature is invalid"))
+}
+
+/// Check `file` against its signature file `sig` and the public `key`.
+pub fn verify(file: &Path, key: &VerifyingKey, sig: &Path) -> anyhow::Result<SignatureFile> {
+    let text = std::fs::read_to_string(sig).with_context(|| format!("cannot read {}", sig.display()))?;
+    let signature = SignatureFile::parse(&text)?;
+    verify_bytes(&std::fs::read(file)?, &signature, key)?;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::Frame;
+
+    fn recording(dir: &Path) -> PathBuf {
+        let path = dir.join("run.ghostline");
+        let mut writer = GhostlineWriter::new(std::fs::File::create(&path).unwrap(), &Header::default()).unwrap();
+        writer.append(&Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 0)).unwrap();
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn sign_then_verify_and_detect_tampering() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let file = recording(dir.path());
+        let public = generate_key(&dir.path().join("key")).unwrap();
+        let secret = load_signing_key(&dir.path().join("key")).unwrap();
+        assert_eq!(load_verifying_key(&dir.path().join("key.pub")).unwrap(), public);
+
+        let sig = sidecar_path(&file);
+        sign(&file, &secret, &sig).unwrap();
+        let reader = GhostlineReader::open(&file).unwrap();
+        assert_eq!(reader.signer_fingerprint, Some(fingerprint(&public)));
+        verify(&file, &public, &sig).unwrap();
+
+        let other = SigningKey::from_bytes(&[9u8; 32]).verifying_key();
+        assert!(verify(&file, &other, &sig).is_err());
+
+        let mut bytes = std::fs::read(&file).unwrap();
+        let last = bytes.len() - 20;
+        bytes[last] ^= 1;
+        std::fs::write(&file, bytes).unwrap();
+        assert!(verify(&file, &public, &sig).is_err());
+    }
+
+    #[test]
+    fn sidecar_text_roundtrip() {
+        let signature = sign_bytes(b"anything", &SigningKey::from_bytes(&[1u8; 32]));
+        assert_eq!(SignatureFile::parse(&signature.to_text()).unwrap(), signature);
+        assert!(SignatureFile::parse("something else\n").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use ghostline_core::GhostlineReader;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Seek};
+
+/// Counts in power-of-two buckets: bucket 0 holds 0, bucket `b` holds
+/// `2^(b-1)..2^b`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Histogram {
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn bucket(value: u64) -> usize {
+        (u64::BITS - value.leading_zeros()) as usize
+    }
+
+    fn lower_bound(bucket: usize) -> u64 {
+        if bucket == 0 { 0 } else { 1 << (bucket - 1) }
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let b = Self::bucket(value);
+        if b >= self.counts.len() {
+            self.counts.resize(b + 1, 0);
+        }
+        self.counts[b] += 1;
+    }
+
+    /// `(lower bound, count)` from the first non-empty bucket to the last.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        let first = self.counts.iter().position(|&c| c > 0).unwrap_or(self.counts.len());
+        self.counts.iter().enumerate().skip(first).map(|(b, &c)| (Self::lower_bound(b), c))
+    }
+
+    /// An ASCII bar per bucket, labelled with its range in `unit`.
+    fn render(&self, f: &mut fmt::Formatter<'_>, unit: fn(u64) -> String) -> fmt::Result {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let rows: Vec<_> = self
+            .buckets()
+            .map(|(lower, count)| {
+                let upper = if lower == 0 { 1 } else { lower * 2 };
+                (format!("{}–{}", unit(lower), unit(upper)), count)
+            })
+            .collect();
+        let width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+        for (label, count) in rows {
+            let bar = "█".repeat(((count * HISTOGRAM_WIDTH).div_ceil(max)) as usize);
+            write!(f, "\n  {:>width$} │{} {}", label, bar, count, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+/// In JSON, parallel arrays: `{"lower_bounds": [...], "counts": [...]}`.
+impl Serialize for Histogram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let (lower_bounds, counts): (Vec<u64>, Vec<u64>) = self.buckets().unzip();
+        let mut s = serializer.serialize_struct("Histogram", 2)?;
+        s.serialize_field("lower_bounds", &lower_bounds)?;
+        s.serialize_field("counts", &counts)?;
+        s.end()
+    }
+}
+
+/// Longest histogram bar, in characters.
+const HISTOGRAM_WIDTH: u64 = 30;
+
+/// Aggregate numbers for a recording, as printed by `ghostline stats`.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct RunStats {
+    pub frames: usize,
+    /// Frames for which [`ghostline_core::Frame::is_error`] holds.
+    pub errors: usize,
+    pub request_bytes: u64,
+    pub response_bytes: u64,
+    /// Sum of the index's decompressed frame sizes (v2+ files; 0 for v1).
+    pub decompressed_bytes: u64,
+    pub latency_min_ms: u64,
+    pub latency_p50_ms: u64,
+    pub latency_p95_ms: u64,
+    pub latency_max_ms: u64,
+    pub latency_mean_ms: f64,
+    /// Time between the first and last frame's timestamps.
+    pub span_ms: u64,
+    /// Frames that enabled each `anthropic-beta` feature, by name.
+    pub anthropic_betas: BTreeMap<String, usize>,
+    pub request_size_histogram: Histogram,
+    pub response_size_histogram: Histogram,
+    pub latency_histogram: Histogram,
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+pub fn compute<R: Read + Seek>(reader: &mut GhostlineReader<R>) -> io::Result<RunStats> {
+    let mut stats = RunStats {
+        frames: reader.frame_count(),
+        decompressed_bytes: reader
+            .index_entries()
+            .iter()
+            .map(|e| e.decompressed_size as u64)
+            .sum(),
+        ..Default::default()
+    };
+    let mut latencies = Vec::with_capacity(stats.frames);
+    let (mut first_ts, mut last_ts) = (u64::MAX, 0);
+    reader.scan(|_, frame| {
+        stats.request_bytes += frame.request_bytes.len() as u64;
+        stats.response_bytes += frame.response_bytes.len() as u64;
+        stats.errors += frame.is_error() as usize;
+        latencies.push(frame.latency_ms);
+        stats.request_size_histogram.record(frame.request_bytes.len() as u64);
+        stats.response_size_histogram.record(frame.response_bytes.len() as u64);
+        stats.latency_histogram.record(frame.latency_ms);
+        first_ts = first_ts.min(frame.timestamp);
+        last_ts = last_ts.max(frame.timestamp);
+        for beta in &frame.anthropic_betas {
+            *stats.anthropic_betas.entry(beta.clone()).or_insert(0) += 1;
+        }
+    })?;
+    if latencies.is_empty() {
+        return Ok(stats);
+    }
+
+    latencies.sort_unstable();
+    stats.latency_min_ms = latencies[0];
+    stats.latency_max_ms = latencies[latencies.len() - 1];
+    stats.latency_p50_ms = percentile(&latencies, 50);
+    stats.latency_p95_ms = percentile(&latencies, 95);
+    stats.latency_mean_ms = latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+    stats.span_ms = last_ts - first_ts;
+    Ok(stats)
+}
+
+/// A power of two in the largest unit it is a whole number of.
+fn fmt_pow2_bytes(n: u64) -> String {
+    match n {
+        n if n >= 1 << 30 => format!("{} GB", n >> 30),
+        n if n >= 1 << 20 => format!("{} MB", n >> 20),
+        n if n >= 1 << 10 => format!("{} KB", n >> 10),
+        n => format!("{} B", n),
+    }
+}
+
+fn fmt_pow2_ms(n: u64) -> String {
+    format!("{}ms", n)
+}
+
+fn fmt_bytes(n: u64) -> String {
+    if n >= 1024 * 1024 {
+        format!("{:.1} MB", n as f64 / (1024.0 * 1024.0))
+    } else if n >= 1024 {
+        format!("{:.1} KB", n as f64 / 1024.0)
+    } else {
+        format!("{} B", n)
+    }
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Frames:      {}", self.frames)?;
+        if self.frames > 0 {
+            writeln!(f, "Errors:      {} ({:.1}%)", self.errors, self.errors as f64 * 100.0 / self.frames as f64)?;
+        }
+        writeln!(f, "Duration:    {:.1}s", self.span_ms as f64 / 1000.0)?;
+        writeln!(f, "Requests:    {}", fmt_bytes(self.request_bytes))?;
+        writeln!(f, "Responses:   {}", fmt_bytes(self.response_bytes))?;
+        if self.decompressed_bytes > 0 {
+            writeln!(f, "Frame data:  {} decompressed", fmt_bytes(self.decompressed_bytes))?;
+        }
+        if !self.anthropic_betas.is_empty() {
+            let betas: Vec<String> = self
+                .anthropic_betas
+                .iter()
+                .map(|(beta, frames)| format!("{} ({})", beta, frames))
+                .collect();
+            writeln!(f, "Betas:       {}", betas.join(" · "))?;
+        }
+        write!(
+            f,
+            "Latency:     min {}ms · p50 {}ms · p95 {}ms · max {}ms · mean {:.0}ms",
+            self.latency_min_ms,
+            self.latency_p50_ms,
+            self.latency_p95_ms,
+            self.latency_max_ms,
+            self.latency_mean_ms
+        )?;
+        if self.frames > 0 {
+            write!(f, "\n\nRequest sizes:")?;
+            self.request_size_histogram.render(f, fmt_pow2_bytes)?;
+            write!(f, "\n\nResponse sizes:")?;
+            self.response_size_histogram.render(f, fmt_pow2_bytes)?;
+            write!(f, "\n\nLatencies:")?;
+            self.latency_histogram.render(f, fmt_pow2_ms)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::{Frame, GhostlineWriter, Header};
+    use std::io::Cursor;
+
+    #[test]
+    fn percentile_nearest_rank() {
+        let v: Vec<u64> = (1..=20).collect();
+        assert_eq!(percentile(&v, 50), 10);
+        assert_eq!(percentile(&v, 95), 19);
+        assert_eq!(percentile(&[7], 95), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn compute_totals_and_latency() {
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        for (i, latency) in [30u64, 10, 20].into_iter().enumerate() {
+            let mut frame = Frame::new(vec![b'q'; 4], vec![b'r'; 10], latency, 1000 + i as u64 * 500);
+            if i > 0 {
+                frame.anthropic_betas = vec!["files-api-2025-04-14".into()];
+            }
+            writer.append(&frame).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        let stats = compute(&mut reader).unwrap();
+        assert_eq!((stats.frames, stats.errors), (3, 0));
+        assert_eq!((stats.request_bytes, stats.response_bytes), (12, 30));
+        assert_eq!((stats.latency_min_ms, stats.latency_p50_ms, stats.latency_max_ms), (10, 20, 30));
+        assert_eq!(stats.span_ms, 1000);
+        assert!(stats.decompressed_bytes > 42);
+        assert_eq!(stats.anthropic_betas.get("files-api-2025-04-14"), Some(&2));
+        // 10, 20 and 30ms fall in 8–16 and 16–32.
+        assert_eq!(stats.latency_histogram.buckets().collect::<Vec<_>>(), vec![(8, 1), (16, 2)]);
+        let json = serde_json::to_value(&stats.response_size_histogram).unwrap();
+        assert_eq!(json, serde_json::json!({"lower_bounds": [8], "counts": [3]}));
+    }
+}
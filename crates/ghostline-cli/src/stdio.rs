@@ -0,0 +1,128 @@
+use ghostline_core::GhostlineReader;
+use std::io::{self, BufReader, Cursor, IsTerminal, Read, Seek, SeekFrom, Write};
+
+/// The path argument that means stdin (for inputs) or stdout (for outputs).
+pub const STDIO: &str = "-";
+
+pub fn is_stdio(path: &str) -> bool {
+    path == STDIO
+}
+
+/// Input source for a read command: a file on disk, or stdin buffered into memory.
+///
+/// The format needs `Seek` (the index lives at the end), so stdin can't be read
+/// in place.
+pub enum Input {
+    File(BufReader<std::fs::File>),
+    Memory(Cursor<Vec<u8>>),
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(r) => r.read(buf),
+            Input::Memory(r) => r.read(buf),
+        }
+    }
+}
+
+impl Seek for Input {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Input::File(r) => r.seek(pos),
+            Input::Memory(r) => r.seek(pos),
+        }
+    }
+}
+
+/// Stdin is held in memory up to this size, then spooled to a temp file.
+pub const STDIN_MEMORY_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// Buffer all of `src` so it can be seeked: in memory while it stays under
+/// `limit` bytes, otherwise in an anonymous temp file.
+fn buffer_seekable(mut src: impl Read, limit: u64) -> io::Result<Input> {
+    let mut head = Vec::new();
+    (&mut src).take(limit + 1).read_to_end(&mut head)?;
+    if head.len() as u64 <= limit {
+        return Ok(Input::Memory(Cursor::new(head)));
+    }
+    let mut spool = tempfile::tempfile()?;
+    spool.write_all(&head)?;
+    io::copy(&mut src, &mut spool)?;
+    spool.rewind()?;
+    Ok(Input::File(BufReader::new(spool)))
+}
+
+/// Open `path` as a seekable input, buffering stdin when it is `-`.
+pub fn open_input(path: &str) -> io::Result<Input> {
+    if is_stdio(path) {
+        buffer_seekable(io::stdin().lock(), STDIN_MEMORY_LIMIT)
+    } else {
+        Ok(Input::File(BufReader::new(std::fs::File::open(path)?)))
+    }
+}
+
+/// Open `path` as a .ghostline reader, reading stdin when it is `-`. Encrypted
+/// files are decrypted in memory, taking the passphrase from
+/// `GHOSTLINE_PASSPHRASE` or a prompt.
+pub fn open_reader(path: &str) -> anyhow::Result<GhostlineReader<Input>> {
+    Ok(GhostlineReader::from_reader(open_decrypted(path)?)?)
+}
+
+/// [`open_input`], decrypting encrypted files as [`open_reader`] does.
+pub fn open_decrypted(path: &str) -> anyhow::Result<Input> {
+    crate::crypt::decrypt_input(open_input(path)?, crate::crypt::read_passphrase)
+}
+
+/// Refuse to dump binary data onto an interactive terminal.
+pub fn guard_binary_stdout() -> anyhow::Result<()> {
+    if io::stdout().is_terminal() {
+        anyhow::bail!("refusing to write binary .ghostline data to a terminal — redirect or pipe it");
+    }
+    Ok(())
+}
+
+/// Open an output destination, stdout when `path` is `-`.
+pub fn create_output(path: &str) -> io::Result<Box<dyn Write>> {
+    if is_stdio(path) {
+        Ok(Box::new(io::BufWriter::new(io::stdout().lock())))
+    } else {
+        Ok(Box::new(io::BufWriter::new(std::fs::File::create(path)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::{Frame, GhostlineWriter, Header};
+
+    #[test]
+    fn memory_input_reads_like_a_file() {
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        writer.append(&Frame::new(b"req".to_vec(), b"resp".to_vec(), 1, 0)).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Input::Memory(Cursor::new(buf))).unwrap();
+        assert_eq!(reader.frame_count(), 1);
+        assert_eq!(reader.get_frame(0).unwrap().response_bytes, b"resp");
+    }
+
+    #[test]
+    fn large_input_spools_to_disk() {
+        let data = vec![7u8; 100];
+        assert!(matches!(buffer_seekable(&data[..], 100).unwrap(), Input::Memory(_)));
+        let mut spooled = buffer_seekable(&data[..], 10).unwrap();
+        assert!(matches!(spooled, Input::File(_)));
+        let mut back = Vec::new();
+        spooled.read_to_end(&mut back).unwrap();
+        assert_eq!(back, data);
+    }
+
+    #[test]
+    fn only_dash_is_stdio() {
+        assert!(is_stdio("-"));
+        assert!(!is_stdio("./-"));
+        assert!(!is_stdio("run.ghostline"));
+    }
+}
@@ -0,0 +1,99 @@
+//! `display.timezone`: how timestamps are printed, the same in every command.
+
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Zone timestamps are shown in: `utc` (the default), the machine's `local`
+/// zone, or a named IANA zone such as `Europe/Paris`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Timezone {
+    #[default]
+    Utc,
+    Local,
+    Named(chrono_tz::Tz),
+}
+
+impl std::str::FromStr for Timezone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "utc" | "UTC" => Ok(Self::Utc),
+            "local" => Ok(Self::Local),
+            name => name
+                .parse()
+                .map(Self::Named)
+                .map_err(|_| anyhow::anyhow!("unknown timezone '{}' (use utc, local or a name like Europe/Paris)", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Timezone> for String {
+    fn from(tz: Timezone) -> String {
+        tz.to_string()
+    }
+}
+
+impl std::fmt::Display for Timezone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Utc => f.write_str("utc"),
+            Self::Local => f.write_str("local"),
+            Self::Named(tz) => f.write_str(tz.name()),
+        }
+    }
+}
+
+/// `ms` (Unix epoch milliseconds) with `pattern` in zone `tz`; the raw number
+/// if it is out of range.
+fn format_in(ms: u64, tz: Timezone, pattern: &str) -> String {
+    let Some(utc) = DateTime::<Utc>::from_timestamp_millis(ms as i64) else {
+        return ms.to_string();
+    };
+    match tz {
+        Timezone::Utc => utc.format(pattern).to_string(),
+        Timezone::Local => utc.with_timezone(&Local).format(pattern).to_string(),
+        Timezone::Named(zone) => utc.with_timezone(&zone).format(pattern).to_string(),
+    }
+}
+
+/// Date, time and zone, e.g. `2026-01-02 03:04:05 UTC` or
+/// `2026-01-02 04:04:05 CET`; `local` shows its offset.
+pub fn format_timestamp(ms: u64, tz: Timezone) -> String {
+    format_in(ms, tz, "%Y-%m-%d %H:%M:%S %Z")
+}
+
+/// As [`format_timestamp`] to the minute, for tables.
+pub fn format_minute(ms: u64, tz: Timezone) -> String {
+    format_in(ms, tz, "%Y-%m-%d %H:%M %Z")
+}
+
+/// Time of day now, for live log lines.
+pub fn clock_now(tz: Timezone) -> String {
+    format_in(Utc::now().timestamp_millis() as u64, tz, "%H:%M:%S")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_in_each_zone() {
+        let ms = 1_767_323_045_000; // 2026-01-02T03:04:05Z
+        assert_eq!(format_timestamp(ms, Timezone::Utc), "2026-01-02 03:04:05 UTC");
+        let paris: Timezone = "Europe/Paris".parse().unwrap();
+        assert_eq!(format_timestamp(ms, paris), "2026-01-02 04:04:05 CET");
+        assert_eq!(format_minute(ms, "America/New_York".parse().unwrap()), "2026-01-01 22:04 EST");
+        assert_eq!(paris.to_string().parse::<Timezone>().unwrap(), paris);
+        assert!("Mars/Olympus".parse::<Timezone>().is_err());
+    }
+}
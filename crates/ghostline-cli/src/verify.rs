@@ -0,0 +1,215 @@
+use ghostline_core::{check_structure, GhostlineReader, StructureIssue};
+use std::collections::HashSet;
+use std::io::{self, Read, Seek};
+
+/// Result of `ghostline verify`.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Structural invariants the file violates. When non-empty the frames
+    /// may not have been checked at all.
+    pub structure: Vec<StructureIssue>,
+    pub frames: usize,
+    /// Frames whose stored `request_hash` doesn't match their request bytes.
+    /// Frames with a truncated request are not checked (the hash is of the full request).
+    pub hash_mismatches: Vec<usize>,
+    /// Frames whose index entry carries a different hash than the frame itself.
+    pub index_mismatches: Vec<usize>,
+    /// Frames whose recorded response length differs from the upstream's
+    /// `content-length`, as (frame, declared, recorded).
+    pub length_mismatches: Vec<(usize, u64, u64)>,
+    /// Frames whose request hash already appeared earlier in the file.
+    /// Replay can only serve one response per hash (the latest).
+    pub duplicate_hashes: Vec<usize>,
+    /// Frames that failed to decode, with the error.
+    pub unreadable: Vec<(usize, String)>,
+}
+
+impl VerifyReport {
+    /// Problems that make the file wrong, as opposed to merely ambiguous.
+    pub fn has_errors(&self) -> bool {
+        !self.structure.is_empty()
+            || !self.hash_mismatches.is_empty()
+            || !self.index_mismatches.is_empty()
+            || !self.length_mismatches.is_empty()
+            || !self.unreadable.is_empty()
+    }
+}
+
+/// Check the file's structure, then decode every frame it indexes.
+pub fn verify<R: Read + Seek>(mut input: R) -> io::Result<VerifyReport> {
+    let structure = check_structure(&mut input)?;
+    input.rewind()?;
+    let mut reader = match GhostlineReader::from_reader(input) {
+        Ok(reader) => reader,
+        Err(e) => {
+            let mut structure = structure;
+            if structure.is_empty() {
+                structure.push(StructureIssue::BadHeader { reason: e.to_string() });
+            }
+            return Ok(VerifyReport {
+                structure,
+                ..Default::default()
+            });
+        }
+    };
+    let mut report = VerifyReport {
+        structure,
+        frames: reader.frame_count(),
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    for (i, entry) in reader.index_entries().iter().enumerate() {
+        if !seen.insert(entry.request_hash) {
+            report.duplicate_hashes.push(i);
+        }
+    }
+    for i in 0..report.frames {
+        match reader.get_frame(i) {
+            Ok(frame) => {
+                if reader.index_entries()[i].request_hash != frame.request_hash {
+                    report.index_mismatches.push(i);
+                }
+                if frame.original_request_len.is_none() && !frame.request_hash_matches(reader.hash_algo) {
+                    report.hash_mismatches.push(i);
+                }
+                if let Some((declared, recorded)) = frame.response_length_mismatch() {
+                    report.length_mismatches.push((i, declared, recorded));
+                }
+            }
+            Err(e) => report.unreadable.push((i, e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+pub fn print_report(report: &VerifyReport) {
+    println!("Structure:         {}", if report.structure.is_empty() { "ok" } else { "BROKEN" });
+    for issue in &report.structure {
+        println!("  {}", issue);
+    }
+    println!("Frames:            {}", report.frames);
+    println!("Hash mismatches:   {}", report.hash_mismatches.len());
+    for i in &report.hash_mismatches {
+        println!("  [{}] request_hash does not match request bytes", i);
+    }
+    println!("Index mismatches:  {}", report.index_mismatches.len());
+    for i in &report.index_mismatches {
+        println!("  [{}] index entry hash differs from the frame's request_hash", i);
+    }
+    println!("Length mismatches: {}", report.length_mismatches.len());
+    for (i, declared, recorded) in &report.length_mismatches {
+        println!("  [{}] response is {} bytes but upstream declared content-length {}", i, recorded, declared);
+    }
+    println!("Unreadable frames: {}", report.unreadable.len());
+    for (i, e) in &report.unreadable {
+        println!("  [{}] {}", i, e);
+    }
+    println!("Duplicate hashes:  {}", report.duplicate_hashes.len());
+    if !report.duplicate_hashes.is_empty() {
+        println!("  frames {:?} repeat an earlier request; replay serves the latest response (each in turn with --advance-duplicates)", report.duplicate_hashes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::{Frame, GhostlineWriter, Header};
+    use std::io::Cursor;
+
+    #[test]
+    fn reports_duplicates_and_bad_hashes() {
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        writer.append(&Frame::new(b"a".to_vec(), b"1".to_vec(), 1, 0)).unwrap();
+        writer.append(&Frame::new(b"a".to_vec(), b"2".to_vec(), 1, 0)).unwrap();
+        let mut forged = Frame::new(b"b".to_vec(), b"3".to_vec(), 1, 0);
+        forged.request_hash = Frame::hash_request(b"c");
+        writer.append(&forged).unwrap();
+        let short = Frame::builder().request(b"d".to_vec()).response(b"cut".to_vec()).original_content_length(Some(64)).build();
+        writer.append(&short).unwrap();
+        writer.finish().unwrap();
+
+        let report = verify(Cursor::new(buf)).unwrap();
+        assert!(report.structure.is_empty());
+        assert_eq!(report.frames, 4);
+        assert_eq!(report.duplicate_hashes, vec![1]);
+        assert_eq!(report.hash_mismatches, vec![2]);
+        assert_eq!(report.length_mismatches, vec![(3, 64, 3)]);
+        assert!(report.unreadable.is_empty());
+        assert!(report.has_errors());
+    }
+
+    fn sample_file() -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        for i in 0..4u8 {
+            writer.append(&Frame::new(vec![b'q', i], vec![b'r'; 20], 1, 0)).unwrap();
+        }
+        writer.finish().unwrap();
+        buf
+    }
+
+    fn index_offset(buf: &[u8]) -> usize {
+        u64::from_le_bytes(buf[buf.len() - 8..].try_into().unwrap()) as usize
+    }
+
+    #[test]
+    fn clean_file_has_no_structure_issues() {
+        let report = verify(Cursor::new(sample_file())).unwrap();
+        assert!(!report.has_errors(), "{:?}", report);
+    }
+
+    #[test]
+    fn reports_specific_structure_issues() {
+        let clean = sample_file();
+        let idx = index_offset(&clean);
+
+        let mut bad_trailer = clean.clone();
+        let end = bad_trailer.len();
+        bad_trailer[end - 8..].copy_from_slice(&(end as u64 * 2).to_le_bytes());
+        let report = verify(Cursor::new(bad_trailer)).unwrap();
+        assert!(matches!(report.structure[..], [StructureIssue::IndexOffsetOutOfRange { .. }]));
+
+        let mut bad_count = clean.clone();
+        bad_count[end - 12..end - 8].copy_from_slice(&5u32.to_le_bytes());
+        let report = verify(Cursor::new(bad_count)).unwrap();
+        assert!(matches!(report.structure[..], [StructureIssue::CountMismatch { count: 5, .. }]));
+
+        // Point entry 2 back at entry 1's frame.
+        let mut swapped = clean.clone();
+        let entry = |i: usize| idx + i * 44 + 32;
+        let first = swapped[entry(1)..entry(1) + 8].to_vec();
+        swapped[entry(2)..entry(2) + 8].copy_from_slice(&first);
+        let report = verify(Cursor::new(swapped)).unwrap();
+        assert!(report
+            .structure
+            .iter()
+            .any(|i| matches!(i, StructureIssue::OffsetNotIncreasing { entry: 2, .. })));
+    }
+
+    #[test]
+    fn random_corruption_never_panics() {
+        let clean = sample_file();
+        let idx = index_offset(&clean);
+        // xorshift: deterministic without a rand dependency.
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let mut buf = clean.clone();
+            let at = next() as usize % buf.len();
+            let flip = (next() as u8) | 1;
+            buf[at] ^= flip;
+            let report = verify(Cursor::new(buf)).unwrap();
+            // Offsets, count and trailer are fully covered by the structure checks.
+            let in_offset_field = at >= idx && at < clean.len() - 12 && (at - idx) % 44 >= 32 && (at - idx) % 44 < 40;
+            if at >= clean.len() - 12 || in_offset_field {
+                assert!(report.has_errors(), "flip at byte {} went unnoticed", at);
+            }
+        }
+    }
+}
@@ -1,25 +1,117 @@
 use axum::{
-    extract::{ws, Path, State, WebSocketUpgrade},
-    http::{header, HeaderMap, StatusCode},
+    extract::{ws, Path, Query, State, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode, Uri},
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
 use serde_json::json;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
 use tower_http::cors::CorsLayer;
 
+use crate::banner::info;
 use crate::config::Config;
+use crate::replay::LoadedFrames;
 use crate::viewer_assets::ViewerAssets;
 
-pub type FrameSender = broadcast::Sender<String>;
+/// Messages kept for `/api/recent` and late-joining `/ws/live` sockets.
+const RECENT_FRAMES: usize = 200;
+
+/// The live feed: broadcasts each message (a JSON frame summary or replay
+/// event) to `/ws/live` and keeps the latest ones in memory, so recent
+/// traffic can be shown without reading the recording from disk.
+#[derive(Clone)]
+pub struct FrameSender {
+    tx: broadcast::Sender<String>,
+    recent: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl Default for FrameSender {
+    fn default() -> Self {
+        Self::with_recent(RECENT_FRAMES)
+    }
+}
+
+impl FrameSender {
+    /// A feed sized by `viewer.broadcast_capacity`.
+    pub fn from_config(cfg: &Config) -> Self {
+        Self::with_capacity(RECENT_FRAMES, cfg.viewer.broadcast_capacity)
+    }
+
+    /// A feed remembering the last `capacity` messages.
+    pub fn with_recent(capacity: usize) -> Self {
+        Self::with_capacity(capacity, Config::default().viewer.broadcast_capacity)
+    }
+
+    /// A feed remembering the last `capacity` messages, which a socket can
+    /// fall `broadcast` messages behind before skipping ahead.
+    pub fn with_capacity(capacity: usize, broadcast: usize) -> Self {
+        let (tx, _) = broadcast::channel(broadcast.max(1));
+        Self {
+            tx,
+            recent: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    pub fn send(&self, msg: String) {
+        // Hold the buffer while broadcasting so `subscribe` sees each message
+        // exactly once, in the backlog or on the receiver.
+        let mut recent = self.recent.lock().unwrap();
+        if recent.len() == self.capacity {
+            recent.pop_front();
+        }
+        if self.capacity > 0 {
+            recent.push_back(msg.clone());
+        }
+        let _ = self.tx.send(msg);
+    }
+
+    /// Up to `n` of the latest messages, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<String> {
+        let recent = self.recent.lock().unwrap();
+        recent.iter().skip(recent.len().saturating_sub(n)).cloned().collect()
+    }
+
+    /// A receiver for new messages along with up to `backlog` earlier ones.
+    pub fn subscribe(&self, backlog: usize) -> (Vec<String>, broadcast::Receiver<String>) {
+        let recent = self.recent.lock().unwrap();
+        let earlier = recent.iter().skip(recent.len().saturating_sub(backlog)).cloned().collect();
+        (earlier, self.tx.subscribe())
+    }
+}
 
 #[derive(Clone)]
 pub struct ViewerState {
     pub config: Arc<Config>,
     pub frame_tx: FrameSender,
     pub frame_count: Arc<std::sync::atomic::AtomicUsize>,
+    /// Serve only this run instead of the runs directory (`ghostline serve`).
+    pub scope: Option<ScopedRun>,
+}
+
+/// A single run the viewer is limited to, with frames shared from the replay cache.
+#[derive(Clone)]
+pub struct ScopedRun {
+    /// File name the run is listed under.
+    pub name: String,
+    pub path: std::path::PathBuf,
+    /// Filled in by the replay server once loaded; until then frames are read from disk.
+    pub frames: LoadedFrames,
+}
+
+impl ViewerState {
+    /// Resolve a requested run name, which may name a subdirectory of the
+    /// runs directory (`project/run.ghostline`), to its file, honouring the scope.
+    fn run_path(&self, name: &str) -> Option<std::path::PathBuf> {
+        match &self.scope {
+            Some(scope) => (scope.name == name).then(|| scope.path.clone()),
+            None => crate::runs::resolve_run(&Config::runs_dir(), name),
+        }
+    }
 }
 
 pub fn router(state: ViewerState) -> Router {
@@ -33,23 +125,59 @@ pub fn router(state: ViewerState) -> Router {
             format!("http://127.0.0.1:{}", state.config.viewer.port)
                 .parse()
                 .unwrap(),
+            format!("http://[::1]:{}", state.config.viewer.port)
+                .parse()
+                .unwrap(),
         ])
         .allow_methods([axum::http::Method::GET])
         .allow_headers(tower_http::cors::Any);
 
     Router::new()
         .route("/", get(serve_index))
-        .route("/assets/{*path}", get(serve_asset))
+        .route("/assets/*path", get(serve_asset))
         .route("/api/runs", get(list_runs))
-        .route("/api/runs/{name}", get(get_run))
-        .route("/api/runs/{name}/frames", get(get_run_frames))
+        .route("/api/runs/*path", get(run_api))
+        .route("/api/diff", get(get_diff))
         .route("/api/status", get(get_status))
+        .route("/api/recent", get(get_recent))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
         .route("/ws/live", get(ws_handler))
         .layer(cors)
         .with_state(state)
 }
 
+/// Served at `/` when the binary was built without the viewer bundle, so the
+/// page says why instead of a bare 404.
+const MISSING_VIEWER_HTML: &str = r#"<!doctype html>
+<html lang="en">
+<head><meta charset="utf-8"><title>ghostline viewer</title></head>
+<body style="font-family: system-ui, sans-serif; max-width: 40rem; margin: 4rem auto; line-height: 1.5">
+<h1>The viewer isn't built into this binary</h1>
+<p>This <code>ghostline</code> was compiled without the viewer bundle, most likely with
+<code>GHOSTLINE_SKIP_VIEWER_BUILD</code> set and no <code>viewer/dist</code> present.
+To include it, build the viewer and then the CLI:</p>
+<pre>cd viewer &amp;&amp; npm install &amp;&amp; npm run build
+cd .. &amp;&amp; cargo build -p ghostline</pre>
+<p>The API still works: try <a href="/api/runs"><code>/api/runs</code></a>
+or <a href="/api/status"><code>/api/status</code></a>.</p>
+</body>
+</html>
+"#;
+
+fn viewer_assets_embedded() -> bool {
+    ViewerAssets::get("index.html").is_some()
+}
+
 async fn serve_index() -> impl IntoResponse {
+    if !viewer_assets_embedded() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
+            MISSING_VIEWER_HTML,
+        )
+            .into_response();
+    }
     serve_embedded_file("index.html")
 }
 
@@ -70,48 +198,93 @@ fn serve_embedded_file(path: &str) -> Response {
     }
 }
 
-async fn list_runs() -> impl IntoResponse {
-    let runs_dir = Config::runs_dir();
-    let mut runs = vec![];
-    if let Ok(entries) = std::fs::read_dir(&runs_dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().map(|e| e == "ghostline").unwrap_or(false) {
-                let name = path.file_name().unwrap().to_string_lossy().to_string();
-                let size = path.metadata().map(|m| m.len()).unwrap_or(0);
-                runs.push(json!({"name": name, "size": size}));
-            }
-        }
+async fn list_runs(State(state): State<ViewerState>) -> impl IntoResponse {
+    if let Some(scope) = &state.scope {
+        let size = scope.path.metadata().map(|m| m.len()).unwrap_or(0);
+        return Json(vec![json!({"name": scope.name, "size": size})]);
     }
-    Json(runs)
+    // Names are relative to the runs directory, so runs in subdirectories
+    // come back as `project/run.ghostline`.
+    let runs = crate::runs::list_runs(&Config::runs_dir()).unwrap_or_default();
+    Json(runs.into_iter().map(|run| json!({"name": run.name, "size": run.size})).collect())
+}
+
+/// Whether a request's `Accept` header asks for JSON. Anything else,
+/// including `*/*` and no header, gets the binary file.
+fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let media = parts.next().unwrap_or_default();
+            let refused = parts.any(|p| matches!(p.strip_prefix("q="), Some(q) if q.parse::<f32>() == Ok(0.0)));
+            media.eq_ignore_ascii_case("application/json") && !refused
+        })
 }
 
-/// Sanitize a run name: reject path traversal, enforce .ghostline extension.
-fn sanitize_run_name(name: &str) -> Option<&str> {
-    // Must end with .ghostline
-    if !name.ends_with(".ghostline") {
-        return None;
+/// `/api/runs/<name>[/frames[/<index>/<part>] | /stats]`. Run names may be
+/// nested (`project/run.ghostline`), sent with or without their slashes
+/// encoded, so one wildcard route takes the whole path and splits the name
+/// from what is asked of it here.
+async fn run_api(State(state): State<ViewerState>, Path(path): Path<String>, uri: Uri, request_headers: HeaderMap) -> Response {
+    if path.ends_with(".ghostline") {
+        return get_run(State(state), Path(path), request_headers).await.into_response();
     }
-    // Must be a plain filename — no directory separators or traversal sequences
-    if name.contains('/') || name.contains('\\') || name.contains("..") {
-        return None;
+    if let Some(name) = path.strip_suffix("/frames") {
+        let query = match Query::<FramesQuery>::try_from_uri(&uri) {
+            Ok(query) => query,
+            Err(rejection) => return rejection.into_response(),
+        };
+        return get_run_frames(State(state), Path(name.to_string()), query).await.into_response();
     }
-    // Extra: must not start with a dot (hidden files)
-    if name.starts_with('.') {
-        return None;
+    if let Some(name) = path.strip_suffix("/stats") {
+        return get_run_stats(State(state), Path(name.to_string())).await;
     }
-    Some(name)
+    let mut tail = path.rsplitn(4, '/');
+    if let (Some(part), Some(index), Some("frames"), Some(name)) = (tail.next(), tail.next(), tail.next(), tail.next()) {
+        let Ok(index) = index.parse() else {
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": "frame index must be a number" }))).into_response();
+        };
+        return get_frame_body(State(state), Path((name.to_string(), index, part.to_string()))).await.into_response();
+    }
+    StatusCode::NOT_FOUND.into_response()
 }
 
-async fn get_run(Path(name): Path<String>) -> impl IntoResponse {
-    let safe_name = match sanitize_run_name(&name) {
-        Some(n) => n,
+/// The run's file, or with `Accept: application/json` its frames in the
+/// `ghostline export --format json` shape.
+async fn get_run(State(state): State<ViewerState>, Path(name): Path<String>, request_headers: HeaderMap) -> impl IntoResponse {
+    let path = match state.run_path(&name) {
+        Some(p) => p,
         None => return StatusCode::BAD_REQUEST.into_response(),
     };
-    let path = Config::runs_dir().join(safe_name);
-    match std::fs::read(&path) {
+    match tokio::fs::read(&path).await {
         Ok(data) => {
+            // The browser parses the plain format; decrypt here with GHOSTLINE_PASSPHRASE.
+            let data = if crate::crypt::is_encrypted(&data) {
+                match crate::crypt::env_passphrase().and_then(|p| crate::crypt::decrypt(&data, &p)) {
+                    Ok(plain) => plain,
+                    Err(e) => return (StatusCode::FORBIDDEN, e.to_string()).into_response(),
+                }
+            } else {
+                data
+            };
             let mut headers = HeaderMap::new();
+            headers.insert(header::VARY, "accept".parse().unwrap());
+            if wants_json(&request_headers) {
+                let frames = tokio::task::spawn_blocking(move || {
+                    let mut reader = ghostline_core::GhostlineReader::from_reader(std::io::Cursor::new(data))?;
+                    crate::export::frames_json(&mut reader, crate::export::Selection::All, false)
+                })
+                .await;
+                return match frames {
+                    Ok(Ok(frames)) => (StatusCode::OK, headers, Json(frames)).into_response(),
+                    Ok(Err(e)) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+                    Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+                };
+            }
             headers.insert(header::CONTENT_TYPE, "application/octet-stream".parse().unwrap());
             (StatusCode::OK, headers, data).into_response()
         }
@@ -119,32 +292,294 @@ async fn get_run(Path(name): Path<String>) -> impl IntoResponse {
     }
 }
 
-async fn get_run_frames(Path(name): Path<String>) -> impl IntoResponse {
-    use ghostline_core::GhostlineReader;
+#[derive(Debug, Default, serde::Deserialize)]
+struct FramesQuery {
+    /// Only return frames carrying this tag.
+    tag: Option<String>,
+    /// First frame index to return (inclusive).
+    since: Option<usize>,
+    /// Last frame index to return (inclusive).
+    until: Option<usize>,
+    /// `ndjson`: one summary per line, sent as the frames are read.
+    stream: Option<FrameStream>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FrameStream {
+    Ndjson,
+}
 
-    let safe_name = match sanitize_run_name(&name) {
-        Some(n) => n,
+/// Summaries in flight between the reading thread and the response body.
+const NDJSON_BUFFER: usize = 64;
+
+impl FramesQuery {
+    /// The frame indices `since..=until` selects out of `count`, defaulting to
+    /// all of them.
+    fn window(&self, count: usize) -> Result<std::ops::Range<usize>, String> {
+        for (name, index) in [("since", self.since), ("until", self.until)] {
+            if let Some(i) = index.filter(|&i| i >= count) {
+                return Err(format!("{} {} is out of range for {} frames", name, i, count));
+            }
+        }
+        let since = self.since.unwrap_or(0);
+        let end = self.until.map_or(count, |u| u + 1);
+        if since >= end && count > 0 {
+            return Err(format!("since {} is after until {}", since, end - 1));
+        }
+        Ok(since..end)
+    }
+}
+
+fn bad_window(reason: String) -> Response {
+    (StatusCode::BAD_REQUEST, Json(json!({ "error": reason }))).into_response()
+}
+
+/// JSON summary of frame `i`, or `None` when it doesn't carry `tag`.
+fn frame_summary(i: usize, frame: &ghostline_core::Frame, tag: Option<&str>) -> Option<serde_json::Value> {
+    if let Some(tag) = tag {
+        if !frame.tags.iter().any(|t| t == tag) {
+            return None;
+        }
+    }
+    serde_json::to_value(frame.summary(i)).ok()
+}
+
+/// Decode the frames of a run `query` selects into JSON summaries; the inner
+/// error is a bad `since`/`until`. Blocking — call via `spawn_blocking` from handlers.
+fn frame_summaries(
+    path: &std::path::Path,
+    query: &FramesQuery,
+) -> anyhow::Result<Result<Vec<serde_json::Value>, String>> {
+    let mut reader = crate::crypt::open_for_server(path)?;
+    let window = match query.window(reader.frame_count()) {
+        Ok(window) => window,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let mut frames = vec![];
+    for i in window {
+        if let Ok(frame) = reader.get_frame(i) {
+            frames.extend(frame_summary(i, &frame, query.tag.as_deref()));
+        }
+    }
+    Ok(Ok(frames))
+}
+
+async fn get_run_frames(
+    State(state): State<ViewerState>,
+    Path(name): Path<String>,
+    Query(query): Query<FramesQuery>,
+) -> impl IntoResponse {
+    let path = match state.run_path(&name) {
+        Some(p) => p,
         None => return (StatusCode::BAD_REQUEST, Json(json!([]))).into_response(),
     };
-    let path = Config::runs_dir().join(safe_name);
-    let mut reader = match GhostlineReader::open(path.to_str().unwrap_or("")) {
-        Ok(r) => r,
-        Err(_) => return (StatusCode::NOT_FOUND, Json(json!([]))).into_response(),
+    if let Some(loaded) = state.scope.as_ref().and_then(|s| s.frames.get()) {
+        let window = match query.window(loaded.len()) {
+            Ok(window) => window,
+            Err(reason) => return bad_window(reason),
+        };
+        let tag = query.tag.as_deref();
+        let frames: Vec<_> = window.filter_map(|i| frame_summary(i, &loaded[i], tag)).collect();
+        if query.stream == Some(FrameStream::Ndjson) {
+            let lines = frames.into_iter().map(|f| Ok::<_, std::io::Error>(ndjson_line(&f)));
+            return ndjson_response(axum::body::Body::from_stream(futures_util::stream::iter(lines)));
+        }
+        return Json(frames).into_response();
+    }
+    if query.stream == Some(FrameStream::Ndjson) {
+        return stream_frame_summaries(path, query).await;
+    }
+
+    // Reading and decompressing is synchronous; keep it off the async workers.
+    let result = tokio::task::spawn_blocking(move || frame_summaries(&path, &query)).await;
+    match result {
+        Ok(Ok(Ok(frames))) => Json(frames).into_response(),
+        Ok(Ok(Err(reason))) => bad_window(reason),
+        Ok(Err(_)) => (StatusCode::NOT_FOUND, Json(json!([]))).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+fn ndjson_line(value: &serde_json::Value) -> String {
+    let mut line = value.to_string();
+    line.push('\n');
+    line
+}
+
+fn ndjson_response(body: axum::body::Body) -> Response {
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response()
+}
+
+/// `?stream=ndjson` from disk: a blocking task walks the index and sends each
+/// summary on as it is decoded, so the first lines arrive long before a large
+/// recording has been read, and a client that goes away stops the walk.
+async fn stream_frame_summaries(path: std::path::PathBuf, query: FramesQuery) -> Response {
+    // Open and check the window first, so a bad request still gets a status.
+    let opened = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let reader = crate::crypt::open_for_server(&path)?;
+        let window = query.window(reader.frame_count());
+        Ok((reader, window, query.tag))
+    })
+    .await;
+    let (mut reader, window, tag) = match opened {
+        Ok(Ok((reader, Ok(window), tag))) => (reader, window, tag),
+        Ok(Ok((_, Err(reason), _))) => return bad_window(reason),
+        Ok(Err(_)) => return (StatusCode::NOT_FOUND, Json(json!([]))).into_response(),
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     };
 
-    let mut frames = vec![];
-    for i in 0..reader.frame_count() {
-        if let Ok(frame) = reader.get_frame(i) {
-            frames.push(json!({
-                "index": i,
-                "timestamp": frame.timestamp,
-                "latency_ms": frame.latency_ms,
-                "request_size": frame.request_bytes.len(),
-                "response_size": frame.response_bytes.len(),
-            }));
+    let (tx, rx) = tokio::sync::mpsc::channel::<String>(NDJSON_BUFFER);
+    tokio::task::spawn_blocking(move || {
+        for i in window {
+            let line = match reader.get_frame(i) {
+                Ok(frame) => match frame_summary(i, &frame, tag.as_deref()) {
+                    Some(summary) => ndjson_line(&summary),
+                    None => continue,
+                },
+                Err(_) => continue,
+            };
+            if tx.blocking_send(line).is_err() {
+                return;
+            }
         }
+    });
+    let lines = futures_util::stream::unfold(rx, |mut rx| async {
+        rx.recv().await.map(|line| (Ok::<_, std::io::Error>(line), rx))
+    });
+    ndjson_response(axum::body::Body::from_stream(lines))
+}
+
+/// `ghostline stats --json` for one run.
+async fn get_run_stats(State(state): State<ViewerState>, Path(name): Path<String>) -> Response {
+    let Some(path) = state.run_path(&name) else {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid run name" }))).into_response();
+    };
+    let result = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        Ok(crate::stats::compute(&mut crate::crypt::open_for_server(&path)?)?)
+    })
+    .await;
+    match result {
+        Ok(Ok(stats)) => Json(stats).into_response(),
+        Ok(Err(_)) => (StatusCode::NOT_FOUND, Json(json!({ "error": "run not found or unreadable" }))).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
-    Json(frames).into_response()
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DiffQuery {
+    left: String,
+    right: String,
+}
+
+/// Two runs compared the way `ghostline diff` does, plus where each changed
+/// response first differs.
+async fn get_diff(State(state): State<ViewerState>, Query(query): Query<DiffQuery>) -> Response {
+    let (Some(left), Some(right)) = (state.run_path(&query.left), state.run_path(&query.right)) else {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid run name" }))).into_response();
+    };
+    let result = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let mut a = crate::crypt::open_for_server(&left)?;
+        let mut b = crate::crypt::open_for_server(&right)?;
+        if let Err(e) = crate::diff::check_hash_algos(a.hash_algo, b.hash_algo) {
+            return Ok(Err(e.to_string()));
+        }
+        let run_diff = crate::diff::diff(&crate::diff::frame_keys(&mut a)?, &crate::diff::frame_keys(&mut b)?);
+        let changed = run_diff.response_changes(&mut a, &mut b)?;
+        Ok(Ok((run_diff, changed)))
+    })
+    .await;
+    match result {
+        Ok(Ok(Err(error))) => (StatusCode::UNPROCESSABLE_ENTITY, Json(json!({ "error": error }))).into_response(),
+        Ok(Ok(Ok((run_diff, changed)))) => Json(json!({
+            "left": query.left,
+            "right": query.right,
+            "frames_left": run_diff.frames_a,
+            "frames_right": run_diff.frames_b,
+            "only_left": run_diff.only_in_a,
+            "only_right": run_diff.only_in_b,
+            "matched": run_diff.matched,
+            "changed": changed,
+        }))
+        .into_response(),
+        Ok(Err(_)) => (StatusCode::NOT_FOUND, Json(json!({ "error": "run not found or unreadable" }))).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Download name for one body of a frame, e.g. `run-3-response.json`.
+fn attachment_filename(run: &str, index: usize, part: &str, content_type: &str) -> String {
+    let stem: String = run
+        .trim_end_matches(".ghostline")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    let extension = match content_type {
+        "application/json" => "json",
+        "text/event-stream" => "txt",
+        "application/gzip" => "gz",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        _ => "bin",
+    };
+    format!("{}-{}-{}.{}", stem, index, part, extension)
+}
+
+fn frame_out_of_range(index: usize, count: usize) -> Response {
+    let error = format!("frame {} is out of range for {} frames", index, count);
+    (StatusCode::NOT_FOUND, Json(json!({ "error": error }))).into_response()
+}
+
+/// One body (`part` is `request` or `response`) of frame `index`, as a file
+/// download with a sniffed content type.
+async fn get_frame_body(
+    State(state): State<ViewerState>,
+    Path((name, index, part)): Path<(String, usize, String)>,
+) -> impl IntoResponse {
+    let body = match part.as_str() {
+        "request" => ghostline_core::Body::Request,
+        "response" => ghostline_core::Body::Response,
+        _ => {
+            let error = "part must be request or response";
+            return (StatusCode::BAD_REQUEST, Json(json!({ "error": error }))).into_response();
+        }
+    };
+    let path = match state.run_path(&name) {
+        Some(p) => p,
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    let bytes = if let Some(loaded) = state.scope.as_ref().and_then(|s| s.frames.get()) {
+        let Some(frame) = loaded.get(index) else {
+            return frame_out_of_range(index, loaded.len());
+        };
+        match body {
+            ghostline_core::Body::Request => frame.request_bytes.clone(),
+            ghostline_core::Body::Response => frame.response_bytes.clone(),
+        }
+    } else {
+        let result = tokio::task::spawn_blocking(move || -> anyhow::Result<Result<Vec<u8>, usize>> {
+            let mut reader = crate::crypt::open_for_server(&path)?;
+            if index >= reader.frame_count() {
+                return Ok(Err(reader.frame_count()));
+            }
+            let mut out = Vec::new();
+            reader.get_body_to(index, body, &mut out)?;
+            Ok(Ok(out))
+        })
+        .await;
+        match result {
+            Ok(Ok(Ok(bytes))) => bytes,
+            Ok(Ok(Err(count))) => return frame_out_of_range(index, count),
+            Ok(Err(_)) => return StatusCode::NOT_FOUND.into_response(),
+            Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    };
+    let content_type = ghostline_core::sniff_content_type(&bytes);
+    let disposition = format!("attachment; filename=\"{}\"", attachment_filename(&name, index, &part, content_type));
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+    headers.insert(header::CONTENT_DISPOSITION, disposition.parse().unwrap());
+    (StatusCode::OK, headers, bytes).into_response()
 }
 
 async fn get_status(State(state): State<ViewerState>) -> impl IntoResponse {
@@ -154,20 +589,131 @@ async fn get_status(State(state): State<ViewerState>) -> impl IntoResponse {
         "frame_count": count,
         "proxy_port": state.config.proxy.port,
         "viewer_port": state.config.viewer.port,
+        "version": env!("CARGO_PKG_VERSION"),
+        "format_version": ghostline_core::format_version(),
+        "format": ghostline_core::format_description(),
     }))
 }
 
+#[derive(serde::Deserialize)]
+struct RecentQuery {
+    /// How many of the latest messages to return.
+    #[serde(default = "default_recent")]
+    n: usize,
+}
+
+fn default_recent() -> usize {
+    50
+}
+
+/// The latest live messages, oldest first, from memory rather than disk.
+async fn get_recent(State(state): State<ViewerState>, Query(query): Query<RecentQuery>) -> impl IntoResponse {
+    let frames: Vec<serde_json::Value> = state
+        .frame_tx
+        .recent(query.n)
+        .iter()
+        .filter_map(|msg| serde_json::from_str(msg).ok())
+        .collect();
+    Json(frames)
+}
+
+async fn healthz() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Ready once the embedded viewer bundle is present.
+async fn readyz(State(state): State<ViewerState>) -> impl IntoResponse {
+    // `ghostline serve`: not ready until the replay server has loaded the run.
+    if state.scope.as_ref().is_some_and(|scope| scope.frames.get().is_none()) {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not ready", "reason": "run still loading" })),
+        )
+    } else if viewer_assets_embedded() {
+        (StatusCode::OK, Json(json!({ "status": "ready" })))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not ready", "reason": "viewer assets not embedded" })),
+        )
+    }
+}
+
+/// Per-connection filter for `/ws/live`, e.g. `?min_latency=1000&errors_only=true`.
+///
+/// Best-effort and scoped to the one client: it only decides which broadcast
+/// messages that socket sees, never what gets recorded.
+#[derive(Debug, Default, serde::Deserialize)]
+struct LiveFilter {
+    /// Only frames at least this slow (ms). Messages without a latency are dropped.
+    min_latency: Option<u64>,
+    /// Only frames with an HTTP status of 400 or above (replay misses included).
+    #[serde(default)]
+    errors_only: bool,
+}
+
+impl LiveFilter {
+    fn is_active(&self) -> bool {
+        self.min_latency.is_some() || self.errors_only
+    }
+
+    /// Whether a broadcast message passes. Messages that aren't JSON objects pass.
+    fn matches(&self, msg: &str) -> bool {
+        if !self.is_active() {
+            return true;
+        }
+        let Ok(serde_json::Value::Object(fields)) = serde_json::from_str::<serde_json::Value>(msg) else {
+            return true;
+        };
+        if let Some(min) = self.min_latency {
+            match fields.get("latency_ms").and_then(|v| v.as_u64()) {
+                Some(latency) if latency >= min => {}
+                _ => return false,
+            }
+        }
+        if self.errors_only {
+            // Frame summaries say so (`Frame::is_error`); replay events carry a status.
+            let is_error = match fields.get("is_error").and_then(|v| v.as_bool()) {
+                Some(is_error) => is_error,
+                None => fields.get("status").and_then(|v| v.as_u64()).is_some_and(|status| status >= 400),
+            };
+            if !is_error {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// `/ws/live?backlog=50` first replays up to that many recent messages, so a
+/// viewer that connects late has context. Off by default.
+#[derive(Debug, Default, serde::Deserialize)]
+struct BacklogQuery {
+    #[serde(default)]
+    backlog: usize,
+}
+
 async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<ViewerState>,
+    Query(filter): Query<LiveFilter>,
+    Query(backlog): Query<BacklogQuery>,
 ) -> impl IntoResponse {
-    let mut rx = state.frame_tx.subscribe();
+    let (earlier, mut rx) = state.frame_tx.subscribe(backlog.backlog);
     ws.on_upgrade(move |mut socket| async move {
+        for msg in earlier.into_iter().filter(|m| filter.matches(m)) {
+            if socket.send(ws::Message::Text(msg)).await.is_err() {
+                return;
+            }
+        }
         loop {
             match rx.recv().await {
                 Ok(msg) => {
+                    if !filter.matches(&msg) {
+                        continue;
+                    }
                     if socket
-                        .send(ws::Message::Text(msg.into()))
+                        .send(ws::Message::Text(msg))
                         .await
                         .is_err()
                     {
@@ -175,7 +721,14 @@ async fn ws_handler(
                     }
                 }
                 Err(broadcast::error::RecvError::Closed) => break,
-                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                // Fell more than `viewer.broadcast_capacity` behind: say how
+                // much was skipped, so the viewer knows its list has gaps.
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let notice = json!({ "type": "lagged", "skipped": skipped }).to_string();
+                    if socket.send(ws::Message::Text(notice)).await.is_err() {
+                        break;
+                    }
+                }
             }
         }
     })
@@ -186,16 +739,257 @@ pub async fn start(
     frame_tx: FrameSender,
     frame_count: Arc<std::sync::atomic::AtomicUsize>,
 ) -> anyhow::Result<()> {
-    let port = config.viewer.port;
-    let state = ViewerState {
+    serve(ViewerState {
         config,
         frame_tx,
         frame_count,
-    };
+        scope: None,
+    })
+    .await
+}
+
+/// Serve the viewer for a prepared state on `state.config.viewer.port`.
+pub async fn serve(state: ViewerState) -> anyhow::Result<()> {
+    use std::future::IntoFuture;
+    let port = state.config.viewer.port;
+    let bind = state.config.server.bind;
     let app = router(state);
-    // Bind to localhost only — viewer must not be exposed on the network
-    let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", port)).await?;
-    eprintln!(" ✓ Viewer serving on  http://localhost:{}", port);
-    axum::serve(listener, app).await?;
+    // Bind to loopback only — viewer must not be exposed on the network
+    let mut servers = Vec::new();
+    for listener in bind.listen(port)? {
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        servers.push(axum::serve(listener, app.clone()).into_future());
+    }
+    info!(" ✓ Viewer serving on  http://{}:{}", bind.url_host(), port);
+    if !viewer_assets_embedded() {
+        eprintln!("ghostline: warning: this build has no viewer assets; / explains how to build them, /api still works");
+    }
+    futures_util::future::try_join_all(servers).await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::IntoFuture;
+
+    #[test]
+    fn readyz_waits_for_the_scoped_run() {
+        let frames = LoadedFrames::default();
+        let state = ViewerState {
+            config: Arc::new(Config::default()),
+            frame_tx: FrameSender::default(),
+            frame_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            scope: Some(ScopedRun { name: "run.ghostline".into(), path: "run.ghostline".into(), frames: frames.clone() }),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (addr, server) = rt.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            (listener.local_addr().unwrap(), tokio::spawn(axum::serve(listener, router(state)).into_future()))
+        });
+        let readyz = || {
+            rt.block_on(async {
+                let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+                (response.status().as_u16(), serde_json::from_slice::<serde_json::Value>(&response.bytes().await.unwrap()).unwrap())
+            })
+        };
+
+        let (status, body) = readyz();
+        assert_eq!(status, 503);
+        assert_eq!(body["reason"], "run still loading");
+
+        frames.set(Arc::new(Vec::new())).unwrap();
+        let (status, body) = readyz();
+        if viewer_assets_embedded() {
+            assert_eq!((status, body["status"].as_str()), (200, Some("ready")));
+        } else {
+            assert_eq!((status, body["reason"].as_str()), (503, Some("viewer assets not embedded")));
+        }
+        server.abort();
+    }
+
+    #[test]
+    fn nested_run_names_reach_every_route() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.ghostline");
+        let mut writer = ghostline_core::GhostlineWriter::new(std::fs::File::create(&path).unwrap(), &Default::default()).unwrap();
+        for i in 0..3u8 {
+            writer.append(&ghostline_core::Frame::new(vec![i], vec![b'r', i], 1, i as u64)).unwrap();
+        }
+        writer.finish().unwrap();
+        let state = ViewerState {
+            config: Arc::new(Config::default()),
+            frame_tx: FrameSender::default(),
+            frame_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            scope: Some(ScopedRun { name: "proj/run.ghostline".into(), path: path.clone(), frames: Default::default() }),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (addr, server) = rt.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            (listener.local_addr().unwrap(), tokio::spawn(axum::serve(listener, router(state)).into_future()))
+        });
+        let get = |route: &str| {
+            rt.block_on(async {
+                let response = reqwest::get(format!("http://{}/api/runs/{}", addr, route)).await.unwrap();
+                (response.status().as_u16(), response.bytes().await.unwrap().to_vec())
+            })
+        };
+
+        assert_eq!(get("proj/run.ghostline"), (200, std::fs::read(&path).unwrap()));
+        for name in ["proj/run.ghostline", "proj%2Frun.ghostline"] {
+            let (status, body) = get(&format!("{}/frames?since=1", name));
+            assert_eq!(status, 200);
+            assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap().as_array().unwrap().len(), 2);
+        }
+        let (status, body) = get("proj/run.ghostline/stats");
+        assert_eq!(status, 200);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap()["frames"], 3);
+        assert_eq!(get("proj/run.ghostline/frames/2/response"), (200, vec![b'r', 2]));
+        assert_eq!(get("proj/run.ghostline/frames/two/response").0, 400);
+        assert_eq!(get("proj/run.ghostline/frames?since=nope").0, 400);
+        assert_eq!(get("other/run.ghostline").0, 400);
+        assert_eq!(get("proj/run.ghostline/nothing").0, 404);
+        server.abort();
+    }
+
+    #[test]
+    fn attachment_names_are_plain_and_typed() {
+        assert_eq!(attachment_filename("run.ghostline", 3, "response", "application/json"), "run-3-response.json");
+        assert_eq!(
+            attachment_filename("my \"repro\".ghostline", 0, "request", "application/octet-stream"),
+            "my__repro_-0-request.bin"
+        );
+    }
+
+    #[test]
+    fn recent_frames_are_bounded_and_backfill_subscribers() {
+        let feed = FrameSender::with_recent(3);
+        for i in 0..5 {
+            feed.send(format!("{{\"index\": {}}}", i));
+        }
+        assert_eq!(feed.recent(2), vec![r#"{"index": 3}"#, r#"{"index": 4}"#]);
+        assert_eq!(feed.recent(50).len(), 3);
+
+        let (earlier, mut rx) = feed.subscribe(10);
+        assert_eq!(earlier.first().map(String::as_str), Some(r#"{"index": 2}"#));
+        feed.send("next".into());
+        assert_eq!(rx.try_recv().unwrap(), "next");
+        assert!(rx.try_recv().is_err());
+        assert!(feed.subscribe(0).0.is_empty());
+
+        let narrow = FrameSender::with_capacity(0, 2);
+        let (_, mut rx) = narrow.subscribe(0);
+        for i in 0..3 {
+            narrow.send(i.to_string());
+        }
+        assert_eq!(rx.try_recv(), Err(broadcast::error::TryRecvError::Lagged(1)));
+        assert_eq!(rx.try_recv().unwrap(), "1");
+    }
+
+    #[test]
+    fn frames_stream_as_ndjson() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.ghostline");
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = ghostline_core::GhostlineWriter::new(file, &Default::default()).unwrap();
+        for i in 0..5u8 {
+            writer.append(&ghostline_core::Frame::new(vec![i], vec![i], 1, i as u64)).unwrap();
+        }
+        writer.finish().unwrap();
+        let state = ViewerState {
+            config: Arc::new(Config::default()),
+            frame_tx: FrameSender::default(),
+            frame_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            scope: Some(ScopedRun { name: "run.ghostline".into(), path, frames: Default::default() }),
+        };
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let get = |since, stream| {
+            let query = FramesQuery { since, stream, ..Default::default() };
+            let response = rt.block_on(get_run_frames(State(state.clone()), Path("run.ghostline".into()), Query(query)));
+            let response = response.into_response();
+            let status = response.status();
+            let content_type = response.headers().get(header::CONTENT_TYPE).cloned();
+            let body = rt.block_on(axum::body::to_bytes(response.into_body(), usize::MAX)).unwrap();
+            (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+        };
+
+        let (status, content_type, body) = get(Some(2), Some(FrameStream::Ndjson));
+        assert_eq!((status, content_type.unwrap().to_str().unwrap()), (StatusCode::OK, "application/x-ndjson"));
+        let lines: Vec<serde_json::Value> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.iter().map(|f| f["index"].as_u64().unwrap()).collect::<Vec<_>>(), vec![2, 3, 4]);
+        let (_, _, array) = get(Some(2), None);
+        assert_eq!(serde_json::from_str::<Vec<serde_json::Value>>(&array).unwrap(), lines);
+        assert_eq!(get(Some(9), Some(FrameStream::Ndjson)).0, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn frame_window_is_inclusive_and_validated() {
+        let query = |since, until| FramesQuery { since, until, ..Default::default() };
+        assert_eq!(query(None, None).window(5), Ok(0..5));
+        assert_eq!(query(Some(1), Some(3)).window(5), Ok(1..4));
+        assert_eq!(query(Some(2), Some(2)).window(5), Ok(2..3));
+        assert_eq!(query(Some(3), None).window(5), Ok(3..5));
+        assert_eq!(query(None, None).window(0), Ok(0..0));
+        assert!(query(Some(3), Some(1)).window(5).is_err());
+        assert!(query(None, Some(5)).window(5).is_err());
+        assert!(query(Some(0), None).window(0).is_err());
+    }
+
+    #[test]
+    fn accept_header_selects_json() {
+        let accept = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::ACCEPT, value.parse().unwrap());
+            wants_json(&headers)
+        };
+        assert!(accept("application/json"));
+        assert!(accept("text/html, application/json;q=0.9"));
+        assert!(!accept("*/*"));
+        assert!(!accept("application/octet-stream"));
+        assert!(!accept("application/json;q=0"));
+        assert!(!wants_json(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn live_filter_matches_latency_and_status() {
+        let slow_ok = r#"{"latency_ms": 1500, "status": 200}"#;
+        let fast_err = r#"{"latency_ms": 20, "status": 500}"#;
+        let miss = r#"{"type": "replay_miss", "status": 404}"#;
+
+        let all = LiveFilter::default();
+        assert!(all.matches(slow_ok) && all.matches(fast_err) && all.matches(miss));
+
+        let slow = LiveFilter { min_latency: Some(1000), errors_only: false };
+        assert!(slow.matches(slow_ok));
+        assert!(!slow.matches(fast_err));
+        assert!(!slow.matches(miss));
+
+        let errors = LiveFilter { min_latency: None, errors_only: true };
+        assert!(!errors.matches(slow_ok));
+        assert!(errors.matches(fast_err));
+        assert!(errors.matches(miss));
+
+        assert!(errors.matches("not json"));
+    }
+
+    #[test]
+    fn scope_limits_run_paths() {
+        let frame_tx = FrameSender::default();
+        let state = ViewerState {
+            config: Arc::new(Config::default()),
+            frame_tx,
+            frame_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+            scope: Some(ScopedRun {
+                name: "a.ghostline".into(),
+                path: "/tmp/elsewhere/a.ghostline".into(),
+                frames: Default::default(),
+            }),
+        };
+        assert_eq!(state.run_path("a.ghostline"), Some("/tmp/elsewhere/a.ghostline".into()));
+        assert_eq!(state.run_path("b.ghostline"), None);
+        assert_eq!(state.run_path("../a.ghostline"), None);
+        let state = ViewerState { scope: None, ..state };
+        assert_eq!(state.run_path("proj/a.ghostline"), Some(Config::runs_dir().join("proj").join("a.ghostline")));
+        assert_eq!(state.run_path("proj/../../a.ghostline"), None);
+    }
+}
@@ -0,0 +1,171 @@
+use ghostline_core::GhostlineReader;
+use notify::{RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::banner::info;
+
+/// How long a file must go without filesystem events before we try to export it.
+const DEBOUNCE: Duration = Duration::from_millis(750);
+
+/// Watch `dir` and export every newly finalized `.ghostline` file into `out`
+/// as `format` ("html" or "json"). Runs until interrupted.
+pub fn watch(dir: &str, format: &str, out: &str) -> anyhow::Result<()> {
+    if format != "html" && format != "json" {
+        anyhow::bail!("unknown format '{}' (use html or json)", format);
+    }
+    std::fs::create_dir_all(out)?;
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(Path::new(dir), RecursiveMode::NonRecursive)?;
+
+    info!("ghostline watch");
+    info!("  dir:    {}", dir);
+    info!("  format: {}", format);
+    info!("  out:    {}", out);
+    info!("Ctrl+C to stop.");
+
+    let mut exporter = Exporter::new(format, Path::new(out));
+    loop {
+        match rx.recv_timeout(DEBOUNCE / 3) {
+            Ok(Ok(event)) => {
+                for path in event.paths {
+                    exporter.changed(path, Instant::now());
+                }
+            }
+            Ok(Err(e)) => eprintln!("[watch] error: {}", e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+        for (path, result) in exporter.export_settled(Instant::now()) {
+            match result {
+                Ok(dest) => info!("[watch] exported {} → {}", path.display(), dest.display()),
+                Err(e) => eprintln!("[watch] export failed for {}: {}", path.display(), e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `.ghostline` files seen changing, exported once they have settled.
+struct Exporter<'a> {
+    format: &'a str,
+    out: &'a Path,
+    /// Path → time of last event, for files waiting to settle.
+    pending: HashMap<PathBuf, Instant>,
+    /// Path → mtime at last export, so rewrites are re-exported but repeats aren't.
+    exported: HashMap<PathBuf, SystemTime>,
+}
+
+impl<'a> Exporter<'a> {
+    fn new(format: &'a str, out: &'a Path) -> Self {
+        Self { format, out, pending: HashMap::new(), exported: HashMap::new() }
+    }
+
+    /// Note a filesystem event on `path` at `at`.
+    fn changed(&mut self, path: PathBuf, at: Instant) {
+        if path.extension().map(|e| e == "ghostline").unwrap_or(false) {
+            self.pending.insert(path, at);
+        }
+    }
+
+    /// Export every pending file that has gone `DEBOUNCE` without events by
+    /// `now`, returning each attempt with where it was written.
+    fn export_settled(&mut self, now: Instant) -> Vec<(PathBuf, anyhow::Result<PathBuf>)> {
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, &at)| now.saturating_duration_since(at) >= DEBOUNCE)
+            .map(|(p, _)| p.clone())
+            .collect();
+        let mut attempts = Vec::new();
+        for path in settled {
+            self.pending.remove(&path);
+            let Ok(mtime) = path.metadata().and_then(|m| m.modified()) else {
+                continue; // deleted or moved away
+            };
+            if self.exported.get(&path) == Some(&mtime) {
+                continue;
+            }
+            // A readable index means the writer has finished; otherwise the
+            // next write event will bring the file back here.
+            if GhostlineReader::open(&path).is_err() {
+                continue;
+            }
+            let result = export_one(&path, self.format, self.out);
+            if result.is_ok() {
+                self.exported.insert(path.clone(), mtime);
+            }
+            attempts.push((path, result));
+        }
+        attempts
+    }
+}
+
+fn export_one(path: &Path, format: &str, out: &Path) -> anyhow::Result<PathBuf> {
+    let file = path.to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let dest = out.join(format!("{}.{}", stem, format));
+    let content = match format {
+        "html" => crate::export::render_html(&file)?,
+        _ => crate::export::render_json(&file, crate::export::Selection::All, false)?,
+    };
+    std::fs::write(&dest, content)?;
+    Ok(dest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ghostline_core::{Frame, GhostlineWriter, Header};
+
+    #[test]
+    fn exports_once_finished_and_again_when_frames_are_added() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (path, out) = (tmp.path().join("run.ghostline"), tmp.path().join("out"));
+        std::fs::create_dir(&out).unwrap();
+        let mut exporter = Exporter::new("json", &out);
+        let write = |frames: usize, finish: bool| {
+            let mut writer = GhostlineWriter::new(std::fs::File::create(&path).unwrap(), &Header::default()).unwrap();
+            for i in 0..frames {
+                writer.append(&Frame::new(format!("req {}", i).into_bytes(), b"ok".to_vec(), 1, 0)).unwrap();
+            }
+            if finish {
+                writer.finish().unwrap();
+            }
+        };
+        let settle = |exporter: &mut Exporter| {
+            let at = Instant::now();
+            exporter.changed(path.clone(), at);
+            assert!(exporter.export_settled(at).is_empty(), "exported before settling");
+            exporter.export_settled(at + DEBOUNCE)
+        };
+        let exported_frames = || {
+            let json: serde_json::Value = serde_json::from_slice(&std::fs::read(out.join("run.json")).unwrap()).unwrap();
+            json.as_array().unwrap().len()
+        };
+
+        // Still being written: no index yet, so it is skipped.
+        write(1, false);
+        assert!(settle(&mut exporter).is_empty());
+        assert!(!out.join("run.json").exists());
+
+        write(1, true);
+        let attempts = settle(&mut exporter);
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].1.as_ref().unwrap(), &out.join("run.json"));
+        assert_eq!(exported_frames(), 1);
+        // An event without a change doesn't export it again.
+        assert!(settle(&mut exporter).is_empty());
+
+        // Rewritten with more frames: exported again.
+        write(3, true);
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        assert_eq!(settle(&mut exporter).len(), 1);
+        assert_eq!(exported_frames(), 3);
+    }
+}
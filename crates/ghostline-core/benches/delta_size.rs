@@ -0,0 +1,42 @@
+//! File size of a 100-turn session with and without `FLAG_DELTA_REQUESTS`.
+//! Each turn resends the whole conversation, so only the delta-encoded file
+//! stops paying for the history again on every frame.
+
+use ghostline_core::{Frame, GhostlineWriter, Header, FLAG_DELTA_REQUESTS};
+use std::io::Cursor;
+
+const TURNS: usize = 100;
+
+fn session() -> Vec<Frame> {
+    let mut messages = Vec::new();
+    (0..TURNS)
+        .map(|turn| {
+            let text = format!("Turn {}: {}", turn, "please look at the next file and summarise what changed. ".repeat(6));
+            messages.push(serde_json::json!({"role": "user", "content": text}));
+            let request = serde_json::json!({"model": "claude-sonnet-4-20250514", "max_tokens": 1024, "messages": messages});
+            let reply = format!("Reply {}: {}", turn, "the function now returns early when the cache is warm. ".repeat(5));
+            let response = serde_json::json!({"type": "message", "content": [{"type": "text", "text": reply}]});
+            messages.push(serde_json::json!({"role": "assistant", "content": reply}));
+            Frame::new(serde_json::to_vec(&request).unwrap(), serde_json::to_vec(&response).unwrap(), 100, turn as u64)
+        })
+        .collect()
+}
+
+fn file_size(frames: &[Frame], flags: u32) -> usize {
+    let header = Header { flags, ..Default::default() };
+    let mut writer = GhostlineWriter::new(Cursor::new(Vec::new()), &header).unwrap();
+    for frame in frames {
+        writer.append(frame).unwrap();
+    }
+    writer.finish().unwrap().into_inner().len()
+}
+
+fn main() {
+    let frames = session();
+    let raw: usize = frames.iter().map(|f| f.request_bytes.len() + f.response_bytes.len()).sum();
+    let plain = file_size(&frames, 0);
+    let delta = file_size(&frames, FLAG_DELTA_REQUESTS);
+    println!("{} turns, {:.1} KB of bodies", TURNS, raw as f64 / 1024.0);
+    println!("  plain:            {:>8.1} KB", plain as f64 / 1024.0);
+    println!("  delta requests:   {:>8.1} KB ({:.0}% of plain)", delta as f64 / 1024.0, 100.0 * delta as f64 / plain as f64);
+}
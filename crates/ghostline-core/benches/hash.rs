@@ -0,0 +1,31 @@
+//! Request hashing throughput of each `HashAlgo` over a long session, the
+//! per-frame cost `recording.hash_algo` chooses between: 2,000 requests whose
+//! history grows by 1 KB per request, 2 GB in all.
+
+use ghostline_core::HashAlgo;
+use std::time::{Duration, Instant};
+
+const REQUESTS: usize = 2_000;
+const GROWTH: usize = 1024;
+const ROUNDS: usize = 3;
+
+fn main() {
+    // Each request is a prefix of the final one, as each turn resends the history.
+    let turn = b"{\"role\":\"user\",\"content\":\"next step\"},";
+    let history: Vec<u8> = turn.iter().copied().cycle().take(REQUESTS * GROWTH).collect();
+    let total: usize = (1..=REQUESTS).map(|i| i * GROWTH).sum();
+    println!("{} requests, {:.2} GB (best of {})", REQUESTS, total as f64 / 1e9, ROUNDS);
+
+    for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+        let mut best = Duration::MAX;
+        for _ in 0..ROUNDS {
+            let start = Instant::now();
+            for i in 1..=REQUESTS {
+                std::hint::black_box(algo.hash(&history[..i * GROWTH]));
+            }
+            best = best.min(start.elapsed());
+        }
+        let secs = best.as_secs_f64();
+        println!("  {:<7} {:>6.2} s  {:>5.1} GB/s", format!("{:?}", algo), secs, total as f64 / 1e9 / secs);
+    }
+}
@@ -0,0 +1,66 @@
+//! `GhostlineReader::scan` against the `get_frame` loop it replaced in export
+//! and stats: many small frames, where the per-frame seek dominates, and
+//! fewer large ones, where decoding does.
+
+use ghostline_core::{Frame, GhostlineReader, GhostlineWriter, Header};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 3;
+
+fn best_of(mut run: impl FnMut() -> usize) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut total = 0;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        total = run();
+        best = best.min(start.elapsed());
+    }
+    (best, total)
+}
+
+fn write_recording(path: &Path, frames: usize, body_bytes: usize) {
+    let file = std::io::BufWriter::new(std::fs::File::create(path).unwrap());
+    let mut writer = GhostlineWriter::new(file, &Header::default()).unwrap();
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    for i in 0..frames {
+        // Incompressible (xorshift) bodies, so the file is about frames * body_bytes.
+        let body: Vec<u8> = (0..body_bytes)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        writer.append(&Frame::new(format!("request {}", i).into_bytes(), body, 10, i as u64)).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+fn compare(frames: usize, body_bytes: usize) {
+    let path = std::env::temp_dir().join(format!("ghostline-bench-scan-{}.ghostline", std::process::id()));
+    write_recording(&path, frames, body_bytes);
+    let size = std::fs::metadata(&path).unwrap().len();
+    println!("{} frames of {} B, {:.1} MB file (best of {})", frames, body_bytes, size as f64 / (1024.0 * 1024.0), ROUNDS);
+
+    let (get_frame, a) = best_of(|| {
+        let mut reader = GhostlineReader::open(&path).unwrap();
+        (0..reader.frame_count()).map(|i| reader.get_frame(i).unwrap().response_bytes.len()).sum()
+    });
+    let (scan, b) = best_of(|| {
+        let mut reader = GhostlineReader::open(&path).unwrap();
+        let mut total = 0;
+        reader.scan(|_, frame| total += frame.response_bytes.len()).unwrap();
+        total
+    });
+    assert_eq!(a, b);
+    println!("  get_frame loop: {:>8.1} ms", get_frame.as_secs_f64() * 1000.0);
+    println!("  scan:           {:>8.1} ms ({:.2}x)", scan.as_secs_f64() * 1000.0, get_frame.as_secs_f64() / scan.as_secs_f64());
+    let _ = std::fs::remove_file(&path);
+}
+
+fn main() {
+    compare(200_000, 64);
+    compare(5_000, 16 * 1024);
+}
@@ -0,0 +1,112 @@
+//! Request delta encoding, used when a file's header sets
+//! [`FLAG_DELTA_REQUESTS`](crate::FLAG_DELTA_REQUESTS).
+//!
+//! Agents resend the whole conversation every turn, so consecutive requests
+//! share a long prefix (earlier messages) and usually a long suffix (system
+//! prompt, tool definitions). A stored request is either the full body or the
+//! bytes that differ from the previous request:
+//!
+//! ```text
+//! full:  [0x00] [request bytes]
+//! delta: [0x01] [prefix_len: u32 LE] [suffix_len: u32 LE] [middle bytes]
+//! ```
+//!
+//! Reconstruction is `prev[..prefix_len] + middle + prev[prev.len() - suffix_len..]`,
+//! which is byte-exact, so `request_hash` still verifies.
+
+use std::io;
+
+const TAG_FULL: u8 = 0;
+const TAG_DELTA: u8 = 1;
+
+/// Encode `cur`, as a delta against `prev` when given, otherwise in full.
+pub(crate) fn encode(prev: Option<&[u8]>, cur: &[u8]) -> Vec<u8> {
+    let Some(prev) = prev else {
+        let mut out = Vec::with_capacity(cur.len() + 1);
+        out.push(TAG_FULL);
+        out.extend_from_slice(cur);
+        return out;
+    };
+
+    let prefix = prev.iter().zip(cur).take_while(|(a, b)| a == b).count();
+    let max_suffix = prev.len().min(cur.len()) - prefix;
+    let suffix = prev
+        .iter()
+        .rev()
+        .zip(cur.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let middle = &cur[prefix..cur.len() - suffix];
+    let mut out = Vec::with_capacity(middle.len() + 9);
+    out.push(TAG_DELTA);
+    out.extend_from_slice(&(prefix as u32).to_le_bytes());
+    out.extend_from_slice(&(suffix as u32).to_le_bytes());
+    out.extend_from_slice(middle);
+    out
+}
+
+/// Whether a stored request is a full snapshot (needs no previous request).
+pub(crate) fn is_full(stored: &[u8]) -> bool {
+    stored.first() == Some(&TAG_FULL)
+}
+
+/// Rebuild the original request from its stored form.
+pub(crate) fn decode(stored: &[u8], prev: Option<&[u8]>) -> io::Result<Vec<u8>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    match stored.first() {
+        Some(&TAG_FULL) => Ok(stored[1..].to_vec()),
+        Some(&TAG_DELTA) => {
+            if stored.len() < 9 {
+                return Err(invalid("truncated request delta"));
+            }
+            let prefix = u32::from_le_bytes(stored[1..5].try_into().unwrap()) as usize;
+            let suffix = u32::from_le_bytes(stored[5..9].try_into().unwrap()) as usize;
+            let prev = prev.ok_or_else(|| invalid("request delta without a base"))?;
+            if prefix + suffix > prev.len() {
+                return Err(invalid("request delta exceeds its base"));
+            }
+            let middle = &stored[9..];
+            let mut out = Vec::with_capacity(prefix + middle.len() + suffix);
+            out.extend_from_slice(&prev[..prefix]);
+            out.extend_from_slice(middle);
+            out.extend_from_slice(&prev[prev.len() - suffix..]);
+            Ok(out)
+        }
+        _ => Err(invalid("unknown request encoding")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_growing_conversation() {
+        let a = br#"{"messages":[{"role":"user","content":"hi"}],"system":"be brief"}"#;
+        let b = br#"{"messages":[{"role":"user","content":"hi"},{"role":"assistant","content":"hello"}],"system":"be brief"}"#;
+        let stored = encode(Some(a), b);
+        assert!(!is_full(&stored));
+        assert!(stored.len() < 50);
+        assert_eq!(decode(&stored, Some(a)).unwrap(), b);
+    }
+
+    #[test]
+    fn roundtrip_edge_cases() {
+        let cases: [(&[u8], &[u8]); 5] = [
+            (b"", b"abc"),
+            (b"abc", b""),
+            (b"aaaa", b"aa"),
+            (b"aa", b"aaaa"),
+            (b"xyz", b"abc"),
+        ];
+        for (prev, cur) in cases {
+            assert_eq!(decode(&encode(Some(prev), cur), Some(prev)).unwrap(), cur);
+        }
+        let full = encode(None, b"abc");
+        assert!(is_full(&full));
+        assert_eq!(decode(&full, None).unwrap(), b"abc");
+        assert!(decode(&encode(Some(b"abc"), b"abd"), None).is_err());
+    }
+}
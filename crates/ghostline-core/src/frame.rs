@@ -1,10 +1,51 @@
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeStruct, Serializer};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
+use crate::hash::HashAlgo;
+use std::fmt;
+
+/// HTTP protocol version of a recorded exchange (diagnostic only).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HttpVersion {
+    #[serde(rename = "HTTP/0.9")]
+    Http09,
+    #[serde(rename = "HTTP/1.0")]
+    Http10,
+    #[serde(rename = "HTTP/1.1")]
+    Http11,
+    #[serde(rename = "HTTP/2.0")]
+    Http2,
+    #[serde(rename = "HTTP/3.0")]
+    Http3,
+}
+
+impl HttpVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HttpVersion::Http09 => "HTTP/0.9",
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2.0",
+            HttpVersion::Http3 => "HTTP/3.0",
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 /// A single captured request/response pair.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Deserialization ignores fields it doesn't know (trailing array elements or
+/// unknown map keys), so frames written by a newer minor format version that
+/// add optional fields stay readable.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Frame {
-    /// SHA-256 hash of the request (model + messages + params, excluding timestamps).
+    /// Hash of the request body with the file's [`HashAlgo`] (SHA-256 unless
+    /// the header says otherwise).
     pub request_hash: [u8; 32],
     /// Raw request bytes (MessagePack-encoded).
     pub request_bytes: Vec<u8>,
@@ -14,6 +55,368 @@ pub struct Frame {
     pub latency_ms: u64,
     /// Unix timestamp (milliseconds) when the frame was captured.
     pub timestamp: u64,
+    /// Free-form labels attached after capture (e.g. "bug-starts-here").
+    /// Omitted from the encoding when empty (and no later field is set), so
+    /// untagged frames stay readable by older versions.
+    pub tags: Vec<String>,
+    /// HTTP version the client used for the request (0.5+).
+    pub http_version: Option<HttpVersion>,
+    /// HTTP version of the upstream response (0.5+).
+    pub upstream_http_version: Option<HttpVersion>,
+    /// A body was cut to the recording's `max_body_bytes` (0.6+). The request
+    /// hash is still of the full request, so replay keys are unaffected.
+    pub truncated: bool,
+    /// Full request length, when the request was truncated (0.6+).
+    pub original_request_len: Option<u64>,
+    /// Full response length, when the response was truncated (0.6+).
+    pub original_response_len: Option<u64>,
+    /// Features the client enabled with `anthropic-beta` headers, in header
+    /// order (0.7+). Metadata only; not part of the request hash.
+    pub anthropic_betas: Vec<String>,
+    /// Base URL of the upstream that answered, when the proxy routes between
+    /// several targets (0.9+). Unset for single-target recordings.
+    pub upstream: Option<String>,
+    /// HTTP status of the recorded response (0.10+). Replay serves it, and
+    /// treats an unset status as 200.
+    pub status: Option<u16>,
+    /// The upstream's `content-length` for the response, when it sent one
+    /// (0.11+). Lets replay and `verify` catch a body that was cut short.
+    pub original_content_length: Option<u64>,
+    /// Request headers, `(name, value)` in order, from whichever side of the
+    /// proxy `recording.capture` names (0.14+), with credentials redacted.
+    /// Metadata only; not part of the request hash.
+    pub request_headers: Vec<(String, String)>,
+}
+
+/// Chained construction of a [`Frame`], so call sites only name the fields
+/// they set. [`build`](Self::build) hashes the request with
+/// [`hash_algo`](Self::hash_algo) (SHA-256 by default) unless a hash was given.
+#[derive(Debug, Clone, Default)]
+pub struct FrameBuilder {
+    request_hash: Option<[u8; 32]>,
+    hash_algo: HashAlgo,
+    request_bytes: Vec<u8>,
+    response_bytes: Vec<u8>,
+    latency_ms: u64,
+    timestamp: u64,
+    tags: Vec<String>,
+    http_version: Option<HttpVersion>,
+    upstream_http_version: Option<HttpVersion>,
+    anthropic_betas: Vec<String>,
+    upstream: Option<String>,
+    status: Option<u16>,
+    original_content_length: Option<u64>,
+    request_headers: Vec<(String, String)>,
+}
+
+impl FrameBuilder {
+    /// Takes any owned buffer: a `Vec`, or a `bytes::Bytes` that is moved in
+    /// without a copy when nothing else shares it.
+    pub fn request(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.request_bytes = bytes.into();
+        self
+    }
+
+    pub fn response(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.response_bytes = bytes.into();
+        self
+    }
+
+    /// Use an already-computed request hash, e.g. one hashed while streaming.
+    pub fn request_hash(mut self, hash: [u8; 32]) -> Self {
+        self.request_hash = Some(hash);
+        self
+    }
+
+    /// The algorithm of the file the frame is for, when `build` hashes the
+    /// request; a BLAKE3 recording needs BLAKE3 hashes for lookups to match.
+    pub fn hash_algo(mut self, algo: HashAlgo) -> Self {
+        self.hash_algo = algo;
+        self
+    }
+
+    pub fn latency_ms(mut self, latency_ms: u64) -> Self {
+        self.latency_ms = latency_ms;
+        self
+    }
+
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn http_version(mut self, version: Option<HttpVersion>) -> Self {
+        self.http_version = version;
+        self
+    }
+
+    pub fn upstream_http_version(mut self, version: Option<HttpVersion>) -> Self {
+        self.upstream_http_version = version;
+        self
+    }
+
+    pub fn anthropic_betas(mut self, betas: Vec<String>) -> Self {
+        self.anthropic_betas = betas;
+        self
+    }
+
+    pub fn upstream(mut self, upstream: Option<String>) -> Self {
+        self.upstream = upstream;
+        self
+    }
+
+    pub fn status(mut self, status: Option<u16>) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn original_content_length(mut self, length: Option<u64>) -> Self {
+        self.original_content_length = length;
+        self
+    }
+
+    pub fn request_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.request_headers = headers;
+        self
+    }
+
+    pub fn build(self) -> Frame {
+        Frame {
+            request_hash: self
+                .request_hash
+                .unwrap_or_else(|| self.hash_algo.hash(&self.request_bytes)),
+            request_bytes: self.request_bytes,
+            response_bytes: self.response_bytes,
+            latency_ms: self.latency_ms,
+            timestamp: self.timestamp,
+            tags: self.tags,
+            http_version: self.http_version,
+            upstream_http_version: self.upstream_http_version,
+            truncated: false,
+            original_request_len: None,
+            original_response_len: None,
+            anthropic_betas: self.anthropic_betas,
+            upstream: self.upstream,
+            status: self.status,
+            original_content_length: self.original_content_length,
+            request_headers: self.request_headers,
+        }
+    }
+}
+
+/// The JSON-facing summary of a frame: sizes and metadata, no bodies.
+///
+/// Every surface that lists frames (live feed, viewer API, JSON export) starts
+/// from this, so a field added here shows up in all of them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameSummary {
+    /// Position of the frame in its file.
+    pub index: usize,
+    /// Hex-encoded `request_hash`.
+    pub request_hash: String,
+    /// Capture time in milliseconds (Unix epoch, or from the first frame
+    /// with the monotonic clock).
+    pub timestamp: u64,
+    pub latency_ms: u64,
+    pub request_size: usize,
+    pub response_size: usize,
+    pub tags: Vec<String>,
+    pub truncated: bool,
+    pub http_version: Option<HttpVersion>,
+    pub upstream_http_version: Option<HttpVersion>,
+    /// Recorded HTTP status, when the file has one (0.10+).
+    pub status: Option<u16>,
+    /// [`Frame::is_error`].
+    pub is_error: bool,
+}
+
+const FIELDS: &[&str] = &[
+    "request_hash",
+    "request_bytes",
+    "response_bytes",
+    "latency_ms",
+    "timestamp",
+    "tags",
+    "http_version",
+    "upstream_http_version",
+    "truncated",
+    "original_request_len",
+    "original_response_len",
+    "anthropic_betas",
+    "upstream",
+    "status",
+    "original_content_length",
+    "request_headers",
+];
+
+impl Serialize for Frame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WithRequest(self, &self.request_bytes).serialize(serializer)
+    }
+}
+
+/// A frame encoded with another request body, so writers can store a delta
+/// in its place without copying the frame.
+struct WithRequest<'a>(&'a Frame, &'a [u8]);
+
+impl Serialize for WithRequest<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let WithRequest(frame, request) = *self;
+        // Optional fields trail the required five. Frames are array-encoded, so
+        // a field can't be skipped from the middle: write every optional field
+        // up to the last one that is set, and none after it.
+        let optional = [
+            !frame.tags.is_empty(),
+            frame.http_version.is_some(),
+            frame.upstream_http_version.is_some(),
+            frame.truncated,
+            frame.original_request_len.is_some(),
+            frame.original_response_len.is_some(),
+            !frame.anthropic_betas.is_empty(),
+            frame.upstream.is_some(),
+            frame.status.is_some(),
+            frame.original_content_length.is_some(),
+            !frame.request_headers.is_empty(),
+        ]
+        .iter()
+        .rposition(|&set| set)
+        .map_or(0, |i| i + 1);
+        let mut s = serializer.serialize_struct("Frame", 5 + optional)?;
+        s.serialize_field("request_hash", &frame.request_hash)?;
+        s.serialize_field("request_bytes", request)?;
+        s.serialize_field("response_bytes", &frame.response_bytes)?;
+        s.serialize_field("latency_ms", &frame.latency_ms)?;
+        s.serialize_field("timestamp", &frame.timestamp)?;
+        if optional >= 1 {
+            s.serialize_field("tags", &frame.tags)?;
+        }
+        if optional >= 2 {
+            s.serialize_field("http_version", &frame.http_version)?;
+        }
+        if optional >= 3 {
+            s.serialize_field("upstream_http_version", &frame.upstream_http_version)?;
+        }
+        if optional >= 4 {
+            s.serialize_field("truncated", &frame.truncated)?;
+        }
+        if optional >= 5 {
+            s.serialize_field("original_request_len", &frame.original_request_len)?;
+        }
+        if optional >= 6 {
+            s.serialize_field("original_response_len", &frame.original_response_len)?;
+        }
+        if optional >= 7 {
+            s.serialize_field("anthropic_betas", &frame.anthropic_betas)?;
+        }
+        if optional >= 8 {
+            s.serialize_field("upstream", &frame.upstream)?;
+        }
+        if optional >= 9 {
+            s.serialize_field("status", &frame.status)?;
+        }
+        if optional >= 10 {
+            s.serialize_field("original_content_length", &frame.original_content_length)?;
+        }
+        if optional >= 11 {
+            s.serialize_field("request_headers", &frame.request_headers)?;
+        }
+        s.end()
+    }
+}
+
+struct FrameVisitor;
+
+impl<'de> Visitor<'de> for FrameVisitor {
+    type Value = Frame;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a ghostline frame")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frame, A::Error> {
+        let missing = |i| de::Error::invalid_length(i, &"at least 5 frame fields");
+        let frame = Frame {
+            request_hash: seq.next_element()?.ok_or_else(|| missing(0))?,
+            request_bytes: seq.next_element()?.ok_or_else(|| missing(1))?,
+            response_bytes: seq.next_element()?.ok_or_else(|| missing(2))?,
+            latency_ms: seq.next_element()?.ok_or_else(|| missing(3))?,
+            timestamp: seq.next_element()?.ok_or_else(|| missing(4))?,
+            tags: seq.next_element()?.unwrap_or_default(),
+            http_version: seq.next_element()?.flatten(),
+            upstream_http_version: seq.next_element()?.flatten(),
+            truncated: seq.next_element()?.unwrap_or_default(),
+            original_request_len: seq.next_element()?.flatten(),
+            original_response_len: seq.next_element()?.flatten(),
+            anthropic_betas: seq.next_element()?.unwrap_or_default(),
+            upstream: seq.next_element()?.flatten(),
+            status: seq.next_element()?.flatten(),
+            original_content_length: seq.next_element()?.flatten(),
+            request_headers: seq.next_element()?.unwrap_or_default(),
+        };
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(frame)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frame, A::Error> {
+        let (mut request_hash, mut request_bytes, mut response_bytes) = (None, None, None);
+        let (mut latency_ms, mut timestamp, mut tags) = (None, None, None);
+        let (mut http_version, mut upstream_http_version) = (None, None);
+        let (mut truncated, mut original_request_len, mut original_response_len) = (None, None, None);
+        let (mut anthropic_betas, mut upstream, mut status) = (None, None, None);
+        let (mut original_content_length, mut request_headers) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "request_hash" => request_hash = Some(map.next_value()?),
+                "request_bytes" => request_bytes = Some(map.next_value()?),
+                "response_bytes" => response_bytes = Some(map.next_value()?),
+                "latency_ms" => latency_ms = Some(map.next_value()?),
+                "timestamp" => timestamp = Some(map.next_value()?),
+                "tags" => tags = Some(map.next_value()?),
+                "http_version" => http_version = map.next_value()?,
+                "upstream_http_version" => upstream_http_version = map.next_value()?,
+                "truncated" => truncated = Some(map.next_value()?),
+                "original_request_len" => original_request_len = map.next_value()?,
+                "original_response_len" => original_response_len = map.next_value()?,
+                "anthropic_betas" => anthropic_betas = Some(map.next_value()?),
+                "upstream" => upstream = map.next_value()?,
+                "status" => status = map.next_value()?,
+                "original_content_length" => original_content_length = map.next_value()?,
+                "request_headers" => request_headers = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(Frame {
+            request_hash: request_hash.ok_or_else(|| de::Error::missing_field("request_hash"))?,
+            request_bytes: request_bytes.ok_or_else(|| de::Error::missing_field("request_bytes"))?,
+            response_bytes: response_bytes.ok_or_else(|| de::Error::missing_field("response_bytes"))?,
+            latency_ms: latency_ms.ok_or_else(|| de::Error::missing_field("latency_ms"))?,
+            timestamp: timestamp.ok_or_else(|| de::Error::missing_field("timestamp"))?,
+            tags: tags.unwrap_or_default(),
+            http_version,
+            upstream_http_version,
+            truncated: truncated.unwrap_or_default(),
+            original_request_len,
+            original_response_len,
+            anthropic_betas: anthropic_betas.unwrap_or_default(),
+            upstream,
+            status,
+            original_content_length,
+            request_headers: request_headers.unwrap_or_default(),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Frame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_struct("Frame", FIELDS, FrameVisitor)
+    }
 }
 
 impl Frame {
@@ -24,21 +427,155 @@ impl Frame {
         latency_ms: u64,
         timestamp: u64,
     ) -> Self {
-        let request_hash = Self::hash_request(&request_bytes);
-        Self {
-            request_hash,
-            request_bytes,
-            response_bytes,
-            latency_ms,
-            timestamp,
+        Self::builder()
+            .request(request_bytes)
+            .response(response_bytes)
+            .latency_ms(latency_ms)
+            .timestamp(timestamp)
+            .build()
+    }
+
+    pub fn builder() -> FrameBuilder {
+        FrameBuilder::default()
+    }
+
+    /// Cut request and response bodies longer than `max` bytes, recording the
+    /// original lengths. The request hash is left as computed from the full
+    /// request. Returns whether anything was cut.
+    pub fn truncate_bodies(&mut self, max: usize) -> bool {
+        if self.request_bytes.len() > max {
+            self.original_request_len = Some(self.request_bytes.len() as u64);
+            self.request_bytes.truncate(max);
+            self.truncated = true;
+        }
+        if self.response_bytes.len() > max {
+            self.original_response_len = Some(self.response_bytes.len() as u64);
+            self.response_bytes.truncate(max);
+            self.truncated = true;
         }
+        self.truncated
     }
 
-    /// Compute SHA-256 hash of raw request bytes.
+    /// Keep no request body, only its original length, as a body truncated to
+    /// nothing (`recording.capture_request_body = false`). The hash is kept.
+    pub fn drop_request_body(&mut self) {
+        if !self.request_bytes.is_empty() {
+            self.original_request_len.get_or_insert(self.request_bytes.len() as u64);
+            self.request_bytes.clear();
+            self.truncated = true;
+        }
+    }
+
+    /// As [`drop_request_body`](Self::drop_request_body), for the response.
+    pub fn drop_response_body(&mut self) {
+        if !self.response_bytes.is_empty() {
+            self.original_response_len.get_or_insert(self.response_bytes.len() as u64);
+            self.response_bytes.clear();
+            self.truncated = true;
+        }
+    }
+
+    /// The upstream answered with a body but none was kept, so there is
+    /// nothing to replay.
+    pub fn response_dropped(&self) -> bool {
+        self.response_bytes.is_empty() && self.original_response_len.is_some_and(|n| n > 0)
+    }
+
+    /// The upstream's declared `content-length` and the response length
+    /// actually recorded, when they disagree. A body cut by
+    /// [`truncate_bodies`](Self::truncate_bodies) is compared at its full length.
+    pub fn response_length_mismatch(&self) -> Option<(u64, u64)> {
+        let declared = self.original_content_length?;
+        let recorded = self.original_response_len.unwrap_or(self.response_bytes.len() as u64);
+        (declared != recorded).then_some((declared, recorded))
+    }
+
+    /// Summary of this frame as the `index`th of its file.
+    pub fn summary(&self, index: usize) -> FrameSummary {
+        FrameSummary {
+            index,
+            request_hash: self.request_hash.iter().map(|b| format!("{:02x}", b)).collect(),
+            timestamp: self.timestamp,
+            latency_ms: self.latency_ms,
+            request_size: self.request_bytes.len(),
+            response_size: self.response_bytes.len(),
+            tags: self.tags.clone(),
+            truncated: self.truncated,
+            http_version: self.http_version,
+            upstream_http_version: self.upstream_http_version,
+            status: self.status,
+            is_error: self.is_error(),
+        }
+    }
+
+    /// Whether the request failed: a recorded status of 400 or more, or, for
+    /// frames from before statuses were stored, a JSON response with a
+    /// top-level `error` key (the shape of Anthropic and OpenAI error bodies).
+    pub fn is_error(&self) -> bool {
+        match self.status {
+            Some(status) => status >= 400,
+            None => self.response_json().is_some_and(|body| body.get("error").is_some()),
+        }
+    }
+
+    /// Whether the two frames captured the same exchange: same request hash and
+    /// bodies, whatever their timestamps and latencies. For golden tests of
+    /// recordings, where timing differs run to run. Metadata such as tags and
+    /// status isn't compared; `with_zeroed_timing` and `==` compare it too.
+    pub fn content_eq(&self, other: &Frame) -> bool {
+        self.request_hash == other.request_hash
+            && self.request_bytes == other.request_bytes
+            && self.response_bytes == other.response_bytes
+    }
+
+    /// A copy with `timestamp` and `latency_ms` set to zero, so recorded
+    /// frames can be compared with `==` against expected ones.
+    pub fn with_zeroed_timing(&self) -> Frame {
+        Frame { timestamp: 0, latency_ms: 0, ..self.clone() }
+    }
+
+    /// Compute SHA-256 hash of raw request bytes, the default
+    /// [`HashAlgo`]. A file's own algorithm is [`GhostlineReader::hash_algo`](crate::GhostlineReader::hash_algo).
     pub fn hash_request(data: &[u8]) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        hasher.update(data);
-        hasher.finalize().into()
+        HashAlgo::Sha256.hash(data)
+    }
+
+    /// Request hash that ignores encoding noise. A `multipart/form-data` body
+    /// (going by `content_type`) hashes its part names, filenames, content
+    /// types and content hashes, so the random boundary doesn't matter; any
+    /// other body, or one that doesn't parse, hashes as [`hash_request`](Self::hash_request).
+    /// SHA-256; see [`HashAlgo::hash_normalized`] for the others.
+    pub fn hash_normalized(content_type: Option<&str>, body: &[u8]) -> [u8; 32] {
+        HashAlgo::Sha256.hash_normalized(content_type, body)
+    }
+
+    /// Whether `request_hash` matches `request_bytes` under `algo` (the
+    /// file's), raw or, for a multipart upload, normalized. The content type
+    /// isn't stored, so the boundary is taken from the body's first line.
+    pub fn request_hash_matches(&self, algo: HashAlgo) -> bool {
+        algo.hash(&self.request_bytes) == self.request_hash
+            || crate::multipart::sniff_boundary(&self.request_bytes)
+                .and_then(|b| crate::multipart::canonical_hash(algo, &self.request_bytes, b))
+                == Some(self.request_hash)
+    }
+
+    /// The request body as JSON, if it is JSON or MessagePack.
+    pub fn request_json(&self) -> Option<serde_json::Value> {
+        decode_json(&self.request_bytes)
+    }
+
+    /// The response body as JSON, if it is JSON or MessagePack.
+    pub fn response_json(&self) -> Option<serde_json::Value> {
+        decode_json(&self.response_bytes)
+    }
+
+    /// For a streamed Messages API response, the final message its events add
+    /// up to: text, thinking and tool input deltas folded into their content
+    /// blocks, with `stop_reason` and `usage` from `message_delta`. An
+    /// interrupted stream gives what had arrived. `None` if the response
+    /// isn't such a stream; use [`response_json`](Self::response_json) then.
+    pub fn reconstruct_message(&self) -> Option<serde_json::Value> {
+        crate::stream::reconstruct_message(&self.response_bytes)
     }
 
     /// Serialize this frame to MessagePack bytes.
@@ -46,12 +583,28 @@ impl Frame {
         rmp_serde::to_vec(self)
     }
 
+    /// [`to_msgpack`](Self::to_msgpack) with `request` stored as the request body.
+    pub(crate) fn to_msgpack_with_request(&self, request: &[u8]) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec(&WithRequest(self, request))
+    }
+
     /// Deserialize a frame from MessagePack bytes.
     pub fn from_msgpack(data: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
         rmp_serde::from_slice(data)
     }
 }
 
+/// JSON first, then MessagePack. Only unrecognized binary is tried as
+/// MessagePack; a gzip or image body would "decode" as a single small integer.
+fn decode_json(data: &[u8]) -> Option<serde_json::Value> {
+    if let Ok(value) = serde_json::from_slice(data) {
+        return Some(value);
+    }
+    (crate::sniff::sniff_content_type(data) == "application/octet-stream")
+        .then(|| rmp_serde::from_slice(data).ok())
+        .flatten()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,6 +626,181 @@ mod tests {
         assert_eq!(frame.timestamp, unpacked.timestamp);
     }
 
+    #[test]
+    fn bodies_decode_as_json_or_msgpack() {
+        let packed = rmp_serde::to_vec_named(&serde_json::json!({"ok": true})).unwrap();
+        let frame = Frame::new(br#"{"model":"m"}"#.to_vec(), packed, 1, 0);
+        assert_eq!(frame.request_json().unwrap()["model"], "m");
+        assert_eq!(frame.response_json().unwrap()["ok"], true);
+
+        let binary = Frame::new(b"plain text".to_vec(), vec![0x1f, 0x8b, 0x08, 0x00], 1, 0);
+        assert_eq!(binary.request_json(), None);
+        assert_eq!(binary.response_json(), None);
+    }
+
+    #[test]
+    fn response_length_mismatch_uses_full_length() {
+        let frame = |body: &[u8], declared| {
+            Frame::builder().response(body.to_vec()).original_content_length(declared).build()
+        };
+        assert_eq!(frame(b"short", Some(10)).response_length_mismatch(), Some((10, 5)));
+        assert_eq!(frame(b"exact", Some(5)).response_length_mismatch(), None);
+        assert_eq!(frame(b"unknown", None).response_length_mismatch(), None);
+
+        let mut cut = frame(b"0123456789", Some(10));
+        cut.truncate_bodies(4);
+        assert_eq!(cut.response_length_mismatch(), None);
+        let roundtrip = Frame::from_msgpack(&cut.to_msgpack().unwrap()).unwrap();
+        assert_eq!(roundtrip.original_content_length, Some(10));
+    }
+
+    #[test]
+    fn tags_roundtrip_and_default() {
+        let mut frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 2);
+        let untagged = frame.to_msgpack().unwrap();
+        assert!(Frame::from_msgpack(&untagged).unwrap().tags.is_empty());
+
+        frame.tags = vec!["bug".to_string(), "retry".to_string()];
+        let tagged = Frame::from_msgpack(&frame.to_msgpack().unwrap()).unwrap();
+        assert_eq!(tagged.tags, frame.tags);
+        assert!(frame.to_msgpack().unwrap().len() > untagged.len());
+    }
+
+    #[test]
+    fn builder_hashes_unless_given_a_hash() {
+        let frame = Frame::builder()
+            .request(b"req".to_vec())
+            .response(b"res".to_vec())
+            .latency_ms(7)
+            .tags(vec!["t".into()])
+            .upstream_http_version(Some(HttpVersion::Http2))
+            .build();
+        assert_eq!(frame.request_hash, Frame::hash_request(b"req"));
+        assert_eq!((frame.latency_ms, frame.tags.len()), (7, 1));
+        assert_eq!(frame.upstream_http_version, Some(HttpVersion::Http2));
+
+        let given = Frame::builder().request(b"req".to_vec()).request_hash([9; 32]).build();
+        assert_eq!(given.request_hash, [9; 32]);
+
+        let blake3 = Frame::builder().request(b"req".to_vec()).hash_algo(HashAlgo::Blake3).build();
+        assert_eq!(blake3.request_hash, HashAlgo::Blake3.hash(b"req"));
+        assert!(blake3.request_hash_matches(HashAlgo::Blake3));
+    }
+
+    #[test]
+    fn http_versions_keep_field_positions() {
+        let mut frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 2);
+        frame.upstream_http_version = Some(HttpVersion::Http2);
+        let decoded = Frame::from_msgpack(&frame.to_msgpack().unwrap()).unwrap();
+        assert!(decoded.tags.is_empty());
+        assert_eq!(decoded.http_version, None);
+        assert_eq!(decoded.upstream_http_version, Some(HttpVersion::Http2));
+
+        frame.http_version = Some(HttpVersion::Http11);
+        frame.tags = vec!["t".into()];
+        let decoded = Frame::from_msgpack(&frame.to_msgpack().unwrap()).unwrap();
+        assert_eq!(decoded.tags, vec!["t"]);
+        assert_eq!(decoded.http_version, Some(HttpVersion::Http11));
+
+        let plain = Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 2);
+        let old_layout = rmp_serde::to_vec(&(
+            plain.request_hash,
+            &plain.request_bytes,
+            &plain.response_bytes,
+            1u64,
+            2u64,
+        ))
+        .unwrap();
+        assert_eq!(plain.to_msgpack().unwrap(), old_layout);
+    }
+
+    #[test]
+    fn truncation_keeps_hash_and_lengths() {
+        let mut frame = Frame::new(vec![b'q'; 100], b"short".to_vec(), 1, 2);
+        let hash = frame.request_hash;
+        assert!(frame.truncate_bodies(10));
+        assert_eq!(frame.request_bytes.len(), 10);
+        assert_eq!(frame.request_hash, hash);
+        assert_eq!(frame.original_request_len, Some(100));
+        assert_eq!(frame.original_response_len, None);
+
+        let decoded = Frame::from_msgpack(&frame.to_msgpack().unwrap()).unwrap();
+        assert!(decoded.truncated);
+        assert_eq!(decoded.original_request_len, Some(100));
+        assert_eq!(decoded.response_bytes, b"short");
+
+        assert!(!Frame::new(b"a".to_vec(), b"b".to_vec(), 1, 2).truncate_bodies(10));
+    }
+
+    #[test]
+    fn dropped_bodies_keep_hash_and_lengths() {
+        let mut frame = Frame::new(vec![b'q'; 100], vec![b'r'; 40], 1, 2);
+        let hash = frame.request_hash;
+        frame.truncate_bodies(30);
+        frame.drop_response_body();
+        assert!(frame.response_dropped());
+        assert_eq!((frame.response_bytes.len(), frame.original_response_len), (0, Some(40)));
+        frame.drop_request_body();
+        assert_eq!((frame.request_bytes.len(), frame.original_request_len), (0, Some(100)));
+        assert_eq!(frame.request_hash, hash);
+
+        let mut empty = Frame::new(b"q".to_vec(), Vec::new(), 1, 2);
+        empty.drop_response_body();
+        assert!(!empty.response_dropped() && !empty.truncated);
+    }
+
+    #[test]
+    fn ignores_unknown_trailing_fields() {
+        let frame = Frame::builder()
+            .request(b"req".to_vec())
+            .response(b"res".to_vec())
+            .latency_ms(1)
+            .timestamp(2)
+            .tags(vec!["t".into()])
+            .http_version(Some(HttpVersion::Http11))
+            .anthropic_betas(vec!["files-api-2025-04-14".into()])
+            .upstream(Some("https://api.openai.com".into()))
+            .status(Some(429))
+            .request_headers(vec![("x-app".into(), "ghostline".into())])
+            .build();
+        // Every field this version knows, as a 16-element array, then one more
+        // from a later minor version.
+        let mut newer = frame.to_msgpack().unwrap();
+        assert_eq!(newer[..3], [0xdc, 0, 16]);
+        newer[2] = 17;
+        newer.extend(rmp_serde::to_vec("field from a later minor version").unwrap());
+        let decoded = Frame::from_msgpack(&newer).unwrap();
+        assert_eq!(decoded, frame);
+
+        let short = rmp_serde::to_vec(&(frame.request_hash, &frame.request_bytes)).unwrap();
+        assert!(Frame::from_msgpack(&short).is_err());
+    }
+
+    #[test]
+    fn summary_json_shape() {
+        let mut frame = Frame::new(b"req".to_vec(), b"response".to_vec(), 12, 1700000000000);
+        frame.tags = vec!["bug".into()];
+        frame.upstream_http_version = Some(HttpVersion::Http2);
+        let json = serde_json::to_value(frame.summary(3)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "index": 3,
+                "request_hash": "c3f7bdf537c46724392c4428e47e04c148c56966190c3c9ed92114800c9f35bb",
+                "timestamp": 1700000000000u64,
+                "latency_ms": 12,
+                "request_size": 3,
+                "response_size": 8,
+                "tags": ["bug"],
+                "truncated": false,
+                "http_version": null,
+                "upstream_http_version": "HTTP/2.0",
+                "status": null,
+                "is_error": false,
+            })
+        );
+    }
+
     #[test]
     fn deterministic_hash() {
         let data = b"same input";
@@ -83,4 +811,37 @@ mod tests {
         let h3 = Frame::hash_request(b"different input");
         assert_ne!(h1, h3);
     }
+
+    #[test]
+    fn multipart_hash_ignores_boundary() {
+        let body = |b: &str| format!("--{b}\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\ndata\r\n--{b}--\r\n");
+        let a = Frame::hash_normalized(Some("multipart/form-data; boundary=aaa"), body("aaa").as_bytes());
+        let b = Frame::hash_normalized(Some("multipart/form-data; boundary=bbb"), body("bbb").as_bytes());
+        assert_eq!(a, b);
+        assert_eq!(Frame::hash_normalized(Some("application/json"), b"{}"), Frame::hash_request(b"{}"));
+
+        let frame = Frame::builder().request(body("aaa").into_bytes()).request_hash(a).build();
+        assert!(frame.request_hash_matches(HashAlgo::Sha256));
+    }
+
+    #[test]
+    fn errors_by_status_or_error_body() {
+        let error_body = br#"{"type":"error","error":{"type":"overloaded_error"}}"#.to_vec();
+        let frame = |status, body: &[u8]| Frame::builder().response(body.to_vec()).status(status).build();
+        assert!(frame(Some(529), b"").is_error());
+        assert!(!frame(Some(200), &error_body).is_error());
+        assert!(frame(None, &error_body).is_error());
+        assert!(!frame(None, br#"{"type":"message"}"#).is_error());
+        assert!(frame(Some(400), b"").summary(0).is_error);
+    }
+
+    #[test]
+    fn content_eq_ignores_timing() {
+        let recorded = Frame::new(b"req".to_vec(), b"res".to_vec(), 812, 1700000000000);
+        let expected = Frame::new(b"req".to_vec(), b"res".to_vec(), 0, 0);
+        assert!(recorded.content_eq(&expected));
+        assert_eq!(recorded.with_zeroed_timing(), expected);
+        assert_ne!(recorded, expected);
+        assert!(!recorded.content_eq(&Frame::new(b"req".to_vec(), b"other".to_vec(), 812, 1700000000000)));
+    }
 }
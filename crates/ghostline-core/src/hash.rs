@@ -0,0 +1,121 @@
+//! Request hash algorithms. A file's header names the one its request hashes
+//! were computed with; both produce 32 bytes, so hashes stay `[u8; 32]`.
+
+use sha2::{Digest, Sha256};
+
+/// Algorithm a recording's request hashes use (header extension, 0.12+).
+/// Files from earlier minors are SHA-256.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    /// Several times faster than SHA-256 on large bodies.
+    Blake3,
+}
+
+impl HashAlgo {
+    /// The header tag for this algorithm.
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::Sha256 => 0,
+            Self::Blake3 => 1,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0 => Some(Self::Sha256),
+            1 => Some(Self::Blake3),
+            _ => None,
+        }
+    }
+
+    pub fn hash(self, data: &[u8]) -> [u8; 32] {
+        let mut hasher = self.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    /// An incremental hasher, for bodies that arrive in chunks.
+    pub fn hasher(self) -> Hasher {
+        match self {
+            Self::Sha256 => Hasher::Sha256(Sha256::new()),
+            Self::Blake3 => Hasher::Blake3(Box::default()),
+        }
+    }
+
+    /// As [`Frame::hash_normalized`](crate::Frame::hash_normalized), with this algorithm.
+    pub fn hash_normalized(self, content_type: Option<&str>, body: &[u8]) -> [u8; 32] {
+        content_type
+            .and_then(crate::multipart::boundary)
+            .and_then(|b| crate::multipart::canonical_hash(self, body, b))
+            .unwrap_or_else(|| self.hash(body))
+    }
+}
+
+impl std::fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+        })
+    }
+}
+
+impl std::str::FromStr for HashAlgo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "sha256" => Ok(Self::Sha256),
+            "blake3" => Ok(Self::Blake3),
+            other => Err(format!("unknown hash algorithm '{}' (expected sha256 or blake3)", other)),
+        }
+    }
+}
+
+/// Running hash from [`HashAlgo::hasher`].
+pub enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Sha256(h) => h.update(data),
+            Self::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        match self {
+            Self::Sha256(h) => h.finalize().into(),
+            Self::Blake3(h) => h.finalize().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithms_differ_and_stream_like_one_shot() {
+        let blake3 = HashAlgo::Blake3.hash(b"hello world");
+        assert_ne!(blake3, HashAlgo::Sha256.hash(b"hello world"));
+        assert_eq!(blake3, *blake3::hash(b"hello world").as_bytes());
+        let mut hasher = HashAlgo::Blake3.hasher();
+        hasher.update(b"hello ");
+        hasher.update(b"world");
+        assert_eq!(hasher.finalize(), blake3);
+        for algo in [HashAlgo::Sha256, HashAlgo::Blake3] {
+            assert_eq!(HashAlgo::from_tag(algo.tag()), Some(algo));
+            assert_eq!(algo.to_string().parse::<HashAlgo>(), Ok(algo));
+        }
+        assert_eq!(HashAlgo::from_tag(7), None);
+    }
+}
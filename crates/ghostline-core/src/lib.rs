@@ -1,7 +1,21 @@
+mod delta;
 pub mod frame;
+pub mod hash;
+mod msgpack;
+mod multipart;
 pub mod reader;
+pub mod replay;
+pub mod rewrite;
+pub mod sniff;
+mod stream;
 pub mod writer;
 
-pub use frame::Frame;
-pub use reader::{GhostlineReader, IndexEntry};
-pub use writer::{GhostlineWriter, Header, MAGIC, FORMAT_VERSION};
+pub use frame::{Frame, FrameBuilder, FrameSummary, HttpVersion};
+pub use hash::HashAlgo;
+pub use reader::{check_structure, read_header, Body, GhostlineReader, IndexEntry, StructureIssue};
+pub use replay::{ReplayOutcome, Replayer, ResponseOverride};
+pub use rewrite::{replace_response, rewrite_frame};
+pub use sniff::sniff_content_type;
+pub use writer::{
+    format_description, format_version, Codec, GhostlineWriter, Header, ENCRYPTED_MAGIC, FLAG_DELTA_REQUESTS, FORMAT_MAJOR, FORMAT_MINOR, FORMAT_VERSION, MAGIC,
+};
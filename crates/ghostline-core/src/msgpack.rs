@@ -0,0 +1,88 @@
+//! Just enough MessagePack to stream byte fields out of a frame payload
+//! without decoding the rest of it.
+//!
+//! `rmp_serde` writes `Vec<u8>` as an array of integers (one byte each below
+//! 0x80, `0xcc`-prefixed above); other writers use `bin`. Both are accepted.
+
+use std::io::{self, Read, Write};
+
+/// Bytes buffered before each write when unpacking an integer array.
+const CHUNK: usize = 8192;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn byte(r: &mut impl Read) -> io::Result<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn uint_be(r: &mut impl Read, width: usize) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf[8 - width..])?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn array_len(marker: u8, r: &mut impl Read) -> io::Result<Option<u64>> {
+    Ok(match marker {
+        0x90..=0x9f => Some((marker & 0x0f) as u64),
+        0xdc => Some(uint_be(r, 2)?),
+        0xdd => Some(uint_be(r, 4)?),
+        _ => None,
+    })
+}
+
+/// Read an array header, or `None` when the next value isn't an array (its
+/// marker byte is consumed either way).
+pub(crate) fn read_array_len(r: &mut impl Read) -> io::Result<Option<u64>> {
+    let marker = byte(r)?;
+    array_len(marker, r)
+}
+
+/// Copy the byte string at `r` to `out`, returning its length.
+pub(crate) fn copy_bytes(r: &mut impl Read, out: &mut dyn Write) -> io::Result<u64> {
+    let marker = byte(r)?;
+    let bin_len = match marker {
+        0xc4 => Some(uint_be(r, 1)?),
+        0xc5 => Some(uint_be(r, 2)?),
+        0xc6 => Some(uint_be(r, 4)?),
+        _ => None,
+    };
+    if let Some(len) = bin_len {
+        let copied = io::copy(&mut r.by_ref().take(len), out)?;
+        if copied != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "byte string is truncated"));
+        }
+        return Ok(len);
+    }
+
+    let len = array_len(marker, r)?.ok_or_else(|| invalid("expected a byte string"))?;
+    let mut buf = Vec::with_capacity(CHUNK.min(len as usize));
+    for _ in 0..len {
+        let b = match byte(r)? {
+            b @ 0x00..=0x7f => b,
+            0xcc => byte(r)?,
+            _ => return Err(invalid("byte array holds a non-byte value")),
+        };
+        buf.push(b);
+        if buf.len() == CHUNK {
+            out.write_all(&buf)?;
+            buf.clear();
+        }
+    }
+    out.write_all(&buf)?;
+    Ok(len)
+}
+
+/// Write the `bin` header for a byte string of `len` bytes.
+pub(crate) fn write_bin_header(out: &mut impl Write, len: u32) -> io::Result<()> {
+    out.write_all(&[0xc6])?;
+    out.write_all(&len.to_be_bytes())
+}
+
+/// Skip over the byte string at `r`.
+pub(crate) fn skip_bytes(r: &mut impl Read) -> io::Result<()> {
+    copy_bytes(r, &mut io::sink()).map(|_| ())
+}
@@ -0,0 +1,127 @@
+//! Boundary-independent hashing of `multipart/form-data` bodies.
+//!
+//! Clients pick a fresh random boundary for every upload, so the raw bytes of
+//! two identical uploads never match. The canonical form hashes each part's
+//! name, filename, content type and a hash of its content, in order, all
+//! with the recording's [`HashAlgo`].
+
+use crate::hash::HashAlgo;
+
+struct Part<'a> {
+    headers: &'a [u8],
+    content: &'a [u8],
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// `name=value` parameters after the first `;` of a header value, with
+/// quotes stripped and names lowercased.
+fn params(value: &str) -> impl Iterator<Item = (String, &str)> {
+    value.split(';').skip(1).filter_map(|p| {
+        let (name, v) = p.split_once('=')?;
+        Some((name.trim().to_ascii_lowercase(), v.trim().trim_matches('"')))
+    })
+}
+
+/// The boundary of a `multipart/form-data` content type.
+pub(crate) fn boundary(content_type: &str) -> Option<&str> {
+    let essence = content_type.split(';').next()?.trim();
+    if !essence.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    params(content_type)
+        .find(|(name, _)| name == "boundary")
+        .map(|(_, v)| v)
+        .filter(|b| !b.is_empty())
+}
+
+/// The boundary a body opens with (`--boundary\r\n`), for frames recorded
+/// without their content type.
+pub(crate) fn sniff_boundary(body: &[u8]) -> Option<&str> {
+    let rest = body.strip_prefix(b"--")?;
+    let end = find(rest, b"\r\n")?;
+    std::str::from_utf8(&rest[..end]).ok().filter(|b| !b.is_empty())
+}
+
+fn parts<'a>(body: &'a [u8], boundary: &str) -> Option<Vec<Part<'a>>> {
+    let open = format!("--{}", boundary).into_bytes();
+    let delim = format!("\r\n--{}", boundary).into_bytes();
+    let mut rest = match body.strip_prefix(open.as_slice()) {
+        Some(rest) => rest,
+        None => &body[find(body, &delim)? + delim.len()..],
+    };
+    let mut parts = Vec::new();
+    while !rest.starts_with(b"--") {
+        rest = rest.strip_prefix(b"\r\n")?;
+        let end = find(rest, &delim)?;
+        let part = &rest[..end];
+        let (headers, content) = match part.strip_prefix(b"\r\n") {
+            Some(content) => (&b""[..], content),
+            None => {
+                let split = find(part, b"\r\n\r\n")?;
+                (&part[..split], &part[split + 4..])
+            }
+        };
+        parts.push(Part { headers, content });
+        rest = &rest[end + delim.len()..];
+    }
+    Some(parts)
+}
+
+/// Canonical hash of a multipart body, or `None` if it doesn't parse.
+pub(crate) fn canonical_hash(algo: HashAlgo, body: &[u8], boundary: &str) -> Option<[u8; 32]> {
+    let mut hasher = algo.hasher();
+    hasher.update(b"multipart/form-data\n");
+    for part in parts(body, boundary)? {
+        let (mut name, mut filename, mut content_type) = ("", "", String::new());
+        for line in std::str::from_utf8(part.headers).ok()?.split("\r\n") {
+            let Some((header, value)) = line.split_once(':') else { continue };
+            let header = header.trim();
+            if header.eq_ignore_ascii_case("content-disposition") {
+                for (param, v) in params(value) {
+                    match param.as_str() {
+                        "name" => name = v,
+                        "filename" => filename = v,
+                        _ => {}
+                    }
+                }
+            } else if header.eq_ignore_ascii_case("content-type") {
+                content_type = value.trim().to_ascii_lowercase();
+            }
+        }
+        hasher.update(format!("{}\0{}\0{}\0", name, filename, content_type).as_bytes());
+        hasher.update(&algo.hash(part.content));
+    }
+    Some(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload(boundary: &str, content: &str) -> Vec<u8> {
+        format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"purpose\"\r\n\r\nbatch\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.jsonl\"\r\n\
+             Content-Type: application/jsonl\r\n\r\n{c}\r\n--{b}--\r\n",
+            b = boundary,
+            c = content
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn hash_ignores_the_boundary() {
+        let a = canonical_hash(HashAlgo::Sha256, &upload("xyz123", "{}"), "xyz123").unwrap();
+        let b = canonical_hash(HashAlgo::Sha256, &upload("----other", "{}"), "----other").unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, canonical_hash(HashAlgo::Sha256, &upload("xyz123", "{ }"), "xyz123").unwrap());
+        assert_eq!(canonical_hash(HashAlgo::Sha256, &upload("xyz123", "{}"), "nope"), None);
+
+        assert_eq!(boundary("multipart/form-data; boundary=\"xyz123\""), Some("xyz123"));
+        assert_eq!(boundary("application/json"), None);
+        assert_eq!(sniff_boundary(&upload("----other", "{}")), Some("----other"));
+    }
+}
@@ -1,12 +1,62 @@
+use crate::delta;
 use crate::frame::Frame;
-use crate::writer::{FORMAT_VERSION, MAGIC};
-use std::io::{self, Read, Seek, SeekFrom};
+use crate::hash::HashAlgo;
+use crate::msgpack;
+use crate::writer::{Codec, Header, ENCRYPTED_MAGIC, FLAG_DELTA_REQUESTS, FORMAT_MAJOR, KNOWN_FLAGS, MAGIC};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
-#[derive(Debug, Clone)]
+/// Decompression cap used when the index doesn't record a frame's size (v1 files).
+const LEGACY_DECOMPRESS_CAP: usize = 10 * 1024 * 1024;
+
+/// Most of a frame's recorded size reserved before decompressing it. The size
+/// comes from the file, so a corrupt one mustn't allocate gigabytes up front;
+/// larger frames grow the buffer as they decompress.
+const MAX_PREALLOC: usize = 16 * 1024 * 1024;
+
+/// Decompress a frame block into `out`, replacing its contents, failing if it
+/// holds more than `limit` bytes.
+fn decompress_frame(
+    decompressor: &mut zstd::bulk::Decompressor,
+    compressed: &[u8],
+    limit: usize,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    out.clear();
+    if limit <= MAX_PREALLOC {
+        out.reserve(limit);
+        decompressor
+            .decompress_to_buffer(compressed, out)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        return Ok(());
+    }
+    out.reserve(MAX_PREALLOC);
+    zstd::stream::read::Decoder::with_buffer(compressed)?
+        .take(limit as u64 + 1)
+        .read_to_end(out)?;
+    if out.len() > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "frame decompresses to more than its recorded size",
+        ));
+    }
+    Ok(())
+}
+
+/// Which body of a frame to stream with [`GhostlineReader::get_body_to`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Body {
+    Request,
+    Response,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IndexEntry {
     pub request_hash: [u8; 32],
     pub offset: u64,
+    /// Size of the frame's MessagePack payload after decompression.
+    /// Zero for v1 files, which don't store it.
+    pub decompressed_size: u32,
 }
 
 pub struct GhostlineReader<R: Read + Seek> {
@@ -16,63 +66,321 @@ pub struct GhostlineReader<R: Read + Seek> {
     pub git_sha: Option<[u8; 20]>,
     pub parent_run_id: Option<[u8; 32]>,
     pub fork_at_step: Option<u32>,
+    /// First parent step of a forked slice (0.13+); `None` when it starts at 0.
+    pub fork_start_step: Option<u32>,
+    pub flags: u32,
+    /// ghostline-core version that wrote the file; empty before v3.
+    pub writer_version: String,
+    /// `<os>-<arch>` of the writing build; empty before v3.
+    pub platform: String,
+    /// Fingerprint of the key the file was signed with (0.8+).
+    pub signer_fingerprint: Option<[u8; 32]>,
+    /// Algorithm the request hashes were computed with; SHA-256 before 0.12.
+    /// Hash a request with it before looking the request up.
+    pub hash_algo: HashAlgo,
     index: Vec<IndexEntry>,
+    header_len: u64,
+    index_offset: u64,
+    /// Last reconstructed request in a delta-encoded file, so sequential
+    /// reads apply one delta per frame instead of walking back each time.
+    last_request: Option<(usize, Vec<u8>)>,
+}
+
+/// Read just the file header, returning the format version alongside it.
+///
+/// Leaves `r` positioned at the first frame. Useful for listing or sorting
+/// recordings without loading their index.
+pub fn read_header<R: Read>(r: &mut R) -> io::Result<(u32, Header)> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic == ENCRYPTED_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "file is encrypted; decrypt it first"));
+    }
+    if &magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid magic"));
+    }
+
+    let mut buf4 = [0u8; 4];
+    r.read_exact(&mut buf4)?;
+    let version = u32::from_le_bytes(buf4);
+    let (major, minor) = ((version >> 16) as u16, version as u16);
+    if major != FORMAT_MAJOR || minor == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported version"));
+    }
+
+    let mut buf8 = [0u8; 8];
+    r.read_exact(&mut buf8)?;
+    let started_at = u64::from_le_bytes(buf8);
+
+    let mut has_sha = [0u8; 1];
+    r.read_exact(&mut has_sha)?;
+    let git_sha = if has_sha[0] == 1 {
+        let mut sha = [0u8; 20];
+        r.read_exact(&mut sha)?;
+        Some(sha)
+    } else {
+        None
+    };
+
+    // Fork metadata (v1 extension)
+    let mut has_fork = [0u8; 1];
+    let (parent_run_id, fork_at_step) = if r.read_exact(&mut has_fork).is_ok() && has_fork[0] == 1 {
+        let mut run_id = [0u8; 32];
+        r.read_exact(&mut run_id)?;
+        r.read_exact(&mut buf4)?;
+        (Some(run_id), Some(u32::from_le_bytes(buf4)))
+    } else {
+        (None, None)
+    };
+
+    let flags = if minor >= 2 {
+        r.read_exact(&mut buf4)?;
+        u32::from_le_bytes(buf4)
+    } else {
+        0
+    };
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported header flags"));
+    }
+
+    let (writer_version, platform) = if minor >= 3 {
+        (read_str(r)?, read_str(r)?)
+    } else {
+        (String::new(), String::new())
+    };
+
+    // Extension block: parse the fields this version knows, skip the ones
+    // written by newer minors.
+    let mut signer_fingerprint = None;
+    let mut hash_algo = HashAlgo::Sha256;
+    let mut fork_start_step = None;
+    if minor >= 4 {
+        r.read_exact(&mut buf4)?;
+        let ext_len = u32::from_le_bytes(buf4) as u64;
+        let mut ext = Vec::new();
+        if r.by_ref().take(ext_len).read_to_end(&mut ext)? as u64 != ext_len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated header extension"));
+        }
+        if minor >= 8 {
+            signer_fingerprint = match ext.first() {
+                Some(1) if ext.len() >= 33 => Some(ext[1..33].try_into().unwrap()),
+                Some(0) => None,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid signer fingerprint")),
+            };
+        }
+        let algo_at = if signer_fingerprint.is_some() { 33 } else { 1 };
+        if minor >= 12 {
+            let tag = ext.get(algo_at);
+            hash_algo = match tag.copied().map(HashAlgo::from_tag) {
+                Some(Some(algo)) => algo,
+                Some(None) => return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported hash algorithm")),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "missing hash algorithm")),
+            };
+        }
+        if minor >= 13 {
+            let at = algo_at + 1;
+            fork_start_step = match ext.get(at) {
+                Some(1) if ext.len() >= at + 5 => Some(u32::from_le_bytes(ext[at + 1..at + 5].try_into().unwrap())),
+                Some(0) => None,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid fork start step")),
+            };
+        }
+    }
+
+    Ok((
+        version,
+        Header {
+            started_at,
+            git_sha,
+            parent_run_id,
+            fork_at_step,
+            fork_start_step,
+            flags,
+            writer_version,
+            platform,
+            signer_fingerprint,
+            hash_algo,
+        },
+    ))
+}
+
+/// A violated structural invariant of a .ghostline file, found by [`check_structure`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureIssue {
+    /// The header doesn't parse.
+    BadHeader { reason: String },
+    /// Too short to hold the header and the index footer.
+    FileTooShort { file_len: u64 },
+    /// The trailer's `index_offset` is before the first frame or past the footer.
+    IndexOffsetOutOfRange { index_offset: u64, file_len: u64 },
+    /// The entry count doesn't fill the space between `index_offset` and the footer.
+    CountMismatch { count: u32, index_offset: u64, index_bytes: u64, entry_len: u64 },
+    /// A frame offset is outside the frame region.
+    OffsetOutOfRange { entry: usize, offset: u64 },
+    /// Frame offsets must strictly increase in index order.
+    OffsetNotIncreasing { entry: usize, offset: u64, previous: u64 },
+    /// The `[len]` prefix at the offset is zero or runs past the next frame (or the index).
+    BadLengthPrefix { entry: usize, offset: u64, len: u32, limit: u64 },
+}
+
+impl std::fmt::Display for StructureIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StructureIssue::BadHeader { reason } => write!(f, "header: {}", reason),
+            StructureIssue::FileTooShort { file_len } => write!(f, "file is only {} bytes", file_len),
+            StructureIssue::IndexOffsetOutOfRange { index_offset, file_len } => write!(
+                f,
+                "index_offset {} is outside the file's frame/index region (file is {} bytes)",
+                index_offset, file_len
+            ),
+            StructureIssue::CountMismatch { count, index_offset, index_bytes, entry_len } => write!(
+                f,
+                "index at byte {} holds {} bytes, not {} entries of {} bytes",
+                index_offset, index_bytes, count, entry_len
+            ),
+            StructureIssue::OffsetOutOfRange { entry, offset } => {
+                write!(f, "entry {}: offset {} is outside the frame region", entry, offset)
+            }
+            StructureIssue::OffsetNotIncreasing { entry, offset, previous } => write!(
+                f,
+                "entry {}: offset {} is not after the previous entry's offset {}",
+                entry, offset, previous
+            ),
+            StructureIssue::BadLengthPrefix { entry, offset, len, limit } => write!(
+                f,
+                "entry {}: frame at byte {} has length {}, but the next boundary is byte {}",
+                entry, offset, len, limit
+            ),
+        }
+    }
+}
+
+/// Check a file's structural invariants without decoding any frame: the
+/// trailer's `index_offset` lies within the file, the entry count exactly fills
+/// the index, and every entry's offset increases and lands on a `[len]` prefix
+/// that fits before the next frame. Returns every violation found; I/O errors
+/// other than a short read are returned as `Err`.
+pub fn check_structure<R: Read + Seek>(r: &mut R) -> io::Result<Vec<StructureIssue>> {
+    r.rewind()?;
+    let version = match read_header(r) {
+        Ok((version, _)) => version,
+        Err(e) => return Ok(vec![StructureIssue::BadHeader { reason: e.to_string() }]),
+    };
+    let header_end = r.stream_position()?;
+    let file_len = r.seek(SeekFrom::End(0))?;
+    if file_len < header_end + 12 {
+        return Ok(vec![StructureIssue::FileTooShort { file_len }]);
+    }
+
+    let mut buf4 = [0u8; 4];
+    let mut buf8 = [0u8; 8];
+    r.seek(SeekFrom::Start(file_len - 12))?;
+    r.read_exact(&mut buf4)?;
+    r.read_exact(&mut buf8)?;
+    let count = u32::from_le_bytes(buf4);
+    let index_offset = u64::from_le_bytes(buf8);
+    let index_end = file_len - 12;
+    if index_offset < header_end || index_offset > index_end {
+        return Ok(vec![StructureIssue::IndexOffsetOutOfRange { index_offset, file_len }]);
+    }
+    let entry_len: u64 = if version as u16 >= 2 { 44 } else { 40 };
+    let index_bytes = index_end - index_offset;
+    if count as u64 * entry_len != index_bytes {
+        return Ok(vec![StructureIssue::CountMismatch {
+            count,
+            index_offset,
+            index_bytes,
+            entry_len,
+        }]);
+    }
+
+    let mut offsets = Vec::with_capacity(count as usize);
+    for i in 0..count as u64 {
+        r.seek(SeekFrom::Start(index_offset + i * entry_len + 32))?;
+        r.read_exact(&mut buf8)?;
+        offsets.push(u64::from_le_bytes(buf8));
+    }
+
+    let mut issues = Vec::new();
+    let mut previous: Option<u64> = None;
+    for (entry, &offset) in offsets.iter().enumerate() {
+        if offset < header_end || offset.saturating_add(4) > index_offset {
+            issues.push(StructureIssue::OffsetOutOfRange { entry, offset });
+            continue;
+        }
+        if let Some(previous) = previous.filter(|&p| offset <= p) {
+            issues.push(StructureIssue::OffsetNotIncreasing { entry, offset, previous });
+        }
+        previous = Some(offset);
+        let limit = offsets
+            .get(entry + 1)
+            .copied()
+            .filter(|&next| next > offset && next <= index_offset)
+            .unwrap_or(index_offset);
+        r.seek(SeekFrom::Start(offset))?;
+        r.read_exact(&mut buf4)?;
+        let len = u32::from_le_bytes(buf4);
+        if len == 0 || offset + 4 + len as u64 > limit {
+            issues.push(StructureIssue::BadLengthPrefix { entry, offset, len, limit });
+        }
+    }
+    Ok(issues)
+}
+
+/// Read a u16-length-prefixed UTF-8 string.
+fn read_str<R: Read>(r: &mut R) -> io::Result<String> {
+    let mut len = [0u8; 2];
+    r.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u16::from_le_bytes(len) as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
 }
 
 impl GhostlineReader<io::BufReader<std::fs::File>> {
-    /// Open a .ghostline file from disk.
+    /// Open a .ghostline file from disk, buffered.
     pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_file(std::fs::File::open(path)?)
+    }
+
+    /// Read an already-open file, buffered. It is read from the start.
+    pub fn from_file(file: std::fs::File) -> io::Result<Self> {
+        Self::from_reader(io::BufReader::new(file))
+    }
+}
+
+impl GhostlineReader<std::fs::File> {
+    /// Open a .ghostline file from disk without a `BufReader`, for callers
+    /// that only fetch a few frames and would rather not read ahead.
+    pub fn from_path_unbuffered(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl GhostlineReader<io::Cursor<memmap2::Mmap>> {
+    /// Map a .ghostline file into memory (`mmap` feature). Frames are read
+    /// straight from the page cache, which suits random access into large
+    /// recordings.
+    ///
+    /// The file must not be truncated or rewritten while mapped; ghostline
+    /// only ever replaces recordings by renaming a new file over them.
+    pub fn open_mmap(path: impl AsRef<Path>) -> io::Result<Self> {
         let file = std::fs::File::open(path)?;
-        let reader = io::BufReader::new(file);
-        Self::from_reader(reader)
+        // SAFETY: the mapping is read-only; see the note above on concurrent writers.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_reader(io::Cursor::new(map))
     }
 }
 
 impl<R: Read + Seek> GhostlineReader<R> {
-    /// Create a reader from any Read+Seek source.
+    /// Create a reader from any `Read + Seek` source: an in-memory buffer, a
+    /// decrypted stream, a file opened some other way. It is read from the
+    /// start. [`open`](GhostlineReader::open) is the shortcut for a path.
     pub fn from_reader(mut inner: R) -> io::Result<Self> {
-        // Read magic
-        let mut magic = [0u8; 8];
-        inner.read_exact(&mut magic)?;
-        if &magic != MAGIC {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid magic"));
-        }
-
-        // Read version
+        let (version, header) = read_header(&mut inner)?;
+        let header_len = inner.stream_position()?;
         let mut buf4 = [0u8; 4];
-        inner.read_exact(&mut buf4)?;
-        let version = u32::from_le_bytes(buf4);
-        if version != FORMAT_VERSION {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported version"));
-        }
-
-        // Read started_at
         let mut buf8 = [0u8; 8];
-        inner.read_exact(&mut buf8)?;
-        let started_at = u64::from_le_bytes(buf8);
-
-        // Read git sha
-        let mut has_sha = [0u8; 1];
-        inner.read_exact(&mut has_sha)?;
-        let git_sha = if has_sha[0] == 1 {
-            let mut sha = [0u8; 20];
-            inner.read_exact(&mut sha)?;
-            Some(sha)
-        } else {
-            None
-        };
-
-        // Read fork metadata (v1 extension)
-        let mut has_fork = [0u8; 1];
-        let (parent_run_id, fork_at_step) = if inner.read_exact(&mut has_fork).is_ok() && has_fork[0] == 1 {
-            let mut run_id = [0u8; 32];
-            inner.read_exact(&mut run_id)?;
-            inner.read_exact(&mut buf4)?;
-            let step = u32::from_le_bytes(buf4);
-            (Some(run_id), Some(step))
-        } else {
-            (None, None)
-        };
 
         // Read index from the end
         // Last 8 bytes = index_offset
@@ -85,6 +393,22 @@ impl<R: Read + Seek> GhostlineReader<R> {
         inner.read_exact(&mut buf4)?;
         let count = u32::from_le_bytes(buf4) as usize;
 
+        // The index must run right up to the footer; anything else means the
+        // file is still being written or was truncated.
+        let file_len = inner.seek(SeekFrom::End(0))?;
+        let minor = version as u16;
+        let entry_len: u64 = if minor >= 2 { 44 } else { 40 };
+        let index_end = (count as u64)
+            .checked_mul(entry_len)
+            .and_then(|n| n.checked_add(index_offset))
+            .and_then(|n| n.checked_add(12));
+        if index_end != Some(file_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incomplete file: index does not end at footer",
+            ));
+        }
+
         // Read index entries
         inner.seek(SeekFrom::Start(index_offset))?;
         let mut index = Vec::with_capacity(count);
@@ -93,20 +417,36 @@ impl<R: Read + Seek> GhostlineReader<R> {
             inner.read_exact(&mut hash)?;
             inner.read_exact(&mut buf8)?;
             let offset = u64::from_le_bytes(buf8);
+            let decompressed_size = if minor >= 2 {
+                inner.read_exact(&mut buf4)?;
+                u32::from_le_bytes(buf4)
+            } else {
+                0
+            };
             index.push(IndexEntry {
                 request_hash: hash,
                 offset,
+                decompressed_size,
             });
         }
 
         Ok(Self {
             inner,
-            started_at,
+            started_at: header.started_at,
             version,
-            git_sha,
-            parent_run_id,
-            fork_at_step,
+            git_sha: header.git_sha,
+            parent_run_id: header.parent_run_id,
+            fork_at_step: header.fork_at_step,
+            fork_start_step: header.fork_start_step,
+            flags: header.flags,
+            writer_version: header.writer_version,
+            platform: header.platform,
+            signer_fingerprint: header.signer_fingerprint,
+            hash_algo: header.hash_algo,
             index,
+            header_len,
+            index_offset,
+            last_request: None,
         })
     }
 
@@ -114,17 +454,83 @@ impl<R: Read + Seek> GhostlineReader<R> {
         self.index.len()
     }
 
+    /// The file header, for writing a derived file with the same metadata.
+    /// The signer is left out: a derived file isn't covered by the signature.
+    pub fn header(&self) -> Header {
+        Header {
+            started_at: self.started_at,
+            git_sha: self.git_sha,
+            parent_run_id: self.parent_run_id,
+            fork_at_step: self.fork_at_step,
+            fork_start_step: self.fork_start_step,
+            flags: self.flags,
+            writer_version: self.writer_version.clone(),
+            platform: self.platform.clone(),
+            signer_fingerprint: None,
+            hash_algo: self.hash_algo,
+        }
+    }
+
     /// Access raw index entries.
     pub fn index_entries(&self) -> &[IndexEntry] {
         &self.index
     }
 
+    /// Size in bytes of the file header, including the extension block.
+    pub fn header_len(&self) -> u64 {
+        self.header_len
+    }
+
+    /// Byte offset of the tail index; frame data ends here.
+    pub fn index_offset(&self) -> u64 {
+        self.index_offset
+    }
+
     pub fn get_frame(&mut self, index: usize) -> io::Result<Frame> {
+        let mut frame = self.read_stored_frame(index)?;
+        if self.flags & FLAG_DELTA_REQUESTS != 0 {
+            frame.request_bytes = self.resolve_request(index, frame.request_bytes)?;
+        }
+        Ok(frame)
+    }
+
+    /// Rebuild the full request of a delta-encoded frame from the nearest
+    /// snapshot (or the cached previous request) forward.
+    fn resolve_request(&mut self, index: usize, stored: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut chain = vec![stored];
+        let mut base = None;
+        let mut j = index;
+        while !delta::is_full(chain.last().unwrap()) {
+            if j == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "delta chain has no snapshot"));
+            }
+            j -= 1;
+            if let Some((cached, ref request)) = self.last_request {
+                if cached == j {
+                    base = Some(request.clone());
+                    break;
+                }
+            }
+            chain.push(self.read_stored_frame(j)?.request_bytes);
+        }
+
+        let mut request = base;
+        for stored in chain.iter().rev() {
+            request = Some(delta::decode(stored, request.as_deref())?);
+        }
+        let request = request.unwrap_or_default();
+        self.last_request = Some((index, request.clone()));
+        Ok(request)
+    }
+
+    /// The stored (compressed) block of frame `index`, without its length
+    /// prefix, for copying frames between files without recompressing. In
+    /// delta-encoded files the block holds the request delta, not the request.
+    pub fn get_frame_raw(&mut self, index: usize) -> io::Result<(Vec<u8>, Codec)> {
         if index >= self.index.len() {
             return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame index out of bounds"));
         }
-        let offset = self.index[index].offset;
-        self.inner.seek(SeekFrom::Start(offset))?;
+        self.inner.seek(SeekFrom::Start(self.index[index].offset))?;
 
         // Read compressed length
         let mut buf4 = [0u8; 4];
@@ -134,16 +540,129 @@ impl<R: Read + Seek> GhostlineReader<R> {
         // Read compressed data
         let mut compressed = vec![0u8; len];
         self.inner.read_exact(&mut compressed)?;
+        Ok((compressed, Codec::Zstd))
+    }
+
+    /// Stream frame `index`'s decompressed MessagePack payload into `out`
+    /// without the size cap or whole-output allocation of the bulk path that
+    /// `get_frame` uses, returning the number of bytes written.
+    pub fn get_frame_to(&mut self, index: usize, out: &mut dyn Write) -> io::Result<u64> {
+        io::copy(&mut self.frame_decoder(index)?, out)
+    }
+
+    /// Stream one body of frame `index` into `out`, for bodies too large to
+    /// hold in memory. Map-encoded frames and delta-encoded requests fall back
+    /// to a buffered `get_frame`.
+    pub fn get_body_to(&mut self, index: usize, body: Body, out: &mut dyn Write) -> io::Result<u64> {
+        let delta = self.flags & FLAG_DELTA_REQUESTS != 0;
+        if !(delta && body == Body::Request) {
+            let mut payload = self.frame_decoder(index)?;
+            if matches!(msgpack::read_array_len(&mut payload)?, Some(n) if n >= 3) {
+                msgpack::skip_bytes(&mut payload)?; // request_hash
+                if body == Body::Response {
+                    msgpack::skip_bytes(&mut payload)?;
+                }
+                return msgpack::copy_bytes(&mut payload, out);
+            }
+        }
+        let frame = self.get_frame(index)?;
+        let bytes = match body {
+            Body::Request => frame.request_bytes,
+            Body::Response => frame.response_bytes,
+        };
+        out.write_all(&bytes)?;
+        Ok(bytes.len() as u64)
+    }
+
+    fn frame_decoder(&mut self, index: usize) -> io::Result<impl Read + '_> {
+        if index >= self.index.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "frame index out of bounds"));
+        }
+        self.inner.seek(SeekFrom::Start(self.index[index].offset))?;
+        let mut buf4 = [0u8; 4];
+        self.inner.read_exact(&mut buf4)?;
+        let len = u32::from_le_bytes(buf4) as u64;
+        let decoder = zstd::stream::read::Decoder::new((&mut self.inner).take(len))?;
+        Ok(BufReader::new(decoder))
+    }
+
+    /// Read and decode a frame exactly as stored, without delta reconstruction.
+    fn read_stored_frame(&mut self, index: usize) -> io::Result<Frame> {
+        let (compressed, Codec::Zstd) = self.get_frame_raw(index)?;
+        let capacity = match self.index[index].decompressed_size {
+            0 => LEGACY_DECOMPRESS_CAP,
+            n => n as usize,
+        };
 
         // Decompress
-        let decompressed = zstd::bulk::decompress(&compressed, 10 * 1024 * 1024)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut decompressed = Vec::new();
+        decompress_frame(&mut zstd::bulk::Decompressor::new()?, &compressed, capacity, &mut decompressed)?;
 
         // Deserialize
         Frame::from_msgpack(&decompressed)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
     }
 
+    /// Visit every frame in file order with a single forward pass.
+    ///
+    /// Unlike calling `get_frame` in a loop, this never seeks when frames are
+    /// contiguous (so a `BufReader` keeps its buffer) and decompresses into
+    /// reused buffers. `f` receives each frame's index.
+    pub fn scan(&mut self, mut f: impl FnMut(usize, &Frame)) -> io::Result<()> {
+        let mut order: Vec<usize> = (0..self.index.len()).collect();
+        order.sort_by_key(|&i| self.index[i].offset);
+
+        let mut decompressor = zstd::bulk::Decompressor::new()?;
+        let mut compressed = Vec::new();
+        let mut decompressed = Vec::new();
+        let mut pos = None;
+        let delta = self.flags & FLAG_DELTA_REQUESTS != 0;
+        let mut prev_request: Option<(usize, Vec<u8>)> = None;
+
+        for i in order {
+            let entry = &self.index[i];
+            if pos != Some(entry.offset) {
+                self.inner.seek(SeekFrom::Start(entry.offset))?;
+            }
+            let mut buf4 = [0u8; 4];
+            self.inner.read_exact(&mut buf4)?;
+            let len = u32::from_le_bytes(buf4) as usize;
+            compressed.resize(len, 0);
+            self.inner.read_exact(&mut compressed)?;
+            pos = Some(entry.offset + 4 + len as u64);
+
+            let capacity = match entry.decompressed_size {
+                0 => LEGACY_DECOMPRESS_CAP,
+                n => n as usize,
+            };
+            decompress_frame(&mut decompressor, &compressed, capacity, &mut decompressed)?;
+            let mut frame = Frame::from_msgpack(&decompressed)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            if delta {
+                let base = match &prev_request {
+                    Some((j, request)) if *j + 1 == i => Some(request.as_slice()),
+                    _ => None,
+                };
+                frame.request_bytes = if delta::is_full(&frame.request_bytes) || base.is_some() {
+                    delta::decode(&frame.request_bytes, base)?
+                } else {
+                    // Out-of-order file: fall back to walking the chain.
+                    pos = None;
+                    self.resolve_request(i, frame.request_bytes)?
+                };
+                f(i, &frame);
+                prev_request = Some((i, frame.request_bytes));
+            } else {
+                f(i, &frame);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the first frame recorded for `hash`. Note that `ghostline replay`
+    /// serves the *latest* frame when a hash was recorded more than once.
+    /// `hash` must come from this file's [`hash_algo`](Self::hash_algo).
     pub fn lookup_by_hash(&mut self, hash: &[u8; 32]) -> io::Result<Option<Frame>> {
         for i in 0..self.index.len() {
             if &self.index[i].request_hash == hash {
@@ -157,16 +676,14 @@ impl<R: Read + Seek> GhostlineReader<R> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::writer::{GhostlineWriter, Header};
+    use crate::writer::GhostlineWriter;
     use std::io::Cursor;
 
     fn write_test_frames() -> Vec<u8> {
         let mut buf = Vec::new();
         let header = Header {
             started_at: 1700000000000,
-            git_sha: None,
-            parent_run_id: None,
-            fork_at_step: None,
+            ..Default::default()
         };
         let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
 
@@ -183,6 +700,22 @@ mod tests {
         buf
     }
 
+    #[test]
+    fn opens_files_buffered_unbuffered_and_mapped() {
+        let path = std::env::temp_dir().join(format!("ghostline-open-{}.ghostline", std::process::id()));
+        std::fs::write(&path, write_test_frames()).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(GhostlineReader::from_file(file).unwrap().frame_count(), 3);
+        let mut unbuffered = GhostlineReader::from_path_unbuffered(&path).unwrap();
+        assert_eq!(unbuffered.get_frame(2).unwrap().response_bytes, b"response-2");
+        #[cfg(feature = "mmap")]
+        {
+            let mut mapped = GhostlineReader::open_mmap(&path).unwrap();
+            assert_eq!(mapped.get_frame(1).unwrap().request_bytes, b"request-1");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn read_frame_count() {
         let buf = write_test_frames();
@@ -190,6 +723,16 @@ mod tests {
         assert_eq!(reader.frame_count(), 3);
     }
 
+    #[test]
+    fn layout_offsets() {
+        let buf = write_test_frames();
+        let reader = GhostlineReader::from_reader(Cursor::new(buf.clone())).unwrap();
+        assert_eq!(reader.header_len(), reader.index_entries()[0].offset);
+        assert_eq!(reader.header_len(), Header::default().byte_size());
+        let stored = u64::from_le_bytes(buf[buf.len() - 8..].try_into().unwrap());
+        assert_eq!(reader.index_offset(), stored);
+    }
+
     #[test]
     fn read_all_frames() {
         let buf = write_test_frames();
@@ -229,6 +772,8 @@ mod tests {
             git_sha: None,
             parent_run_id: Some(parent_id),
             fork_at_step: Some(5),
+            fork_start_step: Some(2),
+            ..Default::default()
         };
         let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
         let frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 10, 1700000000000);
@@ -237,10 +782,348 @@ mod tests {
 
         let reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
         assert_eq!(reader.parent_run_id, Some(parent_id));
-        assert_eq!(reader.fork_at_step, Some(5));
+        assert_eq!((reader.fork_at_step, reader.fork_start_step), (Some(5), Some(2)));
         assert_eq!(reader.frame_count(), 1);
     }
 
+    #[test]
+    fn index_records_decompressed_size() {
+        let buf = write_test_frames();
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        for i in 0..3 {
+            let expected = reader.get_frame(i).unwrap().to_msgpack().unwrap().len() as u32;
+            assert_eq!(reader.index_entries()[i].decompressed_size, expected);
+        }
+    }
+
+    #[test]
+    fn oversized_index_sizes_are_not_preallocated() {
+        let mut buf = write_test_frames();
+        let index_offset = u64::from_le_bytes(buf[buf.len() - 8..].try_into().unwrap()) as usize;
+        // Entries are hash (32), offset (8), decompressed size (4).
+        buf[index_offset + 40..index_offset + 44].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.index_entries()[0].decompressed_size, u32::MAX);
+
+        assert_eq!(reader.get_frame(0).unwrap().response_bytes, b"response-0");
+        let mut scanned = Vec::new();
+        reader.scan(|_, frame| scanned.push(frame.response_bytes.clone())).unwrap();
+        assert_eq!(scanned, [&b"response-0"[..], b"response-1", b"response-2"]);
+    }
+
+    #[test]
+    fn reads_v1_index_without_sizes() {
+        let frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 10, 1700000000000);
+        let compressed = zstd::bulk::compress(&frame.to_msgpack().unwrap(), 3).unwrap();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&1700000000000u64.to_le_bytes());
+        buf.extend_from_slice(&[0u8, 0u8]); // no git sha, no fork
+        let frame_offset = buf.len() as u64;
+        buf.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&compressed);
+        let index_offset = buf.len() as u64;
+        buf.extend_from_slice(&frame.request_hash);
+        buf.extend_from_slice(&frame_offset.to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&index_offset.to_le_bytes());
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.version, 1);
+        assert_eq!(reader.index_entries()[0].decompressed_size, 0);
+        assert_eq!(reader.get_frame(0).unwrap().response_bytes, b"res");
+    }
+
+    #[test]
+    fn unfinished_file_is_rejected() {
+        let mut buf = Vec::new();
+        let header = Header {
+            started_at: 1700000000000,
+            ..Default::default()
+        };
+        let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
+        writer
+            .append(&Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 2))
+            .unwrap();
+        drop(writer);
+        assert!(GhostlineReader::from_reader(Cursor::new(buf)).is_err());
+
+        let mut truncated = write_test_frames();
+        truncated.remove(40);
+        assert!(GhostlineReader::from_reader(Cursor::new(truncated)).is_err());
+    }
+
+    #[test]
+    fn delta_requests_roundtrip() {
+        let mut buf = Vec::new();
+        let header = Header {
+            started_at: 1700000000000,
+            flags: FLAG_DELTA_REQUESTS,
+            ..Default::default()
+        };
+        let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
+        let mut requests = Vec::new();
+        let mut messages = String::new();
+        for i in 0..40 {
+            messages.push_str(&format!(r#"{{"role":"user","content":"turn {}"}},"#, i));
+            let request = format!(r#"{{"messages":[{}],"system":"be brief"}}"#, messages);
+            writer
+                .append(&Frame::new(request.clone().into_bytes(), b"ok".to_vec(), 1, i))
+                .unwrap();
+            requests.push(request.into_bytes());
+        }
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.flags, FLAG_DELTA_REQUESTS);
+        // Random access, then sequential.
+        for i in [39, 17, 16, 0, 5] {
+            let frame = reader.get_frame(i).unwrap();
+            assert_eq!(frame.request_bytes, requests[i]);
+            assert_eq!(frame.request_hash, Frame::hash_request(&requests[i]));
+        }
+        for (i, request) in requests.iter().enumerate() {
+            assert_eq!(&reader.get_frame(i).unwrap().request_bytes, request);
+        }
+        let hash = Frame::hash_request(&requests[30]);
+        assert!(reader.lookup_by_hash(&hash).unwrap().is_some());
+
+        let mut scanned = Vec::new();
+        reader.scan(|i, frame| scanned.push((i, frame.request_bytes.clone()))).unwrap();
+        assert_eq!(scanned, requests.into_iter().enumerate().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn scan_matches_get_frame() {
+        let buf = write_test_frames();
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        let mut scanned = Vec::new();
+        reader.scan(|i, frame| scanned.push((i, frame.clone()))).unwrap();
+        assert_eq!(scanned.len(), 3);
+        for (i, frame) in scanned {
+            let direct = reader.get_frame(i).unwrap();
+            assert_eq!(frame.request_bytes, direct.request_bytes);
+            assert_eq!(frame.response_bytes, direct.response_bytes);
+            assert_eq!(frame.timestamp, direct.timestamp);
+        }
+    }
+
+    #[test]
+    fn read_header_only() {
+        let buf = write_test_frames();
+        let (version, header) = read_header(&mut Cursor::new(buf)).unwrap();
+        assert_eq!(version, crate::writer::FORMAT_VERSION);
+        assert_eq!(header.started_at, 1700000000000);
+        assert!(header.parent_run_id.is_none());
+        assert_eq!(header.writer_version, crate::writer::WRITER_VERSION);
+        assert!(header.platform.contains(std::env::consts::OS));
+    }
+
+    /// Every header and frame field this version knows, as [`future_minor_file`] writes them.
+    fn future_header() -> Header {
+        Header {
+            started_at: 1700000000000,
+            git_sha: Some([1u8; 20]),
+            parent_run_id: Some([2u8; 32]),
+            fork_at_step: Some(9),
+            fork_start_step: Some(3),
+            signer_fingerprint: Some([4u8; 32]),
+            hash_algo: HashAlgo::Blake3,
+            ..Default::default()
+        }
+    }
+
+    fn future_frame() -> Frame {
+        Frame::builder()
+            .hash_algo(HashAlgo::Blake3)
+            .request(b"req".to_vec())
+            .response(b"res".to_vec())
+            .latency_ms(10)
+            .timestamp(1700000000000)
+            .tags(vec!["t".into()])
+            .http_version(Some(crate::HttpVersion::Http11))
+            .upstream_http_version(Some(crate::HttpVersion::Http2))
+            .anthropic_betas(vec!["files-api-2025-04-14".into()])
+            .upstream(Some("https://api.openai.com".into()))
+            .status(Some(200))
+            .original_content_length(Some(3))
+            .request_headers(vec![("x-app".into(), "ghostline".into())])
+            .build()
+    }
+
+    /// A file as the next minor's writer might produce it: every extension
+    /// field this version knows followed by unknown ones, and a frame with
+    /// every field followed by an unknown one. `version` goes in the header.
+    fn future_minor_file(version: u32) -> Vec<u8> {
+        let frame = future_frame();
+        let mut packed = frame.to_msgpack().unwrap();
+        assert_eq!(packed[..3], [0xdc, 0, 16]);
+        packed[2] = 17;
+        packed.extend(rmp_serde::to_vec(&42u32).unwrap());
+        let compressed = zstd::bulk::compress(&packed, 3).unwrap();
+
+        let header = future_header();
+        let mut buf = Vec::new();
+        header.write_to(&mut buf).unwrap();
+        buf[8..12].copy_from_slice(&version.to_le_bytes());
+        let unknown = b"future!";
+        let length_at = buf.len() - header.extension_len() - 4;
+        buf[length_at..length_at + 4].copy_from_slice(&((header.extension_len() + unknown.len()) as u32).to_le_bytes());
+        buf.extend_from_slice(unknown);
+        let frame_offset = buf.len() as u64;
+        buf.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&compressed);
+        let index_offset = buf.len() as u64;
+        buf.extend_from_slice(&frame.request_hash);
+        buf.extend_from_slice(&frame_offset.to_le_bytes());
+        buf.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&index_offset.to_le_bytes());
+        buf
+    }
+
+    #[test]
+    fn reads_newer_minor_version() {
+        let next_minor = crate::writer::FORMAT_VERSION + 1;
+        let mut reader = GhostlineReader::from_reader(Cursor::new(future_minor_file(next_minor))).unwrap();
+        assert_eq!(reader.version, next_minor);
+        assert_eq!(reader.get_frame(0).unwrap(), future_frame());
+
+        let (_, header) = read_header(&mut Cursor::new(future_minor_file(next_minor))).unwrap();
+        let expected = future_header();
+        assert_eq!(header.started_at, expected.started_at);
+        assert_eq!(header.git_sha, expected.git_sha);
+        assert_eq!((header.parent_run_id, header.fork_at_step), (expected.parent_run_id, expected.fork_at_step));
+        assert_eq!(header.fork_start_step, expected.fork_start_step);
+        assert_eq!(header.signer_fingerprint, expected.signer_fingerprint);
+        assert_eq!(header.hash_algo, expected.hash_algo);
+        assert_eq!(header.writer_version, crate::writer::WRITER_VERSION);
+    }
+
+    #[test]
+    fn signer_fingerprint_roundtrip() {
+        let header = Header {
+            signer_fingerprint: Some([7u8; 32]),
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        GhostlineWriter::new(&mut buf, &header).unwrap().finish().unwrap();
+
+        let reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.signer_fingerprint, Some([7u8; 32]));
+        assert_eq!(reader.header_len(), header.byte_size());
+        assert_eq!(reader.header().signer_fingerprint, None);
+    }
+
+    #[test]
+    fn hash_algo_roundtrip() {
+        for signer_fingerprint in [None, Some([7u8; 32])] {
+            let header = Header { hash_algo: HashAlgo::Blake3, signer_fingerprint, ..Default::default() };
+            let hash = HashAlgo::Blake3.hash(b"req");
+            let mut buf = Vec::new();
+            let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
+            writer.append(&Frame::builder().request(b"req".to_vec()).request_hash(hash).build()).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = GhostlineReader::from_reader(Cursor::new(buf.clone())).unwrap();
+            assert_eq!((reader.hash_algo, reader.header().hash_algo), (HashAlgo::Blake3, HashAlgo::Blake3));
+            assert_eq!(reader.header_len(), header.byte_size());
+            assert!(reader.get_frame(0).unwrap().request_hash_matches(HashAlgo::Blake3));
+            let replayer = crate::Replayer::from_reader(&mut reader).unwrap();
+            assert_eq!(replayer.hash_algo(), HashAlgo::Blake3);
+            assert!(replayer.contains(&replayer.hash_algo().hash(b"req")));
+
+            // The tag comes just before the (absent) fork start, the last byte.
+            buf[header.byte_size() as usize - 2] = 9;
+            let err = GhostlineReader::from_reader(Cursor::new(buf)).err().unwrap();
+            assert_eq!(err.to_string(), "unsupported hash algorithm");
+        }
+    }
+
+    #[test]
+    fn rejects_other_major_version() {
+        let next_major = (1 << 16) | 1;
+        assert!(GhostlineReader::from_reader(Cursor::new(future_minor_file(next_major))).is_err());
+        assert!(GhostlineReader::from_reader(Cursor::new(future_minor_file(0))).is_err());
+    }
+
+    #[test]
+    fn reads_v2_header_without_provenance() {
+        let frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 10, 1700000000000);
+        let packed = frame.to_msgpack().unwrap();
+        let compressed = zstd::bulk::compress(&packed, 3).unwrap();
+
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&2u32.to_le_bytes());
+        buf.extend_from_slice(&1700000000000u64.to_le_bytes());
+        buf.extend_from_slice(&[0u8, 0u8]); // no git sha, no fork
+        buf.extend_from_slice(&0u32.to_le_bytes()); // flags
+        let frame_offset = buf.len() as u64;
+        buf.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&compressed);
+        let index_offset = buf.len() as u64;
+        buf.extend_from_slice(&frame.request_hash);
+        buf.extend_from_slice(&frame_offset.to_le_bytes());
+        buf.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&index_offset.to_le_bytes());
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        assert_eq!(reader.version, 2);
+        assert!(reader.writer_version.is_empty() && reader.platform.is_empty());
+        assert_eq!(reader.get_frame(0).unwrap().response_bytes, b"res");
+    }
+
+    #[test]
+    fn raw_copy_preserves_frame_blocks() {
+        let src = write_test_frames();
+        let mut reader = GhostlineReader::from_reader(Cursor::new(src.clone())).unwrap();
+        let mut dst = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut dst, &reader.header()).unwrap();
+        for i in 0..reader.frame_count() {
+            let (block, codec) = reader.get_frame_raw(i).unwrap();
+            assert_eq!(codec, Codec::Zstd);
+            let hash = reader.index_entries()[i].request_hash;
+            writer.append_raw(&block, hash, codec).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let frame_region = |buf: &[u8]| {
+            let first = GhostlineReader::from_reader(Cursor::new(buf.to_vec())).unwrap().index_entries()[0].offset;
+            let index_offset = u64::from_le_bytes(buf[buf.len() - 8..].try_into().unwrap());
+            buf[first as usize..index_offset as usize].to_vec()
+        };
+        assert_eq!(frame_region(&src), frame_region(&dst));
+
+        let copied = GhostlineReader::from_reader(Cursor::new(dst)).unwrap();
+        assert_eq!(copied.index_entries(), reader.index_entries());
+    }
+
+    #[test]
+    fn streams_large_bodies() {
+        let response: Vec<u8> = (0..300_000u32).map(|i| (i % 251) as u8).collect();
+        let frame = Frame::new(b"request".to_vec(), response.clone(), 5, 0);
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        writer.append(&frame).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
+        let mut payload = Vec::new();
+        reader.get_frame_to(0, &mut payload).unwrap();
+        assert_eq!(payload, frame.to_msgpack().unwrap());
+
+        let mut body = Vec::new();
+        assert_eq!(reader.get_body_to(0, Body::Response, &mut body).unwrap(), response.len() as u64);
+        assert_eq!(body, response);
+        body.clear();
+        reader.get_body_to(0, Body::Request, &mut body).unwrap();
+        assert_eq!(body, b"request");
+    }
+
     #[test]
     fn roundtrip_request_bytes() {
         let originals: Vec<Vec<u8>> = (0..3)
@@ -250,9 +1133,9 @@ mod tests {
         let buf = write_test_frames();
         let mut reader = GhostlineReader::from_reader(Cursor::new(buf)).unwrap();
 
-        for i in 0..3 {
+        for (i, original) in originals.iter().enumerate() {
             let frame = reader.get_frame(i).unwrap();
-            assert_eq!(frame.request_bytes, originals[i]);
+            assert_eq!(&frame.request_bytes, original);
         }
     }
 }
@@ -0,0 +1,467 @@
+use crate::frame::Frame;
+use crate::hash::HashAlgo;
+use crate::reader::GhostlineReader;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Result of looking a request up in a [`Replayer`].
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayOutcome<'a> {
+    /// A recorded frame answers this request.
+    Hit { index: usize, frame: &'a Frame },
+    /// A response override answers this request instead of the recording.
+    /// `index` is the recorded frame it replaces, if there is one.
+    Overridden {
+        index: Option<usize>,
+        response: &'a ResponseOverride,
+    },
+    /// Nothing was recorded for this request hash, or the frame that was
+    /// is excluded by [`set_allowed_statuses`](Replayer::set_allowed_statuses).
+    Miss { hash: [u8; 32] },
+    /// A recorded frame would answer this request, but the check passed to
+    /// [`try_lookup_if`](Replayer::try_lookup_if) turned it down.
+    Declined { index: usize, frame: &'a Frame },
+}
+
+/// A response served in place of a recorded one, e.g. to inject a fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseOverride {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// What [`Replayer::open_lazy`] reads frames from.
+#[cfg(feature = "mmap")]
+type LazySource = io::Cursor<memmap2::Mmap>;
+#[cfg(not(feature = "mmap"))]
+type LazySource = io::BufReader<std::fs::File>;
+
+/// Frames read from the file when first served, keeping the most recently
+/// served ones up to `budget` bytes of bodies.
+struct LazyFrames {
+    reader: GhostlineReader<LazySource>,
+    budget: usize,
+    used: usize,
+    /// Cached frames and the tick each was last served at.
+    cached: HashMap<usize, (Frame, u64)>,
+    /// Last-served tick → frame index, oldest first.
+    recency: BTreeMap<u64, usize>,
+    tick: u64,
+}
+
+fn cached_size(frame: &Frame) -> usize {
+    frame.request_bytes.len() + frame.response_bytes.len()
+}
+
+impl LazyFrames {
+    /// Frame `index`, from the cache or the file. A frame larger than the
+    /// whole budget is still kept until the next one is served.
+    fn get(&mut self, index: usize) -> io::Result<&Frame> {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((_, last)) = self.cached.get_mut(&index) {
+            self.recency.remove(last);
+            *last = tick;
+        } else {
+            let frame = self.reader.get_frame(index)?;
+            let size = cached_size(&frame);
+            while self.used + size > self.budget {
+                let Some((_, oldest)) = self.recency.pop_first() else { break };
+                if let Some((evicted, _)) = self.cached.remove(&oldest) {
+                    self.used -= cached_size(&evicted);
+                }
+            }
+            self.used += size;
+            self.cached.insert(index, (frame, tick));
+        }
+        self.recency.insert(tick, index);
+        Ok(&self.cached[&index].0)
+    }
+}
+
+enum Store {
+    Loaded(Arc<Vec<Frame>>),
+    Lazy(Box<LazyFrames>),
+}
+
+/// Replay index over a run's frames, keyed by request hash. The frames are
+/// either all in memory ([`new`](Self::new), [`from_reader`](Self::from_reader))
+/// or read from the file as they are served ([`open_lazy`](Self::open_lazy)).
+///
+/// When a hash was recorded more than once the latest frame answers it, so
+/// the earlier ones are never served and show up in [`unused_frames`](Self::unused_frames),
+/// unless [`advance_duplicates`](Self::advance_duplicates) serves them in turn.
+pub struct Replayer {
+    store: Store,
+    by_hash: HashMap<[u8; 32], usize>,
+    /// How many lookups each frame has answered.
+    hits: Vec<u64>,
+    overrides: HashMap<[u8; 32], ResponseOverride>,
+    /// Only frames with one of these statuses are served, when set.
+    allowed_statuses: Option<Vec<u16>>,
+    /// Lookups that found a frame but missed because of its status.
+    filtered: u64,
+    hash_algo: HashAlgo,
+    /// With [`advance_duplicates`](Self::advance_duplicates): each hash recorded
+    /// more than once.
+    sequences: Option<HashMap<[u8; 32], Sequence>>,
+}
+
+/// The frames recorded for one request hash, in file order, and how many
+/// lookups of it have been answered.
+struct Sequence {
+    frames: Vec<usize>,
+    served: usize,
+}
+
+impl Replayer {
+    pub fn new(frames: impl Into<Arc<Vec<Frame>>>) -> Self {
+        let frames = frames.into();
+        let by_hash = frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| (frame.request_hash, i))
+            .collect();
+        let hits = vec![0; frames.len()];
+        Self {
+            store: Store::Loaded(frames),
+            by_hash,
+            hits,
+            overrides: HashMap::new(),
+            allowed_statuses: None,
+            filtered: 0,
+            hash_algo: HashAlgo::Sha256,
+            sequences: None,
+        }
+    }
+
+    /// Load every frame of a recording.
+    pub fn from_reader<R: Read + Seek>(reader: &mut GhostlineReader<R>) -> io::Result<Self> {
+        let mut frames = Vec::with_capacity(reader.frame_count());
+        reader.scan(|_, frame| frames.push(frame.clone()))?;
+        Ok(Self { hash_algo: reader.hash_algo, ..Self::new(frames) })
+    }
+
+    /// Index a recording without loading its frames: each is decompressed
+    /// when a lookup first needs it, and the most recently served are kept
+    /// up to `memory_budget` bytes of request and response bodies. The file
+    /// is memory-mapped when the `mmap` feature is on.
+    pub fn open_lazy(path: impl AsRef<Path>, memory_budget: usize) -> io::Result<Self> {
+        #[cfg(feature = "mmap")]
+        let reader = GhostlineReader::open_mmap(path)?;
+        #[cfg(not(feature = "mmap"))]
+        let reader = GhostlineReader::open(path)?;
+        let by_hash = reader
+            .index_entries()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| (entry.request_hash, i))
+            .collect();
+        let hits = vec![0; reader.frame_count()];
+        let hash_algo = reader.hash_algo;
+        Ok(Self {
+            store: Store::Lazy(Box::new(LazyFrames {
+                reader,
+                budget: memory_budget,
+                used: 0,
+                cached: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+            })),
+            by_hash,
+            hits,
+            overrides: HashMap::new(),
+            allowed_statuses: None,
+            filtered: 0,
+            hash_algo,
+            sequences: None,
+        })
+    }
+
+    /// All frames, in file order; `None` when they are read on demand.
+    pub fn frames(&self) -> Option<&Arc<Vec<Frame>>> {
+        match &self.store {
+            Store::Loaded(frames) => Some(frames),
+            Store::Lazy(_) => None,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.hits.len()
+    }
+
+    /// Algorithm the recording's request hashes use (SHA-256 for [`new`](Self::new)),
+    /// so lookups hash incoming requests the same way.
+    pub fn hash_algo(&self) -> HashAlgo {
+        self.hash_algo
+    }
+
+    /// Each frame's request hash, in file order.
+    pub fn request_hashes(&self) -> Box<dyn Iterator<Item = [u8; 32]> + '_> {
+        match &self.store {
+            Store::Loaded(frames) => Box::new(frames.iter().map(|f| f.request_hash)),
+            Store::Lazy(lazy) => Box::new(lazy.reader.index_entries().iter().map(|e| e.request_hash)),
+        }
+    }
+
+    /// Bytes of frame bodies held by a lazy replayer's cache, and its budget.
+    pub fn lazy_cache_usage(&self) -> Option<(usize, usize)> {
+        match &self.store {
+            Store::Loaded(_) => None,
+            Store::Lazy(lazy) => Some((lazy.used, lazy.budget)),
+        }
+    }
+
+    /// Number of distinct request hashes that can be answered.
+    pub fn unique_hashes(&self) -> usize {
+        self.by_hash.len()
+    }
+
+    pub fn contains(&self, hash: &[u8; 32]) -> bool {
+        self.by_hash.contains_key(hash)
+    }
+
+    /// Answer `hash` with `status` and `body` instead of the recorded frame,
+    /// without touching the recording. Replaces any earlier override.
+    pub fn set_response_override(&mut self, hash: [u8; 32], status: u16, body: Vec<u8>) {
+        self.overrides.insert(hash, ResponseOverride { status, body });
+    }
+
+    pub fn override_count(&self) -> usize {
+        self.overrides.len()
+    }
+
+    /// Serve only frames whose status is in `statuses`; a lookup that finds
+    /// any other frame is a miss. Frames without a recorded status count as
+    /// 200, which is what they replay as. Overrides are unaffected.
+    pub fn set_allowed_statuses(&mut self, statuses: Vec<u16>) {
+        self.allowed_statuses = Some(statuses);
+    }
+
+    /// Answer successive lookups of a hash recorded more than once with its
+    /// frames in recorded order instead of always the latest, so a retried
+    /// request gets the recording's retry progression (a 529, then the 200).
+    /// Past the last frame, lookups keep getting the last one.
+    pub fn advance_duplicates(&mut self) {
+        let mut frames: HashMap<[u8; 32], Vec<usize>> = HashMap::new();
+        for (i, hash) in self.request_hashes().enumerate() {
+            frames.entry(hash).or_default().push(i);
+        }
+        frames.retain(|_, frames| frames.len() > 1);
+        self.sequences = Some(frames.into_iter().map(|(hash, frames)| (hash, Sequence { frames, served: 0 })).collect());
+    }
+
+    /// Lookups turned into misses by [`set_allowed_statuses`](Self::set_allowed_statuses).
+    pub fn filtered_count(&self) -> u64 {
+        self.filtered
+    }
+
+    /// Look up a request hash, marking the answering frame as served. An
+    /// override takes precedence; the frame it replaces still counts as served.
+    ///
+    /// A frame a lazy replayer can't read is a miss here; see
+    /// [`try_lookup`](Self::try_lookup) for the error.
+    pub fn lookup(&mut self, hash: &[u8; 32]) -> ReplayOutcome<'_> {
+        self.try_lookup(hash).unwrap_or(ReplayOutcome::Miss { hash: *hash })
+    }
+
+    /// [`lookup`](Self::lookup), failing if the answering frame has to be
+    /// read from the file and can't be. In-memory frames never fail.
+    pub fn try_lookup(&mut self, hash: &[u8; 32]) -> io::Result<ReplayOutcome<'_>> {
+        self.try_lookup_if(hash, |_| true)
+    }
+
+    /// [`try_lookup`](Self::try_lookup), serving the recorded frame only if
+    /// `accept` returns true for it. A frame turned down is
+    /// [`Declined`](ReplayOutcome::Declined) and the lookup leaves no trace:
+    /// the frame isn't counted as served, and with
+    /// [`advance_duplicates`](Self::advance_duplicates) the next lookup gets
+    /// the same frame. Overrides are served without asking `accept`.
+    pub fn try_lookup_if(
+        &mut self,
+        hash: &[u8; 32],
+        accept: impl FnOnce(&Frame) -> bool,
+    ) -> io::Result<ReplayOutcome<'_>> {
+        if let Some(response) = self.overrides.get(hash) {
+            let index = self.by_hash.get(hash).copied();
+            if let Some(i) = index {
+                self.hits[i] += 1;
+            }
+            return Ok(ReplayOutcome::Overridden { index, response });
+        }
+        let Some(&latest) = self.by_hash.get(hash) else {
+            return Ok(ReplayOutcome::Miss { hash: *hash });
+        };
+        let sequence = self.sequences.as_mut().and_then(|s| s.get_mut(hash));
+        let index = match &sequence {
+            Some(sequence) => sequence.frames[sequence.served.min(sequence.frames.len() - 1)],
+            None => latest,
+        };
+        let frame = match &mut self.store {
+            Store::Loaded(frames) => &frames[index],
+            Store::Lazy(lazy) => lazy.get(index)?,
+        };
+        if !status_allowed(&self.allowed_statuses, frame) {
+            if let Some(sequence) = sequence {
+                sequence.served += 1;
+            }
+            self.filtered += 1;
+            return Ok(ReplayOutcome::Miss { hash: *hash });
+        }
+        if !accept(frame) {
+            return Ok(ReplayOutcome::Declined { index, frame });
+        }
+        if let Some(sequence) = sequence {
+            sequence.served += 1;
+        }
+        self.hits[index] += 1;
+        Ok(ReplayOutcome::Hit { index, frame })
+    }
+
+    /// How many lookups each frame has answered, by frame index. Overridden
+    /// lookups count for the frame they replace.
+    pub fn hit_counts(&self) -> &[u64] {
+        &self.hits
+    }
+
+    /// Indices of frames no lookup has returned yet, in file order.
+    pub fn unused_frames(&self) -> Vec<usize> {
+        self.hits
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits == 0)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+fn status_allowed(allowed: &Option<Vec<u16>>, frame: &Frame) -> bool {
+    allowed
+        .as_ref()
+        .is_none_or(|allowed| allowed.contains(&frame.status.unwrap_or(200)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_misses_and_unused_frames() {
+        let frames = vec![
+            Frame::new(b"a".to_vec(), b"first".to_vec(), 1, 0),
+            Frame::new(b"b".to_vec(), b"only".to_vec(), 1, 0),
+            Frame::new(b"a".to_vec(), b"latest".to_vec(), 1, 0),
+        ];
+        let mut replayer = Replayer::new(frames);
+        assert_eq!(replayer.unique_hashes(), 2);
+
+        match replayer.lookup(&Frame::hash_request(b"a")) {
+            ReplayOutcome::Hit { index, frame } => {
+                assert_eq!(index, 2);
+                assert_eq!(frame.response_bytes, b"latest");
+            }
+            other => panic!("expected a hit, got {:?}", other),
+        }
+        let missing = Frame::hash_request(b"zzz");
+        assert!(matches!(replayer.lookup(&missing), ReplayOutcome::Miss { hash } if hash == missing));
+
+        replayer.lookup(&Frame::hash_request(b"a"));
+        assert_eq!(replayer.hit_counts(), &[0, 0, 2]);
+        assert_eq!(replayer.unused_frames(), vec![0, 1]);
+    }
+
+    #[test]
+    fn duplicates_advance_in_recorded_order() {
+        let frame = |request: &[u8], status| Frame::builder().request(request.to_vec()).status(Some(status)).build();
+        let mut replayer = Replayer::new(vec![frame(b"a", 529), frame(b"b", 200), frame(b"a", 529), frame(b"a", 200)]);
+        replayer.advance_duplicates();
+        let mut served = |request: &[u8]| match replayer.lookup(&Frame::hash_request(request)) {
+            ReplayOutcome::Hit { index, .. } => index,
+            other => panic!("expected a hit, got {:?}", other),
+        };
+        let order: Vec<usize> = (0..5).map(|_| served(b"a")).collect();
+        assert_eq!(order, vec![0, 2, 3, 3, 3]);
+        assert_eq!((served(b"b"), served(b"b")), (1, 1));
+        assert!(replayer.unused_frames().is_empty());
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let mut replayer = Replayer::new(vec![Frame::new(b"a".to_vec(), b"ok".to_vec(), 1, 0)]);
+        let a = Frame::hash_request(b"a");
+        let unrecorded = Frame::hash_request(b"b");
+        replayer.set_response_override(a, 500, b"boom".to_vec());
+        replayer.set_response_override(unrecorded, 429, Vec::new());
+
+        match replayer.lookup(&a) {
+            ReplayOutcome::Overridden { index, response } => {
+                assert_eq!(index, Some(0));
+                assert_eq!((response.status, response.body.as_slice()), (500, &b"boom"[..]));
+            }
+            other => panic!("expected an override, got {:?}", other),
+        }
+        assert!(matches!(
+            replayer.lookup(&unrecorded),
+            ReplayOutcome::Overridden { index: None, response } if response.status == 429
+        ));
+        assert!(replayer.unused_frames().is_empty());
+    }
+
+    #[test]
+    fn status_filter_turns_hits_into_misses() {
+        let error = Frame::builder().request(b"e".to_vec()).response(b"overloaded".to_vec()).status(Some(529)).build();
+        let ok = Frame::builder().request(b"o".to_vec()).response(b"fine".to_vec()).status(Some(200)).build();
+        let old = Frame::new(b"u".to_vec(), b"no status".to_vec(), 1, 0);
+        let mut replayer = Replayer::new(vec![error, ok, old]);
+        replayer.set_allowed_statuses(vec![200]);
+
+        assert!(matches!(replayer.lookup(&Frame::hash_request(b"e")), ReplayOutcome::Miss { .. }));
+        assert!(matches!(replayer.lookup(&Frame::hash_request(b"o")), ReplayOutcome::Hit { index: 1, .. }));
+        assert!(matches!(replayer.lookup(&Frame::hash_request(b"u")), ReplayOutcome::Hit { index: 2, .. }));
+        assert_eq!(replayer.filtered_count(), 1);
+        assert_eq!(replayer.unused_frames(), vec![0]);
+    }
+
+    #[test]
+    fn declined_lookups_leave_no_trace() {
+        let frame = |status| Frame::builder().request(b"a".to_vec()).status(Some(status)).build();
+        let mut replayer = Replayer::new(vec![frame(529), frame(200)]);
+        replayer.advance_duplicates();
+        let a = Frame::hash_request(b"a");
+
+        assert!(matches!(replayer.try_lookup_if(&a, |_| false).unwrap(), ReplayOutcome::Declined { index: 0, .. }));
+        assert_eq!(replayer.unused_frames(), vec![0, 1]);
+        // The declined frame is still the one the next lookup gets.
+        assert!(matches!(replayer.lookup(&a), ReplayOutcome::Hit { index: 0, .. }));
+        assert!(matches!(replayer.lookup(&a), ReplayOutcome::Hit { index: 1, .. }));
+    }
+
+    #[test]
+    fn lazy_replayer_reads_frames_within_budget() {
+        let path = std::env::temp_dir().join(format!("ghostline-lazy-{}.ghostline", std::process::id()));
+        let mut writer =
+            crate::writer::GhostlineWriter::new(std::fs::File::create(&path).unwrap(), &Default::default()).unwrap();
+        for name in ["a", "b", "c"] {
+            writer.append(&Frame::new(name.as_bytes().to_vec(), vec![b'x'; 100], 1, 0)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        // Room for two frames' bodies (101 bytes each).
+        let mut replayer = Replayer::open_lazy(&path, 250).unwrap();
+        assert!(replayer.frames().is_none());
+        assert_eq!(replayer.frame_count(), 3);
+        assert_eq!(replayer.request_hashes().nth(1), Some(Frame::hash_request(b"b")));
+        for name in ["a", "b", "c", "a"] {
+            match replayer.try_lookup(&Frame::hash_request(name.as_bytes())).unwrap() {
+                ReplayOutcome::Hit { frame, .. } => assert_eq!(frame.request_bytes, name.as_bytes()),
+                other => panic!("expected a hit, got {:?}", other),
+            }
+        }
+        assert_eq!(replayer.lazy_cache_usage(), Some((202, 250)));
+        assert_eq!(replayer.hit_counts(), &[2, 1, 1]);
+        assert!(matches!(replayer.lookup(&Frame::hash_request(b"zzz")), ReplayOutcome::Miss { .. }));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
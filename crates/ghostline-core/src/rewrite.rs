@@ -0,0 +1,104 @@
+//! Copies of a recording with one frame's response swapped for a canned one,
+//! for building test fixtures out of real traffic.
+
+use crate::reader::GhostlineReader;
+use crate::writer::{GhostlineWriter, FLAG_DELTA_REQUESTS};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+/// Write `reader`'s recording to `out` with frame `index`'s response body
+/// replaced by `new_response`. The request, and so its hash, is untouched, so
+/// replaying the copy serves the new body for the recorded request; status,
+/// latency and the other metadata are kept. The header is copied, and other
+/// frames keep their stored blocks unless the file delta-encodes requests.
+pub fn replace_response<R: Read + Seek, W: Write>(
+    reader: &mut GhostlineReader<R>,
+    out: W,
+    index: usize,
+    new_response: &[u8],
+) -> io::Result<W> {
+    let count = reader.frame_count();
+    if index >= count {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame {} is out of range; the recording has {}", index, count),
+        ));
+    }
+    let header = reader.header();
+    let delta = header.flags & FLAG_DELTA_REQUESTS != 0;
+    let mut writer = GhostlineWriter::new(out, &header)?;
+    for i in 0..count {
+        if i == index {
+            let mut frame = reader.get_frame(i)?;
+            frame.response_bytes = new_response.to_vec();
+            // The canned body is whole, whatever happened to the recorded one.
+            frame.original_response_len = None;
+            frame.original_content_length = None;
+            frame.truncated = frame.original_request_len.is_some();
+            writer.append(&frame)?;
+        } else if delta {
+            writer.append(&reader.get_frame(i)?)?;
+        } else {
+            let (block, codec) = reader.get_frame_raw(i)?;
+            writer.append_raw(&block, reader.index_entries()[i].request_hash, codec)?;
+        }
+    }
+    writer.finish()
+}
+
+/// [`replace_response`] from the file at `src` to a new file at `dst`, which
+/// may be `src` itself: the copy is written beside `dst` and renamed into place.
+pub fn rewrite_frame(src: impl AsRef<Path>, dst: impl AsRef<Path>, index: usize, new_response: &[u8]) -> io::Result<()> {
+    let dst = dst.as_ref();
+    let mut tmp = dst.as_os_str().to_owned();
+    tmp.push(".rewrite");
+    let mut reader = GhostlineReader::open(src)?;
+    let result = std::fs::File::create(&tmp)
+        .and_then(|file| replace_response(&mut reader, io::BufWriter::new(file), index, new_response))
+        .and_then(|mut out| out.flush());
+    drop(reader);
+    match result {
+        Ok(()) => std::fs::rename(&tmp, dst),
+        Err(e) => {
+            let _ = std::fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Frame, Header};
+    use std::io::Cursor;
+
+    fn recording(flags: u32) -> Vec<u8> {
+        let header = Header { started_at: 7, flags, ..Default::default() };
+        let mut writer = GhostlineWriter::new(Cursor::new(Vec::new()), &header).unwrap();
+        for i in 0..3u8 {
+            let mut frame = Frame::new(vec![b'q', i], vec![b'r', i], 10 + i as u64, i as u64);
+            frame.status = Some(200);
+            writer.append(&frame).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn replaces_one_response_and_keeps_the_rest() {
+        for flags in [0, FLAG_DELTA_REQUESTS] {
+            let mut reader = GhostlineReader::from_reader(Cursor::new(recording(flags))).unwrap();
+            let out = replace_response(&mut reader, Cursor::new(Vec::new()), 1, b"canned").unwrap();
+            let mut copy = GhostlineReader::from_reader(Cursor::new(out.into_inner())).unwrap();
+            assert_eq!((copy.frame_count(), copy.started_at, copy.flags), (3, 7, flags));
+            for i in 0..3 {
+                let (before, after) = (reader.get_frame(i).unwrap(), copy.get_frame(i).unwrap());
+                assert_eq!(after.request_hash, before.request_hash);
+                assert_eq!((after.latency_ms, after.status), (before.latency_ms, Some(200)));
+                let expected = if i == 1 { b"canned".to_vec() } else { before.response_bytes };
+                assert_eq!(after.response_bytes, expected);
+            }
+            let err = replace_response(&mut reader, Cursor::new(Vec::new()), 3, b"x").unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Best-effort content-type detection for recorded bodies, which are stored
+//! without their HTTP headers.
+
+/// Guess the MIME type of `bytes` from magic bytes and leading characters.
+///
+/// Recognizes gzip, PNG, JPEG, server-sent events (a leading `data:` or
+/// `event:` line), and JSON (first non-whitespace byte is `{` or `[`).
+/// Other valid UTF-8 is `text/plain`; anything else is
+/// `application/octet-stream`.
+pub fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        return "application/gzip";
+    }
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return "image/png";
+    }
+    if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        return "image/jpeg";
+    }
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let trimmed = &bytes[start..];
+    if trimmed.starts_with(b"data:") || trimmed.starts_with(b"event:") {
+        return "text/event-stream";
+    }
+    if matches!(trimmed.first(), Some(b'{') | Some(b'[')) {
+        return "application/json";
+    }
+    if !bytes.is_empty() && std::str::from_utf8(bytes).is_ok() {
+        return "text/plain; charset=utf-8";
+    }
+    "application/octet-stream"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_binary_formats() {
+        assert_eq!(sniff_content_type(&[0x1f, 0x8b, 0x08, 0x00]), "application/gzip");
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff_content_type(&[0xff, 0xd8, 0xff, 0xe0]), "image/jpeg");
+        assert_eq!(sniff_content_type(&[0x00, 0xff, 0xfe]), "application/octet-stream");
+        assert_eq!(sniff_content_type(b""), "application/octet-stream");
+    }
+
+    #[test]
+    fn detects_json() {
+        assert_eq!(sniff_content_type(br#"{"ok":true}"#), "application/json");
+        assert_eq!(sniff_content_type(b"  \n[1,2]"), "application/json");
+    }
+
+    #[test]
+    fn detects_sse() {
+        assert_eq!(sniff_content_type(b"event: message_start\ndata: {}\n\n"), "text/event-stream");
+        assert_eq!(sniff_content_type(b"data: {\"type\":\"ping\"}\n\n"), "text/event-stream");
+    }
+
+    #[test]
+    fn detects_plain_text() {
+        assert_eq!(sniff_content_type("héllo".as_bytes()), "text/plain; charset=utf-8");
+    }
+}
@@ -0,0 +1,196 @@
+//! Folding a recorded Anthropic Messages stream back into the message it
+//! delivered.
+//!
+//! A streamed response is a series of SSE events: `message_start` carries the
+//! message with empty `content`, each content block arrives as
+//! `content_block_start`, any number of `content_block_delta`s and a
+//! `content_block_stop`, and `message_delta` sets `stop_reason` and the final
+//! `usage`. Applying them in order gives the object a non-streamed request
+//! would have returned.
+
+use serde_json::{Map, Value};
+
+/// Content blocks being built, with the `input_json_delta` fragments of
+/// `tool_use` blocks kept as text until the block stops.
+struct Message {
+    message: Value,
+    blocks: Vec<(Value, String)>,
+}
+
+impl Message {
+    fn block(&mut self, event: &Value) -> Option<&mut (Value, String)> {
+        let index = event["index"].as_u64()? as usize;
+        self.blocks.get_mut(index)
+    }
+
+    fn apply(&mut self, event: &Value) {
+        match event["type"].as_str() {
+            Some("content_block_start") => {
+                // Blocks arrive in order; an index past the next one is
+                // garbage, and not worth allocating a gap for.
+                let index = event["index"].as_u64().unwrap_or(self.blocks.len() as u64);
+                let block = (event["content_block"].clone(), String::new());
+                match usize::try_from(index) {
+                    Ok(index) if index < self.blocks.len() => self.blocks[index] = block,
+                    Ok(index) if index == self.blocks.len() => self.blocks.push(block),
+                    _ => {}
+                }
+            }
+            Some("content_block_delta") => {
+                let delta = &event["delta"];
+                let Some((block, partial_json)) = self.block(event) else { return };
+                let append = |block: &mut Value, key: &str, text: &str| {
+                    if let Some(object) = block.as_object_mut() {
+                        let current = object.entry(key).or_insert_with(|| Value::String(String::new()));
+                        if let Value::String(s) = current {
+                            s.push_str(text);
+                        }
+                    }
+                };
+                match delta["type"].as_str() {
+                    Some("text_delta") => append(block, "text", delta["text"].as_str().unwrap_or_default()),
+                    Some("thinking_delta") => append(block, "thinking", delta["thinking"].as_str().unwrap_or_default()),
+                    Some("signature_delta") => append(block, "signature", delta["signature"].as_str().unwrap_or_default()),
+                    Some("input_json_delta") => partial_json.push_str(delta["partial_json"].as_str().unwrap_or_default()),
+                    Some("citations_delta") => {
+                        if let Some(object) = block.as_object_mut() {
+                            let citations = object.entry("citations").or_insert_with(|| Value::Array(Vec::new()));
+                            if let Value::Array(list) = citations {
+                                list.push(delta["citation"].clone());
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some("message_delta") => {
+                let Some(message) = self.message.as_object_mut() else { return };
+                if let Some(delta) = event["delta"].as_object() {
+                    for (key, value) in delta {
+                        message.insert(key.clone(), value.clone());
+                    }
+                }
+                if let Some(usage) = event["usage"].as_object() {
+                    let total = message.entry("usage").or_insert_with(|| Value::Object(Map::new()));
+                    if let Value::Object(total) = total {
+                        for (key, value) in usage {
+                            total.insert(key.clone(), value.clone());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The message so far. Tool input that never finished streaming is left
+    /// as recorded in `partial_json` rather than dropped.
+    fn finish(mut self) -> Value {
+        let content = self
+            .blocks
+            .into_iter()
+            .filter(|(block, _)| !block.is_null())
+            .map(|(mut block, partial_json)| {
+                if !partial_json.is_empty() {
+                    if let Some(object) = block.as_object_mut() {
+                        match serde_json::from_str(&partial_json) {
+                            Ok(input) => object.insert("input".into(), input),
+                            Err(_) => object.insert("partial_json".into(), Value::String(partial_json)),
+                        };
+                    }
+                }
+                block
+            })
+            .collect();
+        self.message["content"] = Value::Array(content);
+        self.message
+    }
+}
+
+/// The message a recorded Messages API stream adds up to, or `None` if
+/// `body` isn't one (no `message_start` event). An interrupted stream gives
+/// whatever had arrived.
+pub(crate) fn reconstruct_message(body: &[u8]) -> Option<Value> {
+    if crate::sniff::sniff_content_type(body) != "text/event-stream" {
+        return None;
+    }
+    let text = String::from_utf8_lossy(body);
+    let mut events = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("data:"))
+        .filter_map(|data| serde_json::from_str::<Value>(data.trim()).ok());
+    let start = events.find(|event| event["type"] == "message_start")?;
+    let mut message = Message {
+        message: start["message"].clone(),
+        blocks: Vec::new(),
+    };
+    if !message.message.is_object() {
+        return None;
+    }
+    for event in events {
+        message.apply(&event);
+    }
+    Some(message.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stream(events: &[Value]) -> Vec<u8> {
+        events
+            .iter()
+            .map(|e| format!("event: {}\ndata: {}\n\n", e["type"].as_str().unwrap(), e))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn folds_text_and_tool_use_blocks() {
+        let body = stream(&[
+            json!({"type": "message_start", "message": {"id": "msg_1", "role": "assistant", "content": [], "stop_reason": null, "usage": {"input_tokens": 10, "output_tokens": 1}}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}}),
+            json!({"type": "ping"}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Checking "}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "the weather."}}),
+            json!({"type": "content_block_stop", "index": 0}),
+            json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"city\": "}}),
+            json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"Paris\"}"}}),
+            json!({"type": "content_block_stop", "index": 1}),
+            json!({"type": "message_delta", "delta": {"stop_reason": "tool_use", "stop_sequence": null}, "usage": {"output_tokens": 25}}),
+            json!({"type": "message_stop"}),
+        ]);
+        let message = reconstruct_message(&body).unwrap();
+        assert_eq!(message["content"][0], json!({"type": "text", "text": "Checking the weather."}));
+        assert_eq!(message["content"][1]["input"], json!({"city": "Paris"}));
+        assert_eq!(message["stop_reason"], "tool_use");
+        assert_eq!(message["usage"], json!({"input_tokens": 10, "output_tokens": 25}));
+    }
+
+    #[test]
+    fn interrupted_stream_keeps_what_arrived() {
+        let body = stream(&[
+            json!({"type": "message_start", "message": {"id": "msg_2", "content": []}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "tool_use", "id": "t", "name": "n", "input": {}}}),
+            json!({"type": "content_block_delta", "index": 0, "delta": {"type": "input_json_delta", "partial_json": "{\"query\": \"par"}}),
+        ]);
+        let message = reconstruct_message(&body).unwrap();
+        assert_eq!(message["content"][0]["input"], json!({}));
+        assert_eq!(message["content"][0]["partial_json"], "{\"query\": \"par");
+        assert_eq!(reconstruct_message(br#"{"type":"message"}"#), None);
+        assert_eq!(reconstruct_message(b"data: {\"type\":\"ping\"}\n\n"), None);
+    }
+
+    #[test]
+    fn ignores_block_indices_past_the_next() {
+        let body = stream(&[
+            json!({"type": "message_start", "message": {"id": "msg_3", "content": []}}),
+            json!({"type": "content_block_start", "index": u64::MAX, "content_block": {"type": "text", "text": "x"}}),
+            json!({"type": "content_block_start", "index": 1u64 << 40, "content_block": {"type": "text", "text": "y"}}),
+            json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": "z"}}),
+        ]);
+        assert_eq!(reconstruct_message(&body).unwrap()["content"], json!([{"type": "text", "text": "z"}]));
+    }
+}
@@ -1,14 +1,91 @@
+use crate::delta;
 use crate::frame::Frame;
-use std::io::{self, Write};
+use crate::hash::HashAlgo;
+use crate::msgpack;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 
 /// Magic bytes identifying a .ghostline file.
 pub const MAGIC: &[u8; 8] = b"GHSTLINE";
 
-/// Current format version.
-pub const FORMAT_VERSION: u32 = 1;
+/// Magic bytes of a passphrase-encrypted .ghostline container. The format
+/// itself doesn't read these; it only recognises them to report the file as
+/// encrypted rather than corrupt.
+pub const ENCRYPTED_MAGIC: &[u8; 8] = b"GHSTLENC";
+
+/// Major format version. Readers reject files with a different major.
+pub const FORMAT_MAJOR: u16 = 0;
+
+/// Minor format version. Minor bumps are additive, so a reader accepts any
+/// minor of its major, skipping header fields and frame fields it doesn't know.
+///
+/// 2 adds the decompressed frame size to each index entry and header flags;
+/// 3 adds writer provenance (version and platform) to the header;
+/// 4 adds the header extension block that later minors append fields to;
+/// 5 adds the optional HTTP version fields to frames;
+/// 6 adds the truncation marker and original body lengths to frames;
+/// 7 adds the `anthropic-beta` features to frames;
+/// 8 adds the signer's public key fingerprint to the header extension;
+/// 9 adds the routed upstream to frames;
+/// 10 adds the response status to frames;
+/// 11 adds the upstream's response `content-length` to frames;
+/// 12 adds the request hash algorithm to the header extension;
+/// 13 adds the first parent step of a forked slice to the header extension;
+/// 14 adds the recorded request headers to frames.
+pub const FORMAT_MINOR: u16 = 14;
+
+/// Current format version as stored in the header: major in the high 16 bits,
+/// minor in the low 16. Versions 1–3 predate the split and read as 0.1–0.3.
+pub const FORMAT_VERSION: u32 = ((FORMAT_MAJOR as u32) << 16) | FORMAT_MINOR as u32;
+
+/// The on-disk format this crate writes, as [`FORMAT_VERSION`]. Independent
+/// of the crate version: a new release may keep the format, and only a
+/// different major stops older readers.
+pub fn format_version() -> u32 {
+    FORMAT_VERSION
+}
+
+/// One line naming the format [`format_version`] refers to.
+pub fn format_description() -> &'static str {
+    "ghostline recording format 0.14: zstd MessagePack frames with a tail index; reads any 0.x"
+}
+
+/// Version recorded in the `writer_version` of every file this crate writes.
+pub const WRITER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `<os>-<arch>` of the running build, recorded as the header's `platform`.
+fn current_platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Write a u16-length-prefixed UTF-8 string.
+fn write_str<W: Write>(w: &mut W, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "header string too long"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+/// Compression codec of a stored frame block. Every frame is zstd-compressed
+/// MessagePack today; the tag lets raw-block APIs say so explicitly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Zstd,
+}
+
+/// Header flag: requests are stored as deltas against the previous frame's
+/// request (see the `delta` module). Readers reconstruct full requests.
+pub const FLAG_DELTA_REQUESTS: u32 = 1 << 0;
+
+/// Header flags this version knows how to read.
+pub const KNOWN_FLAGS: u32 = FLAG_DELTA_REQUESTS;
+
+/// In delta-encoded files, every Nth frame stores its full request, so random
+/// access never has to apply more than N-1 deltas.
+pub const DELTA_SNAPSHOT_INTERVAL: usize = 16;
 
 /// File header written at the start of every .ghostline file.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Header {
     /// Unix timestamp (ms) when the recording started.
     pub started_at: u64,
@@ -20,6 +97,22 @@ pub struct Header {
     /// Step index in the parent run where the fork occurred.
     /// Only meaningful when parent_run_id is Some.
     pub fork_at_step: Option<u32>,
+    /// First parent step of a fork that doesn't start at 0 (0.13+), so the
+    /// file holds parent steps `fork_start_step..=fork_at_step`.
+    pub fork_start_step: Option<u32>,
+    /// Feature bits (`FLAG_*`), v2+.
+    pub flags: u32,
+    /// ghostline-core version that wrote the file (v3+, empty for older files).
+    /// Ignored when writing: the writer always records its own.
+    pub writer_version: String,
+    /// `<os>-<arch>` of the writing build (v3+). Ignored when writing.
+    pub platform: String,
+    /// SHA-256 of the ed25519 public key the file is signed with (0.8+). The
+    /// signature itself lives in a sidecar file, since it covers these bytes.
+    pub signer_fingerprint: Option<[u8; 32]>,
+    /// Algorithm of every frame's `request_hash` (0.12+; SHA-256 before).
+    /// The writer doesn't hash; frames must already use this one.
+    pub hash_algo: HashAlgo,
 }
 
 impl Header {
@@ -47,6 +140,27 @@ impl Header {
                 w.write_all(&[0u8])?; // no fork
             }
         }
+        w.write_all(&self.flags.to_le_bytes())?;
+        // Provenance (v3)
+        write_str(w, WRITER_VERSION)?;
+        write_str(w, &current_platform())?;
+        // Extension block (0.4): u32 length, then fields added by later minors.
+        w.write_all(&(self.extension_len() as u32).to_le_bytes())?;
+        match &self.signer_fingerprint {
+            Some(fingerprint) => {
+                w.write_all(&[1u8])?;
+                w.write_all(fingerprint)?;
+            }
+            None => w.write_all(&[0u8])?,
+        }
+        w.write_all(&[self.hash_algo.tag()])?;
+        match self.fork_start_step {
+            Some(step) => {
+                w.write_all(&[1u8])?;
+                w.write_all(&step.to_le_bytes())?;
+            }
+            None => w.write_all(&[0u8])?,
+        }
         Ok(())
     }
 
@@ -54,7 +168,17 @@ impl Header {
     pub fn byte_size(&self) -> u64 {
         let base = 8 + 4 + 8 + 1 + if self.git_sha.is_some() { 20 } else { 0 };
         let fork = 1 + if self.parent_run_id.is_some() { 32 + 4 } else { 0 };
-        (base + fork) as u64
+        let flags = 4;
+        let provenance = 2 + WRITER_VERSION.len() + 2 + current_platform().len();
+        let extension = 4 + self.extension_len();
+        (base + fork + flags + provenance + extension) as u64
+    }
+
+    /// Length of the extension block's fields: signer, hash algorithm, fork start.
+    pub(crate) fn extension_len(&self) -> usize {
+        let signer = 1 + if self.signer_fingerprint.is_some() { 32 } else { 0 };
+        let fork_start = 1 + if self.fork_start_step.is_some() { 4 } else { 0 };
+        signer + 1 + fork_start
     }
 }
 
@@ -63,6 +187,7 @@ impl Header {
 struct IndexEntry {
     request_hash: [u8; 32],
     offset: u64,
+    decompressed_size: u32,
 }
 
 /// Writes frames to a .ghostline file.
@@ -72,13 +197,39 @@ struct IndexEntry {
 /// [Header] [zstd-compressed frame 0] [frame 1] ... [frame N] [Index] [index_offset: u64]
 /// ```
 ///
-/// The index is a sequence of (request_hash: 32 bytes, offset: u64) entries,
-/// followed by a u32 entry count. The last 8 bytes of the file store the
+/// The index is a sequence of (request_hash: 32 bytes, offset: u64,
+/// decompressed_size: u32) entries, followed by a u32 entry count. The last 8 bytes of the file store the
 /// byte offset where the index begins, enabling O(1) seek to any frame.
 pub struct GhostlineWriter<W: Write> {
     inner: W,
     index: Vec<IndexEntry>,
     bytes_written: u64,
+    flags: u32,
+    /// Previous frame's full request, the base for the next delta.
+    prev_request: Option<Vec<u8>>,
+    spill: Option<IndexSpill>,
+    /// Request hashes written so far, to spot ambiguous replay keys. Kept
+    /// apart from the index, which may have spilled.
+    seen_hashes: HashSet<[u8; 32]>,
+    duplicate_hashes: usize,
+}
+
+/// Where [`GhostlineWriter::with_index_spill`] parks index entries until
+/// `finish`, in their on-disk encoding.
+struct IndexSpill {
+    file: BufWriter<File>,
+    /// Entries held in memory before they are moved to `file`.
+    max_in_memory: usize,
+    spilled: usize,
+}
+
+/// Size of one index entry on disk: hash, offset, decompressed size.
+const INDEX_ENTRY_SIZE: u64 = 32 + 8 + 4;
+
+fn write_index_entry<W: Write>(w: &mut W, entry: &IndexEntry) -> io::Result<()> {
+    w.write_all(&entry.request_hash)?;
+    w.write_all(&entry.offset.to_le_bytes())?;
+    w.write_all(&entry.decompressed_size.to_le_bytes())
 }
 
 impl<W: Write> GhostlineWriter<W> {
@@ -89,48 +240,232 @@ impl<W: Write> GhostlineWriter<W> {
             inner,
             index: Vec::new(),
             bytes_written: header.byte_size(),
+            flags: header.flags,
+            prev_request: None,
+            spill: None,
+            seen_hashes: HashSet::new(),
+            duplicate_hashes: 0,
         })
     }
 
+    /// Keep at most `max_in_memory` index entries in memory, moving them to
+    /// `file` (which should be empty, e.g. an anonymous temp file) whenever
+    /// that many accumulate; `finish` streams them back into the index. For
+    /// recordings that run for days. The request hashes stay in memory (32
+    /// bytes a frame) for [`duplicate_hash_count`](Self::duplicate_hash_count).
+    pub fn with_index_spill(mut self, file: File, max_in_memory: usize) -> Self {
+        self.spill = Some(IndexSpill {
+            file: BufWriter::new(file),
+            max_in_memory: max_in_memory.max(1),
+            spilled: 0,
+        });
+        self
+    }
+
+    /// Number of appended frames whose request hash had already been written.
+    /// The first such append also prints a warning to stderr.
+    ///
+    /// Replay keys on the request hash, so each of these is a request the
+    /// replay server can only answer one way (it serves the latest recorded
+    /// response for a hash).
+    pub fn duplicate_hash_count(&self) -> usize {
+        self.duplicate_hashes
+    }
+
     /// Append a frame, compressing it with zstd.
     pub fn append(&mut self, frame: &Frame) -> io::Result<()> {
-        let msgpack = frame
-            .to_msgpack()
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.append_with_request(frame, &frame.request_bytes)
+    }
+
+    /// Append `frame` with `request` as its request body.
+    fn append_with_request(&mut self, frame: &Frame, request: &[u8]) -> io::Result<()> {
+        let encoded = if self.flags & FLAG_DELTA_REQUESTS != 0 {
+            let snapshot = self.frame_count().is_multiple_of(DELTA_SNAPSHOT_INTERVAL);
+            let base = if snapshot { None } else { self.prev_request.as_deref() };
+            let encoded = frame.to_msgpack_with_request(&delta::encode(base, request));
+            let prev = self.prev_request.get_or_insert_with(Vec::new);
+            prev.clear();
+            prev.extend_from_slice(request);
+            encoded
+        } else {
+            frame.to_msgpack_with_request(request)
+        };
+        let msgpack = encoded.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
         // Compress with zstd level 3
         let compressed = zstd::bulk::compress(&msgpack, 3)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
+
+        self.write_block(&compressed, frame.request_hash, msgpack.len() as u32)
+    }
+
+    /// Append `frame` with its request body read from `request`, which must
+    /// yield `request_len` bytes, in place of `frame.request_bytes` (which
+    /// must be empty). The proxy records large uploads this way, straight
+    /// from the file they were spooled to. The compressed block is staged in
+    /// `scratch` (truncated first), since its length precedes it in the file.
+    ///
+    /// Delta-encoded files diff each request against the previous one, so
+    /// there the body is read into memory and appended as usual.
+    pub fn append_streamed(
+        &mut self,
+        frame: &Frame,
+        request: impl Read,
+        request_len: u64,
+        scratch: &mut File,
+    ) -> io::Result<()> {
+        if !frame.request_bytes.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the frame already holds a request body"));
+        }
+        let short = || io::Error::new(io::ErrorKind::UnexpectedEof, "request body is shorter than its length");
+        let mut request = request.take(request_len);
+        if self.flags & FLAG_DELTA_REQUESTS != 0 {
+            let mut body = Vec::new();
+            request.read_to_end(&mut body)?;
+            if body.len() as u64 != request_len {
+                return Err(short());
+            }
+            return self.append_with_request(frame, &body);
+        }
+        let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "frame is too large for the format");
+        let body_len = u32::try_from(request_len).map_err(|_| too_large())?;
 
+        // `rmp_serde` writes a frame as an array led by its request hash and
+        // request body: the (empty) body is replaced by the streamed one, as
+        // `bin`, which reads back the same.
+        let encoded = frame.to_msgpack().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut fields = io::Cursor::new(&encoded[..]);
+        msgpack::read_array_len(&mut fields)?;
+        msgpack::skip_bytes(&mut fields)?;
+        let at = fields.position() as usize;
+        if encoded.get(at) != Some(&0x90) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected frame encoding"));
+        }
+        let decompressed_size = u32::try_from(encoded.len() as u64 - 1 + 5 + request_len).map_err(|_| too_large())?;
+
+        scratch.set_len(0)?;
+        scratch.seek(SeekFrom::Start(0))?;
+        let mut encoder = zstd::stream::write::Encoder::new(BufWriter::new(&mut *scratch), 3)?;
+        // Recorded in the zstd frame header, as `bulk::compress` does.
+        encoder.set_pledged_src_size(Some(decompressed_size as u64))?;
+        encoder.write_all(&encoded[..at])?;
+        msgpack::write_bin_header(&mut encoder, body_len)?;
+        if io::copy(&mut request, &mut encoder)? != request_len {
+            return Err(short());
+        }
+        encoder.write_all(&encoded[at + 1..])?;
+        encoder.finish()?.flush()?;
+
+        let compressed_len = scratch.stream_position()?;
+        let len = u32::try_from(compressed_len).map_err(|_| too_large())?;
+        scratch.seek(SeekFrom::Start(0))?;
+        let frame_offset = self.bytes_written;
+        self.inner.write_all(&len.to_le_bytes())?;
+        if io::copy(&mut (&mut *scratch).take(compressed_len), &mut self.inner)? != compressed_len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "scratch file is shorter than written"));
+        }
+        self.bytes_written += 4 + compressed_len;
+        self.index_block(frame.request_hash, frame_offset, decompressed_size)
+    }
+
+    /// Append an already-compressed frame block, as returned by
+    /// `GhostlineReader::get_frame_raw`, without decoding it.
+    ///
+    /// Not available when writing delta-encoded requests: a raw block can't be
+    /// re-based onto this file's delta chain.
+    pub fn append_raw(&mut self, compressed: &[u8], request_hash: [u8; 32], codec: Codec) -> io::Result<()> {
+        let Codec::Zstd = codec;
+        if self.flags & FLAG_DELTA_REQUESTS != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "raw frames can't be appended to a delta-encoded file",
+            ));
+        }
+        // zstd records the content size in the frame header; 0 marks it unknown,
+        // as in 0.1 files.
+        let decompressed_size = zstd::zstd_safe::get_frame_content_size(compressed)
+            .ok()
+            .flatten()
+            .and_then(|n| u32::try_from(n).ok())
+            .unwrap_or(0);
+        self.write_block(compressed, request_hash, decompressed_size)
+    }
+
+    fn write_block(&mut self, compressed: &[u8], request_hash: [u8; 32], decompressed_size: u32) -> io::Result<()> {
         let frame_offset = self.bytes_written;
 
         // Write: [compressed_len: u32] [compressed_data]
         let len = compressed.len() as u32;
         self.inner.write_all(&len.to_le_bytes())?;
-        self.inner.write_all(&compressed)?;
+        self.inner.write_all(compressed)?;
 
         self.bytes_written += 4 + compressed.len() as u64;
+        self.index_block(request_hash, frame_offset, decompressed_size)
+    }
 
+    /// Index a block just written at `frame_offset`.
+    fn index_block(&mut self, request_hash: [u8; 32], frame_offset: u64, decompressed_size: u32) -> io::Result<()> {
+        if !self.seen_hashes.insert(request_hash) {
+            if self.duplicate_hashes == 0 {
+                let short: String = request_hash[..8].iter().map(|b| format!("{:02x}", b)).collect();
+                eprintln!(
+                    "ghostline: warning: request hash {} recorded more than once; \
+                     replay will serve the latest response for it",
+                    short
+                );
+            }
+            self.duplicate_hashes += 1;
+        }
         self.index.push(IndexEntry {
-            request_hash: frame.request_hash,
+            request_hash,
             offset: frame_offset,
+            decompressed_size,
         });
+        if let Some(spill) = self.spill.as_mut().filter(|s| self.index.len() >= s.max_in_memory) {
+            for entry in self.index.drain(..) {
+                write_index_entry(&mut spill.file, &entry)?;
+                spill.spilled += 1;
+            }
+        }
 
         Ok(())
     }
 
+    /// Flush the frames written so far to the underlying writer, without
+    /// writing the index: until [`finish`](Self::finish), the file is not one
+    /// `GhostlineReader` will open.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    /// Give back the underlying writer without finalizing. What it holds is the
+    /// header and frames but no index or trailer, so readers reject it; useful
+    /// for testing partial writes or appending a trailer of your own. Buffered
+    /// writers are not flushed first.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
     /// Flush the index and finalize the file. Must be called when done writing.
     pub fn finish(mut self) -> io::Result<W> {
         let index_offset = self.bytes_written;
 
-        // Write index entries: [hash: 32][offset: 8] each
+        // Write index entries: [hash: 32][offset: 8][decompressed_size: 4] each,
+        // the spilled ones first
+        let count = self.frame_count() as u32;
+        if let Some(spill) = self.spill.take() {
+            let mut file = spill.file.into_inner().map_err(|e| e.into_error())?;
+            file.seek(SeekFrom::Start(0))?;
+            let bytes = spill.spilled as u64 * INDEX_ENTRY_SIZE;
+            if io::copy(&mut BufReader::new(file).take(bytes), &mut self.inner)? != bytes {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "index spill file is shorter than written"));
+            }
+        }
         for entry in &self.index {
-            self.inner.write_all(&entry.request_hash)?;
-            self.inner.write_all(&entry.offset.to_le_bytes())?;
+            write_index_entry(&mut self.inner, entry)?;
         }
 
         // Write entry count
-        let count = self.index.len() as u32;
         self.inner.write_all(&count.to_le_bytes())?;
 
         // Write index offset as the final 8 bytes
@@ -142,7 +477,7 @@ impl<W: Write> GhostlineWriter<W> {
 
     /// Number of frames written so far.
     pub fn frame_count(&self) -> usize {
-        self.index.len()
+        self.index.len() + self.spill.as_ref().map_or(0, |s| s.spilled)
     }
 }
 
@@ -151,14 +486,82 @@ mod tests {
     use super::*;
     use crate::frame::Frame;
 
+    #[test]
+    fn spilled_index_matches_in_memory() {
+        let frames: Vec<Frame> = (0..5u8).map(|i| Frame::new(vec![i], vec![i; 3], 1, i as u64)).collect();
+        let write = |spill: Option<File>| {
+            let mut out = Vec::new();
+            let mut writer = GhostlineWriter::new(&mut out, &Header::default()).unwrap();
+            if let Some(file) = spill {
+                writer = writer.with_index_spill(file, 2);
+            }
+            for frame in &frames {
+                writer.append(frame).unwrap();
+            }
+            assert_eq!(writer.frame_count(), 5);
+            writer.finish().unwrap();
+            out
+        };
+        let path = std::env::temp_dir().join(format!("ghostline-spill-{}", std::process::id()));
+        let spill = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let spilled = write(Some(spill));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(spilled, write(None));
+    }
+
+    #[test]
+    fn streamed_requests_read_back_like_appended_ones() {
+        use crate::reader::{Body, GhostlineReader};
+        let request: Vec<u8> = (0..=255u8).cycle().take(70_000).collect();
+        let frame = Frame::builder().request(request.clone()).response(b"ok".to_vec()).status(Some(201)).build();
+        let shell = Frame { request_bytes: Vec::new(), ..frame.clone() };
+        let path = std::env::temp_dir().join(format!("ghostline-scratch-{}", std::process::id()));
+        let mut scratch = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+
+        for flags in [0, FLAG_DELTA_REQUESTS] {
+            let mut out = Vec::new();
+            let mut writer = GhostlineWriter::new(&mut out, &Header { flags, ..Default::default() }).unwrap();
+            writer.append_streamed(&shell, &request[..], request.len() as u64, &mut scratch).unwrap();
+            assert!(writer.append_streamed(&shell, &request[..10], 11, &mut scratch).is_err());
+            writer.finish().unwrap();
+
+            let mut reader = GhostlineReader::from_reader(io::Cursor::new(out)).unwrap();
+            assert_eq!(reader.get_frame(0).unwrap(), frame);
+            let mut body = Vec::new();
+            reader.get_body_to(0, Body::Request, &mut body).unwrap();
+            assert_eq!(body, request);
+            let (block, _) = reader.get_frame_raw(0).unwrap();
+            let content_size = zstd::zstd_safe::get_frame_content_size(&block).unwrap();
+            assert_eq!(content_size, Some(reader.index_entries()[0].decompressed_size as u64));
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn into_inner_leaves_file_unfinalized() {
+        let header = Header::default();
+        let mut writer = GhostlineWriter::new(io::BufWriter::new(Vec::new()), &header).unwrap();
+        let frame = Frame::new(b"req".to_vec(), b"res".to_vec(), 1, 2);
+        writer.append(&frame).unwrap();
+        writer.flush().unwrap();
+        let partial = writer.into_inner().into_inner().unwrap();
+
+        let mut finished = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut finished, &header).unwrap();
+        writer.append(&frame).unwrap();
+        writer.finish().unwrap();
+        // Everything but the index and trailer.
+        assert_eq!(partial.len(), finished.len() - (32 + 8 + 4) - 4 - 8);
+        assert!(finished.starts_with(&partial));
+        assert!(crate::GhostlineReader::from_reader(io::Cursor::new(partial)).is_err());
+    }
+
     #[test]
     fn write_and_verify_structure() {
         let mut buf = Vec::new();
         let header = Header {
             started_at: 1700000000000,
-            git_sha: None,
-            parent_run_id: None,
-            fork_at_step: None,
+            ..Default::default()
         };
 
         let mut writer = GhostlineWriter::new(&mut buf, &header).unwrap();
@@ -177,6 +580,8 @@ mod tests {
         assert_eq!(&buf[..8], MAGIC);
         // Verify version
         assert_eq!(u32::from_le_bytes(buf[8..12].try_into().unwrap()), FORMAT_VERSION);
+        let version = format!("format {}.{}:", format_version() >> 16, format_version() as u16);
+        assert!(format_description().contains(&version), "bump format_description for {}", version);
 
         // Verify index offset is stored in last 8 bytes
         let len = buf.len();
@@ -186,4 +591,26 @@ mod tests {
         assert_eq!(entry_count, 2);
         assert!(index_offset > 0 && index_offset < len as u64);
     }
+
+    #[test]
+    fn counts_duplicate_request_hashes() {
+        let mut buf = Vec::new();
+        let mut writer = GhostlineWriter::new(&mut buf, &Header::default()).unwrap();
+        writer.append(&Frame::new(b"a".to_vec(), b"1".to_vec(), 1, 0)).unwrap();
+        writer.append(&Frame::new(b"b".to_vec(), b"2".to_vec(), 1, 0)).unwrap();
+        assert_eq!(writer.duplicate_hash_count(), 0);
+        writer.append(&Frame::new(b"a".to_vec(), b"3".to_vec(), 1, 0)).unwrap();
+        writer.append(&Frame::new(b"a".to_vec(), b"4".to_vec(), 1, 0)).unwrap();
+        assert_eq!(writer.duplicate_hash_count(), 2);
+
+        // Still counted once earlier entries have spilled.
+        let path = std::env::temp_dir().join(format!("ghostline-dup-spill-{}", std::process::id()));
+        let spill = File::options().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        let mut writer = GhostlineWriter::new(Vec::new(), &Header::default()).unwrap().with_index_spill(spill, 2);
+        for (i, request) in [b"a", b"b", b"a", b"b", b"a"].iter().enumerate() {
+            writer.append(&Frame::new(request.to_vec(), i.to_string().into_bytes(), 1, 0)).unwrap();
+        }
+        assert_eq!(writer.duplicate_hash_count(), 3);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
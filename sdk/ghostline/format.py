@@ -5,18 +5,57 @@ Layout: [Header] [zstd(msgpack(frame))...] [Index] [index_offset: u64]
 """
 
 import hashlib
+import platform
 import struct
+import time
 import zstandard as zstd
 import msgpack
 
 MAGIC = b"GHSTLINE"
-FORMAT_VERSION = 1
+# Major in the high 16 bits (breaking), minor in the low 16 (additive).
+FORMAT_MAJOR = 0
+FORMAT_MINOR = 4
+FORMAT_VERSION = (FORMAT_MAJOR << 16) | FORMAT_MINOR
+
+# Header flags (v2+)
+FLAG_DELTA_REQUESTS = 1 << 0
+
+# Request hash algorithms by header tag (0.12+); older files are SHA-256.
+HASH_ALGOS = {0: "sha256", 1: "blake3"}
+
+
+def hash_request(data: bytes, algo: str = "sha256") -> bytes:
+    """Hash a request body the way a file with `algo` keys its frames."""
+    if algo == "blake3":
+        try:
+            import blake3
+        except ImportError:
+            raise RuntimeError("this recording uses BLAKE3 request hashes; pip install blake3") from None
+        return blake3.blake3(data).digest()
+    return hashlib.sha256(data).digest()
+
+
+def _writer_version() -> str:
+    from . import __version__
+
+    return f"py-{__version__}"
+
+
+def _write_str(f, s: str):
+    data = s.encode()
+    f.write(struct.pack("<H", len(data)))
+    f.write(data)
+
+
+def _read_str(f) -> str:
+    (length,) = struct.unpack("<H", f.read(2))
+    return f.read(length).decode()
 
 
 class Frame:
     """A single captured request/response pair."""
 
-    __slots__ = ("request_hash", "request_bytes", "response_bytes", "latency_ms", "timestamp")
+    __slots__ = ("request_hash", "request_bytes", "response_bytes", "latency_ms", "timestamp", "tags")
 
     def __init__(
         self,
@@ -25,21 +64,26 @@ class Frame:
         latency_ms: int,
         timestamp: int,
         request_hash: bytes | None = None,
+        tags: list[str] | None = None,
     ):
         self.request_bytes = request_bytes
         self.response_bytes = response_bytes
         self.latency_ms = latency_ms
         self.timestamp = timestamp
         self.request_hash = request_hash or hashlib.sha256(request_bytes).digest()
+        self.tags = tags or []
 
     def to_msgpack(self) -> bytes:
-        return msgpack.packb({
+        fields = {
             "request_hash": self.request_hash,
             "request_bytes": self.request_bytes,
             "response_bytes": self.response_bytes,
             "latency_ms": self.latency_ms,
             "timestamp": self.timestamp,
-        })
+        }
+        if self.tags:
+            fields["tags"] = self.tags
+        return msgpack.packb(fields)
 
     @classmethod
     def from_msgpack(cls, data: bytes) -> "Frame":
@@ -50,6 +94,7 @@ class Frame:
             latency_ms=d[b"latency_ms"],
             timestamp=d[b"timestamp"],
             request_hash=d[b"request_hash"],
+            tags=[t.decode() for t in d.get(b"tags", [])],
         )
 
 
@@ -65,7 +110,7 @@ class GhostlineWriter:
         fork_at_step: int | None = None,
     ):
         self._f = f
-        self._index: list[tuple[bytes, int]] = []
+        self._index: list[tuple[bytes, int, int]] = []
         self._compressor = zstd.ZstdCompressor(level=3)
 
         # Write header
@@ -86,6 +131,15 @@ class GhostlineWriter:
         else:
             f.write(b"\x00")
 
+        f.write(struct.pack("<I", 0))  # flags
+
+        # Provenance (v3)
+        _write_str(f, _writer_version())
+        _write_str(f, f"{platform.system().lower()}-{platform.machine().lower()}")
+
+        # Header extension block (0.4+): length, then fields from later minors
+        f.write(struct.pack("<I", 0))
+
         self._offset = f.tell()
 
     def append(self, frame: Frame):
@@ -97,14 +151,15 @@ class GhostlineWriter:
         self._f.write(compressed)
         self._offset += 4 + len(compressed)
 
-        self._index.append((frame.request_hash, offset))
+        self._index.append((frame.request_hash, offset, len(packed)))
 
     def finish(self):
         index_offset = self._offset
 
-        for req_hash, offset in self._index:
+        for req_hash, offset, size in self._index:
             self._f.write(req_hash)  # 32 bytes
             self._f.write(struct.pack("<Q", offset))
+            self._f.write(struct.pack("<I", size))  # decompressed size (v2)
 
         self._f.write(struct.pack("<I", len(self._index)))
         self._f.write(struct.pack("<Q", index_offset))
@@ -124,8 +179,9 @@ class GhostlineReader:
             raise ValueError(f"not a .ghostline file (got {magic!r})")
 
         (self.version,) = struct.unpack("<I", f.read(4))
-        if self.version != FORMAT_VERSION:
-            raise ValueError(f"unsupported version: {self.version}")
+        major, minor = self.version >> 16, self.version & 0xFFFF
+        if major != FORMAT_MAJOR or minor == 0:
+            raise ValueError(f"unsupported version: {major}.{minor}")
 
         (self.started_at,) = struct.unpack("<Q", f.read(8))
 
@@ -141,6 +197,36 @@ class GhostlineReader:
             self.parent_run_id = None
             self.fork_at_step = None
 
+        self.flags = 0
+        if minor >= 2:
+            (self.flags,) = struct.unpack("<I", f.read(4))
+        if self.flags & FLAG_DELTA_REQUESTS:
+            raise ValueError("delta-encoded requests are not supported by the Python reader")
+        if self.flags & ~FLAG_DELTA_REQUESTS:
+            raise ValueError(f"unsupported header flags: {self.flags:#x}")
+
+        self.writer_version = ""
+        self.platform = ""
+        if minor >= 3:
+            self.writer_version = _read_str(f)
+            self.platform = _read_str(f)
+
+        # Header extension: the fields this reader knows, skipping newer ones
+        self.hash_algo = "sha256"
+        self.fork_start_step = None
+        if minor >= 4:
+            (ext_len,) = struct.unpack("<I", f.read(4))
+            ext = f.read(ext_len)
+            algo_at = 33 if ext[:1] == b"\x01" else 1
+            if minor >= 12:
+                tag = ext[algo_at]
+                if tag not in HASH_ALGOS:
+                    raise ValueError(f"unsupported hash algorithm: {tag}")
+                self.hash_algo = HASH_ALGOS[tag]
+            # First parent step of a forked slice (0.13+)
+            if minor >= 13 and ext[algo_at + 1 : algo_at + 2] == b"\x01":
+                (self.fork_start_step,) = struct.unpack("<I", ext[algo_at + 2 : algo_at + 6])
+
         # Read index from end
         f.seek(-8, 2)
         (index_offset,) = struct.unpack("<Q", f.read(8))
@@ -152,6 +238,8 @@ class GhostlineReader:
         for _ in range(count):
             req_hash = f.read(32)
             (offset,) = struct.unpack("<Q", f.read(8))
+            if minor >= 2:
+                f.read(4)  # decompressed size — not needed by the streaming decompressor
             self._index.append((req_hash, offset))
 
     @property
@@ -219,7 +307,9 @@ def fork(source_path: str, at_step: int, output_path: str | None = None) -> str:
     with open(output_path, "wb") as out:
         writer = GhostlineWriter(
             out,
-            started_at=reader.started_at,
+            # A fork starts when it is made, so its own run id differs from
+            # the parent's even though both begin with the same frame.
+            started_at=max(int(time.time() * 1000), reader.started_at + 1),
             git_sha=reader.git_sha,
             parent_run_id=parent_run_id,
             fork_at_step=at_step,
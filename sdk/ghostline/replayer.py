@@ -1,9 +1,8 @@
 """Replay cached responses from a .ghostline file."""
 
-import hashlib
 from pathlib import Path
 
-from ghostline.format import GhostlineReader
+from ghostline.format import GhostlineReader, hash_request
 
 
 class GhostlineReplayer:
@@ -48,7 +47,7 @@ class GhostlineReplayer:
         """Look up a cached response by request body hash."""
         if not self._started:
             raise RuntimeError("replayer not started")
-        req_hash = hashlib.sha256(request_bytes).digest()
+        req_hash = hash_request(request_bytes, self._reader.hash_algo)
         result = self._cache.get(req_hash)
         if result is not None:
             self.hits += 1
@@ -97,6 +97,11 @@ export function DetailPanel() {
             </div>
           )}
         </div>
+        {frame.truncated && (
+          <div style={{ marginBottom: 16, padding: 8, background: 'rgba(234,179,8,0.1)', borderRadius: 6, fontSize: 12, fontFamily: 'var(--font-mono)' }}>
+            Truncated at record time — bodies are incomplete
+          </div>
+        )}
         {frame.request != null && <JsonBlock label="Request" data={frame.request} showRaw={showRaw} />}
         {frame.response != null && <JsonBlock label="Response" data={frame.response} showRaw={showRaw} />}
         {frame.meta != null && <JsonBlock label="Meta" data={frame.meta} showRaw={showRaw} />}
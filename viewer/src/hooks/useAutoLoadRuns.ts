@@ -14,6 +14,6 @@ export async function fetchRuns(): Promise<RunMeta[]> {
 }
 
 export async function fetchRunData(name: string): Promise<ArrayBuffer> {
-  const res = await fetch(`/api/runs/${name}`);
+  const res = await fetch(`/api/runs/${encodeURIComponent(name)}`);
   return res.arrayBuffer();
 }
@@ -1,12 +1,18 @@
 import { useEffect, useRef } from 'react';
 
+/** `Frame::summary` plus the proxy's `run_name` and HTTP `status`. */
 export interface LiveFrame {
   index: number;
+  request_hash: string;
   run_name: string;
-  timestamp: string;
+  timestamp: number;
   request_size: number;
   response_size: number;
   latency_ms: number;
+  tags: string[];
+  truncated: boolean;
+  is_error: boolean;
+  status: number;
 }
 
 export function useLiveFrames(onFrame: (frame: LiveFrame) => void) {
@@ -25,8 +31,14 @@ export function useLiveFrames(onFrame: (frame: LiveFrame) => void) {
 
     ws.onmessage = (event) => {
       try {
-        const frame: LiveFrame = JSON.parse(event.data);
-        onFrame(frame);
+        const message = JSON.parse(event.data);
+        // Frames have no `type`; replay events and notices do.
+        if (message.type === 'lagged') {
+          console.warn(`[ghostline] Live stream fell behind; ${message.skipped} messages skipped`);
+          return;
+        }
+        if (message.type) return;
+        onFrame(message as LiveFrame);
       } catch (e) {
         console.warn('[ghostline] Failed to parse live frame:', e);
       }
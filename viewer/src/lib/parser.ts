@@ -37,6 +37,8 @@ function extractFrameData(decoded: unknown, idx: number): Frame {
   let responseBytes: Uint8Array = new Uint8Array();
   let latencyMs = 0;
   let timestamp = 0;
+  let truncated = false;
+  const meta: Record<string, unknown> = {};
 
   if (Array.isArray(decoded)) {
     // Rust rmp_serde array format: [request_hash, request_bytes, response_bytes, latency_ms, timestamp]
@@ -44,6 +46,20 @@ function extractFrameData(decoded: unknown, idx: number): Frame {
     responseBytes = decoded[2] instanceof Uint8Array ? decoded[2] : new Uint8Array();
     latencyMs     = Number(decoded[3] ?? 0);
     timestamp     = Number(decoded[4] ?? 0);
+    // 0.6+: [.., tags, http_version, upstream_http_version, truncated, original_request_len, original_response_len]
+    truncated     = decoded[8] === true;
+    if (decoded[9] != null) meta['original_request_len'] = Number(decoded[9]);
+    if (decoded[10] != null) meta['original_response_len'] = Number(decoded[10]);
+    // 0.7+: anthropic_betas
+    if (Array.isArray(decoded[11]) && decoded[11].length > 0) meta['anthropic_betas'] = decoded[11];
+    // 0.9+: upstream the proxy routed to
+    if (typeof decoded[12] === 'string') meta['upstream'] = decoded[12];
+    // 0.10+: response status
+    if (typeof decoded[13] === 'number') meta['status'] = decoded[13];
+    // 0.11+: upstream content-length
+    if (decoded[14] != null) meta['original_content_length'] = Number(decoded[14]);
+    // 0.14+: request headers as [name, value] pairs
+    if (Array.isArray(decoded[15]) && decoded[15].length > 0) meta['request_headers'] = decoded[15];
   } else if (decoded && typeof decoded === 'object') {
     // Python SDK map format: {request_bytes, response_bytes, latency_ms, timestamp, request_hash}
     const m = decoded as Record<string, unknown>;
@@ -51,6 +67,7 @@ function extractFrameData(decoded: unknown, idx: number): Frame {
     responseBytes = m['response_bytes'] instanceof Uint8Array ? m['response_bytes'] : new Uint8Array();
     latencyMs     = Number(m['latency_ms']  ?? 0);
     timestamp     = Number(m['timestamp']   ?? 0);
+    truncated     = m['truncated'] === true;
   }
 
   const requestStr  = bytesToString(requestBytes);
@@ -64,7 +81,8 @@ function extractFrameData(decoded: unknown, idx: number): Frame {
     duration_ms: latencyMs,
     request:  tryParseJson(requestStr),
     response: tryParseJson(responseStr),
-    meta: {},
+    truncated,
+    meta,
   };
 }
 
@@ -81,6 +99,11 @@ export async function parseGhostline(buffer: ArrayBuffer, fileName: string): Pro
 
   let offset = 8;
   const version   = readU32LE(view, offset); offset += 4;
+  // Major in the high 16 bits, minor in the low 16; only major 0 exists.
+  if (version >>> 16 !== 0) {
+    throw new Error(`Unsupported .ghostline major version: ${version >>> 16}`);
+  }
+  const minor = version & 0xffff;
   const startedAt = readU64LE(view, offset); offset += 8;
   const hasSha    = bytes[offset];           offset += 1;
 
@@ -92,6 +115,31 @@ export async function parseGhostline(buffer: ArrayBuffer, fileName: string): Pro
     offset += 20;
   }
 
+  // Fork metadata: has_fork + parent_run_id (32) + fork_at_step (4)
+  const hasFork = bytes[offset]; offset += 1;
+  if (hasFork) offset += 36;
+
+  // 0.2+: header flags
+  if (minor >= 2) {
+    const flags = readU32LE(view, offset); offset += 4;
+    if (flags !== 0) {
+      throw new Error(`Unsupported .ghostline header flags: 0x${flags.toString(16)}`);
+    }
+  }
+
+  // 0.3+: writer_version and platform, each u16 length + UTF-8
+  if (minor >= 3) {
+    for (let i = 0; i < 2; i++) {
+      const len = view.getUint16(offset, true);
+      offset += 2 + len;
+    }
+  }
+
+  // 0.4+: header extension block (u32 length), skipped
+  if (minor >= 4) {
+    offset += 4 + readU32LE(view, offset);
+  }
+
   const frames: Frame[] = [];
   let idx = 0;
   // Stop before last 8 bytes (index_offset) and leave room for index entries
@@ -11,6 +11,8 @@ export interface Frame {
   request?: unknown;
   response?: unknown;
   error?: string;
+  /** Bodies were cut to the recording's max_body_bytes. */
+  truncated?: boolean;
   meta?: Record<string, unknown>;
 }
 